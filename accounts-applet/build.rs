@@ -0,0 +1,4 @@
+fn main() {
+    // Rebuild if i18n files change
+    println!("cargo:rerun-if-changed=i18n");
+}
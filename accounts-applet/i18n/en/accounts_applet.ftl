@@ -0,0 +1,4 @@
+applet-title = Accounts
+all-accounts-working = All accounts are working
+needs-sign-in = Sign in again to keep using this account
+open-accounts = Account settings…
@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::fl;
+use accounts::models::{Account, AccountEvent, AccountState};
+use accounts::{AccountsClient, Uuid};
+use cosmic::iced::platform_specific::shell::commands::popup::{destroy_popup, get_popup};
+use cosmic::iced::window::Id;
+use cosmic::iced::{Limits, Subscription, stream};
+use cosmic::prelude::*;
+use cosmic::theme::spacing;
+use cosmic::widget;
+use futures_util::{SinkExt, StreamExt};
+
+/// Binary of the accounts app, opened at the account which needs attention.
+const ACCOUNTS_APP: &str = "accounts-ui";
+
+/// The applet model, tracking the accounts to tell when one of them is broken.
+pub struct Applet {
+    /// Application state which is managed by the COSMIC runtime.
+    core: cosmic::Core,
+    /// The popup listing the accounts needing attention, if open.
+    popup: Option<Id>,
+    /// Client for interacting with the Accounts for COSMIC API.
+    client: Option<AccountsClient>,
+    // Accounts data.
+    accounts: Vec<Account>,
+}
+
+/// Messages emitted by the applet and its widgets.
+#[derive(Debug, Clone)]
+pub enum Message {
+    TogglePopup,
+    PopupClosed(Id),
+    CreateClient,
+    SetClient(Option<AccountsClient>),
+    LoadAccounts,
+    SetAccounts(Vec<Account>),
+    UpdateAccount(Account),
+    RemoveAccount(Uuid),
+    OpenAccounts(Option<Uuid>),
+}
+
+impl Applet {
+    /// Accounts the user has to sign in to again or whose last refresh failed.
+    fn broken_accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter().filter(|account| {
+            matches!(
+                account.state(),
+                AccountState::NeedsReauth | AccountState::Error(_)
+            )
+        })
+    }
+}
+
+impl cosmic::Application for Applet {
+    /// The async executor that will be used to run your application's commands.
+    type Executor = cosmic::executor::Default;
+
+    /// Data that your application receives to its init method.
+    type Flags = ();
+
+    /// Messages which the application and its widgets will emit.
+    type Message = Message;
+
+    /// Unique identifier in RDNN (reverse domain name notation) format.
+    const APP_ID: &'static str = "dev.edfloreshz.AccountsApplet";
+
+    fn core(&self) -> &cosmic::Core {
+        &self.core
+    }
+
+    fn core_mut(&mut self) -> &mut cosmic::Core {
+        &mut self.core
+    }
+
+    /// Initializes the applet and connects to the daemon.
+    fn init(
+        core: cosmic::Core,
+        _flags: Self::Flags,
+    ) -> (Self, Task<cosmic::Action<Self::Message>>) {
+        let applet = Applet {
+            core,
+            popup: None,
+            client: None,
+            accounts: Vec::new(),
+        };
+
+        (applet, cosmic::task::message(Message::CreateClient))
+    }
+
+    fn on_close_requested(&self, id: Id) -> Option<Message> {
+        Some(Message::PopupClosed(id))
+    }
+
+    /// The panel button, a warning sign while any account needs attention.
+    fn view(&self) -> Element<'_, Self::Message> {
+        let icon = if self.broken_accounts().next().is_some() {
+            "dialog-warning-symbolic"
+        } else {
+            "system-users-symbolic"
+        };
+        self.core
+            .applet
+            .icon_button(icon)
+            .on_press_down(Message::TogglePopup)
+            .into()
+    }
+
+    /// The popup listing the accounts needing attention.
+    fn view_window(&self, _id: Id) -> Element<'_, Self::Message> {
+        let mut content = widget::column().padding([spacing().space_xxs, 0]);
+
+        let mut broken_accounts = self.broken_accounts().peekable();
+        if broken_accounts.peek().is_none() {
+            content = content.push(cosmic::applet::padded_control(widget::text::body(fl!(
+                "all-accounts-working"
+            ))));
+        }
+        for account in broken_accounts {
+            let reason = match account.state() {
+                AccountState::Error(reason) => reason,
+                _ => fl!("needs-sign-in"),
+            };
+            content = content.push(
+                cosmic::applet::menu_button(
+                    widget::column()
+                        .push(widget::text::body(format!(
+                            "{} ({})",
+                            account.username, account.provider
+                        )))
+                        .push(widget::text::caption(reason)),
+                )
+                .on_press(Message::OpenAccounts(Some(account.id))),
+            );
+        }
+
+        content = content
+            .push(cosmic::applet::padded_control(
+                widget::divider::horizontal::default(),
+            ))
+            .push(
+                cosmic::applet::menu_button(widget::text::body(fl!("open-accounts")))
+                    .on_press(Message::OpenAccounts(None)),
+            );
+
+        self.core.applet.popup_container(content).into()
+    }
+
+    /// Keeps the accounts up to date with the daemon signals.
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let Some(client) = self.client.clone() else {
+            return Subscription::none();
+        };
+
+        Subscription::run_with_id(
+            "account_events",
+            stream::channel(1, move |mut output| async move {
+                if let Ok(mut events) = client.watch().await {
+                    while let Some(event) = events.next().await {
+                        let message = match event {
+                            AccountEvent::Added(account)
+                            | AccountEvent::Changed(account)
+                            | AccountEvent::AttentionNeeded(account) => {
+                                Message::UpdateAccount(account)
+                            }
+                            AccountEvent::Removed(id) => Message::RemoveAccount(id),
+                            AccountEvent::DaemonRestarted => Message::LoadAccounts,
+                        };
+                        if let Err(err) = output.send(message).await {
+                            tracing::warn!("failed to send message from subscription: {}", err);
+                        }
+                    }
+                }
+            }),
+        )
+    }
+
+    /// Handles messages emitted by the applet and its widgets.
+    fn update(&mut self, message: Self::Message) -> Task<cosmic::Action<Self::Message>> {
+        match message {
+            Message::TogglePopup => {
+                if let Some(popup) = self.popup.take() {
+                    return destroy_popup(popup);
+                }
+                let Some(main_window_id) = self.core.main_window_id() else {
+                    return Task::none();
+                };
+                let popup = Id::unique();
+                self.popup = Some(popup);
+                let mut popup_settings =
+                    self.core
+                        .applet
+                        .get_popup_settings(main_window_id, popup, None, None, None);
+                popup_settings.positioner.size_limits = Limits::NONE
+                    .min_width(300.0)
+                    .max_width(372.0)
+                    .min_height(60.0)
+                    .max_height(1080.0);
+                return get_popup(popup_settings);
+            }
+            Message::PopupClosed(id) => {
+                if self.popup == Some(id) {
+                    self.popup = None;
+                }
+            }
+            Message::CreateClient => {
+                return Task::perform(
+                    async {
+                        match AccountsClient::new().await {
+                            Ok(client) => Some(client.with_cache()),
+                            Err(err) => {
+                                tracing::error!("{err}");
+                                None
+                            }
+                        }
+                    },
+                    |client| cosmic::Action::App(Message::SetClient(client)),
+                );
+            }
+            Message::SetClient(client) => {
+                self.client = client;
+                return cosmic::task::message(Message::LoadAccounts);
+            }
+            Message::LoadAccounts => {
+                if let Some(client) = self.client.clone() {
+                    return Task::perform(
+                        async move { client.list_accounts().await },
+                        |accounts| match accounts {
+                            Ok(accounts) => cosmic::Action::App(Message::SetAccounts(accounts)),
+                            Err(err) => {
+                                tracing::error!("{err}");
+                                cosmic::Action::None
+                            }
+                        },
+                    );
+                }
+            }
+            Message::SetAccounts(accounts) => self.accounts = accounts,
+            Message::UpdateAccount(account) => {
+                match self.accounts.iter_mut().find(|a| a.id == account.id) {
+                    Some(existing) => *existing = account,
+                    None => self.accounts.push(account),
+                }
+                self.accounts.sort_by_key(|account| account.order);
+            }
+            Message::RemoveAccount(id) => self.accounts.retain(|account| account.id != id),
+            Message::OpenAccounts(account_id) => {
+                let mut command = tokio::process::Command::new(ACCOUNTS_APP);
+                if let Some(account_id) = account_id {
+                    command.arg("--account").arg(account_id.to_string());
+                }
+                // Not waited for, the child is reaped by tokio once it exits
+                if let Err(err) = command.spawn() {
+                    tracing::error!("Failed to open {}: {}", ACCOUNTS_APP, err);
+                }
+                if let Some(popup) = self.popup.take() {
+                    return destroy_popup(popup);
+                }
+            }
+        }
+        Task::none()
+    }
+
+    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
+        Some(cosmic::applet::style())
+    }
+}
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+mod app;
+#[path = "../../accounts-ui/src/i18n.rs"]
+mod i18n;
+
+fn main() -> cosmic::iced::Result {
+    // Get the system's preferred languages.
+    let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
+
+    // Enable localizations to be applied.
+    i18n::init(&requested_languages);
+
+    if std::env::var("RUST_LOG").is_err() {
+        unsafe {
+            std::env::set_var("RUST_LOG", "accounts_applet=info");
+        }
+    }
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_env("RUST_LOG"))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    // Starts the applet's event loop with `()` as the application's flags.
+    cosmic::applet::run::<app::Applet>(())
+}
@@ -0,0 +1,133 @@
+use std::process::ExitCode;
+
+use accounts::{
+    AccountsClient, Uuid,
+    models::{Account, Service},
+    zbus,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+const USAGE: &str = "Usage: accounts-cli <command>
+
+Commands:
+  list                                   List the accounts
+  token --account <id|email> [--format <format>]
+                                         Print a mail access token, refreshed when needed
+
+Formats:
+  raw          The access token, the default
+  xoauth2      A base64 SASL XOAUTH2 initial response
+  oauthbearer  A base64 SASL OAUTHBEARER initial response (RFC 7628)";
+
+/// How the token is printed, mail clients either take the bare token and build the SASL
+/// response themselves or expect it ready to send.
+#[derive(Clone, Copy)]
+enum Format {
+    Raw,
+    XOAuth2,
+    OAuthBearer,
+}
+
+impl Format {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "raw" => Some(Self::Raw),
+            "xoauth2" => Some(Self::XOAuth2),
+            "oauthbearer" => Some(Self::OAuthBearer),
+            _ => None,
+        }
+    }
+
+    fn encode(self, user: &str, access_token: &str) -> String {
+        match self {
+            Format::Raw => access_token.to_string(),
+            Format::XOAuth2 => {
+                STANDARD.encode(format!("user={user}\x01auth=Bearer {access_token}\x01\x01"))
+            }
+            Format::OAuthBearer => {
+                STANDARD.encode(format!("n,a={user},\x01auth=Bearer {access_token}\x01\x01"))
+            }
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("list") => list().await,
+        Some("token") => match parse_token_args(&args[1..]) {
+            Some((account, format)) => token(&account, format).await,
+            None => return usage(),
+        },
+        Some("--help" | "-h" | "help") => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        _ => return usage(),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Mail clients show whatever the helper printed on failure
+            eprintln!("accounts-cli: {}", describe(&err));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::from(2)
+}
+
+fn parse_token_args(args: &[String]) -> Option<(String, Format)> {
+    let mut account = None;
+    let mut format = Format::Raw;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--account" => account = Some(args.next()?.clone()),
+            "--format" => format = Format::parse(args.next()?)?,
+            _ => return None,
+        }
+    }
+    Some((account?, format))
+}
+
+async fn list() -> zbus::fdo::Result<()> {
+    let client = AccountsClient::new().await?;
+    for account in client.list_accounts().await? {
+        println!(
+            "{}\t{}\t{}{}",
+            account.id,
+            account.provider,
+            account.email.as_deref().unwrap_or(&account.username),
+            if account.enabled { "" } else { "\t(disabled)" }
+        );
+    }
+    Ok(())
+}
+
+/// Prints a token for the mail service of the account, which the daemon refreshes first
+/// when it expired, so mail clients can call this before every connection.
+async fn token(account: &str, format: Format) -> zbus::fdo::Result<()> {
+    let mut client = AccountsClient::new().await?;
+    let account: Account = match Uuid::parse_str(account) {
+        Ok(id) => client.get_account(&id.to_string()).await?,
+        Err(_) => client.find_account(account).await?,
+    };
+    let access_token = client
+        .get_access_token_for_service(&account.id, &Service::Email)
+        .await?;
+    let user = account.email.as_deref().unwrap_or(&account.username);
+    println!("{}", format.encode(user, &access_token));
+    Ok(())
+}
+
+fn describe(err: &zbus::fdo::Error) -> String {
+    match err {
+        zbus::fdo::Error::ZBus(err) => format!("Failed to reach the accounts daemon: {err}"),
+        err => err.to_string(),
+    }
+}
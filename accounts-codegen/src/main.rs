@@ -0,0 +1,117 @@
+use std::{collections::BTreeMap, error::Error, path::Path, process::ExitCode};
+
+use accounts::zbus::{self, Connection, fdo::IntrospectableProxy};
+
+const USAGE: &str = "Usage: accounts-codegen [--address <address>] <dir>
+
+Writes the D-Bus introspection XML of every interface the running accounts daemon
+serves to <dir>, one <interface>.xml each, for binding generators such as
+gdbus-codegen, qdbusxml2cpp or zbus-xmlgen.
+
+Service interfaces are only served for accounts having the service,
+all of them are checked in under accounts-daemon/data/interfaces.";
+
+/// The bus names of the daemon, the portal is served under its own.
+const DESTINATIONS: [&str; 2] = ["dev.edfloreshz.Accounts", "dev.edfloreshz.Accounts.Portal"];
+
+const DOCTYPE: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">"#;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (address, dir) = match args.as_slice() {
+        [flag, address, dir] if flag == "--address" => (Some(address.as_str()), dir),
+        [dir] if !dir.starts_with('-') => (None, dir),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(address, Path::new(dir)).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("accounts-codegen: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(address: Option<&str>, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let connection = match address {
+        Some(address) => zbus::connection::Builder::address(address)?.build().await?,
+        None => Connection::session().await?,
+    };
+
+    let mut interfaces = BTreeMap::new();
+    for destination in DESTINATIONS {
+        if let Err(err) = collect(&connection, destination, &mut interfaces).await {
+            eprintln!("accounts-codegen: Skipping {destination}: {err}");
+        }
+    }
+    if interfaces.is_empty() {
+        return Err("The accounts daemon serves no interfaces, is it running?".into());
+    }
+
+    std::fs::create_dir_all(dir)?;
+    for (name, xml) in &interfaces {
+        let path = dir.join(format!("{name}.xml"));
+        std::fs::write(&path, format!("{DOCTYPE}\n<node>\n  {xml}\n</node>\n"))?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Walks the objects of a bus name, keeping the description of each interface but the
+/// standard `org.freedesktop.DBus` ones.
+async fn collect(
+    connection: &Connection,
+    destination: &str,
+    interfaces: &mut BTreeMap<String, String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut paths = vec!["/".to_string()];
+    while let Some(path) = paths.pop() {
+        let xml = IntrospectableProxy::builder(connection)
+            .destination(destination)?
+            .path(path.as_str())?
+            .build()
+            .await?
+            .introspect()
+            .await?;
+        for child in elements(&xml, "<node name=\"", "/>").filter_map(name) {
+            paths.push(match path.as_str() {
+                "/" => format!("/{child}"),
+                _ => format!("{path}/{child}"),
+            });
+        }
+        for interface in elements(&xml, "<interface name=\"", "</interface>") {
+            if let Some(name) = name(interface)
+                && !name.starts_with("org.freedesktop.DBus.")
+            {
+                interfaces
+                    .entry(name.to_string())
+                    .or_insert_with(|| interface.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The elements of the XML starting and ending with the given text, which zbus doesn't
+/// nest within each other.
+fn elements<'a>(xml: &'a str, start: &'a str, end: &'a str) -> impl Iterator<Item = &'a str> {
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let from = rest.find(start)?;
+        let to = from + rest[from..].find(end)? + end.len();
+        let element = &rest[from..to];
+        rest = &rest[to..];
+        Some(element)
+    })
+}
+
+/// The value of the first `name` attribute of an element.
+fn name(element: &str) -> Option<&str> {
+    let (_, value) = element.split_once("name=\"")?;
+    value.split('"').next()
+}
@@ -0,0 +1,4 @@
+app-name = Accounts
+authorization-requested = An application asks for an account
+authorization-requested-body = { $app } wants to use { $account }. Open Accounts to allow or deny it.
+open-accounts = Open Accounts
@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    os::fd::{AsFd, AsRawFd, BorrowedFd},
+};
+
+use accounts::models::AccessLogEntry;
+use chrono::Utc;
+use futures_util::StreamExt;
+use uuid::Uuid;
+use zbus::{
+    Connection,
+    fdo::DBusProxy,
+    message::Header,
+    names::{BusName, UniqueName},
+};
+
+/// How many requests are remembered per account, older ones are dropped first.
+const MAX_ENTRIES: usize = 100;
+
+/// A rolling log of the peers which requested the tokens of each account.
+#[derive(Debug, Default)]
+pub struct AccessLog {
+    entries: HashMap<Uuid, VecDeque<AccessLogEntry>>,
+}
+
+impl AccessLog {
+    pub fn record(&mut self, account_id: Uuid, entry: AccessLogEntry) {
+        let entries = self.entries.entry(account_id).or_default();
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Returns the requests made for an account, oldest first.
+    pub fn entries(&self, account_id: &Uuid) -> Vec<AccessLogEntry> {
+        self.entries
+            .get(account_id)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, account_id: &Uuid) {
+        self.entries.remove(account_id);
+    }
+}
+
+/// Describes the caller of a method, resolving its PID and executable when the bus allows it.
+pub async fn caller(connection: &Connection, header: &Header<'_>, method: &str) -> AccessLogEntry {
+    let sender = header.sender().map(|sender| sender.to_owned());
+    let credentials = match &sender {
+        Some(sender) => match DBusProxy::new(connection).await {
+            Ok(proxy) => proxy
+                .get_connection_credentials(BusName::from(sender.clone()))
+                .await
+                .inspect_err(|err| {
+                    tracing::debug!("Failed to resolve the credentials of {}: {}", sender, err)
+                })
+                .ok(),
+            Err(err) => {
+                tracing::debug!("Failed to connect to the bus daemon: {}", err);
+                None
+            }
+        },
+        None => None,
+    };
+    // Like xdg-desktop-portal, the process is followed through a pidfd when the bus hands
+    // one out, since a PID may be reused once the process exits
+    let pidfd = credentials
+        .as_ref()
+        .and_then(|credentials| credentials.process_fd())
+        .map(|fd| fd.as_fd());
+    let pid = match pidfd {
+        Some(pidfd) => pidfd_pid(pidfd),
+        None => credentials
+            .as_ref()
+            .and_then(|credentials| credentials.process_id()),
+    };
+    let uid = credentials
+        .as_ref()
+        .and_then(|credentials| credentials.unix_user_id());
+    let mut executable = pid
+        .and_then(|pid| std::fs::read_link(format!("/proc/{pid}/exe")).ok())
+        .map(|path| path.display().to_string());
+    let mut app_id = pid.zip(uid).and_then(|(pid, uid)| flatpak_app_id(pid, uid));
+    if let Some(pidfd) = pidfd
+        && pidfd_pid(pidfd) != pid
+    {
+        tracing::debug!("The caller exited while it was identified");
+        executable = None;
+        app_id = None;
+    }
+
+    AccessLogEntry {
+        accessed_at: Utc::now().to_rfc3339(),
+        method: method.to_string(),
+        sender: sender.map(|sender| sender.to_string()).unwrap_or_default(),
+        pid,
+        executable,
+        app_id,
+    }
+}
+
+/// Waits until the connection of a caller leaves the bus.
+pub async fn disconnected(connection: &Connection, sender: UniqueName<'_>) -> zbus::Result<()> {
+    let proxy = DBusProxy::new(connection).await?;
+    let mut name_owner_changed = proxy
+        .receive_name_owner_changed_with_args(&[(0, sender.as_str())])
+        .await?;
+    // The caller could have left before the match rule was added
+    if !proxy
+        .name_has_owner(BusName::from(sender.to_owned()))
+        .await?
+    {
+        return Ok(());
+    }
+    while let Some(signal) = name_owner_changed.next().await {
+        if signal.args()?.new_owner().is_none() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// The PID of the process a pidfd refers to, `None` once it exited.
+fn pidfd_pid(pidfd: BorrowedFd<'_>) -> Option<u32> {
+    let fdinfo =
+        std::fs::read_to_string(format!("/proc/self/fdinfo/{}", pidfd.as_raw_fd())).ok()?;
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("Pid:"))
+        .and_then(|pid| pid.trim().parse().ok())
+}
+
+/// What a Flatpak info file says about a sandboxed application.
+#[derive(Debug, PartialEq, Eq)]
+struct FlatpakInfo {
+    app_id: String,
+    instance_id: Option<String>,
+}
+
+/// Reads the app ID of a Flatpak sandboxed process from the `/.flatpak-info` file Flatpak
+/// places at the root of its sandbox.
+///
+/// A process can fake its root with a user namespace of its own, so like
+/// xdg-desktop-portal the app ID is only trusted when the instance directory `flatpak run`
+/// keeps outside the sandbox, `/run/user/<uid>/.flatpak/<instance>`, names the same app.
+fn flatpak_app_id(pid: u32, uid: u32) -> Option<String> {
+    let info = read_regular_file(&format!("/proc/{pid}/root/.flatpak-info"))?;
+    let FlatpakInfo {
+        app_id,
+        instance_id,
+    } = parse_flatpak_info(&info)?;
+    let instance_id = instance_id.filter(|id| {
+        !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })?;
+    let instance_info = read_regular_file(&format!("/run/user/{uid}/.flatpak/{instance_id}/info"))?;
+    match parse_flatpak_info(&instance_info) {
+        Some(instance) if instance.app_id == app_id => Some(app_id),
+        _ => {
+            tracing::warn!("Process {pid} claims to be {app_id} without a Flatpak instance");
+            None
+        }
+    }
+}
+
+/// Reads a file, refusing anything else than a regular file, e.g. a FIFO left to block on.
+fn read_regular_file(path: &str) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    Some(contents)
+}
+
+/// Parses the `name` of the `[Application]` group and the `instance-id` of the `[Instance]`
+/// group of a Flatpak info key file.
+fn parse_flatpak_info(info: &str) -> Option<FlatpakInfo> {
+    let mut group = "";
+    let mut app_id = None;
+    let mut instance_id = None;
+    for line in info.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            group = name;
+        } else if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().to_string();
+            match (group, key.trim()) {
+                ("Application", "name") => app_id = Some(value),
+                ("Instance", "instance-id") => instance_id = Some(value),
+                _ => {}
+            }
+        }
+    }
+    Some(FlatpakInfo {
+        app_id: app_id.filter(|app_id| !app_id.is_empty())?,
+        instance_id,
+    })
+}
+
+/// Identifies the application behind a request, by its Flatpak app ID when it is sandboxed
+/// and by its executable otherwise.
+///
+/// Sandboxed applications share the executable of their runtime's tools, e.g.
+/// `/usr/bin/python3`, so the app ID is what tells them apart. Unique bus names change on
+/// every connection, so decisions made for a caller without a resolvable executable only
+/// last as long as its connection.
+pub fn application(entry: &AccessLogEntry) -> String {
+    entry
+        .app_id
+        .clone()
+        .or_else(|| entry.executable.clone())
+        .unwrap_or_else(|| entry.sender.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_app_and_instance_of_flatpak_info() {
+        let info = "[Application]\n\
+                    name=org.example.Mail\n\
+                    runtime=runtime/org.gnome.Platform/x86_64/47\n\
+                    \n\
+                    [Instance]\n\
+                    instance-id=1234567890\n\
+                    app-path=/var/lib/flatpak/app/org.example.Mail\n";
+
+        assert_eq!(
+            parse_flatpak_info(info),
+            Some(FlatpakInfo {
+                app_id: "org.example.Mail".to_string(),
+                instance_id: Some("1234567890".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_names_outside_the_application_group() {
+        let info = "[Runtime]\nname=org.gnome.Platform\n[Instance]\ninstance-id=1\n";
+
+        assert_eq!(parse_flatpak_info(info), None);
+    }
+}
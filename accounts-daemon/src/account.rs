@@ -1,50 +1,335 @@
-use crate::{Error, auth::AuthManager, services::ServiceFactory};
+use crate::{
+    CONNECTION, Error,
+    access::AccessLog,
+    auth::{AuthFlowState, AuthManager},
+    avatar, goa, logging,
+    models::ProviderConfigFile,
+    oidc::OidcTokenResponse,
+    polkit,
+    services::{ServiceFactory, offline},
+    settings,
+    templates::{self, AccountTemplate},
+    uoa, usage,
+};
 use accounts::{
-    config::AccountsConfig,
-    models::{DbusAccount, Provider, Service},
+    AccountsError, capabilities,
+    config::AccountsStore,
+    models::{
+        AccessLogEntry, Account, AccountManifest, Credential, DaemonState, DbusAccount,
+        MANIFEST_VERSION, PendingAuthorization, Provider, STATE_VERSION, SecretString, Service,
+        UsageStats,
+    },
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    sync::{Arc, Mutex, RwLock, RwLockWriteGuard},
+    time::Instant,
 };
+use tokio::sync::Notify;
 use uuid::Uuid;
-use zbus::{fdo::Result, interface, object_server::SignalEmitter};
+use zbus::{
+    Connection, DBusError, interface,
+    message::Header,
+    object_server::{InterfaceRef, SignalEmitter},
+    zvariant::{self, OwnedValue, Value},
+};
+
+type Result<T> = std::result::Result<T, AccountsError>;
+
+pub const OBJECT_PATH: &str = "/dev/edfloreshz/Accounts/Account";
+
+/// How long the portal waits for the user to allow or deny an application.
+pub(crate) const PORTAL_CONSENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// How long a request for the user to allow an application is remembered, once forgotten
+/// the next request of the application asks again.
+pub(crate) const AUTHORIZATION_REQUEST_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(600);
+
+/// How long changes to an account are collected before `AccountChanged` is emitted, so
+/// that e.g. toggling several services is a single signal.
+const CHANGE_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
 
+/// How precisely the last use of an account is tracked.
+const LAST_USED_RESOLUTION: chrono::Duration = chrono::Duration::minutes(1);
+
+/// The accounts interface, whose state is shared by every clone.
+///
+/// Methods only take `&self`, so zbus serves them concurrently, and background tasks such
+/// as the callback server work on a clone instead of holding the interface lock while they
+/// talk to the provider.
+#[derive(Clone)]
 pub struct AccountsInterface {
-    auth_manager: AuthManager,
-    config: AccountsConfig,
+    auth_manager: Arc<AuthManager>,
+    config: Arc<RwLock<AccountsStore>>,
+    /// The accounts as listed over D-Bus, built on the first listing after they changed so
+    /// clients listing them often don't convert every account each time.
+    accounts_snapshot: Arc<Mutex<Option<Vec<DbusAccount>>>>,
+    access_log: Arc<Mutex<AccessLog>>,
+    /// Applications the user was asked about, to only ask once per account, with when they
+    /// were asked. They are forgotten once the request times out or the caller disconnects.
+    pending_authorizations: Arc<Mutex<HashMap<(Uuid, String), Instant>>>,
+    /// Woken whenever the user allows or denies an application.
+    access_decided: Arc<Notify>,
+    templates: Arc<Vec<AccountTemplate>>,
+    /// Accounts whose change is about to be signalled.
+    changed_accounts: Arc<Mutex<HashSet<Uuid>>>,
 }
 
 #[interface(name = "dev.edfloreshz.Accounts.Account")]
 impl AccountsInterface {
+    /// Get the optional features of the daemon, see `accounts::capabilities`
+    async fn get_capabilities(&self) -> Vec<String> {
+        [
+            capabilities::DEVICE_FLOW,
+            capabilities::GOA_IMPORT,
+            capabilities::UOA_IMPORT,
+            capabilities::BATCH_TOKENS,
+            capabilities::PAGED_LISTING,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+    }
+
     /// List all accounts
-    pub(crate) async fn list_accounts(&self) -> Vec<DbusAccount> {
-        self.config.accounts.iter().map(Into::into).collect()
+    async fn list_accounts(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> Result<Vec<DbusAccount>> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        Ok(self.with_snapshot(<[DbusAccount]>::to_vec))
     }
 
-    /// Get a specific account by ID
-    async fn get_account(&self, id: &str) -> Result<DbusAccount> {
-        let uuid = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    /// List up to `limit` accounts from `offset` on, with only the given fields, named
+    /// like the keys of the account dicts, or every field when none are given. Unknown
+    /// fields are left out
+    async fn list_accounts_paged(
+        &self,
+        offset: u32,
+        limit: u32,
+        fields: Vec<String>,
+    ) -> Result<Vec<HashMap<String, OwnedValue>>> {
+        self.with_snapshot(|accounts| {
+            accounts
+                .iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .map(|account| {
+                    let mut dict = account_fields(account)
+                        .map_err(|e| AccountsError::Failed(e.to_string()))?;
+                    if !fields.is_empty() {
+                        dict.retain(|field, _| fields.contains(field));
+                    }
+                    Ok(dict)
+                })
+                .collect()
+        })
+    }
 
-        match self
+    /// List all accounts for a provider
+    async fn list_accounts_by_provider(&self, provider: Provider) -> Result<Vec<DbusAccount>> {
+        Ok(self
             .config
+            .read()
+            .unwrap()
             .accounts
             .iter()
-            .find(|account| account.id == uuid)
-        {
+            .filter(|account| account.provider == provider)
+            .map(Into::into)
+            .collect())
+    }
+
+    /// List enabled accounts which have the given service enabled
+    async fn list_accounts_by_service(&self, service: Service) -> Result<Vec<DbusAccount>> {
+        Ok(self
+            .config
+            .read()
+            .unwrap()
+            .accounts
+            .iter()
+            .filter(|account| {
+                account.enabled && matches!(account.services.get(&service), Some(true))
+            })
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Find an account by email or username
+    async fn find_account(&self, email_or_username: &str) -> Result<DbusAccount> {
+        let config = self.config.read().unwrap();
+        match config.accounts.iter().find(|account| {
+            account.username.eq_ignore_ascii_case(email_or_username)
+                || account
+                    .email
+                    .as_ref()
+                    .is_some_and(|email| email.eq_ignore_ascii_case(email_or_username))
+        }) {
+            Some(account) => Ok(account.into()),
+            None => Err(Error::AccountNotFound(email_or_username.to_string()).into()),
+        }
+    }
+
+    /// Get the account a mailbox belongs to
+    async fn get_account_by_email(&self, email: &str) -> Result<DbusAccount> {
+        let config = self.config.read().unwrap();
+        config
+            .accounts
+            .iter()
+            .find(|account| {
+                account_email(account).is_some_and(|address| address.eq_ignore_ascii_case(email))
+            })
+            .map(Into::into)
+            .ok_or_else(|| Error::AccountNotFound(email.to_string()).into())
+    }
+
+    /// Get every account with an address on a domain
+    async fn get_accounts_by_domain(&self, domain: &str) -> Result<Vec<DbusAccount>> {
+        let domain = domain.trim_start_matches('@');
+        let config = self.config.read().unwrap();
+        Ok(config
+            .accounts
+            .iter()
+            .filter(|account| {
+                account_email(account)
+                    .and_then(|address| address.rsplit_once('@'))
+                    .is_some_and(|(_, host)| host.eq_ignore_ascii_case(domain))
+            })
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Get a specific account by ID
+    async fn get_account(&self, id: &str) -> Result<DbusAccount> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+
+        match self.account(&uuid) {
             Some(account) => Ok(account.into()),
             None => Err(Error::AccountNotFound(id.to_string()).into()),
         }
     }
 
+    /// Check the provider config files, returning the problems found in each of them, none
+    /// for the usable ones. The files are read again, but the daemon only uses changes to
+    /// them once restarted. Client secrets failing to resolve and service scopes which are
+    /// never requested are reported too.
+    async fn validate_providers(&self) -> HashMap<String, Vec<String>> {
+        let mut report = HashMap::new();
+        for provider in Provider::list() {
+            let ProviderConfigFile {
+                path,
+                config,
+                mut errors,
+            } = ProviderConfigFile::load(provider);
+            if let Some(mut config) = config {
+                errors.extend(config.validate_scopes(provider));
+                if let Err(load_errors) = self.auth_manager.load_config(provider).await {
+                    errors.extend(load_errors);
+                }
+            }
+            report.insert(path.display().to_string(), errors);
+        }
+        report
+    }
+
     /// Start OAuth2 authentication flow for a provider
-    async fn start_authentication(&mut self, provider_name: &str) -> Result<String> {
-        let provider = Provider::from_str(provider_name);
+    async fn start_authentication(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        provider: Provider,
+    ) -> Result<String> {
+        match self.auth_manager.start_auth_flow(provider).await {
+            Ok(url) => {
+                emitter.authentication_started(provider).await?;
+                Ok(url)
+            }
+            Err(err) => {
+                tracing::error!("Failed to start authentication flow: {}", err);
+                Err(Error::AuthenticationFailed {
+                    reason: err.to_string(),
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Cancel pending OAuth2 flows by CSRF token or provider name
+    async fn cancel_authentication(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        csrf_token_or_provider: &str,
+    ) -> Result<()> {
+        let cancelled = self.auth_manager.cancel_auth_flow(csrf_token_or_provider);
+        if cancelled.is_empty() {
+            return Err(Error::InvalidArguments(format!(
+                "No pending authentication for {csrf_token_or_provider}"
+            ))
+            .into());
+        }
+
+        for provider in cancelled {
+            tracing::info!("Authentication cancelled for provider: {}", provider);
+            emitter.authentication_cancelled(provider).await?;
+        }
+        Ok(())
+    }
 
-        let Some(provider) = provider else {
-            return Err(Error::InvalidProvider(provider_name.to_string()).into());
+    /// Start OAuth2 authentication flow to renew the credentials of an existing account
+    async fn reauthenticate_account(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: &str,
+    ) -> Result<String> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
         };
 
-        match self.auth_manager.start_auth_flow(provider).await {
-            Ok(url) => Ok(url),
+        match self.auth_manager.start_reauth_flow(&account).await {
+            Ok(url) => {
+                emitter.authentication_started(account.provider).await?;
+                Ok(url)
+            }
             Err(err) => {
-                tracing::error!("Failed to start authentication flow: {}", err);
+                tracing::error!("Failed to start re-authentication flow: {}", err);
+                Err(Error::AuthenticationFailed {
+                    reason: err.to_string(),
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Start an authorization flow for an account which asks for the scopes a service needs
+    ///
+    /// Returns the URL to open, the flow completes like a re-authentication.
+    async fn request_additional_scopes(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: &str,
+        service: Service,
+    ) -> Result<String> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+
+        match self
+            .auth_manager
+            .start_scopes_flow(&account, &service)
+            .await
+        {
+            Ok(url) => {
+                emitter.authentication_started(account.provider).await?;
+                Ok(url)
+            }
+            Err(err) => {
+                tracing::error!("Failed to start the authorization flow for {service}: {err}");
                 Err(Error::AuthenticationFailed {
                     reason: err.to_string(),
                 }
@@ -55,181 +340,2102 @@ impl AccountsInterface {
 
     /// Complete OAuth2 authentication flow
     async fn complete_authentication(
-        &mut self,
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
         csrf_token: &str,
         authorization_code: &str,
     ) -> Result<String> {
-        match self
+        let provider = self.auth_manager.pending_provider(csrf_token);
+        let result = self
             .auth_manager
-            .complete_auth_flow(csrf_token.to_string(), authorization_code.to_string())
+            .complete_auth_flow(
+                csrf_token.to_string(),
+                authorization_code.to_string(),
+                &self.config,
+            )
+            .await;
+        self.finish_authentication(&emitter, provider.as_ref(), result)
             .await
-        {
-            Ok(account) => {
-                let account_id = account.id.to_string();
-                match self.config.save_account(&account) {
-                    Ok(_) => Ok(account_id),
-                    Err(err) => Err(Error::AccountNotSaved(err.to_string()).into()),
+    }
+
+    /// Fail a pending OAuth2 flow the provider redirected back with an OAuth error code
+    async fn fail_authentication(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        csrf_token: &str,
+        error: &str,
+    ) -> Result<()> {
+        self.fail_pending_authentication(&emitter, csrf_token, error)
+            .await
+    }
+
+    /// Start OAuth2 device authorization for a provider
+    ///
+    /// Returns the user code and the verification URL where the user enters it. The daemon
+    /// polls for completion and emits `AccountAdded` once the account is created.
+    async fn start_device_authentication(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        provider: Provider,
+    ) -> Result<(String, String)> {
+        let authorization = match self.auth_manager.start_device_auth_flow(provider).await {
+            Ok(authorization) => authorization,
+            Err(err) => {
+                tracing::error!("Failed to start device authorization: {}", err);
+                return Err(Error::AuthenticationFailed {
+                    reason: err.to_string(),
                 }
+                .into());
             }
-            Err(err) => Err(Error::AuthenticationFailed {
-                reason: err.to_string(),
+        };
+
+        emitter
+            .authentication_started(authorization.provider)
+            .await?;
+
+        let user_code = authorization.user_code().to_string();
+        let verification_uri = authorization.verification_uri();
+
+        let this = self.clone();
+        let emitter = emitter.to_owned();
+        tokio::spawn(async move {
+            let result = authorization.poll().await;
+            if let Err(err) = this
+                .complete_device_authentication(&emitter, authorization.provider, result)
+                .await
+            {
+                tracing::error!("Device authorization failed: {}", err);
+            }
+        });
+
+        Ok((user_code, verification_uri))
+    }
+
+    /// Import Google and Microsoft accounts from GNOME Online Accounts
+    ///
+    /// Accounts which exist already are skipped, the others need the user to sign in. Returns
+    /// the IDs of the imported accounts.
+    async fn import_goa_accounts(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<Vec<String>> {
+        if !settings::get().goa_import {
+            return Err(AccountsError::NotAuthorized(
+                "Importing accounts from GNOME Online Accounts is disabled".to_string(),
+            ));
+        }
+        let mut accounts = Vec::new();
+        for goa_account in goa::read_accounts()? {
+            match self
+                .auth_manager
+                .import_goa_account(&goa_account, &self.config)
+                .await
+            {
+                Ok(Some(account)) => accounts.push(account),
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!("Failed to import GOA account {}: {}", goa_account.id, err)
+                }
+            }
+        }
+        self.save_imported_accounts(&emitter, accounts, "GOA").await
+    }
+
+    /// Import Google and Microsoft accounts from libaccounts-glib (Ubuntu Online Accounts)
+    ///
+    /// Accounts which exist already are skipped. Returns the IDs of the imported accounts.
+    async fn import_uoa_accounts(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<Vec<String>> {
+        if !settings::get().uoa_import {
+            return Err(AccountsError::NotAuthorized(
+                "Importing accounts from Ubuntu Online Accounts is disabled".to_string(),
+            ));
+        }
+        let mut accounts = Vec::new();
+        for uoa_account in uoa::read_accounts()? {
+            match self
+                .auth_manager
+                .import_uoa_account(&uoa_account, &self.config)
+                .await
+            {
+                Ok(Some(account)) => accounts.push(account),
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!("Failed to import UOA account {}: {}", uoa_account.id, err)
+                }
             }
-            .into()),
         }
+        self.save_imported_accounts(&emitter, accounts, "UOA").await
     }
 
     /// Remove an account
-    async fn remove_account(&mut self, id: &str) -> Result<()> {
-        let id = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    async fn remove_account(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> Result<()> {
+        let id = Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if self
+            .account(&id)
+            .is_some_and(|account| is_locked(&self.templates, &account))
+        {
+            return Err(Error::AccountLocked(id.to_string()).into());
+        }
+        polkit::check(connection, &header, polkit::REMOVE_ACCOUNT).await?;
 
-        self.config
+        self.config_mut()
             .remove_account(&id)
-            .map_err(|e| zbus::fdo::Error::Failed(format!("Account {id} not removed: {}", e)))?;
+            .map_err(|e| AccountsError::Failed(format!("Account {id} not removed: {}", e)))?;
         self.auth_manager
             .delete_credentials(&id)
             .await
-            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+            .map_err(|e| AccountsError::Storage(e.to_string()))?;
+        self.access_log.lock().unwrap().remove(&id);
+        avatar::remove(&id);
+        offline::remove(&id);
+        usage::remove(&id);
+        emitter.account_removed(&id.to_string()).await?;
         Ok(())
     }
 
-    /// Enable or disable an account
-    async fn set_account_enabled(&mut self, id: &str, enabled: bool) -> Result<()> {
-        let uuid = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
-
-        match self.config.get_account(&uuid) {
-            Some(mut account) => {
-                account.enabled = enabled;
-                match self.config.save_account(&account) {
-                    Ok(_) => Ok(()),
-                    Err(err) => Err(Error::AccountNotUpdated(format!(
-                        "Account {id} not updated: {}",
-                        err
-                    ))
-                    .into()),
-                }
-            }
-            None => Err(Error::AccountNotFound(id.to_string()).into()),
+    /// Rename an account
+    async fn set_display_name(&self, id: &str, name: &str) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::InvalidArguments("Display name cannot be empty".to_string()).into());
         }
+
+        self.update_account(&uuid, |account| account.display_name = name.to_string())
+            .await
     }
 
-    async fn set_service_enabled(&mut self, id: &str, service: &str, enabled: bool) -> Result<()> {
-        let uuid = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
-        let Some(mut account) = self.config.get_account(&uuid) else {
-            return Err(Error::AccountNotFound(id.to_string()).into());
-        };
-        let Some(service) = Service::from_str(service.to_string()) else {
-            return Err(Error::InvalidService(service.to_string()).into());
-        };
-        account.services.insert(service.clone(), enabled);
-        self.config
-            .save_account(&account)
-            .map_err(|e| zbus::fdo::Error::Failed(format!("Failed to save account: {}", e)))?;
+    /// Set a label to tell accounts apart, an empty label removes it
+    async fn set_account_label(&self, id: &str, label: &str) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let label = label.trim();
+        self.update_account(&uuid, |account| {
+            account.label = (!label.is_empty()).then(|| label.to_string())
+        })
+        .await
+    }
 
-        if let Some(service) = ServiceFactory::create_service(&account, &service) {
-            if enabled {
-                service.add_service().await?;
-            } else {
-                service.remove_service().await?;
-            }
+    /// Set the color of an account as `#rrggbb`, an empty color removes it
+    async fn set_account_color(&self, id: &str, color: &str) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let color = color.trim();
+        let valid = color
+            .strip_prefix('#')
+            .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !color.is_empty() && !valid {
+            return Err(Error::InvalidArguments(format!("Invalid color: {color}")).into());
         }
-        Ok(())
+        self.update_account(&uuid, |account| {
+            account.color = (!color.is_empty()).then(|| color.to_lowercase())
+        })
+        .await
     }
 
-    async fn ensure_credentials(&mut self) -> Result<()> {
-        for account in self.config.accounts.iter_mut() {
-            self.auth_manager
-                .ensure_credentials(account)
-                .await
-                .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    /// Allow or stop background work for an account while the connection is metered
+    async fn set_sync_on_metered(&self, id: &str, enabled: bool) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        self.update_account(&uuid, |account| {
+            account.sync_on_metered = enabled;
+        })
+        .await
+    }
+
+    /// Lock an account so it can't be removed or disabled, or unlock it
+    async fn set_account_locked(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+        locked: bool,
+    ) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if !locked {
+            if self
+                .account(&uuid)
+                .is_some_and(|account| template_locks(&self.templates, &account))
+            {
+                return Err(Error::InvalidArguments(
+                    "The account is locked by your administrator".to_string(),
+                )
+                .into());
+            }
+            polkit::check(connection, &header, polkit::UNLOCK_ACCOUNT).await?;
         }
-        Ok(())
+        self.update_account(&uuid, |account| account.locked = locked)
+            .await
     }
 
-    async fn get_access_token(&mut self, id: &str) -> Result<String> {
-        let uuid = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    /// Enable or disable an account
+    async fn set_account_enabled(&self, id: &str, enabled: bool) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
 
-        match self.config.get_account(&uuid) {
-            Some(account) => self
-                .auth_manager
-                .get_account_credentials(&account.id)
-                .await
-                .map(|credentials| credentials.access_token)
-                .map_err(|e| zbus::fdo::Error::Failed(e.to_string())),
-            None => Err(Error::AccountNotFound(id.to_string()).into()),
-        }
+        self.try_update_account(&uuid, |account| {
+            if !enabled && is_locked(&self.templates, account) {
+                return Err(Error::AccountLocked(id.to_string()).into());
+            }
+            account.enabled = enabled;
+            Ok(())
+        })
+        .await
+        .map(drop)
     }
 
-    async fn get_refresh_token(&mut self, id: &str) -> Result<String> {
-        let uuid = Uuid::parse_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+    /// Order the accounts as listed, the accounts left out are listed after them
+    async fn set_account_order(&self, ids: Vec<String>) -> Result<()> {
+        let mut uuids = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let uuid =
+                Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+            if self.account(&uuid).is_none() {
+                return Err(Error::AccountNotFound(id.to_string()).into());
+            }
+            if uuids.contains(&uuid) {
+                return Err(
+                    Error::InvalidArguments(format!("Account {id} is listed twice")).into(),
+                );
+            }
+            uuids.push(uuid);
+        }
 
-        match self.config.get_account(&uuid) {
-            Some(account) => self
-                .auth_manager
-                .get_account_credentials(&account.id)
-                .await
-                .map(|credentials| credentials.refresh_token.unwrap_or_default())
-                .map_err(|e| zbus::fdo::Error::Failed(e.to_string())),
-            None => Err(Error::AccountNotFound(id.to_string()).into()),
+        let (previous, accounts) = {
+            let mut config = self.config_mut();
+            let previous = config.accounts.clone();
+            config
+                .set_order(&uuids)
+                .map_err(|e| AccountsError::Failed(format!("Account order not saved: {}", e)))?;
+            (previous, config.accounts.clone())
+        };
+        for account in accounts.iter().filter(|account| {
+            previous
+                .iter()
+                .any(|previous| previous.id == account.id && previous.order != account.order)
+        }) {
+            self.account_changed_soon(account.id);
         }
+        Ok(())
     }
 
-    async fn emit_account_added(
+    /// Enable or disable several accounts at once
+    async fn set_accounts_enabled(&self, ids: Vec<String>, enabled: bool) -> Result<()> {
+        let mut uuids = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let uuid =
+                Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+            match self.account(&uuid) {
+                None => return Err(Error::AccountNotFound(id.to_string()).into()),
+                Some(account) if !enabled && is_locked(&self.templates, &account) => {
+                    return Err(Error::AccountLocked(id.to_string()).into());
+                }
+                Some(_) => uuids.push(uuid),
+            }
+        }
+
+        self.edit_accounts(|account| {
+            if !uuids.contains(&account.id) || account.enabled == enabled {
+                return false;
+            }
+            account.enabled = enabled;
+            true
+        })
+        .await
+    }
+
+    /// Create a group accounts can be filed under
+    async fn create_group(
         &self,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
-        account_id: &str,
+        name: &str,
     ) -> Result<()> {
-        emitter.account_added(account_id).await.map_err(Into::into)
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::InvalidArguments("Group name cannot be empty".to_string()).into());
+        }
+
+        {
+            let mut config = self.config_mut();
+            if config.groups.iter().any(|group| group == name) {
+                return Err(Error::InvalidArguments(format!("Group {name} already exists")).into());
+            }
+            let mut groups = config.groups.clone();
+            groups.push(name.to_string());
+            config
+                .save_groups(groups)
+                .map_err(|e| AccountsError::Failed(format!("Group not saved: {}", e)))?;
+        }
+        self.groups_changed(&emitter).await?;
+        Ok(())
     }
 
-    async fn emit_account_removed(
+    /// Rename a group, its accounts move to the new name
+    async fn rename_group(
         &self,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
-        account_id: &str,
+        name: &str,
+        new_name: &str,
     ) -> Result<()> {
-        emitter
-            .account_removed(account_id)
-            .await
-            .map_err(Into::into)
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(Error::InvalidArguments("Group name cannot be empty".to_string()).into());
+        }
+
+        {
+            let mut config = self.config_mut();
+            if !config.groups.iter().any(|group| group == name) {
+                return Err(Error::InvalidArguments(format!("No group named {name}")).into());
+            }
+            if new_name != name && config.groups.iter().any(|group| group == new_name) {
+                return Err(
+                    Error::InvalidArguments(format!("Group {new_name} already exists")).into(),
+                );
+            }
+            let groups = config
+                .groups
+                .iter()
+                .map(|group| {
+                    if group == name {
+                        new_name
+                    } else {
+                        group.as_str()
+                    }
+                })
+                .map(ToString::to_string)
+                .collect();
+            config
+                .save_groups(groups)
+                .map_err(|e| AccountsError::Failed(format!("Group not renamed: {}", e)))?;
+        }
+        self.edit_accounts(|account| {
+            if account.group.as_deref() != Some(name) {
+                return false;
+            }
+            account.group = Some(new_name.to_string());
+            true
+        })
+        .await?;
+        self.groups_changed(&emitter).await?;
+        Ok(())
     }
 
-    async fn emit_account_changed(
+    /// Remove a group, its accounts are left ungrouped
+    async fn remove_group(
         &self,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
-        account_id: &str,
+        name: &str,
     ) -> Result<()> {
-        emitter
-            .account_changed(account_id)
-            .await
-            .map_err(Into::into)
+        {
+            let mut config = self.config_mut();
+            if !config.groups.iter().any(|group| group == name) {
+                return Err(Error::InvalidArguments(format!("No group named {name}")).into());
+            }
+            let groups = config
+                .groups
+                .iter()
+                .filter(|group| *group != name)
+                .cloned()
+                .collect();
+            config
+                .save_groups(groups)
+                .map_err(|e| AccountsError::Failed(format!("Group not removed: {}", e)))?;
+        }
+        self.edit_accounts(|account| {
+            if account.group.as_deref() != Some(name) {
+                return false;
+            }
+            account.group = None;
+            true
+        })
+        .await?;
+        self.groups_changed(&emitter).await?;
+        Ok(())
     }
 
-    async fn emit_account_exists(
+    /// File an account under a group, an empty group removes it from its group
+    async fn set_account_group(&self, id: &str, group: &str) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if !group.is_empty()
+            && !self
+                .config
+                .read()
+                .unwrap()
+                .groups
+                .iter()
+                .any(|g| g == group)
+        {
+            return Err(Error::InvalidArguments(format!("No group named {group}")).into());
+        }
+
+        self.update_account(&uuid, |account| {
+            account.group = (!group.is_empty()).then(|| group.to_string());
+        })
+        .await
+    }
+
+    /// Pause every account, tokens are refused and background work is skipped until they
+    /// are resumed. Any caller may pause them, resuming them needs polkit
+    async fn set_all_accounts_paused(
         &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        paused: bool,
     ) -> Result<()> {
-        emitter.account_exists().await.map_err(Into::into)
+        if !paused && self.paused() {
+            polkit::check_caller(connection, &header, polkit::RESUME_ACCOUNTS).await?;
+        }
+        self.config_mut()
+            .save_paused(paused)
+            .map_err(|e| AccountsError::Failed(format!("Pause not saved: {}", e)))?;
+        tracing::info!("{} all accounts", if paused { "Paused" } else { "Resumed" });
+        self.all_accounts_paused_changed(&emitter).await?;
+        Ok(())
     }
 
-    /// Signals
+    /// Enable or disable every account of a group, locked accounts are left enabled
+    async fn set_group_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        if !self
+            .config
+            .read()
+            .unwrap()
+            .groups
+            .iter()
+            .any(|group| group == name)
+        {
+            return Err(Error::InvalidArguments(format!("No group named {name}")).into());
+        }
 
-    #[zbus(signal)]
-    async fn account_added(emitter: &SignalEmitter<'_>, account_id: &str) -> zbus::Result<()>;
+        self.edit_accounts(|account| {
+            if account.group.as_deref() != Some(name)
+                || account.enabled == enabled
+                || (!enabled && is_locked(&self.templates, account))
+            {
+                return false;
+            }
+            account.enabled = enabled;
+            true
+        })
+        .await
+    }
+
+    async fn set_service_enabled(&self, id: &str, service: Service, enabled: bool) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let account = self
+            .try_update_account(&uuid, |account| {
+                if let Some(template) = self
+                    .templates
+                    .iter()
+                    .find(|template| account.template.as_ref() == Some(&template.name))
+                    && template.enforces(&service, enabled)
+                {
+                    return Err(Error::InvalidArguments(format!(
+                        "{service} is managed by your administrator"
+                    ))
+                    .into());
+                }
+                account.services.insert(service.clone(), enabled);
+                Ok(())
+            })
+            .await?;
+
+        if let Some(service) = ServiceFactory::create_service(&account, &service) {
+            if enabled {
+                service.add_service().await?;
+            } else {
+                service.remove_service().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a setting overriding the defaults of a service, empty if it isn't set
+    async fn get_service_setting(&self, id: &str, service: Service, key: &str) -> Result<String> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+
+        Ok(account
+            .service_settings
+            .get(&service)
+            .and_then(|settings| settings.get(key))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Override a default of a service, an empty value restores the default
+    ///
+    /// Settings such as `uri` decide where the daemon sends the account's requests, so
+    /// other applications can't change them on their own.
+    async fn set_service_setting(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+        service: Service,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if key.is_empty() {
+            return Err(Error::InvalidArguments("Setting key cannot be empty".to_string()).into());
+        }
+        polkit::check_caller(connection, &header, polkit::SET_SERVICE_SETTING).await?;
+
+        self.update_account(&uuid, |account| {
+            let settings = account.service_settings.entry(service.clone()).or_default();
+            if value.is_empty() {
+                settings.remove(key);
+            } else {
+                settings.insert(key.to_string(), value.to_string());
+            }
+            if settings.is_empty() {
+                account.service_settings.remove(&service);
+            }
+        })
+        .await?;
+
+        // Services registered on the bus hold a copy of the account, replace it
+        if let Some(account) = self.account(&uuid)
+            && matches!(account.services.get(&service), Some(true))
+            && let Some(service) = ServiceFactory::create_service(&account, &service)
+        {
+            service.remove_service().await?;
+            service.add_service().await?;
+        }
+        Ok(())
+    }
+
+    /// Get the settings a service is configured with, its defaults with the overrides applied
+    async fn get_service_config(
+        &self,
+        id: &str,
+        service: Service,
+    ) -> Result<HashMap<String, String>> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+        let Some(account_service) = ServiceFactory::create_service(&account, &service) else {
+            return Err(Error::InvalidService(format!("{service} has no settings")).into());
+        };
+
+        let config = account_service.get_config(&account).await?;
+        Ok(config
+            .settings
+            .into_iter()
+            .map(|(key, value)| {
+                // Strings are displayed quoted in the GVariant format
+                let value = match value {
+                    Value::Str(value) => value.to_string(),
+                    value => value.to_string(),
+                };
+                (key, value)
+            })
+            .collect())
+    }
+
+    /// Ensure the credentials of an account are valid, refreshing them if necessary.
+    ///
+    /// Returns whether the credentials are valid and the number of seconds until they
+    /// expire, zero if the expiry is unknown.
+    async fn ensure_credentials(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> Result<(bool, i64)> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        self.check_paused()?;
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+
+        let credentials = self.valid_credentials(&emitter, &account).await?;
+
+        let now = Utc::now();
+        let valid = credentials
+            .expires_at
+            .is_none_or(|expires_at| expires_at > now);
+        let expires_in = credentials
+            .expires_at
+            .map(|expires_at| (expires_at - now).num_seconds().max(0))
+            .unwrap_or_default();
+
+        Ok((valid, expires_in))
+    }
+
+    /// Get a valid access token, concurrent calls share a single refresh
+    async fn get_access_token(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> Result<String> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        self.check_paused()?;
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+        self.authorize_access(
+            &emitter,
+            &account,
+            connection,
+            &header,
+            "GetAccessToken",
+            false,
+        )
+        .await?;
+        let credentials = self.valid_credentials(&emitter, &account).await?;
+
+        Ok(credentials.access_token.into_exposed())
+    }
+
+    /// Get valid access tokens of several accounts at once, by account ID. Accounts whose
+    /// token can't be had, e.g. the ones the caller may not use yet, are left out, and
+    /// `GetAccessToken` tells why
+    async fn get_access_tokens(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        ids: Vec<String>,
+    ) -> Result<HashMap<String, String>> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        self.check_paused()?;
+        let mut accounts = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let uuid =
+                Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+            let Some(account) = self.account(&uuid) else {
+                return Err(Error::AccountNotFound(id.to_string()).into());
+            };
+            accounts.push(account);
+        }
+
+        let mut tokens = HashMap::with_capacity(accounts.len());
+        for account in accounts {
+            if tokens.contains_key(&account.id.to_string()) {
+                continue;
+            }
+            if let Err(err) = self
+                .authorize_access(
+                    &emitter,
+                    &account,
+                    connection,
+                    &header,
+                    "GetAccessTokens",
+                    false,
+                )
+                .await
+            {
+                tracing::debug!("No access token for {}: {}", account.id, err);
+                continue;
+            }
+            match self.valid_credentials(&emitter, &account).await {
+                Ok(credentials) => {
+                    tokens.insert(
+                        account.id.to_string(),
+                        credentials.access_token.into_exposed(),
+                    );
+                }
+                Err(err) => tracing::debug!("No access token for {}: {}", account.id, err),
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Get an access token limited to the scopes a service needs
+    async fn get_access_token_for_service(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+        service: Service,
+    ) -> Result<String> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        self.service_token(
+            &emitter,
+            connection,
+            &header,
+            &uuid,
+            service,
+            "GetAccessTokenForService",
+        )
+        .await
+        .map(SecretString::into_exposed)
+    }
+
+    /// Record that an account is being used
+    async fn touch_account(&self, id: &str) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        self.mark_used(&uuid, true).await
+    }
+
+    async fn get_refresh_token(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> Result<String> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+
+        self.check_paused()?;
+        match self.account(&uuid) {
+            Some(account) => {
+                polkit::check(connection, &header, polkit::GET_REFRESH_TOKEN).await?;
+                self.authorize_access(
+                    &emitter,
+                    &account,
+                    connection,
+                    &header,
+                    "GetRefreshToken",
+                    false,
+                )
+                .await?;
+                self.auth_manager
+                    .get_account_credentials(&account.id)
+                    .await
+                    .map(|credentials| {
+                        credentials
+                            .refresh_token
+                            .map(SecretString::into_exposed)
+                            .unwrap_or_default()
+                    })
+                    .map_err(Into::into)
+            }
+            None => Err(Error::AccountNotFound(id.to_string()).into()),
+        }
+    }
+
+    /// Get when the access token of an account expires as a Unix timestamp, zero if unknown
+    async fn get_token_expiry(&self, id: &str) -> Result<i64> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if self.account(&uuid).is_none() {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        }
+
+        let credentials = self.auth_manager.get_account_credentials(&uuid).await?;
+        Ok(credentials
+            .expires_at
+            .map(|expires_at| expires_at.timestamp())
+            .unwrap_or_default())
+    }
+
+    /// Get the OAuth scopes granted to an account, empty once its tokens are revoked
+    async fn get_granted_scopes(&self, id: &str) -> Result<Vec<String>> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if self.account(&uuid).is_none() {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        }
+
+        Ok(self.auth_manager.granted_scopes(&uuid).await?)
+    }
+
+    /// Revoke the tokens of an account, the user has to sign in again to use it
+    ///
+    /// As it takes the account away like removing it, the caller is checked with polkit
+    /// for the same action, on the session bus too.
+    async fn revoke_credentials(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        let Some(account) = self.account(&uuid) else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+        polkit::check_caller(connection, &header, polkit::REMOVE_ACCOUNT).await?;
+
+        self.auth_manager.revoke_credentials(&account).await?;
+        self.update_account(&uuid, |account| {
+            account.attention_needed = true;
+            account.last_error = None;
+        })
+        .await?;
+        self.accounts_needing_attention_changed(&emitter).await?;
+        Ok(())
+    }
+
+    /// Allow or deny an application access to the tokens of an account
+    ///
+    /// Applications are identified by their Flatpak app ID or the path of their executable,
+    /// as sent in `AuthorizationRequested`. The caller is checked with polkit on the session
+    /// bus too, so applications can't allow themselves.
+    async fn set_app_access(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+        app: &str,
+        allowed: bool,
+    ) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if app.is_empty() {
+            return Err(Error::InvalidArguments("Application cannot be empty".to_string()).into());
+        }
+        polkit::check_caller(connection, &header, polkit::SET_APP_ACCESS).await?;
+
+        self.update_account(&uuid, |account| {
+            account.app_access.insert(app.to_string(), allowed);
+        })
+        .await?;
+        self.pending_authorizations
+            .lock()
+            .unwrap()
+            .remove(&(uuid, app.to_string()));
+        self.access_decided.notify_waiters();
+        Ok(())
+    }
+
+    /// Forget the decision made for an application, asking again on its next request
+    async fn reset_app_access(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+        app: &str,
+    ) -> Result<()> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        polkit::check_caller(connection, &header, polkit::SET_APP_ACCESS).await?;
+        self.update_account(&uuid, |account| {
+            account.app_access.remove(app);
+        })
+        .await
+    }
+
+    /// Change which logs the daemon emits, e.g. `debug` or `accounts_daemon::auth=trace`
+    async fn set_log_level(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        level: &str,
+    ) -> Result<()> {
+        polkit::check(connection, &header, polkit::SET_LOG_LEVEL).await?;
+        logging::set_level(level).map_err(Into::into)
+    }
+
+    /// The peers which recently requested the tokens of an account, oldest first
+    async fn get_access_log(&self, id: &str) -> Result<Vec<AccessLogEntry>> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if self.account(&uuid).is_none() {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        }
+        Ok(self.access_log.lock().unwrap().entries(&uuid))
+    }
+
+    /// The requests made to the provider for an account and the bytes they moved, by
+    /// service
+    async fn get_usage_stats(&self, id: &str) -> Result<Vec<UsageStats>> {
+        let uuid =
+            Uuid::parse_str(id).map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if self.account(&uuid).is_none() {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        }
+        Ok(usage::stats(&uuid))
+    }
+
+    /// Emit `AuthorizationRequested` again for every request the user hasn't answered yet
+    ///
+    /// Called by a UI once it subscribed, for the requests made while none was running.
+    async fn replay_authorization_requests(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<()> {
+        let pending: Vec<(Uuid, String)> = self
+            .pending_authorizations
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        for (account_id, app) in pending {
+            emitter
+                .authorization_requested(&account_id.to_string(), &app)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The daemon state as JSON, without credentials, for scripts and bug reports
+    async fn dump_state(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> Result<String> {
+        polkit::check(connection, &header, polkit::ACCESS_ACCOUNTS).await?;
+        let mut pending_authorizations: Vec<PendingAuthorization> = self
+            .pending_authorizations
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|(account_id, app)| PendingAuthorization {
+                account_id: *account_id,
+                app: app.clone(),
+            })
+            .collect();
+        pending_authorizations.sort();
+
+        let state = DaemonState {
+            version: STATE_VERSION,
+            generated_at: Utc::now(),
+            accounts: self.config.read().unwrap().accounts.clone(),
+            pending_authorizations,
+            authentication_pending: self.auth_manager.has_pending_auth(),
+        };
+        state
+            .to_json()
+            .map_err(|e| AccountsError::Failed(e.to_string()))
+    }
+
+    /// The accounts and groups as JSON, without credentials, for backups
+    async fn export_manifest(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> Result<String> {
+        polkit::check(connection, &header, polkit::EXPORT_ACCOUNTS).await?;
+        let config = self.config.read().unwrap();
+        let manifest = AccountManifest {
+            version: MANIFEST_VERSION,
+            exported_at: Utc::now(),
+            accounts: config.accounts.clone(),
+            groups: config.groups.clone(),
+        };
+        manifest
+            .to_json()
+            .map_err(|e| AccountsError::Failed(e.to_string()))
+    }
+
+    /// Restore the accounts and groups of an exported manifest
+    ///
+    /// Accounts which exist already are skipped, restored ones need the user to sign in
+    /// again. Which applications may use them, their service settings, locks and templates
+    /// are not restored. Returns the IDs of the restored accounts.
+    async fn import_manifest(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        manifest: &str,
+    ) -> Result<Vec<String>> {
+        polkit::check(connection, &header, polkit::IMPORT_ACCOUNTS).await?;
+        let manifest = AccountManifest::from_json(manifest)
+            .map_err(|e| AccountsError::InvalidArguments(e.to_string()))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(AccountsError::InvalidArguments(format!(
+                "Unsupported manifest version {}",
+                manifest.version
+            )));
+        }
+
+        let new_groups = {
+            let mut config = self.config_mut();
+            let mut groups = config.groups.clone();
+            for group in &manifest.groups {
+                if !groups.contains(group) {
+                    groups.push(group.clone());
+                }
+            }
+            let changed = groups.len() != config.groups.len();
+            if changed {
+                config
+                    .save_groups(groups)
+                    .map_err(|e| AccountsError::Failed(format!("Group not saved: {}", e)))?;
+            }
+            changed
+        };
+        if new_groups {
+            self.groups_changed(&emitter).await?;
+        }
+
+        let accounts = manifest
+            .accounts
+            .into_iter()
+            .filter(|account| {
+                let config = self.config.read().unwrap();
+                config.get_account(&account.id).is_none()
+                    && !config.account_exists(
+                        account.remote_id.as_deref(),
+                        &account.username,
+                        &account.provider,
+                    )
+            })
+            .map(|mut account| {
+                account.attention_needed = true;
+                account.last_error = Some("Restored from a backup, sign in again".to_string());
+                account.avatar = None;
+                // Anything could have been written to the file, so nothing which grants
+                // access or points the daemon's requests elsewhere is trusted
+                account.app_access.clear();
+                account.service_settings.clear();
+                account.locked = false;
+                account.template = None;
+                account
+            })
+            .collect();
+        self.save_imported_accounts(&emitter, accounts, "a manifest")
+            .await
+    }
+
+    /// Version of the daemon
+    #[zbus(property)]
+    async fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    /// Level of the API the daemon implements, raised when methods, properties or signals
+    /// are added
+    #[zbus(property)]
+    async fn api_level(&self) -> u32 {
+        capabilities::API_LEVEL
+    }
+
+    /// Groups accounts can be filed under, in the order they are listed
+    #[zbus(property)]
+    async fn groups(&self) -> Vec<String> {
+        self.config.read().unwrap().groups.clone()
+    }
+
+    /// Whether every account is paused with `SetAllAccountsPaused`
+    #[zbus(property)]
+    async fn all_accounts_paused(&self) -> bool {
+        self.paused()
+    }
+
+    /// IDs of the accounts which need the user to sign in again
+    #[zbus(property)]
+    async fn accounts_needing_attention(&self) -> Vec<String> {
+        self.config
+            .read()
+            .unwrap()
+            .accounts
+            .iter()
+            .filter(|account| account.attention_needed)
+            .map(|account| account.id.to_string())
+            .collect()
+    }
+
+    /// When the tokens of accounts whose provider failed repeatedly are refreshed again, as
+    /// RFC 3339 keyed by account ID
+    #[zbus(property)]
+    async fn throttled_until(&self) -> HashMap<String, String> {
+        self.auth_manager
+            .throttled()
+            .into_iter()
+            .map(|(id, until)| (id.to_string(), until.to_rfc3339()))
+            .collect()
+    }
+
+    /// Backend the credentials are stored in, e.g. `secret-service` or `file`
+    #[zbus(property)]
+    async fn credential_store(&self) -> String {
+        self.auth_manager.storage().backend().to_string()
+    }
+
+    /// Why the user should be told where their credentials are stored: `fallback` when the
+    /// Secret Service was unavailable, `not-persistent` when they are lost on reboot, empty
+    /// otherwise
+    #[zbus(property)]
+    async fn credential_store_warning(&self) -> String {
+        self.auth_manager
+            .storage()
+            .warning()
+            .map(|warning| warning.code().to_string())
+            .unwrap_or_default()
+    }
+
+    // Kept for backwards compatibility, the daemon emits lifecycle signals itself.
+
+    async fn emit_account_added(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        account_id: &str,
+    ) -> Result<()> {
+        let account = self.get_account(account_id).await?;
+        emitter.account_added(&account).await.map_err(Into::into)
+    }
+
+    async fn emit_account_removed(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        account_id: &str,
+    ) -> Result<()> {
+        emitter
+            .account_removed(account_id)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn emit_account_changed(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        account_id: &str,
+    ) -> Result<()> {
+        let account = self.get_account(account_id).await?;
+        emitter.account_changed(&account).await.map_err(Into::into)
+    }
+
+    async fn emit_account_exists(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<()> {
+        emitter.account_exists().await.map_err(Into::into)
+    }
+
+    /// Signals
+
+    #[zbus(signal)]
+    async fn account_added(emitter: &SignalEmitter<'_>, account: &DbusAccount) -> zbus::Result<()>;
 
     #[zbus(signal)]
     async fn account_removed(emitter: &SignalEmitter<'_>, account_id: &str) -> zbus::Result<()>;
 
     #[zbus(signal)]
-    async fn account_changed(emitter: &SignalEmitter<'_>, account_id: &str) -> zbus::Result<()>;
+    async fn account_changed(
+        emitter: &SignalEmitter<'_>,
+        account: &DbusAccount,
+    ) -> zbus::Result<()>;
 
     #[zbus(signal)]
     async fn account_exists(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn authentication_started(
+        emitter: &SignalEmitter<'_>,
+        provider: Provider,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn authentication_completed(
+        emitter: &SignalEmitter<'_>,
+        account_id: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn authentication_failed(
+        emitter: &SignalEmitter<'_>,
+        provider: &str,
+        error: &str,
+        detail: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn authentication_cancelled(
+        emitter: &SignalEmitter<'_>,
+        provider: Provider,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn authorization_requested(
+        emitter: &SignalEmitter<'_>,
+        account_id: &str,
+        app: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn account_attention_needed(
+        emitter: &SignalEmitter<'_>,
+        account: &DbusAccount,
+    ) -> zbus::Result<()>;
 }
 
 impl AccountsInterface {
     pub async fn new() -> crate::Result<Self> {
-        Ok(Self {
-            auth_manager: AuthManager::new().await?,
-            config: AccountsConfig::config(),
+        let auth_manager = Arc::new(AuthManager::new().await?);
+        tokio::spawn(auth_manager.clone().save_unsaved_credentials());
+        let interface = Self {
+            auth_manager,
+            config: Arc::new(RwLock::new(AccountsStore::load())),
+            accounts_snapshot: Arc::default(),
+            access_log: Arc::default(),
+            pending_authorizations: Arc::default(),
+            access_decided: Arc::default(),
+            templates: Arc::new(templates::load()),
+            changed_accounts: Arc::default(),
+        };
+        interface.provision_accounts();
+        Ok(interface)
+    }
+
+    /// An interface without accounts whose changes and credentials are kept in memory, for
+    /// tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            auth_manager: Arc::new(AuthManager::in_memory()),
+            config: Arc::new(RwLock::new(AccountsStore::in_memory())),
+            accounts_snapshot: Arc::default(),
+            access_log: Arc::default(),
+            pending_authorizations: Arc::default(),
+            access_decided: Arc::default(),
+            templates: Arc::default(),
+            changed_accounts: Arc::default(),
+        }
+    }
+
+    /// The accounts, for the daemon to start their services before it serves the bus.
+    pub(crate) fn accounts(&self) -> Vec<Account> {
+        self.config.read().unwrap().accounts.clone()
+    }
+
+    fn account(&self, id: &Uuid) -> Option<Account> {
+        self.config.read().unwrap().get_account(id)
+    }
+
+    /// Reads the accounts as listed over D-Bus, converting them first if they changed.
+    fn with_snapshot<T>(&self, read: impl FnOnce(&[DbusAccount]) -> T) -> T {
+        // The config stays locked until the snapshot is stored, so a change can't be missed
+        let config = self.config.read().unwrap();
+        let mut snapshot = self.accounts_snapshot.lock().unwrap();
+        read(snapshot.get_or_insert_with(|| config.accounts.iter().map(Into::into).collect()))
+    }
+
+    /// Locks the config to change it, dropping the snapshot of the listed accounts.
+    fn config_mut(&self) -> RwLockWriteGuard<'_, AccountsStore> {
+        let config = self.config.write().unwrap();
+        self.accounts_snapshot.lock().unwrap().take();
+        config
+    }
+
+    fn save_account(&self, account: &Account) -> std::result::Result<(), cosmic_config::Error> {
+        self.config_mut().save_account(account)
+    }
+
+    fn paused(&self) -> bool {
+        self.config.read().unwrap().paused
+    }
+
+    /// Refuses tokens while every account is paused, before the credentials are read so
+    /// the accounts aren't marked as failing.
+    fn check_paused(&self) -> Result<()> {
+        if self.paused() {
+            return Err(Error::Paused.into());
+        }
+        Ok(())
+    }
+
+    /// Creates the accounts of the administrator templates the user doesn't have yet.
+    ///
+    /// They are saved as needing attention, so the user only has to sign in to them.
+    fn provision_accounts(&self) {
+        let mut config = self.config_mut();
+        for template in self.templates.iter() {
+            let Some(account) = template.account() else {
+                tracing::warn!("No user to provision template {} for", template.name);
+                continue;
+            };
+            let provisioned = config
+                .accounts
+                .iter()
+                .any(|existing| existing.template.as_ref() == Some(&template.name))
+                || config.account_exists(None, &account.username, &account.provider);
+            if provisioned {
+                continue;
+            }
+
+            tracing::info!(
+                "Provisioning account {} from template {}",
+                account.username,
+                template.name
+            );
+            if let Err(err) = config.save_account(&account) {
+                tracing::error!("Failed to provision template {}: {}", template.name, err);
+            }
+        }
+    }
+
+    /// Records which peer requested the tokens of an account and checks it may have them.
+    ///
+    /// Applications the user hasn't decided on yet are refused, and the user is asked once
+    /// through `AuthorizationRequested`. `always_ask` asks even when the
+    /// `app_access_control` setting is off.
+    async fn authorize_access(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        account: &Account,
+        connection: &Connection,
+        header: &Header<'_>,
+        method: &str,
+        always_ask: bool,
+    ) -> Result<()> {
+        let entry = crate::access::caller(connection, header, method).await;
+        let app = crate::access::application(&entry);
+        tracing::debug!(
+            "{} ({}) called {} for account {}",
+            entry.sender,
+            app,
+            method,
+            account.id
+        );
+        self.access_log.lock().unwrap().record(account.id, entry);
+
+        if !always_ask && !settings::get().app_access_control {
+            return Ok(());
+        }
+        match account.app_access.get(&app) {
+            Some(true) => Ok(()),
+            Some(false) => Err(AccountsError::AccessDenied(format!(
+                "{app} is not allowed to access account {}",
+                account.id
+            ))),
+            None => {
+                let key = (account.id, app.clone());
+                let requested_at = Instant::now();
+                let first_request = match self.pending_authorizations.lock().unwrap().entry(key) {
+                    Entry::Occupied(_) => false,
+                    Entry::Vacant(entry) => {
+                        entry.insert(requested_at);
+                        true
+                    }
+                };
+                if first_request {
+                    self.forget_authorization_request(
+                        connection,
+                        header,
+                        (account.id, app.clone()),
+                        requested_at,
+                    );
+                    emitter
+                        .authorization_requested(&account.id.to_string(), &app)
+                        .await?;
+                    tokio::spawn(crate::notify::authorization_requested(
+                        account.clone(),
+                        app.clone(),
+                    ));
+                }
+                Err(AccountsError::AuthorizationPending(format!(
+                    "Waiting for the user to allow {app} to access account {}",
+                    account.id
+                )))
+            }
+        }
+    }
+
+    /// Forgets a request for the user to allow an application once it times out or the
+    /// caller disconnects, so the next request of the application asks again instead of
+    /// waiting on a question nobody answers.
+    fn forget_authorization_request(
+        &self,
+        connection: &Connection,
+        header: &Header<'_>,
+        key: (Uuid, String),
+        requested_at: Instant,
+    ) {
+        let pending_authorizations = self.pending_authorizations.clone();
+        let connection = connection.clone();
+        let sender = header.sender().map(|sender| sender.to_owned());
+        tokio::spawn(async move {
+            let disconnected = async {
+                match sender {
+                    Some(sender) => {
+                        if let Err(err) = crate::access::disconnected(&connection, sender).await {
+                            tracing::debug!("Failed to watch the caller: {}", err);
+                            std::future::pending::<()>().await;
+                        }
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(AUTHORIZATION_REQUEST_TIMEOUT) => {}
+                _ = disconnected => {}
+            }
+            let mut pending_authorizations = pending_authorizations.lock().unwrap();
+            // A later request of the same application has its own timeout
+            if pending_authorizations.get(&key) == Some(&requested_at) {
+                pending_authorizations.remove(&key);
+            }
+        });
+    }
+
+    /// Saves the account produced by an authentication flow and notifies subscribers.
+    ///
+    /// The provider is unknown when the flow could not be matched to a pending request.
+    async fn finish_authentication(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        provider: Option<&Provider>,
+        result: crate::Result<Account>,
+    ) -> Result<String> {
+        let result = self.save_authenticated_account(emitter, result).await;
+        match &result {
+            Ok(account_id) => emitter.authentication_completed(account_id).await?,
+            Err(err) => {
+                let provider = provider.map(ToString::to_string).unwrap_or_default();
+                emitter
+                    .authentication_failed(
+                        &provider,
+                        &err.code(),
+                        err.description().unwrap_or_default(),
+                    )
+                    .await?
+            }
+        }
+        result
+    }
+
+    async fn save_authenticated_account(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        result: crate::Result<Account>,
+    ) -> Result<String> {
+        match result {
+            Ok(account) => {
+                let account_id = account.id.to_string();
+                // Only what signing in changed is applied to the saved account, under one
+                // lock, so changes made while the user signed in aren't undone
+                let existing = {
+                    let mut config = self.config_mut();
+                    let existing = config.get_account(&account.id);
+                    let saved = match existing {
+                        Some(_) => config
+                            .edit_account(&account.id, |saved| {
+                                saved.email.clone_from(&account.email);
+                                saved.remote_id.clone_from(&account.remote_id);
+                                saved.last_used = account.last_used;
+                                saved.attention_needed = account.attention_needed;
+                                saved.last_error.clone_from(&account.last_error);
+                                saved.avatar.clone_from(&account.avatar);
+                                true
+                            })
+                            .map(drop),
+                        None => config.save_account(&account),
+                    };
+                    if let Err(err) = saved {
+                        return Err(Error::AccountNotSaved(err.to_string()).into());
+                    }
+                    existing
+                };
+                match existing {
+                    Some(existing) => {
+                        tracing::info!("Account re-authenticated with ID: {}", account_id);
+                        self.account_changed_soon(account.id);
+                        if existing.attention_needed {
+                            self.accounts_needing_attention_changed(emitter).await?;
+                        }
+                    }
+                    None => {
+                        tracing::info!("Account added with ID: {}", account_id);
+                        emitter.account_added(&account.into()).await?;
+                    }
+                }
+                Ok(account_id)
+            }
+            Err(Error::AccountAlreadyExists) => {
+                emitter.account_exists().await?;
+                Err(Error::AccountAlreadyExists.into())
+            }
+            Err(err) => Err(Error::AuthenticationFailed {
+                reason: err.to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Completes an authorization code flow received by the callback server, from outside
+    /// a D-Bus call.
+    pub async fn complete_callback_authentication(
+        csrf_token: String,
+        authorization_code: String,
+    ) -> Result<String> {
+        let interface = Self::interface_ref().await?;
+        // Work on a clone, the code exchange must not hold the interface lock
+        let this = interface.get().await.clone();
+
+        let provider = this.auth_manager.pending_provider(&csrf_token);
+        let result = this
+            .auth_manager
+            .complete_auth_flow(csrf_token, authorization_code, &this.config)
+            .await;
+        this.finish_authentication(interface.signal_emitter(), provider.as_ref(), result)
+            .await
+    }
+
+    /// Fails the flow the provider redirected back with an OAuth error code, from outside a
+    /// D-Bus call.
+    pub async fn fail_callback_authentication(csrf_token: &str, error: &str) -> Result<()> {
+        let interface = Self::interface_ref().await?;
+        let this = interface.get().await.clone();
+        this.fail_pending_authentication(interface.signal_emitter(), csrf_token, error)
+            .await
+    }
+
+    /// Returns where the authorization code flow started with a CSRF token stands, from
+    /// outside a D-Bus call.
+    pub async fn authentication_state(csrf_token: &str) -> AuthFlowState {
+        let Ok(interface) = Self::interface_ref().await else {
+            return AuthFlowState::Unknown;
+        };
+        interface
+            .get()
+            .await
+            .auth_manager
+            .auth_flow_state(csrf_token)
+    }
+
+    /// Returns the providers of the pending authorization code flows, from outside a D-Bus
+    /// call.
+    pub async fn pending_authentications() -> Result<Vec<Provider>> {
+        let interface = Self::interface_ref().await?;
+        Ok(interface.get().await.auth_manager.pending_providers())
+    }
+
+    /// Returns a token limited to a service for the first enabled account of the provider
+    /// having it enabled, from the portal.
+    ///
+    /// The application is always asked about, and the call waits for the user to decide.
+    pub async fn portal_token(
+        connection: &Connection,
+        header: &Header<'_>,
+        provider: Provider,
+        service: Service,
+    ) -> Result<(String, String)> {
+        let interface = Self::interface_ref().await?;
+        let this = interface.get().await.clone();
+        let emitter = interface.signal_emitter();
+        this.check_paused()?;
+
+        let account = this
+            .config
+            .read()
+            .unwrap()
+            .accounts
+            .iter()
+            .filter(|account| {
+                account.provider == provider
+                    && account.enabled
+                    && matches!(account.services.get(&service), Some(true))
+            })
+            .min_by_key(|account| account.order)
+            .cloned();
+        let Some(account) = account else {
+            return Err(AccountsError::AccountNotFound(format!(
+                "No {provider} account has {service} enabled"
+            )));
+        };
+
+        let decided = async {
+            loop {
+                // Listen before checking, a decision could be made in between
+                let decision = this.access_decided.notified();
+                // Decisions are saved on the account, so it is read again each time
+                let Some(account) = this.account(&account.id) else {
+                    return Err(Error::AccountNotFound(account.id.to_string()).into());
+                };
+                match this
+                    .authorize_access(emitter, &account, connection, header, "PortalToken", true)
+                    .await
+                {
+                    Err(AccountsError::AuthorizationPending(_)) => decision.await,
+                    result => return result,
+                }
+            }
+        };
+        match tokio::time::timeout(PORTAL_CONSENT_TIMEOUT, decided).await {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(AccountsError::AccessDenied(
+                    "The user did not answer the access request".to_string(),
+                ));
+            }
+        }
+
+        match this
+            .auth_manager
+            .get_service_credentials(&account, &service)
+            .await
+        {
+            Ok(credentials) => {
+                this.update_refresh_state(emitter, &account.id, None)
+                    .await?;
+                this.mark_used(&account.id, false).await?;
+                Ok((
+                    account.id.to_string(),
+                    credentials.access_token.into_exposed(),
+                ))
+            }
+            Err(err) => {
+                this.update_refresh_state(emitter, &account.id, Some(&err))
+                    .await?;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Returns a token limited to a service for the service interfaces of an account, once
+    /// the caller was checked like for `GetAccessTokenForService`.
+    pub async fn service_access_token(
+        connection: &Connection,
+        header: &Header<'_>,
+        account_id: &Uuid,
+        service: Service,
+        method: &str,
+    ) -> Result<SecretString> {
+        let interface = Self::interface_ref().await?;
+        let this = interface.get().await.clone();
+        this.service_token(
+            interface.signal_emitter(),
+            connection,
+            header,
+            account_id,
+            service,
+            method,
+        )
+        .await
+    }
+
+    /// Whether every account is paused, for background work the daemon starts on its own.
+    pub async fn daemon_paused() -> bool {
+        match Self::interface_ref().await {
+            Ok(interface) => interface.get().await.paused(),
+            Err(_) => false,
+        }
+    }
+
+    /// The accounts, for background work the daemon starts on its own.
+    pub async fn daemon_accounts() -> Vec<Account> {
+        match Self::interface_ref().await {
+            Ok(interface) => interface
+                .get()
+                .await
+                .config
+                .read()
+                .unwrap()
+                .accounts
+                .clone(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Returns a token limited to a service for requests the daemon makes on its own, such
+    /// as prefetching, so no caller is checked and the account is not marked as used. Fails
+    /// without asking the provider while the account is in backoff.
+    pub async fn daemon_access_token(account_id: &Uuid, service: Service) -> Result<SecretString> {
+        let interface = Self::interface_ref().await?;
+        let this = interface.get().await.clone();
+        this.check_paused()?;
+        let Some(account) = this.account(account_id) else {
+            return Err(Error::AccountNotFound(account_id.to_string()).into());
+        };
+        this.auth_manager.check_backoff(account_id)?;
+
+        match this
+            .auth_manager
+            .get_service_credentials(&account, &service)
+            .await
+        {
+            Ok(credentials) => {
+                this.update_refresh_state(interface.signal_emitter(), &account.id, None)
+                    .await?;
+                Ok(credentials.access_token)
+            }
+            Err(err) => {
+                this.update_refresh_state(interface.signal_emitter(), &account.id, Some(&err))
+                    .await?;
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn service_token(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        connection: &Connection,
+        header: &Header<'_>,
+        account_id: &Uuid,
+        service: Service,
+        method: &str,
+    ) -> Result<SecretString> {
+        self.check_paused()?;
+        let Some(account) = self.account(account_id) else {
+            return Err(Error::AccountNotFound(account_id.to_string()).into());
+        };
+        if !matches!(account.services.get(&service), Some(true)) {
+            return Err(Error::InvalidService(format!(
+                "{service} is not enabled for account {account_id}"
+            ))
+            .into());
+        }
+        self.authorize_access(emitter, &account, connection, header, method, false)
+            .await?;
+
+        match self
+            .auth_manager
+            .get_service_credentials(&account, &service)
+            .await
+        {
+            Ok(credentials) => {
+                self.update_refresh_state(emitter, &account.id, None)
+                    .await?;
+                self.mark_used(account_id, false).await?;
+                Ok(credentials.access_token)
+            }
+            Err(err) => {
+                self.update_refresh_state(emitter, &account.id, Some(&err))
+                    .await?;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Cancels the flow started with the CSRF token and notifies subscribers of the error.
+    async fn fail_pending_authentication(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        csrf_token: &str,
+        error: &str,
+    ) -> Result<()> {
+        for provider in self.auth_manager.cancel_auth_flow(csrf_token) {
+            tracing::info!("Authentication failed for provider {}: {}", provider, error);
+            let error = AccountsError::OAuth(error.to_string());
+            emitter
+                .authentication_failed(
+                    &provider.to_string(),
+                    &error.code(),
+                    error.description().unwrap_or_default(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Saves the accounts imported from another account system and notifies subscribers,
+    /// returning their IDs.
+    async fn save_imported_accounts(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        accounts: Vec<Account>,
+        source: &str,
+    ) -> Result<Vec<String>> {
+        let mut imported = Vec::new();
+        let mut attention_needed = false;
+        for account in accounts {
+            if let Err(err) = self.save_account(&account) {
+                return Err(Error::AccountNotSaved(err.to_string()).into());
+            }
+
+            tracing::info!("Account imported from {} with ID: {}", source, account.id);
+            let dbus_account: DbusAccount = (&account).into();
+            emitter.account_added(&dbus_account).await?;
+            if account.attention_needed {
+                emitter.account_attention_needed(&dbus_account).await?;
+                attention_needed = true;
+            }
+            imported.push(account.id.to_string());
+        }
+
+        if attention_needed {
+            self.accounts_needing_attention_changed(emitter).await?;
+        }
+        Ok(imported)
+    }
+
+    /// Completes a device authorization once polling finished.
+    async fn complete_device_authentication(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        provider: Provider,
+        token_result: crate::Result<OidcTokenResponse>,
+    ) -> Result<String> {
+        let result = match token_result {
+            Ok(token_result) => {
+                self.auth_manager
+                    .complete_device_auth_flow(provider.clone(), &token_result, &self.config)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        self.finish_authentication(emitter, Some(&provider), result)
+            .await
+    }
+
+    /// Applies a user edit to an account, saves it and notifies subscribers.
+    async fn update_account(&self, id: &Uuid, edit: impl FnOnce(&mut Account)) -> Result<()> {
+        self.try_update_account(id, |account| {
+            edit(account);
+            Ok(())
         })
+        .await
+        .map(drop)
+    }
+
+    /// Applies an edit which may refuse the change to an account under one lock, saving
+    /// it, and notifies subscribers. Returns the account as saved.
+    async fn try_update_account(
+        &self,
+        id: &Uuid,
+        edit: impl FnOnce(&mut Account) -> Result<()>,
+    ) -> Result<Account> {
+        let mut refused = Ok(());
+        let edited = self
+            .config_mut()
+            .edit_account(id, |account| {
+                refused = edit(account);
+                refused.is_ok()
+            })
+            .map_err(|e| Error::AccountNotUpdated(format!("Account {id} not updated: {}", e)))?;
+        refused?;
+        let Some(account) = edited else {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        };
+        self.account_changed_soon(*id);
+        Ok(account)
     }
+
+    /// Applies an edit to every account, saving them at once, and notifies subscribers of
+    /// the accounts the edit changed.
+    async fn edit_accounts(&self, edit: impl FnMut(&mut Account) -> bool) -> Result<()> {
+        let edited = self
+            .config_mut()
+            .edit_accounts(edit)
+            .map_err(|e| AccountsError::Failed(format!("Accounts not updated: {}", e)))?;
+        for account in edited {
+            self.account_changed_soon(account.id);
+        }
+        Ok(())
+    }
+
+    /// Tells subscribers `ThrottledUntil` changed once a backoff ends, as nothing else
+    /// happens then.
+    fn throttled_until_changed_at(until: DateTime<Utc>) {
+        tokio::spawn(async move {
+            let wait = (until - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            let result = match Self::interface_ref().await {
+                Ok(interface) => {
+                    let this = interface.get().await;
+                    this.throttled_until_changed(interface.signal_emitter())
+                        .await
+                        .map_err(Into::into)
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                tracing::warn!("Failed to signal the end of a backoff: {}", err);
+            }
+        });
+    }
+
+    /// Emits `AccountChanged` once, with the account as it is then, for every change made to
+    /// it within [`CHANGE_DEBOUNCE`] of this one.
+    fn account_changed_soon(&self, id: Uuid) {
+        if !self.changed_accounts.lock().unwrap().insert(id) {
+            return;
+        }
+        let this = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(CHANGE_DEBOUNCE).await;
+            this.changed_accounts.lock().unwrap().remove(&id);
+            // Subscribers were told about the removal instead
+            let Some(account) = this.account(&id) else {
+                return;
+            };
+            let result = match Self::interface_ref().await {
+                Ok(interface) => Self::account_changed(interface.signal_emitter(), &account.into())
+                    .await
+                    .map_err(Into::into),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                tracing::warn!("Failed to signal the change of account {}: {}", id, err);
+            }
+        });
+    }
+
+    /// Updates when an account was last used and notifies subscribers.
+    ///
+    /// Unless forced, uses within [`LAST_USED_RESOLUTION`] of the previous one are not
+    /// saved, to avoid rewriting the config on every token request.
+    async fn mark_used(&self, id: &Uuid, force: bool) -> Result<()> {
+        let mut found = false;
+        let edited = self
+            .config_mut()
+            .edit_account(id, |account| {
+                found = true;
+                if !force && recently_used(account) {
+                    return false;
+                }
+                account.last_used = Some(Utc::now());
+                true
+            })
+            .map_err(|e| AccountsError::Failed(format!("Failed to save account: {}", e)))?;
+        if !found {
+            return Err(Error::AccountNotFound(id.to_string()).into());
+        }
+        if edited.is_some() {
+            self.account_changed_soon(*id);
+        }
+        Ok(())
+    }
+
+    /// Whether the daemon has no accounts and no sign in in progress.
+    pub async fn is_idle() -> bool {
+        let Ok(interface) = Self::interface_ref().await else {
+            return false;
+        };
+        let this = interface.get().await;
+        this.config.read().unwrap().accounts.is_empty() && !this.auth_manager.has_pending_auth()
+    }
+
+    /// The interface instance served on the bus, used to share its state outside D-Bus calls.
+    async fn interface_ref() -> Result<InterfaceRef<Self>> {
+        let Some(connection) = CONNECTION.get() else {
+            return Err(AccountsError::Failed(
+                "D-Bus connection not available".to_string(),
+            ));
+        };
+        connection
+            .object_server()
+            .interface::<_, Self>(OBJECT_PATH)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Returns valid credentials of an account, refreshing them if necessary, and records
+    /// the outcome of the refresh on the account.
+    async fn valid_credentials(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        account: &Account,
+    ) -> Result<Credential> {
+        match self.auth_manager.ensure_credentials(account).await {
+            Ok(credentials) => {
+                self.update_refresh_state(emitter, &account.id, None)
+                    .await?;
+                self.mark_used(&account.id, false).await?;
+                Ok(credentials)
+            }
+            Err(err) => {
+                self.update_refresh_state(emitter, &account.id, Some(&err))
+                    .await?;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Persists the outcome of a token refresh on the account and notifies subscribers.
+    ///
+    /// Errors never clear the attention needed state, only a successful refresh or
+    /// signing in again does.
+    async fn update_refresh_state(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        id: &Uuid,
+        error: Option<&Error>,
+    ) -> Result<()> {
+        let mut had_error = false;
+        let mut newly_needs_attention = false;
+        let edited = self
+            .config_mut()
+            .edit_account(id, |account| {
+                had_error = account.last_error.is_some();
+                let attention_needed = error.is_some_and(|error| {
+                    account.attention_needed || self.auth_manager.needs_attention(id, error)
+                });
+                let last_error = error.map(ToString::to_string);
+                if account.attention_needed == attention_needed && account.last_error == last_error
+                {
+                    return false;
+                }
+                newly_needs_attention = attention_needed && !account.attention_needed;
+                account.attention_needed = attention_needed;
+                account.last_error = last_error;
+                true
+            })
+            .map_err(|e| AccountsError::Failed(format!("Failed to save account: {}", e)))?;
+        // Backoffs start and end with errors, and their end is not part of the account
+        if error.is_some() || had_error {
+            self.throttled_until_changed(emitter).await?;
+        }
+        if error.is_some()
+            && let Some(until) = self.auth_manager.throttled_until(id)
+        {
+            Self::throttled_until_changed_at(until);
+        }
+        let Some(account) = edited else {
+            return Ok(());
+        };
+
+        self.account_changed_soon(account.id);
+        if newly_needs_attention {
+            emitter.account_attention_needed(&account.into()).await?;
+        }
+        self.accounts_needing_attention_changed(emitter).await?;
+        Ok(())
+    }
+}
+
+/// The fields of an account, as in the dict it is sent as.
+fn account_fields(account: &DbusAccount) -> zvariant::Result<HashMap<String, OwnedValue>> {
+    let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+    let (fields, _) = zvariant::to_bytes(ctxt, account)?.deserialize()?;
+    Ok(fields)
+}
+
+fn recently_used(account: &Account) -> bool {
+    account
+        .last_used
+        .is_some_and(|last_used| Utc::now() - last_used < LAST_USED_RESOLUTION)
+}
+
+/// The address of an account, providers which don't report one sign in with it instead.
+fn account_email(account: &Account) -> Option<&str> {
+    account.email.as_deref().or_else(|| {
+        account
+            .username
+            .contains('@')
+            .then_some(account.username.as_str())
+    })
+}
+
+/// Whether the account is locked, by the user or by the template it was provisioned from.
+fn is_locked(templates: &[AccountTemplate], account: &Account) -> bool {
+    account.locked || template_locks(templates, account)
+}
+
+/// Whether the template the account was provisioned from keeps it locked.
+fn template_locks(templates: &[AccountTemplate], account: &Account) -> bool {
+    templates
+        .iter()
+        .any(|template| account.template.as_ref() == Some(&template.name) && template.locked)
 }
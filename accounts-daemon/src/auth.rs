@@ -1,67 +1,303 @@
 use accounts::{
-    config::AccountsConfig,
-    models::{Account, Credential, Provider},
+    config::AccountsStore,
+    models::{Account, Credential, Provider, SecretString, Service},
+    scopes,
 };
-use chrono::{Duration, Utc};
-use oauth2::basic::BasicClient;
+use chrono::{DateTime, Duration, Utc};
+use oauth2::basic::BasicErrorResponseType;
 use oauth2::reqwest::async_http_client;
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
-    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse, TokenUrl,
+    AccessToken, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    DeviceAuthorizationUrl, HttpRequest, HttpResponse, PkceCodeChallenge, PkceCodeVerifier,
+    RedirectUrl, RefreshToken, RequestTokenError, RevocationUrl, Scope,
+    StandardDeviceAuthorizationResponse, StandardErrorResponse, StandardRevocableToken,
+    TokenResponse, TokenUrl,
 };
 use reqwest;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use url::Url;
 use uuid::Uuid;
 
-use crate::models::AccountProviderConfig;
-use crate::{error::*, models::ProviderConfig, storage::CredentialStorage};
+use crate::callback::{AUTH_TIMEOUT, CallbackServer};
+use crate::goa::GoaAccount;
+use crate::oidc::{IdTokenClaims, OidcClient, OidcTokenResponse, decode_id_token};
+use crate::ratelimit::{self, Priority};
+use crate::uoa::UoaAccount;
+use crate::{avatar, paths, settings, usage};
+use crate::{
+    error::*,
+    models::{ProviderConfig, ProviderConfigFile},
+    storage::CredentialStorage,
+};
+
+/// Number of consecutive refresh failures after which an account needs attention.
+pub const MAX_REFRESH_FAILURES: u32 = 3;
+
+/// How long refreshes are held back after a provider failed once, doubled after each
+/// further failure up to [`MAX_BACKOFF`].
+const BACKOFF: Duration = Duration::seconds(30);
+const MAX_BACKOFF: Duration = Duration::minutes(30);
+
+/// Attempts at a token request which couldn't be sent.
+const TOKEN_ATTEMPTS: u32 = 2;
+/// Pause before a token request is sent again, up to twice as long with jitter.
+const TOKEN_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+type HttpClientError = oauth2::reqwest::Error<reqwest::Error>;
+/// A provider config, or the problems keeping it from being used.
+pub type ConfigResult = std::result::Result<Arc<ProviderConfig>, Vec<String>>;
+
+type TokenError = RequestTokenError<HttpClientError, StandardErrorResponse<BasicErrorResponseType>>;
+
+/// Consecutive failures of a provider to refresh the tokens of an account.
+struct Backoff {
+    failures: u32,
+    until: DateTime<Utc>,
+}
 
 pub struct AuthManager {
-    configs: HashMap<Provider, ProviderConfig>,
-    pending_auth: HashMap<String, (Provider, PkceCodeVerifier)>,
+    /// The provider configs read so far, or the problems keeping them from being used.
+    configs: tokio::sync::Mutex<HashMap<Provider, ConfigResult>>,
+    callback_server: Mutex<Option<CallbackServer>>,
+    pending_auth: Mutex<HashMap<String, PendingAuth>>,
+    /// CSRF tokens of flows which completed, were cancelled or expired, and when, so a
+    /// replayed redirect can be told apart from an unknown one.
+    closed_auth: Mutex<HashMap<String, Instant>>,
+    refresh_failures: Mutex<HashMap<Uuid, u32>>,
+    /// Accounts whose provider failed to refresh their tokens, which are not refreshed
+    /// again until the backoff ends so an outage doesn't flood the provider and the logs.
+    backoffs: Mutex<HashMap<Uuid, Backoff>>,
+    /// Serializes token refreshes per account so concurrent callers share one refresh.
+    refresh_locks: Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>,
+    /// Credentials with a rotated refresh token the store failed to save. They are used
+    /// instead of the stored ones, whose refresh token the provider may no longer accept,
+    /// until saving them succeeds.
+    unsaved: Mutex<HashMap<Uuid, Credential>>,
+    /// Wakes [`AuthManager::save_unsaved_credentials`] once credentials fail to save.
+    unsaved_added: tokio::sync::Notify,
     storage: CredentialStorage,
-    config: AccountsConfig,
+}
+
+/// An authorization request waiting for the provider to redirect back.
+struct PendingAuth {
+    provider: Provider,
+    pkce_verifier: PkceCodeVerifier,
+    redirect_uri: RedirectUrl,
+    /// The existing account being re-authenticated, if any.
+    account_id: Option<Uuid>,
+    started_at: Instant,
+}
+
+impl PendingAuth {
+    fn is_expired(&self) -> bool {
+        self.started_at.elapsed() >= AUTH_TIMEOUT
+    }
+}
+
+/// Where an authorization code flow stands, looked up by its CSRF token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFlowState {
+    Pending,
+    /// The flow completed, was cancelled or expired, its token can't be used again.
+    Closed,
+    Unknown,
+}
+
+/// A device authorization waiting for the user to enter the code on another device.
+pub struct DeviceAuthorization {
+    pub provider: Provider,
+    client: OidcClient,
+    details: StandardDeviceAuthorizationResponse,
+}
+
+impl DeviceAuthorization {
+    pub fn user_code(&self) -> &str {
+        self.details.user_code().secret()
+    }
+
+    pub fn verification_uri(&self) -> String {
+        self.details.verification_uri().url().to_string()
+    }
+
+    /// Polls the token endpoint until the user approves or denies the request, or the
+    /// device code expires.
+    pub async fn poll(&self) -> Result<OidcTokenResponse> {
+        self.client
+            .exchange_device_access_token(&self.details)
+            .request_async(async_http_client, tokio::time::sleep, None)
+            .await
+            .map_err(|e| Error::DeviceAuthorization(e.to_string()))
+    }
 }
 
 impl AuthManager {
     pub async fn new() -> Result<Self> {
-        let mut configs = HashMap::new();
-
-        for provider in Provider::list() {
-            let config_path =
-                Path::new("accounts-daemon/data/providers").join(provider.file_name());
-            if !config_path.exists() {
-                tracing::error!("Provider config file not found: {}", config_path.display());
-                continue;
-            }
-            let content = std::fs::read_to_string(config_path)?;
-            let toml_config: AccountProviderConfig = toml::from_str(&content)?;
-            configs.insert(provider.clone(), toml_config.provider);
+        let auth = Self::with_storage(CredentialStorage::new().await?);
+        auth.restore_rescued_credentials().await;
+        Ok(auth)
+    }
+
+    fn with_storage(storage: CredentialStorage) -> Self {
+        Self {
+            configs: tokio::sync::Mutex::new(HashMap::new()),
+            callback_server: Mutex::new(None),
+            pending_auth: Mutex::new(HashMap::new()),
+            closed_auth: Mutex::new(HashMap::new()),
+            refresh_failures: Mutex::new(HashMap::new()),
+            backoffs: Mutex::new(HashMap::new()),
+            refresh_locks: Mutex::new(HashMap::new()),
+            unsaved: Mutex::new(HashMap::new()),
+            unsaved_added: tokio::sync::Notify::new(),
+            storage,
         }
+    }
 
-        Ok(Self {
-            configs,
-            pending_auth: HashMap::new(),
-            storage: CredentialStorage::new().await?,
-            config: AccountsConfig::config(),
-        })
+    /// Keeps the credentials in memory only, for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::with_storage(CredentialStorage::with_store(Box::new(
+            crate::storage::MemoryStore::default(),
+        )))
     }
 
-    pub async fn start_auth_flow(&mut self, provider: Provider) -> Result<String> {
-        let config = self
-            .configs
-            .get(&provider)
-            .ok_or(Error::InvalidProviderConfig)?;
+    /// Where the credentials are stored.
+    pub fn storage(&self) -> &CredentialStorage {
+        &self.storage
+    }
 
-        let client = BasicClient::new(
-            ClientId::new(config.client_id.clone()),
-            Some(ClientSecret::new(config.client_secret.clone())),
-            AuthUrl::new(config.auth_url.clone())?,
-            Some(TokenUrl::new(config.token_url.clone())?),
+    /// The config of a provider, read the first time it is needed.
+    async fn config(&self, provider: &Provider) -> Result<Arc<ProviderConfig>> {
+        self.load_config(*provider)
+            .await
+            .map_err(|_| Error::InvalidProviderConfig)
+    }
+
+    /// Reads the config of a provider unless it was, returning the problems keeping it from
+    /// being used, which `ValidateProviders` reports.
+    ///
+    /// Unusable files are not read again, the daemon only uses changes to them once
+    /// restarted. A client secret which failed to resolve, e.g. while the keyring is
+    /// locked, is resolved again on the next use.
+    pub async fn load_config(&self, provider: Provider) -> ConfigResult {
+        let mut configs = self.configs.lock().await;
+        if let Some(config) = configs.get(&provider) {
+            return config.clone();
+        }
+        let ProviderConfigFile {
+            path,
+            config,
+            errors,
+        } = ProviderConfigFile::load(provider);
+        let Some(mut config) = config else {
+            tracing::error!(
+                "Provider config {} is not usable: {}",
+                path.display(),
+                errors.join("; ")
+            );
+            configs.insert(provider, Err(errors.clone()));
+            return Err(errors);
+        };
+        for warning in config.validate_scopes(provider) {
+            tracing::warn!("Provider config {}: {}", path.display(), warning);
+        }
+        match Self::resolve_client_secret(
+            &self.storage,
+            &provider,
+            &path,
+            config.client_secret.take(),
         )
-        .set_redirect_uri(RedirectUrl::new(config.redirect_uri.clone())?);
+        .await
+        {
+            Ok(client_secret) => config.client_secret = client_secret,
+            Err(err) => {
+                tracing::error!("Failed to resolve the {} client secret: {}", provider, err);
+                return Err(vec![format!("client_secret could not be resolved: {err}")]);
+            }
+        }
+        let config = Arc::new(config);
+        configs.insert(provider, Ok(config.clone()));
+        Ok(config)
+    }
+
+    /// Resolves a provider client secret from the environment or the keyring.
+    ///
+    /// Secrets still present in the user's own provider config are moved into the keyring
+    /// so they don't live in world-readable files. Configs of the administrator or of
+    /// packages are never rewritten, their secrets are used as they are.
+    async fn resolve_client_secret(
+        storage: &CredentialStorage,
+        provider: &Provider,
+        config_path: &Path,
+        config_secret: Option<SecretString>,
+    ) -> Result<Option<SecretString>> {
+        let variable = format!(
+            "COSMIC_ACCOUNTS_{}_CLIENT_SECRET",
+            provider.to_string().to_uppercase()
+        );
+        if let Ok(secret) = std::env::var(&variable) {
+            return Ok(Some(secret.into()));
+        }
+
+        let Some(secret) = config_secret else {
+            return storage.get_client_secret(provider).await;
+        };
+        if !user_owned(config_path) {
+            return Ok(Some(secret));
+        }
+
+        storage.set_client_secret(provider, &secret).await?;
+        match remove_client_secret(config_path) {
+            Ok(()) => tracing::info!("Moved the {} client secret to the keyring", provider),
+            Err(err) => tracing::warn!(
+                "Stored the {} client secret in the keyring but could not remove it from {}: {}",
+                provider,
+                config_path.display(),
+                err
+            ),
+        }
+        Ok(Some(secret))
+    }
+
+    pub async fn start_auth_flow(&self, provider: Provider) -> Result<String> {
+        self.authorize(provider, None, &[]).await
+    }
+
+    /// Starts an authorization flow which updates the credentials of an existing account.
+    pub async fn start_reauth_flow(&self, account: &Account) -> Result<String> {
+        self.authorize(account.provider, Some(account), &[]).await
+    }
+
+    /// Starts an authorization flow for an existing account which also asks for the
+    /// scopes a service needs, when the provider config doesn't request them.
+    pub async fn start_scopes_flow(&self, account: &Account, service: &Service) -> Result<String> {
+        let required = scopes::required(account.provider, service);
+        self.authorize(account.provider, Some(account), required)
+            .await
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        fields(provider = %provider, account_id = ?account.map(|a| a.id), operation = "authorize")
+    )]
+    async fn authorize(
+        &self,
+        provider: Provider,
+        account: Option<&Account>,
+        additional_scopes: &[&str],
+    ) -> Result<String> {
+        self.prune_expired_auth();
+        let config = self.config(&provider).await?;
+        let redirect_uri = match &config.native_redirect_uri {
+            Some(native_redirect_uri) => RedirectUrl::new(native_redirect_uri.clone())?,
+            None => Self::redirect_uri(&config, self.callback_port().await?)?,
+        };
+
+        let client = oauth_client(&config)?.set_redirect_uri(redirect_uri.clone());
 
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
@@ -72,88 +308,390 @@ impl AuthManager {
         for scope in &config.scopes {
             auth_request = auth_request.add_scope(Scope::new(scope.clone()));
         }
+        let additional_scopes: Vec<_> = additional_scopes
+            .iter()
+            .filter(|scope| !config.scopes.iter().any(|configured| configured == *scope))
+            .collect();
+        for scope in &additional_scopes {
+            auth_request = auth_request.add_scope(Scope::new(scope.to_string()));
+        }
+        // Google keeps the scopes granted before when the new ones are added
+        if !additional_scopes.is_empty() && matches!(provider, Provider::Google) {
+            auth_request = auth_request.add_extra_param("include_granted_scopes", "true");
+        }
 
         // Add access_type=offline for Google to get refresh tokens
         if matches!(provider, Provider::Google) {
             auth_request = auth_request.add_extra_param("access_type", "offline");
         }
 
+        if let Some(account) = account {
+            let login_hint = account.email.as_ref().unwrap_or(&account.username);
+            auth_request = auth_request.add_extra_param("login_hint", login_hint);
+            // Google only issues a new refresh token when consent is requested again
+            if matches!(provider, Provider::Google) {
+                auth_request = auth_request.add_extra_param("prompt", "consent");
+            }
+        }
+
         let (auth_url, csrf_token) = auth_request.url();
 
         // Store the PKCE verifier for later use
-        self.pending_auth
-            .insert(csrf_token.secret().clone(), (provider, pkce_verifier));
+        self.pending_auth.lock().unwrap().insert(
+            csrf_token.secret().clone(),
+            PendingAuth {
+                provider,
+                pkce_verifier,
+                redirect_uri,
+                account_id: account.map(|account| account.id),
+                started_at: Instant::now(),
+            },
+        );
 
         Ok(auth_url.to_string())
     }
 
+    /// Exchanges the authorization code and returns the new or re-authenticated account.
+    #[tracing::instrument(skip_all, fields(operation = "complete_auth_flow"))]
     pub async fn complete_auth_flow(
-        &mut self,
+        &self,
         csrf_token: String,
         authorization_code: String,
+        accounts: &RwLock<AccountsStore>,
     ) -> Result<Account> {
-        let (provider, pkce_verifier) =
-            self.pending_auth
-                .remove(&csrf_token)
-                .ok_or_else(|| Error::AuthenticationFailed {
-                    reason: "Invalid CSRF token".to_string(),
-                })?;
-
-        let config = self
-            .configs
-            .get(&provider)
-            .ok_or(Error::InvalidProviderConfig)?;
-
-        let client = BasicClient::new(
-            ClientId::new(config.client_id.clone()),
-            Some(ClientSecret::new(config.client_secret.clone())),
-            AuthUrl::new(config.auth_url.clone())?,
-            Some(TokenUrl::new(config.token_url.clone())?),
-        )
-        .set_redirect_uri(RedirectUrl::new(config.redirect_uri.clone())?);
+        let pending = self
+            .pending_auth
+            .lock()
+            .unwrap()
+            .remove(&csrf_token)
+            .ok_or_else(|| Error::AuthenticationFailed {
+                reason: "Invalid CSRF token".to_string(),
+            })?;
+        self.close_auth([csrf_token]);
+        self.prune_expired_auth();
+
+        if pending.is_expired() {
+            return Err(Error::AuthenticationFailed {
+                reason: "Authentication session expired".to_string(),
+            });
+        }
+
+        let PendingAuth {
+            provider,
+            pkce_verifier,
+            redirect_uri,
+            account_id,
+            ..
+        } = pending;
+
+        let config = self.config(&provider).await?;
+
+        let client = oauth_client(&config)?.set_redirect_uri(redirect_uri);
+
+        let token_result = request_token(provider, Priority::SignIn, || {
+            client
+                .exchange_code(AuthorizationCode::new(authorization_code.clone()))
+                .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.secret().clone()))
+                .request_async(|request| token_http_client(None, request))
+        })
+        .await?;
+
+        self.account_from_token(provider, &token_result, account_id, accounts)
+            .await
+    }
 
-        let token_result = client
-            .exchange_code(AuthorizationCode::new(authorization_code))
-            .set_pkce_verifier(pkce_verifier)
+    pub fn has_pending_auth(&self) -> bool {
+        !self.pending_auth.lock().unwrap().is_empty()
+    }
+
+    /// Returns the providers of the pending authorization code flows.
+    pub fn pending_providers(&self) -> Vec<Provider> {
+        self.pending_auth
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|pending| !pending.is_expired())
+            .map(|pending| pending.provider)
+            .collect()
+    }
+
+    /// Returns where the flow started with a CSRF token stands.
+    pub fn auth_flow_state(&self, csrf_token: &str) -> AuthFlowState {
+        if let Some(pending) = self.pending_auth.lock().unwrap().get(csrf_token) {
+            return if pending.is_expired() {
+                AuthFlowState::Closed
+            } else {
+                AuthFlowState::Pending
+            };
+        }
+        if self.closed_auth.lock().unwrap().contains_key(csrf_token) {
+            AuthFlowState::Closed
+        } else {
+            AuthFlowState::Unknown
+        }
+    }
+
+    /// Returns the provider of the pending flow started with a CSRF token.
+    pub fn pending_provider(&self, csrf_token: &str) -> Option<Provider> {
+        self.pending_auth
+            .lock()
+            .unwrap()
+            .get(csrf_token)
+            .map(|pending| pending.provider)
+    }
+
+    /// Cancels the pending flows matching a CSRF token or a provider name, returning the
+    /// providers of the cancelled flows.
+    pub fn cancel_auth_flow(&self, csrf_token_or_provider: &str) -> Vec<Provider> {
+        let provider = Provider::from_str(csrf_token_or_provider);
+        let mut cancelled = Vec::new();
+        let mut closed = Vec::new();
+        self.pending_auth
+            .lock()
+            .unwrap()
+            .retain(|csrf_token, pending| {
+                let matches =
+                    csrf_token == csrf_token_or_provider || Some(pending.provider) == provider;
+                if matches {
+                    cancelled.push(pending.provider);
+                    closed.push(csrf_token.clone());
+                }
+                !matches
+            });
+        self.close_auth(closed);
+        self.prune_expired_auth();
+        cancelled
+    }
+
+    /// Drops abandoned flows and stops the callback server once nothing is pending.
+    fn prune_expired_auth(&self) {
+        let mut expired = Vec::new();
+        let mut pending_auth = self.pending_auth.lock().unwrap();
+        pending_auth.retain(|csrf_token, pending| {
+            if pending.is_expired() {
+                expired.push(csrf_token.clone());
+            }
+            !pending.is_expired()
+        });
+        if pending_auth.is_empty() {
+            *self.callback_server.lock().unwrap() = None;
+        }
+        drop(pending_auth);
+
+        self.close_auth(expired);
+        self.closed_auth
+            .lock()
+            .unwrap()
+            .retain(|_, closed_at| closed_at.elapsed() < AUTH_TIMEOUT);
+    }
+
+    /// Remembers the CSRF tokens of flows which can no longer be completed.
+    fn close_auth(&self, csrf_tokens: impl IntoIterator<Item = String>) {
+        let now = Instant::now();
+        self.closed_auth
+            .lock()
+            .unwrap()
+            .extend(csrf_tokens.into_iter().map(|csrf_token| (csrf_token, now)));
+    }
+
+    /// Returns the port of the callback server, starting it if it isn't running.
+    async fn callback_port(&self) -> Result<u16> {
+        if let Some(port) = self.running_callback_port() {
+            return Ok(port);
+        }
+
+        let server = CallbackServer::start().await?;
+        // Another flow may have started a server meanwhile, keep the one in use
+        if let Some(port) = self.running_callback_port() {
+            return Ok(port);
+        }
+        let port = server.port();
+        *self.callback_server.lock().unwrap() = Some(server);
+        Ok(port)
+    }
+
+    fn running_callback_port(&self) -> Option<u16> {
+        match &*self.callback_server.lock().unwrap() {
+            Some(server) if server.is_running() => {
+                server.extend();
+                Some(server.port())
+            }
+            _ => None,
+        }
+    }
+
+    /// Builds the redirect URI pointing at the callback server for this session.
+    ///
+    /// A `{port}` placeholder in the configured URI is replaced with the port, otherwise the
+    /// port of the URI is.
+    fn redirect_uri(config: &ProviderConfig, callback_port: u16) -> Result<RedirectUrl> {
+        if config.redirect_uri.contains("{port}") {
+            let redirect_uri = config
+                .redirect_uri
+                .replace("{port}", &callback_port.to_string());
+            return Ok(RedirectUrl::new(redirect_uri)?);
+        }
+        let mut redirect_uri = Url::parse(&config.redirect_uri)?;
+        redirect_uri
+            .set_port(Some(callback_port))
+            .map_err(|_| Error::InvalidProviderConfig)?;
+        Ok(RedirectUrl::from_url(redirect_uri))
+    }
+
+    /// Requests a device code the user can enter on another device to authorize an account.
+    #[tracing::instrument(skip_all, fields(provider = %provider, operation = "device_auth_flow"))]
+    pub async fn start_device_auth_flow(&self, provider: Provider) -> Result<DeviceAuthorization> {
+        let config = self.config(&provider).await?;
+
+        let device_auth_url = config.device_auth_url.clone().ok_or_else(|| {
+            Error::DeviceAuthorization(format!("{provider} does not support device authorization"))
+        })?;
+
+        let client = oauth_client(&config)?
+            .set_device_authorization_url(DeviceAuthorizationUrl::new(device_auth_url)?);
+
+        let mut device_request = client
+            .exchange_device_code()
+            .map_err(|e| Error::DeviceAuthorization(e.to_string()))?;
+
+        for scope in &config.scopes {
+            device_request = device_request.add_scope(Scope::new(scope.clone()));
+        }
+
+        ratelimit::acquire(provider, Priority::SignIn).await?;
+        let details: StandardDeviceAuthorizationResponse = device_request
             .request_async(async_http_client)
-            .await?;
+            .await
+            .map_err(|e| Error::DeviceAuthorization(e.to_string()))?;
+
+        Ok(DeviceAuthorization {
+            provider,
+            client,
+            details,
+        })
+    }
+
+    /// Creates the account for a token obtained through device authorization.
+    pub async fn complete_device_auth_flow(
+        &self,
+        provider: Provider,
+        token_result: &OidcTokenResponse,
+        accounts: &RwLock<AccountsStore>,
+    ) -> Result<Account> {
+        self.account_from_token(provider, token_result, None, accounts)
+            .await
+    }
+
+    /// Creates or updates the account the token was issued for and stores its credentials.
+    async fn account_from_token(
+        &self,
+        provider: Provider,
+        token_result: &OidcTokenResponse,
+        account_id: Option<Uuid>,
+        accounts: &RwLock<AccountsStore>,
+    ) -> Result<Account> {
+        let scopes = self.config(&provider).await?.scopes.clone();
 
         let access_token = token_result.access_token().secret();
-        let refresh_token = token_result.refresh_token().map(|t| t.secret().clone());
+        let refresh_token = token_result
+            .refresh_token()
+            .map(|t| SecretString::from(t.secret().as_str()));
         let expires_at = token_result
             .expires_in()
             .map(|duration| Utc::now() + Duration::seconds(duration.as_secs() as i64));
 
-        // Get user information
-        let user_info = self.get_user_info(&provider, access_token).await?;
+        // Prefer the ID token claims, saving a round trip to the userinfo endpoint
+        let user_info = match &token_result.extra_fields().id_token {
+            Some(id_token) => {
+                let config = self.config(&provider).await?;
+                UserInfo::from(decode_id_token(id_token, &config)?)
+            }
+            None => self.get_user_info(&provider, access_token).await?,
+        };
 
-        if self.config.account_exists(&user_info.username, &provider) {
-            return Err(Error::AccountAlreadyExists);
-        }
+        // Providers may grant fewer scopes than requested, they omit them when they are the same
+        let scopes = token_result
+            .scopes()
+            .map(|granted| granted.iter().map(|scope| (**scope).clone()).collect())
+            .unwrap_or(scopes);
 
         let credentials = Credential {
-            access_token: access_token.clone(),
+            access_token: access_token.as_str().into(),
             refresh_token,
             expires_at,
-            scope: config.scopes.clone(),
+            scope: scopes,
             token_type: "Bearer".to_string(),
         };
+        let picture = user_info.picture.clone();
 
-        let account = Account {
-            id: Uuid::new_v4(),
-            provider: provider.clone(),
-            display_name: user_info.display_name,
-            username: user_info.username,
-            email: user_info.email,
-            enabled: true,
-            created_at: Utc::now(),
-            last_used: Some(Utc::now()),
-            services: provider.services(),
+        let mut account = match account_id {
+            Some(account_id) => {
+                let mut account = accounts
+                    .read()
+                    .unwrap()
+                    .get_account(&account_id)
+                    .ok_or_else(|| Error::AccountNotFound(account_id.to_string()))?;
+                let same_user = match (&account.remote_id, &user_info.remote_id) {
+                    (Some(remote_id), Some(signed_in)) => remote_id == signed_in,
+                    _ => account.username == user_info.username,
+                };
+                if !same_user {
+                    return Err(Error::AuthenticationFailed {
+                        reason: format!(
+                            "Signed in as {} instead of {}",
+                            user_info.username, account.username
+                        ),
+                    });
+                }
+                // The display name is left alone, users may have renamed the account
+                account.email = user_info.email;
+                account.remote_id = account.remote_id.or(user_info.remote_id);
+                account.last_used = Some(Utc::now());
+                account.attention_needed = false;
+                account.last_error = None;
+                self.refresh_failures.lock().unwrap().remove(&account.id);
+                account
+            }
+            None => {
+                if accounts.read().unwrap().account_exists(
+                    user_info.remote_id.as_deref(),
+                    &user_info.username,
+                    &provider,
+                ) {
+                    return Err(Error::AccountAlreadyExists);
+                }
+                Account {
+                    id: Uuid::new_v4(),
+                    provider: provider.clone(),
+                    display_name: user_info.display_name,
+                    username: user_info.username,
+                    email: user_info.email,
+                    enabled: true,
+                    created_at: Utc::now(),
+                    last_used: Some(Utc::now()),
+                    services: provider.services(),
+                    attention_needed: false,
+                    last_error: None,
+                    remote_id: user_info.remote_id,
+                    label: None,
+                    color: None,
+                    service_settings: BTreeMap::new(),
+                    app_access: BTreeMap::new(),
+                    template: None,
+                    avatar: None,
+                    order: 0,
+                    group: None,
+                    sync_on_metered: false,
+                    locked: false,
+                }
+            }
         };
 
-        self.storage
-            .set_account_credentials(&account.id, &credentials)
-            .await?;
+        self.save_credentials(&account.id, &credentials).await?;
+        // Replaced on every sign in, the user may have changed their picture
+        account.avatar = avatar::fetch(&account.id, provider, picture.as_deref(), access_token)
+            .await
+            .or(account.avatar);
 
         Ok(account)
     }
@@ -166,6 +704,7 @@ impl AuthManager {
             Provider::Microsoft => "https://graph.microsoft.com/v1.0/me",
         };
 
+        ratelimit::acquire(*provider, Priority::SignIn).await?;
         let response = client
             .get(user_info_url)
             .bearer_auth(access_token)
@@ -188,6 +727,8 @@ impl AuthManager {
                 display_name: user_data["name"].as_str().unwrap_or("Unknown").to_string(),
                 username: user_data["email"].as_str().unwrap_or("Unknown").to_string(),
                 email: user_data["email"].as_str().map(|s| s.to_string()),
+                remote_id: user_data["id"].as_str().map(|s| s.to_string()),
+                picture: user_data["picture"].as_str().map(|s| s.to_string()),
             },
             Provider::Microsoft => UserInfo {
                 display_name: user_data["displayName"]
@@ -202,83 +743,890 @@ impl AuthManager {
                     .as_str()
                     .or_else(|| user_data["userPrincipalName"].as_str())
                     .map(|s| s.to_string()),
+                remote_id: user_data["id"].as_str().map(|s| s.to_string()),
+                picture: None,
             },
         };
 
         Ok(user_info)
     }
 
-    pub async fn refresh_token(&self, account: &Account) -> Result<()> {
-        let config = self
-            .configs
-            .get(&account.provider)
-            .ok_or(Error::InvalidProviderConfig)?;
+    #[tracing::instrument(
+        skip_all,
+        fields(account_id = %account.id, provider = %account.provider, operation = "refresh_token")
+    )]
+    pub async fn refresh_token(&self, account: &Account) -> Result<Credential> {
+        let result = self.request_token_refresh(account).await;
+        // The provider wasn't asked, it didn't fail
+        if let Err(Error::RateLimited { .. }) = result {
+            return result;
+        }
+        self.update_backoff(&account.id, result.as_ref().err());
+        match &result {
+            Ok(_) => {
+                self.refresh_failures.lock().unwrap().remove(&account.id);
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to refresh token for account {}: {}",
+                    account.id,
+                    err
+                );
+                *self
+                    .refresh_failures
+                    .lock()
+                    .unwrap()
+                    .entry(account.id)
+                    .or_default() += 1;
+            }
+        }
+        result
+    }
 
-        let mut credentials = self.storage.get_account_credentials(&account.id).await?;
+    /// Whether the error means the user has to sign in to the account again.
+    pub fn needs_attention(&self, account_id: &Uuid, error: &Error) -> bool {
+        matches!(error, Error::ReauthenticationRequired { .. })
+            || self
+                .refresh_failures
+                .lock()
+                .unwrap()
+                .get(account_id)
+                .is_some_and(|failures| *failures >= MAX_REFRESH_FAILURES)
+    }
 
-        let refresh_token =
-            credentials
-                .refresh_token
-                .as_ref()
-                .ok_or_else(|| Error::TokenExpired {
-                    account_id: account.id.to_string(),
-                })?;
+    /// Starts or extends the backoff of an account after a refresh failed, and ends it once
+    /// one succeeded. Rejected refresh tokens are not retried at all, so they don't count.
+    fn update_backoff(&self, account_id: &Uuid, error: Option<&Error>) {
+        let mut backoffs = self.backoffs.lock().unwrap();
+        match error {
+            None => {
+                backoffs.remove(account_id);
+            }
+            Some(Error::ReauthenticationRequired { .. }) => {}
+            Some(Error::OAuth2(RequestTokenError::ServerResponse(response)))
+                if *response.error() == BasicErrorResponseType::InvalidGrant => {}
+            Some(_) => {
+                let failures = backoffs
+                    .get(account_id)
+                    .map_or(1, |backoff| backoff.failures + 1);
+                let delay = (BACKOFF * 2_i32.pow(failures.min(7) - 1)).min(MAX_BACKOFF);
+                tracing::debug!(
+                    "Holding back token refreshes of account {} for {}s",
+                    account_id,
+                    delay.num_seconds()
+                );
+                backoffs.insert(
+                    *account_id,
+                    Backoff {
+                        failures,
+                        until: Utc::now() + delay,
+                    },
+                );
+            }
+        }
+    }
 
-        let client = BasicClient::new(
-            ClientId::new(config.client_id.clone()),
-            Some(ClientSecret::new(config.client_secret.clone())),
-            AuthUrl::new(config.auth_url.clone())?,
-            Some(TokenUrl::new(config.token_url.clone())?),
-        );
+    /// Fails while the account is in backoff, instead of asking the provider.
+    ///
+    /// Only background work the daemon starts on its own checks it. Callers asking for a
+    /// token always reach the provider, so one failure doesn't lock them out, and their
+    /// refreshes end the backoff once the provider answers again.
+    pub fn check_backoff(&self, account_id: &Uuid) -> Result<()> {
+        match self.throttled_until(account_id) {
+            Some(until) => Err(Error::Throttled {
+                account_id: account_id.to_string(),
+                until,
+            }),
+            None => Ok(()),
+        }
+    }
 
-        let token_result = client
-            .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token.clone()))
-            .request_async(async_http_client)
-            .await?;
+    /// When the tokens of the account may be refreshed again, if it is in backoff.
+    pub fn throttled_until(&self, account_id: &Uuid) -> Option<DateTime<Utc>> {
+        self.backoffs
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .map(|backoff| backoff.until)
+            .filter(|until| *until > Utc::now())
+    }
+
+    /// The accounts in backoff and when it ends.
+    pub fn throttled(&self) -> HashMap<Uuid, DateTime<Utc>> {
+        let now = Utc::now();
+        self.backoffs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, backoff)| backoff.until > now)
+            .map(|(id, backoff)| (*id, backoff.until))
+            .collect()
+    }
+
+    async fn request_token_refresh(&self, account: &Account) -> Result<Credential> {
+        let config = self.config(&account.provider).await?;
+
+        let mut credentials = self.get_account_credentials(&account.id).await?;
+
+        let client = oauth_client(&config)?;
+
+        let mut reread = false;
+        let token_result = loop {
+            let refresh_token = credentials.refresh_token.clone().ok_or_else(|| {
+                Error::ReauthenticationRequired {
+                    account_id: account.id.to_string(),
+                }
+            })?;
+            let refresh_token = oauth2::RefreshToken::new(refresh_token.into_exposed());
+            match request_token(account.provider, Priority::Queued, || {
+                client
+                    .exchange_refresh_token(&refresh_token)
+                    .request_async(|request| token_http_client(Some(account.id), request))
+            })
+            .await
+            {
+                Ok(token_result) => break token_result,
+                Err(Error::OAuth2(RequestTokenError::ServerResponse(response)))
+                    if *response.error() == BasicErrorResponseType::InvalidGrant && !reread =>
+                {
+                    // Rotated by a refresh elsewhere meanwhile, the rejected one was stale
+                    let stored = self.get_account_credentials(&account.id).await?;
+                    if stored.refresh_token != credentials.refresh_token {
+                        credentials = stored;
+                        reread = true;
+                        continue;
+                    }
+                    return Err(refresh_error(
+                        account,
+                        Error::OAuth2(RequestTokenError::ServerResponse(response)),
+                    ));
+                }
+                Err(err) => return Err(refresh_error(account, err)),
+            }
+        };
 
-        credentials.access_token = token_result.access_token().secret().clone();
+        let rotated = token_result.refresh_token().is_some_and(|token| {
+            Some(token.secret().as_str())
+                != credentials.refresh_token.as_ref().map(SecretString::expose)
+        });
+        credentials.access_token = token_result.access_token().secret().as_str().into();
         if let Some(new_refresh_token) = token_result.refresh_token() {
-            credentials.refresh_token = Some(new_refresh_token.secret().clone());
+            credentials.refresh_token = Some(new_refresh_token.secret().as_str().into());
+        }
+        if let Some(scopes) = token_result.scopes() {
+            credentials.scope = scopes.iter().map(|scope| (**scope).clone()).collect();
         }
         credentials.expires_at = token_result
             .expires_in()
             .map(|duration| Utc::now() + Duration::seconds(duration.as_secs() as i64));
 
+        if rotated {
+            self.save_rotated_credentials(&account.id, &credentials)
+                .await;
+        } else {
+            self.save_credentials(&account.id, &credentials).await?;
+        }
+
+        Ok(credentials)
+    }
+
+    /// Saves the credentials of an account, replacing unsaved rotated ones.
+    async fn save_credentials(&self, account_id: &Uuid, credentials: &Credential) -> Result<()> {
         self.storage
-            .set_account_credentials(&account.id, &credentials)
+            .set_account_credentials(account_id, credentials)
             .await?;
-
+        if self.unsaved.lock().unwrap().remove(account_id).is_some()
+            && let Err(err) = self
+                .storage
+                .forget_rescued_account_credentials(account_id)
+                .await
+        {
+            tracing::warn!(
+                "Failed to delete the rescued credentials of account {}: {}",
+                account_id,
+                err
+            );
+        }
         Ok(())
     }
 
-    pub async fn ensure_credentials(&mut self, account: &mut Account) -> Result<()> {
-        // Check if token is expired and refresh if necessary
-        let credentials = self
+    /// Saves credentials with a refresh token the provider just rotated. The provider may
+    /// not accept the previous one anymore, so when the store fails they are kept and used
+    /// until saving them succeeds, rather than being lost with the account. They are
+    /// written to an encrypted file of their own too, to survive a restart until then.
+    async fn save_rotated_credentials(&self, account_id: &Uuid, credentials: &Credential) {
+        let Err(err) = self.save_credentials(account_id, credentials).await else {
+            return;
+        };
+        let rescued = self
             .storage
-            .get_account_credentials(&account.id)
-            .await
-            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+            .rescue_account_credentials(account_id, credentials)
+            .await;
+        match rescued {
+            Ok(()) => tracing::error!(
+                "Failed to save the rotated refresh token of account {}, keeping it in an encrypted file until it is: {}",
+                account_id,
+                err
+            ),
+            Err(rescue_err) => tracing::error!(
+                "Failed to save the rotated refresh token of account {}, keeping it in memory only: {}, {}",
+                account_id,
+                err,
+                rescue_err
+            ),
+        }
+        self.unsaved
+            .lock()
+            .unwrap()
+            .insert(*account_id, credentials.clone());
+        self.unsaved_added.notify_one();
+    }
 
-        if let Some(expires_at) = credentials.expires_at {
-            if expires_at <= Utc::now() {
-                self.refresh_token(&account).await?;
+    /// Uses the credentials rescued before the daemon restarted until they are saved.
+    async fn restore_rescued_credentials(&self) {
+        match self.storage.rescued_account_credentials().await {
+            Ok(rescued) if !rescued.is_empty() => {
+                self.unsaved.lock().unwrap().extend(rescued);
+                self.unsaved_added.notify_one();
             }
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Failed to read the rescued credentials: {}", err),
         }
-        Ok(())
+    }
+
+    /// Saves the rotated credentials the store failed to save again, waiting [`BACKOFF`]
+    /// first and twice as long after each failure, up to [`MAX_BACKOFF`]. Runs as long as
+    /// the daemon.
+    pub async fn save_unsaved_credentials(self: Arc<Self>) {
+        let mut delay = BACKOFF;
+        loop {
+            if self.unsaved.lock().unwrap().is_empty() {
+                delay = BACKOFF;
+                self.unsaved_added.notified().await;
+            }
+            tokio::time::sleep(delay.to_std().unwrap_or_default()).await;
+            if !self.save_unsaved().await {
+                delay = (delay * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    /// Tries to save each of the unsaved credentials once, returning whether all were.
+    async fn save_unsaved(&self) -> bool {
+        let account_ids: Vec<Uuid> = self.unsaved.lock().unwrap().keys().copied().collect();
+        for account_id in account_ids {
+            // A refresh in flight may rotate the credentials again
+            let refresh_lock = self.refresh_lock(&account_id);
+            let _guard = refresh_lock.lock().await;
+            let unsaved = self.unsaved.lock().unwrap().get(&account_id).cloned();
+            if let Some(credentials) = unsaved
+                && let Err(err) = self.save_credentials(&account_id, &credentials).await
+            {
+                tracing::debug!(
+                    "Failed to save the rotated credentials of account {} again: {}",
+                    account_id,
+                    err
+                );
+            }
+        }
+        self.unsaved.lock().unwrap().is_empty()
+    }
+
+    /// Returns valid credentials for the account, refreshing them if they are expired.
+    ///
+    /// Accounts waiting for the user to sign in again are not refreshed, their refresh
+    /// token was already rejected by the provider.
+    #[tracing::instrument(
+        skip_all,
+        fields(account_id = %account.id, provider = %account.provider, operation = "ensure_credentials")
+    )]
+    pub async fn ensure_credentials(&self, account: &Account) -> Result<Credential> {
+        let credentials = self.get_account_credentials(&account.id).await?;
+        if !is_expired(&credentials) {
+            return Ok(credentials);
+        }
+        if account.attention_needed {
+            return Err(Error::ReauthenticationRequired {
+                account_id: account.id.to_string(),
+            });
+        }
+
+        // Callers arriving while a refresh is in flight wait for it and reuse its tokens
+        let refresh_lock = self.refresh_lock(&account.id);
+        let _guard = refresh_lock.lock().await;
+        let credentials = self.get_account_credentials(&account.id).await?;
+        if !is_expired(&credentials) {
+            return Ok(credentials);
+        }
+        self.refresh_token(account).await
+    }
+
+    fn refresh_lock(&self, account_id: &Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.refresh_locks
+            .lock()
+            .unwrap()
+            .entry(*account_id)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns credentials limited to the scopes the service needs, falling back to the
+    /// account credentials when the provider doesn't define scopes for the service.
+    #[tracing::instrument(
+        skip_all,
+        fields(
+            account_id = %account.id,
+            provider = %account.provider,
+            service = %service,
+            operation = "service_credentials"
+        )
+    )]
+    pub async fn get_service_credentials(
+        &self,
+        account: &Account,
+        service: &Service,
+    ) -> Result<Credential> {
+        let config = self.config(&account.provider).await?;
+
+        let Some(scopes) = config.service_scopes.get(service).cloned() else {
+            return self.ensure_credentials(account).await;
+        };
+
+        if let Some(credentials) = self
+            .storage
+            .get_scoped_credentials(&account.id, &scopes)
+            .await?
+            && !is_expired(&credentials)
+        {
+            return Ok(credentials);
+        }
+
+        // Scoped tokens are minted from the account refresh token, which is renewed first
+        self.ensure_credentials(account).await?;
+        // Providers rotating refresh tokens rotate it here too, so this is a refresh as well
+        let refresh_lock = self.refresh_lock(&account.id);
+        let _guard = refresh_lock.lock().await;
+        let mut account_credentials = self.get_account_credentials(&account.id).await?;
+        let refresh_token = account_credentials.refresh_token.clone().ok_or_else(|| {
+            Error::ReauthenticationRequired {
+                account_id: account.id.to_string(),
+            }
+        })?;
+
+        let client = oauth_client(&config)?;
+
+        let refresh_token = oauth2::RefreshToken::new(refresh_token.into_exposed());
+        let token_result = match request_token(account.provider, Priority::Queued, || {
+            client
+                .exchange_refresh_token(&refresh_token)
+                .add_scopes(scopes.iter().cloned().map(Scope::new))
+                .request_async(|request| token_http_client(Some(account.id), request))
+        })
+        .await
+        {
+            Ok(token_result) => {
+                self.update_backoff(&account.id, None);
+                token_result
+            }
+            Err(err @ Error::RateLimited { .. }) => return Err(err),
+            Err(err) => {
+                let err = refresh_error(account, err);
+                self.update_backoff(&account.id, Some(&err));
+                return Err(err);
+            }
+        };
+
+        if let Some(new_refresh_token) = token_result.refresh_token()
+            && new_refresh_token.secret() != refresh_token.secret()
+        {
+            account_credentials.refresh_token = Some(new_refresh_token.secret().as_str().into());
+            self.save_rotated_credentials(&account.id, &account_credentials)
+                .await;
+        }
+
+        let credentials = Credential {
+            access_token: token_result.access_token().secret().as_str().into(),
+            refresh_token: None,
+            expires_at: token_result
+                .expires_in()
+                .map(|duration| Utc::now() + Duration::seconds(duration.as_secs() as i64)),
+            scope: scopes.clone(),
+            token_type: "Bearer".to_string(),
+        };
+
+        self.storage
+            .set_scoped_credentials(&account.id, &scopes, &credentials)
+            .await?;
+
+        Ok(credentials)
+    }
+
+    /// Converts an account from GNOME Online Accounts, returning `None` if it exists already.
+    ///
+    /// GOA's tokens belong to GNOME's OAuth client, the account is flagged for the user to
+    /// sign in instead of sending them to the provider with this one.
+    #[tracing::instrument(
+        skip_all,
+        fields(provider = %goa_account.provider, operation = "import_goa_account")
+    )]
+    pub async fn import_goa_account(
+        &self,
+        goa_account: &GoaAccount,
+        accounts: &RwLock<AccountsStore>,
+    ) -> Result<Option<Account>> {
+        if accounts.read().unwrap().account_exists(
+            None,
+            &goa_account.identity,
+            &goa_account.provider,
+        ) {
+            return Ok(None);
+        }
+        self.import_account(
+            goa_account.provider,
+            &goa_account.identity,
+            goa_account.presentation_identity.as_deref(),
+            &goa_account.services,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Converts an account from libaccounts-glib, returning `None` if it exists already.
+    ///
+    /// Like GOA's, signond's tokens belong to another OAuth client, the account is flagged
+    /// for the user to sign in.
+    #[tracing::instrument(
+        skip_all,
+        fields(provider = %uoa_account.provider, operation = "import_uoa_account")
+    )]
+    pub async fn import_uoa_account(
+        &self,
+        uoa_account: &UoaAccount,
+        accounts: &RwLock<AccountsStore>,
+    ) -> Result<Option<Account>> {
+        if accounts.read().unwrap().account_exists(
+            None,
+            &uoa_account.identity,
+            &uoa_account.provider,
+        ) {
+            return Ok(None);
+        }
+        self.import_account(
+            uoa_account.provider,
+            &uoa_account.identity,
+            uoa_account.display_name.as_deref(),
+            &uoa_account.services,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Creates an account found in another account system, without credentials, flagged
+    /// for the user to sign in.
+    async fn import_account(
+        &self,
+        provider: Provider,
+        identity: &str,
+        display_name: Option<&str>,
+        imported_services: &HashMap<Service, bool>,
+    ) -> Result<Account> {
+        // Only accounts of configured providers can be signed in to
+        self.config(&provider).await?;
+
+        let mut services = provider.services();
+        for (service, enabled) in imported_services {
+            if let Some(current) = services.get_mut(service) {
+                *current = *enabled;
+            }
+        }
+
+        Ok(Account {
+            id: Uuid::new_v4(),
+            provider,
+            display_name: display_name.unwrap_or(identity).to_string(),
+            username: identity.to_string(),
+            email: identity.contains('@').then(|| identity.to_string()),
+            enabled: true,
+            created_at: Utc::now(),
+            last_used: None,
+            services,
+            attention_needed: true,
+            last_error: None,
+            remote_id: None,
+            label: None,
+            color: None,
+            service_settings: BTreeMap::new(),
+            app_access: BTreeMap::new(),
+            template: None,
+            avatar: None,
+            order: 0,
+            group: None,
+            sync_on_metered: false,
+            locked: false,
+        })
     }
 
     pub async fn delete_credentials(&self, id: &Uuid) -> Result<()> {
         self.storage.delete_account_credentials(id).await?;
+        self.storage.forget_rescued_account_credentials(id).await?;
+        self.unsaved.lock().unwrap().remove(id);
+        self.refresh_locks.lock().unwrap().remove(id);
+        self.refresh_failures.lock().unwrap().remove(id);
+        self.backoffs.lock().unwrap().remove(id);
         Ok(())
     }
 
+    /// The credentials of an account, rotated ones the store failed to save first.
     pub async fn get_account_credentials(&self, id: &Uuid) -> Result<Credential> {
-        self.storage.get_account_credentials(id).await
+        let unsaved = self.unsaved.lock().unwrap().get(id).cloned();
+        match unsaved {
+            Some(credentials) => Ok(credentials),
+            None => self.storage.get_account_credentials(id).await,
+        }
+    }
+
+    /// The scopes granted to an account, none when it has no credentials.
+    pub async fn granted_scopes(&self, id: &Uuid) -> Result<Vec<String>> {
+        if let Some(credentials) = self.unsaved.lock().unwrap().get(id) {
+            return Ok(credentials.scope.clone());
+        }
+        Ok(self
+            .storage
+            .find_account_credentials(id)
+            .await?
+            .map(|credentials| credentials.scope)
+            .unwrap_or_default())
+    }
+
+    /// Revokes the tokens of an account at the provider and deletes them.
+    ///
+    /// Revoking the refresh token also revokes the access tokens issued with it. Providers
+    /// without a revocation endpoint keep the tokens valid until they expire.
+    #[tracing::instrument(
+        skip_all,
+        fields(account_id = %account.id, provider = %account.provider, operation = "revoke_credentials")
+    )]
+    pub async fn revoke_credentials(&self, account: &Account) -> Result<()> {
+        let config = self.config(&account.provider).await?;
+
+        if let Some(revocation_url) = &config.revocation_url {
+            let credentials = self.get_account_credentials(&account.id).await?;
+            let token = match credentials.refresh_token {
+                Some(refresh_token) => StandardRevocableToken::RefreshToken(RefreshToken::new(
+                    refresh_token.into_exposed(),
+                )),
+                None => StandardRevocableToken::AccessToken(AccessToken::new(
+                    credentials.access_token.into_exposed(),
+                )),
+            };
+            ratelimit::acquire(account.provider, Priority::Queued).await?;
+            oauth_client(&config)?
+                .set_revocation_uri(RevocationUrl::new(revocation_url.clone())?)
+                .revoke_token(token)
+                .map_err(|err| Error::TokenRevocation(err.to_string()))?
+                .request_async(|request| usage::oauth_http_client(account.id, request))
+                .await
+                .map_err(|err| Error::TokenRevocation(err.to_string()))?;
+        } else {
+            tracing::warn!(
+                "{} has no revocation endpoint, its tokens stay valid until they expire",
+                account.provider
+            );
+        }
+
+        self.delete_credentials(&account.id).await
+    }
+}
+
+/// Whether the credentials expire within the refresh lead time.
+fn is_expired(credentials: &Credential) -> bool {
+    let refresh_at = Utc::now() + settings::get().refresh_lead_time();
+    credentials
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= refresh_at)
+}
+
+/// Whether a provider config lives in the user's config directory and belongs to them,
+/// the only configs the daemon rewrites.
+fn user_owned(config_path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Some(config_home) = paths::config_home().map(|dir| dir.join("accounts")) else {
+        return false;
+    };
+    if !config_path.starts_with(&config_home) {
+        return false;
+    }
+    match (
+        std::fs::metadata(config_path),
+        std::fs::metadata(&config_home),
+    ) {
+        (Ok(config), Ok(config_home)) => config.uid() == config_home.uid(),
+        _ => false,
+    }
+}
+
+/// Rewrites a provider config without its client secret.
+///
+/// Only the secret is removed, the comments and ordering of the user's file are kept. The
+/// file is replaced atomically, so a crash leaves the previous one.
+fn remove_client_secret(config_path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(config_path)?;
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e: toml_edit::TomlError| Error::StorageError(e.to_string()))?;
+    if let Some(provider) = document
+        .get_mut("provider")
+        .and_then(|provider| provider.as_table_like_mut())
+    {
+        provider.remove("client_secret");
+    }
+    let temporary = config_path.with_extension("toml.tmp");
+    std::fs::write(&temporary, document.to_string())?;
+    std::fs::rename(&temporary, config_path)?;
+    Ok(())
+}
+
+/// Turns a refresh token the provider rejected into [`Error::ReauthenticationRequired`]:
+/// it was revoked, expired or already used once by a provider rotating them, so the user
+/// has to sign in again. Other errors are returned as they are.
+fn refresh_error(account: &Account, err: Error) -> Error {
+    match err {
+        Error::OAuth2(RequestTokenError::ServerResponse(response))
+            if *response.error() == BasicErrorResponseType::InvalidGrant =>
+        {
+            tracing::warn!(
+                "{} rejected the refresh token of account {}: {}",
+                account.provider,
+                account.id,
+                response
+                    .error_description()
+                    .map_or("invalid_grant", String::as_str)
+            );
+            Error::ReauthenticationRequired {
+                account_id: account.id.to_string(),
+            }
+        }
+        err => err,
+    }
+}
+
+/// Sends a token request once the rate limit of the provider allows it, and once more
+/// after a short pause when it couldn't be sent. Requests the provider may have seen are
+/// never retried.
+async fn request_token<T, F, Fut>(
+    provider: Provider,
+    priority: Priority,
+    mut request: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, TokenError>>,
+{
+    let mut attempt = 1;
+    loop {
+        ratelimit::acquire(provider, priority).await?;
+        match request().await {
+            Ok(response) => return Ok(response),
+            Err(err) if attempt < TOKEN_ATTEMPTS && is_unsent(&err) => {
+                let delay = TOKEN_RETRY_DELAY.mul_f64(1.0 + rand::random::<f64>());
+                tracing::debug!(
+                    "Token request to {} failed, trying again in {}ms: {}",
+                    provider,
+                    delay.as_millis(),
+                    err
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
     }
 }
 
+/// Whether the token request was never sent, as the token endpoint couldn't be reached.
+///
+/// Authorization codes are single-use, and so are refresh tokens the provider rotates, so
+/// a request is only tried again when the provider can't have seen it. Once it may have,
+/// a retry would fail with a misleading `invalid_grant` instead of the actual error.
+fn is_unsent(err: &TokenError) -> bool {
+    matches!(
+        err,
+        RequestTokenError::Request(oauth2::reqwest::Error::Reqwest(err)) if err.is_connect()
+    )
+}
+
+/// The HTTP client of token requests, counted for the account if there is one.
+///
+/// oauth2 only reads the body of error responses, server errors fail with their status
+/// instead of failing to parse the body.
+async fn token_http_client(
+    account_id: Option<Uuid>,
+    request: HttpRequest,
+) -> std::result::Result<HttpResponse, HttpClientError> {
+    let response = match account_id {
+        Some(account_id) => usage::oauth_http_client(account_id, request).await?,
+        None => async_http_client(request).await?,
+    };
+    if response.status_code.is_server_error() {
+        return Err(oauth2::reqwest::Error::Other(format!(
+            "The token endpoint responded with {}",
+            response.status_code
+        )));
+    }
+    Ok(response)
+}
+
+/// Builds the OAuth2 client for a provider, public clients are configured without a secret.
+fn oauth_client(config: &ProviderConfig) -> Result<OidcClient> {
+    Ok(OidcClient::new(
+        ClientId::new(config.client_id.clone()),
+        config
+            .client_secret
+            .clone()
+            .map(|secret| ClientSecret::new(secret.into_exposed())),
+        AuthUrl::new(config.auth_url.clone())?,
+        Some(TokenUrl::new(config.token_url.clone())?),
+    ))
+}
+
 struct UserInfo {
     display_name: String,
     username: String,
     email: Option<String>,
+    remote_id: Option<String>,
+    picture: Option<String>,
+}
+
+impl From<IdTokenClaims> for UserInfo {
+    fn from(claims: IdTokenClaims) -> Self {
+        // Microsoft usernames are the user principal name, Google only sends the email
+        let username = claims
+            .preferred_username
+            .or_else(|| claims.email.clone())
+            .unwrap_or_else(|| claims.sub.clone());
+        Self {
+            display_name: claims.name.unwrap_or_else(|| username.clone()),
+            username,
+            email: claims.email,
+            // Microsoft subjects differ per application, the object ID matches Graph's `id`
+            remote_id: Some(claims.oid.unwrap_or(claims.sub)),
+            picture: claims.picture,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oauth2::StandardErrorResponse;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::storage::fake::{FakeSecretService, FakeState};
+
+    fn server_response(error: BasicErrorResponseType) -> TokenError {
+        RequestTokenError::ServerResponse(StandardErrorResponse::new(error, None, None))
+    }
+
+    /// A request the token endpoint never received, as nothing listens on its port.
+    async fn connect_error() -> TokenError {
+        let err = reqwest::Client::new()
+            .get("http://127.0.0.1:1/token")
+            .send()
+            .await
+            .unwrap_err();
+        RequestTokenError::Request(oauth2::reqwest::Error::Reqwest(err))
+    }
+
+    #[test]
+    fn removes_only_the_client_secret() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("google.toml");
+        std::fs::write(
+            &path,
+            "# My client\n[provider]\nclient_id = \"id\" # from the console\nclient_secret = \"secret\"\nscopes = [\"openid\"]\n",
+        )
+        .unwrap();
+
+        remove_client_secret(&path).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# My client\n[provider]\nclient_id = \"id\" # from the console\nscopes = [\"openid\"]\n"
+        );
+        assert!(!path.with_extension("toml.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn retries_token_requests_only_when_unsent() {
+        assert!(is_unsent(&connect_error().await));
+        assert!(!is_unsent(&RequestTokenError::Request(
+            oauth2::reqwest::Error::Other("The token endpoint responded with 503".to_string())
+        )));
+        assert!(!is_unsent(&server_response(
+            BasicErrorResponseType::Extension("temporarily_unavailable".to_string())
+        )));
+    }
+
+    #[tokio::test]
+    async fn keeps_a_rotated_refresh_token_the_store_failed_to_save() {
+        let dir = TempDir::new().unwrap();
+        let store = FakeSecretService::new(dir.path().join("secrets.json"));
+        let storage = || {
+            CredentialStorage::with_store(Box::new(store.clone()))
+                .with_rescue_dir(dir.path().join("rescued"))
+        };
+        let auth = AuthManager::with_storage(storage());
+        let account_id = Uuid::new_v4();
+        let mut credentials = Credential {
+            access_token: "access-token".into(),
+            refresh_token: Some("refresh-token".into()),
+            expires_at: Some(Utc::now()),
+            scope: vec!["openid".to_string()],
+            token_type: "Bearer".to_string(),
+        };
+        auth.save_credentials(&account_id, &credentials)
+            .await
+            .unwrap();
+
+        store.set_state(FakeState::Locked);
+        credentials.refresh_token = Some("rotated".into());
+        auth.save_rotated_credentials(&account_id, &credentials)
+            .await;
+        let kept = auth.get_account_credentials(&account_id).await.unwrap();
+        assert_eq!(
+            kept.refresh_token.as_ref().map(SecretString::expose),
+            Some("rotated")
+        );
+
+        // Kept in the encrypted file across a restart
+        let auth = AuthManager::with_storage(storage());
+        auth.restore_rescued_credentials().await;
+        let kept = auth.get_account_credentials(&account_id).await.unwrap();
+        assert_eq!(
+            kept.refresh_token.as_ref().map(SecretString::expose),
+            Some("rotated")
+        );
+
+        // Saved again once the store works again
+        assert!(!auth.save_unsaved().await);
+        store.set_state(FakeState::Unlocked);
+        assert!(auth.save_unsaved().await);
+        let stored = auth
+            .storage
+            .get_account_credentials(&account_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            stored.refresh_token.as_ref().map(SecretString::expose),
+            Some("rotated")
+        );
+        assert!(
+            auth.storage
+                .rescued_account_credentials()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
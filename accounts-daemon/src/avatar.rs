@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use accounts::models::Provider;
+use uuid::Uuid;
+
+use crate::{Error, Result, paths, usage};
+
+/// Microsoft serves profile photos from Graph instead of a URL in the ID token.
+const MICROSOFT_PHOTO_URL: &str = "https://graph.microsoft.com/v1.0/me/photos/96x96/$value";
+
+fn path(account_id: &Uuid) -> PathBuf {
+    paths::cache_dir()
+        .join("avatars")
+        .join(account_id.to_string())
+}
+
+/// Downloads the profile picture of an account and returns the path it is cached at.
+///
+/// Avatars are cosmetic, so failures are logged and `None` is returned.
+pub async fn fetch(
+    account_id: &Uuid,
+    provider: Provider,
+    picture: Option<&str>,
+    access_token: &str,
+) -> Option<String> {
+    match download(account_id, provider, picture, access_token).await {
+        Ok(path) => path,
+        Err(err) => {
+            tracing::debug!("Failed to fetch the avatar of {}: {}", account_id, err);
+            None
+        }
+    }
+}
+
+async fn download(
+    account_id: &Uuid,
+    provider: Provider,
+    picture: Option<&str>,
+    access_token: &str,
+) -> Result<Option<String>> {
+    let request = match (provider, picture) {
+        (Provider::Google, Some(picture)) => reqwest::Client::new().get(picture),
+        (Provider::Google, None) => return Ok(None),
+        (Provider::Microsoft, _) => reqwest::Client::new()
+            .get(MICROSOFT_PHOTO_URL)
+            .bearer_auth(access_token),
+    };
+    let response = usage::send(request, *account_id, provider, usage::PROFILE).await?;
+    // Accounts without a photo get a 404 from Graph
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let image = response.bytes().await?;
+
+    let path = path(account_id);
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    paths::exclude_from_backups(&paths::cache_dir());
+    tokio::fs::write(&path, &image).await?;
+    path.into_os_string()
+        .into_string()
+        .map(Some)
+        .map_err(|_| Error::StorageError("Avatar path is not valid UTF-8".to_string()))
+}
+
+/// Deletes the cached avatar of a removed account.
+pub fn remove(account_id: &Uuid) {
+    let path = path(account_id);
+    if path.exists()
+        && let Err(err) = std::fs::remove_file(&path)
+    {
+        tracing::warn!("Failed to remove avatar {}: {}", path.display(), err);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use accounts::AccountsError;
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::watch, task::JoinHandle, time::Instant};
+use tracing::info;
+use url::Url;
+use zbus::DBusError;
+
+use crate::{Error, Result, account::AccountsInterface, auth::AuthFlowState, settings};
+
+/// How long the callback server waits for the provider to redirect back.
+pub const AUTH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Deep link handled by accounts-ui, which shows the result of the flow itself.
+const DONE_URI: &str = "cosmic-accounts://done";
+
+/// Callbacks a single pending flow may receive per [`RATE_LIMIT_WINDOW`].
+const RATE_LIMIT: u32 = 30;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Loopback HTTP server receiving OAuth redirects while authentication flows are pending.
+///
+/// The server shuts down once its deadline passes or when it is dropped.
+pub struct CallbackServer {
+    port: u16,
+    deadline: watch::Sender<Instant>,
+    handle: JoinHandle<()>,
+}
+
+/// Counts the callbacks of each pending flow during the current window.
+///
+/// Keyed on the `state` of the flow rather than on the peer, every request comes from
+/// loopback, so one local process hammering the port can't lock out the browser.
+#[derive(Default)]
+struct RateLimiter {
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// Counts a callback, returning whether the flow is still within its limit.
+    fn allow(&self, state: &str) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (started_at, _)| started_at.elapsed() < RATE_LIMIT_WINDOW);
+        let (_, requests) = windows
+            .entry(state.to_string())
+            .or_insert((Instant::now(), 0));
+        *requests += 1;
+        *requests <= RATE_LIMIT
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+impl CallbackServer {
+    pub async fn start() -> Result<Self> {
+        let router = Router::new()
+            .route("/callback", get(handle_callback))
+            .route("/healthz", get(|| async { "ok" }))
+            .route("/status", get(handle_status))
+            .layer(middleware::from_fn(guard))
+            .with_state(Arc::new(RateLimiter::default()));
+        let listener = bind().await?;
+        let address = listener.local_addr().map_err(Error::Io)?;
+        let port = address.port();
+
+        let (deadline, mut deadline_rx) = watch::channel(Instant::now() + AUTH_TIMEOUT);
+        let shutdown = async move {
+            loop {
+                let current = *deadline_rx.borrow_and_update();
+                tokio::select! {
+                    _ = tokio::time::sleep_until(current) => break,
+                    changed = deadline_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        };
+
+        let handle = tokio::spawn(async move {
+            if let Err(err) = axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown)
+            .await
+            {
+                tracing::error!("Callback server failed: {}", err);
+            }
+            info!("OAuth callback server on port {port} stopped");
+        });
+
+        info!("OAuth callback URL: http://{address}/callback");
+
+        Ok(Self {
+            port,
+            deadline,
+            handle,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.handle.is_finished()
+    }
+
+    /// Keeps the server alive for another [`AUTH_TIMEOUT`].
+    pub fn extend(&self) {
+        self.deadline.send_replace(Instant::now() + AUTH_TIMEOUT);
+    }
+}
+
+/// Only serves loopback clients.
+///
+/// The server may be bound to another address, but the browser completing the flow always
+/// runs on this machine.
+async fn guard(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !peer.ip().is_loopback() {
+        tracing::warn!("Rejected a callback server request from {}", peer);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    next.run(request).await
+}
+
+/// Reports the daemon version and the providers with a pending flow, so probes and users
+/// can check the server is ready before signing in.
+async fn handle_status() -> std::result::Result<Json<Value>, StatusCode> {
+    let pending = AccountsInterface::pending_authentications()
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let providers: Vec<String> = pending.iter().map(ToString::to_string).collect();
+    Ok(Json(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "pending_authentications": pending.len(),
+        "providers": providers,
+    })))
+}
+
+/// Binds the first free port of the configured range.
+///
+/// Without a range the OS picks a free port, so the callback server never conflicts with
+/// other services.
+async fn bind() -> Result<TcpListener> {
+    let settings = settings::get();
+    let (first, last) = settings.callback_ports;
+    let mut last_error = None;
+    for port in first..=last.max(first) {
+        match TcpListener::bind(SocketAddr::new(settings.callback_address, port)).await {
+            Ok(listener) => return Ok(listener),
+            Err(err) => {
+                tracing::debug!("Callback port {} unavailable: {}", port, err);
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(last_error.map_or(
+        Error::InvalidArguments("Empty callback port range".to_string()),
+        Error::Io,
+    ))
+}
+
+/// Hands the result of a flow back to the browser as a redirect to accounts-ui.
+///
+/// Only requests carrying the state of a pending flow are handled, anything else reaching
+/// the port is turned away without touching the pending flows. Each state is single-use,
+/// replays and expired flows get `410 Gone`.
+async fn handle_callback(
+    State(limiter): State<Arc<RateLimiter>>,
+    Query(params): Query<CallbackQuery>,
+) -> std::result::Result<Redirect, StatusCode> {
+    let Some(csrf_token) = params.state else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    match AccountsInterface::authentication_state(&csrf_token).await {
+        AuthFlowState::Pending => {
+            if !limiter.allow(&csrf_token) {
+                tracing::warn!("Rate limited callbacks for a pending authentication flow");
+                return Err(StatusCode::TOO_MANY_REQUESTS);
+            }
+        }
+        AuthFlowState::Closed => {
+            tracing::warn!("Ignoring a callback for a closed authentication flow");
+            return Err(StatusCode::GONE);
+        }
+        AuthFlowState::Unknown => {
+            tracing::warn!("Ignoring a callback for an unknown authentication flow");
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+    info!("Received OAuth callback for a pending flow");
+
+    let redirect = if let Some(error) = &params.error {
+        if let Some(description) = &params.error_description {
+            tracing::warn!("Provider returned {}: {}", error, description);
+        }
+        if let Err(err) = AccountsInterface::fail_callback_authentication(&csrf_token, error).await
+        {
+            tracing::error!("Failed to report authentication error: {}", err);
+        }
+        failed(&AccountsError::OAuth(error.clone()))
+    } else if let Some(authorization_code) = params.code {
+        match AccountsInterface::complete_callback_authentication(csrf_token, authorization_code)
+            .await
+        {
+            Ok(account_id) => {
+                tracing::info!("User authenticated with ID: {}", account_id);
+                done(&[("account", account_id.as_str())])
+            }
+            Err(err) => failed(&err),
+        }
+    } else {
+        failed(&AccountsError::InvalidArguments(
+            "Missing required parameters".to_string(),
+        ))
+    };
+    Ok(redirect)
+}
+
+/// Redirects to the deep link accounts-ui is registered for, with the result of the flow.
+fn done(query: &[(&str, &str)]) -> Redirect {
+    let mut uri = Url::parse(DONE_URI).expect("Invalid deep link");
+    uri.query_pairs_mut().extend_pairs(query);
+    Redirect::to(uri.as_str())
+}
+
+/// Redirects to accounts-ui with the code and detail of the error.
+fn failed(error: &AccountsError) -> Redirect {
+    done(&[
+        ("error", error.code().as_str()),
+        ("detail", error.description().unwrap_or_default()),
+    ])
+}
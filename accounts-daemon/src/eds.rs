@@ -0,0 +1,253 @@
+use std::{fs, path::PathBuf};
+
+use accounts::{
+    AccountsClient,
+    models::{Account, AccountEvent, Provider, Service},
+};
+use futures_util::StreamExt;
+use uuid::Uuid;
+use zbus::Connection;
+
+use crate::{Result, paths::evolution_sources_dir, settings};
+
+/// Prefix of the source files the bridge owns, others are never touched.
+const SOURCE_PREFIX: &str = "cosmic-accounts-";
+
+/// The registry watches its sources directory, so sources written or removed there show up
+/// in EDS-based applications right away.
+fn source_path(account_id: &Uuid) -> Option<PathBuf> {
+    evolution_sources_dir().map(|dir| dir.join(format!("{SOURCE_PREFIX}{account_id}.source")))
+}
+
+/// Keeps a collection source in Evolution Data Server for each account with calendars,
+/// contacts or mail enabled while the `eds_bridge` setting is on. Sources are removed once
+/// an account or the setting is turned off.
+pub async fn bridge(connection: Connection, accounts: Vec<Account>) -> Result<()> {
+    sync(&accounts);
+
+    let client = AccountsClient::with_connection(&connection).await?;
+    let mut events = client.watch().await?;
+    let mut settings = settings::subscribe();
+    let mut enabled = settings::get().eds_bridge;
+    loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(
+                    AccountEvent::Added(account)
+                    | AccountEvent::Changed(account)
+                    | AccountEvent::AttentionNeeded(account),
+                ) => update_source(&account),
+                Some(AccountEvent::Removed(account_id)) => remove_source(&account_id),
+                Some(AccountEvent::DaemonRestarted) => {}
+                None => break,
+            },
+            Ok(()) = settings.changed() => {
+                let eds_bridge = settings.borrow_and_update().eds_bridge;
+                if eds_bridge != enabled {
+                    enabled = eds_bridge;
+                    sync(&client.list_accounts().await?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the sources of the accounts and removes the ones of accounts which are gone.
+fn sync(accounts: &[Account]) {
+    for account in accounts {
+        update_source(account);
+    }
+
+    let Some(entries) = evolution_sources_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(account_id) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(SOURCE_PREFIX))
+            .and_then(|name| name.strip_suffix(".source"))
+            .and_then(|id| Uuid::parse_str(id).ok())
+        else {
+            continue;
+        };
+        if !accounts.iter().any(|account| account.id == account_id) {
+            remove_source(&account_id);
+        }
+    }
+}
+
+fn update_source(account: &Account) {
+    let Some(source) = settings::get()
+        .eds_bridge
+        .then(|| collection_source(account))
+        .flatten()
+    else {
+        remove_source(&account.id);
+        return;
+    };
+    let Some(path) = source_path(&account.id) else {
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, source));
+    match written {
+        Ok(()) => tracing::debug!("Updated the EDS source of account {}", account.id),
+        Err(err) => tracing::warn!(
+            "Failed to write the EDS source of account {}: {}",
+            account.id,
+            err
+        ),
+    }
+}
+
+fn remove_source(account_id: &Uuid) {
+    let Some(path) = source_path(account_id) else {
+        return;
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => tracing::debug!("Removed the EDS source of account {}", account_id),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => tracing::warn!(
+            "Failed to remove the EDS source of account {}: {}",
+            account_id,
+            err
+        ),
+    }
+}
+
+/// A collection source, from which EDS discovers the calendars, address books and mail
+/// account itself, or `None` when the account has none of them enabled.
+fn collection_source(account: &Account) -> Option<String> {
+    let enabled = |service: Service| {
+        account.enabled && account.services.get(&service).copied().unwrap_or(false)
+    };
+    let (calendar, contacts, mail) = (
+        enabled(Service::Calendar),
+        enabled(Service::Contacts),
+        enabled(Service::Email),
+    );
+    if !(calendar || contacts || mail) {
+        return None;
+    }
+
+    // Tokens come from this daemon rather than from a sign in of EDS's own: the generic
+    // OAuth2 method asks the credentials provider of the collection, which finds the
+    // account by the ID in the `Cosmic Accounts` group
+    let backend = match account.provider {
+        Provider::Google => "google",
+        Provider::Microsoft => "microsoft365",
+    };
+    let identity = escape(account.email.as_deref().unwrap_or(&account.username));
+    Some(format!(
+        "[Data Source]\n\
+         DisplayName={display_name}\n\
+         Enabled=true\n\
+         Parent=\n\
+         \n\
+         [Collection]\n\
+         BackendName={backend}\n\
+         Identity={identity}\n\
+         CalendarEnabled={calendar}\n\
+         ContactsEnabled={contacts}\n\
+         MailEnabled={mail}\n\
+         \n\
+         [Authentication]\n\
+         User={identity}\n\
+         Method=OAuth2\n\
+         \n\
+         [Cosmic Accounts]\n\
+         AccountId={account_id}\n",
+        display_name = escape(&account.display_name),
+        account_id = account.id,
+    ))
+}
+
+/// Escapes a value for a GLib key file.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+
+    use super::*;
+
+    fn account(services: &[(Service, bool)]) -> Account {
+        Account {
+            id: Uuid::parse_str("6f0e3f4e-58c4-4a6a-8d52-3d1c2a0b9e77").unwrap(),
+            provider: Provider::Microsoft,
+            display_name: "Work\nMail".to_string(),
+            username: "user".to_string(),
+            email: Some("user@example.com".to_string()),
+            enabled: true,
+            created_at: Utc::now(),
+            last_used: None,
+            services: services.iter().cloned().collect(),
+            attention_needed: false,
+            last_error: None,
+            remote_id: None,
+            label: None,
+            color: None,
+            service_settings: BTreeMap::new(),
+            app_access: BTreeMap::new(),
+            template: None,
+            avatar: None,
+            order: 0,
+            group: None,
+            sync_on_metered: false,
+            locked: false,
+        }
+    }
+
+    #[test]
+    fn collection_source_points_eds_at_the_daemon() {
+        let source = collection_source(&account(&[
+            (Service::Calendar, true),
+            (Service::Contacts, false),
+            (Service::Email, true),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            source,
+            "[Data Source]\n\
+             DisplayName=Work\\nMail\n\
+             Enabled=true\n\
+             Parent=\n\
+             \n\
+             [Collection]\n\
+             BackendName=microsoft365\n\
+             Identity=user@example.com\n\
+             CalendarEnabled=true\n\
+             ContactsEnabled=false\n\
+             MailEnabled=true\n\
+             \n\
+             [Authentication]\n\
+             User=user@example.com\n\
+             Method=OAuth2\n\
+             \n\
+             [Cosmic Accounts]\n\
+             AccountId=6f0e3f4e-58c4-4a6a-8d52-3d1c2a0b9e77\n"
+        );
+    }
+
+    #[test]
+    fn accounts_without_bridged_services_have_no_source() {
+        assert_eq!(collection_source(&account(&[(Service::Todo, true)])), None);
+
+        let mut disabled = account(&[(Service::Calendar, true)]);
+        disabled.enabled = false;
+        assert_eq!(collection_source(&disabled), None);
+    }
+}
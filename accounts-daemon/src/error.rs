@@ -1,3 +1,4 @@
+use accounts::AccountsError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,6 +18,9 @@ pub enum Error {
     #[error("Account already exists")]
     AccountAlreadyExists,
 
+    #[error("Account is locked: {0}")]
+    AccountLocked(String),
+
     #[error("Invalid service: {0}")]
     InvalidService(String),
 
@@ -32,9 +36,33 @@ pub enum Error {
     #[error("Token expired for account: {account_id}")]
     TokenExpired { account_id: String },
 
+    #[error("Re-authentication required for account: {account_id}")]
+    ReauthenticationRequired { account_id: String },
+
+    #[error("Device authorization failed: {0}")]
+    DeviceAuthorization(String),
+
+    #[error("Token revocation failed: {0}")]
+    TokenRevocation(String),
+
     #[error("Token refresh failed for account: {0}")]
     TokenRefreshFailed(String),
 
+    #[error("The provider failed repeatedly, refreshes are held back until {until}")]
+    Throttled {
+        account_id: String,
+        until: chrono::DateTime<chrono::Utc>,
+    },
+
+    #[error("All accounts are paused")]
+    Paused,
+
+    #[error("Too many requests to {provider}, held back until {until}")]
+    RateLimited {
+        provider: accounts::models::Provider,
+        until: chrono::DateTime<chrono::Utc>,
+    },
+
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
 
@@ -91,6 +119,15 @@ impl Into<zbus::fdo::Error> for Error {
             Error::TokenExpired { account_id } => {
                 zbus::fdo::Error::Failed(format!("Token expired for {account_id}"))
             }
+            Error::ReauthenticationRequired { account_id } => {
+                zbus::fdo::Error::AuthFailed(format!("Re-authentication required for {account_id}"))
+            }
+            Error::DeviceAuthorization(reason) => {
+                zbus::fdo::Error::Failed(format!("Device authorization failed: {reason}"))
+            }
+            Error::TokenRevocation(reason) => {
+                zbus::fdo::Error::Failed(format!("Token revocation failed: {reason}"))
+            }
             Error::Network(error) => zbus::fdo::Error::Failed(format!("Network error: {error}")),
             Error::OAuth2(request_token_error) => {
                 zbus::fdo::Error::Failed(format!("OAuth2 error: {request_token_error}"))
@@ -134,6 +171,13 @@ impl Into<zbus::fdo::Error> for Error {
             Error::TokenRefreshFailed(id) => {
                 zbus::fdo::Error::Failed(format!("Token refresh failed for account: {id}"))
             }
+            Error::Throttled { until, .. } => zbus::fdo::Error::LimitsExceeded(format!(
+                "The provider failed repeatedly, refreshes are held back until {until}"
+            )),
+            Error::Paused => zbus::fdo::Error::Failed("All accounts are paused".to_string()),
+            Error::RateLimited { provider, until } => zbus::fdo::Error::LimitsExceeded(format!(
+                "Too many requests to {provider}, held back until {until}"
+            )),
             Error::InvalidProvider(name) => {
                 zbus::fdo::Error::Failed(format!("Invalid provider: {name}"))
             }
@@ -143,6 +187,9 @@ impl Into<zbus::fdo::Error> for Error {
             Error::AccountAlreadyExists => {
                 zbus::fdo::Error::Failed("Account already exists".to_string())
             }
+            Error::AccountLocked(id) => {
+                zbus::fdo::Error::AccessDenied(format!("Account {id} is locked"))
+            }
             Error::InvalidService(service) => {
                 zbus::fdo::Error::Failed(format!("Invalid service: {service}"))
             }
@@ -158,6 +205,15 @@ impl Into<zbus::Error> for Error {
             Error::TokenExpired { account_id } => {
                 zbus::Error::Failure(format!("Token expired for {account_id}"))
             }
+            Error::ReauthenticationRequired { account_id } => {
+                zbus::Error::Failure(format!("Re-authentication required for {account_id}"))
+            }
+            Error::DeviceAuthorization(reason) => {
+                zbus::Error::Failure(format!("Device authorization failed: {reason}"))
+            }
+            Error::TokenRevocation(reason) => {
+                zbus::Error::Failure(format!("Token revocation failed: {reason}"))
+            }
             Error::Network(error) => zbus::Error::Failure(format!("Network error: {error}")),
             Error::OAuth2(request_token_error) => {
                 zbus::Error::Failure(format!("OAuth2 error: {request_token_error}"))
@@ -195,6 +251,13 @@ impl Into<zbus::Error> for Error {
             Error::TokenRefreshFailed(id) => {
                 zbus::Error::Failure(format!("Token refresh failed for account: {id}"))
             }
+            Error::Throttled { until, .. } => zbus::Error::Failure(format!(
+                "The provider failed repeatedly, refreshes are held back until {until}"
+            )),
+            Error::Paused => zbus::Error::Failure("All accounts are paused".to_string()),
+            Error::RateLimited { provider, until } => zbus::Error::Failure(format!(
+                "Too many requests to {provider}, held back until {until}"
+            )),
             Error::InvalidProvider(name) => {
                 zbus::Error::Failure(format!("Invalid provider: {name}"))
             }
@@ -202,6 +265,7 @@ impl Into<zbus::Error> for Error {
             Error::AccountAlreadyExists => {
                 zbus::Error::Failure("Account already exists".to_string())
             }
+            Error::AccountLocked(id) => zbus::Error::Failure(format!("Account {id} is locked")),
             Error::InvalidService(service) => {
                 zbus::Error::Failure(format!("Invalid service: {service}"))
             }
@@ -209,4 +273,46 @@ impl Into<zbus::Error> for Error {
     }
 }
 
+impl From<Error> for AccountsError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::AccountNotFound(id) => {
+                AccountsError::AccountNotFound(format!("Account {id} not found."))
+            }
+            Error::AccountAlreadyExists => {
+                AccountsError::AccountAlreadyExists("Account already exists".to_string())
+            }
+            Error::AccountLocked(id) => AccountsError::AccountLocked(id),
+            Error::InvalidArguments(args) => AccountsError::InvalidArguments(args),
+            Error::InvalidProvider(name) => AccountsError::InvalidProvider(name),
+            Error::InvalidService(service) => AccountsError::InvalidService(service),
+            Error::AuthenticationFailed { reason } => AccountsError::AuthenticationFailed(reason),
+            Error::DeviceAuthorization(reason) => AccountsError::AuthenticationFailed(format!(
+                "Device authorization failed: {reason}"
+            )),
+            Error::ReauthenticationRequired { account_id } => {
+                AccountsError::ReauthenticationRequired(account_id)
+            }
+            Error::TokenExpired { account_id } => AccountsError::TokenExpired(account_id),
+            Error::TokenRefreshFailed(account_id) => AccountsError::TokenRefreshFailed(account_id),
+            Error::Throttled { until, .. } => AccountsError::Throttled(until.to_rfc3339()),
+            Error::Paused => AccountsError::Paused("All accounts are paused".to_string()),
+            Error::RateLimited { until, .. } => AccountsError::RateLimited(until.to_rfc3339()),
+            Error::TokenRevocation(reason) => AccountsError::TokenRevocationFailed(reason),
+            Error::OAuth2(oauth2::RequestTokenError::ServerResponse(response)) => {
+                AccountsError::OAuth(response.error().to_string())
+            }
+            Error::OAuth2(oauth2::RequestTokenError::Request(error)) => {
+                AccountsError::Network(error.to_string())
+            }
+            Error::Network(error) => AccountsError::Network(error.to_string()),
+            Error::StorageError(error) => AccountsError::Storage(error),
+            Error::CredentialStorage(error) => AccountsError::Storage(error.to_string()),
+            Error::DBus(error) => AccountsError::ZBus(error),
+            Error::Zbus(error) => error.into(),
+            error => AccountsError::Failed(error.to_string()),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
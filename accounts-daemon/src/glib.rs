@@ -0,0 +1,114 @@
+//! Parsers for the text GLib writes, the values of keyfiles and printed GVariants, as found
+//! in the stores of GNOME and Ubuntu Online Accounts.
+
+use std::str::Chars;
+
+/// Resolves the escape sequences of a keyfile value, `\s`, `\n`, `\t`, `\r` and `\\`.
+pub fn keyfile_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Parses a string in the GVariant text format, such as `'user@example.com'`.
+///
+/// The string may be quoted with `'` or `"` and carry an `@s` annotation. Returns `None`
+/// for other values, unterminated strings and text after the closing quote.
+pub fn gvariant_string(text: &str) -> Option<String> {
+    let text = text.trim();
+    let text = text.strip_prefix("@s").map_or(text, str::trim_start);
+    let mut chars = text.chars();
+    let quote = chars.next().filter(|c| matches!(c, '\'' | '"'))?;
+
+    let mut string = String::with_capacity(text.len());
+    loop {
+        match chars.next()? {
+            c if c == quote => break,
+            '\\' => string.push(match chars.next()? {
+                'a' => '\u{7}',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'v' => '\u{b}',
+                'u' => hex_char(&mut chars, 4)?,
+                'U' => hex_char(&mut chars, 8)?,
+                c => c,
+            }),
+            c => string.push(c),
+        }
+    }
+    chars.as_str().trim().is_empty().then_some(string)
+}
+
+/// Reads the hex digits of a `\u` or `\U` escape.
+fn hex_char(chars: &mut Chars, digits: usize) -> Option<char> {
+    let hex: String = chars.by_ref().take(digits).collect();
+    if hex.len() != digits || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_keyfile_values() {
+        assert_eq!(keyfile_value(r"a\sb\\c\n"), "a b\\c\n");
+        assert_eq!(keyfile_value(r"trailing\"), "trailing\\");
+    }
+
+    #[test]
+    fn parses_quoted_gvariant_strings() {
+        assert_eq!(
+            gvariant_string("'user@example.com'").as_deref(),
+            Some("user@example.com")
+        );
+        assert_eq!(gvariant_string(r#" "it's" "#).as_deref(), Some("it's"));
+        assert_eq!(gvariant_string("@s 'name'").as_deref(), Some("name"));
+        assert_eq!(gvariant_string("''").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn resolves_gvariant_escapes() {
+        assert_eq!(
+            gvariant_string(r"'O\'Brien\\'").as_deref(),
+            Some(r"O'Brien\")
+        );
+        assert_eq!(
+            gvariant_string(r#""say \"hi\"""#).as_deref(),
+            Some(r#"say "hi""#)
+        );
+        assert_eq!(gvariant_string(r"'a\tb\nc'").as_deref(), Some("a\tb\nc"));
+        assert_eq!(gvariant_string(r"'café'").as_deref(), Some("café"));
+        assert_eq!(gvariant_string(r"'\U0001F600'").as_deref(), Some("😀"));
+    }
+
+    #[test]
+    fn rejects_other_gvariant_values() {
+        assert_eq!(gvariant_string("uint32 5"), None);
+        assert_eq!(gvariant_string("true"), None);
+        assert_eq!(gvariant_string("'unterminated"), None);
+        assert_eq!(gvariant_string(r"'escaped quote\'"), None);
+        assert_eq!(gvariant_string("'one' 'two'"), None);
+        assert_eq!(gvariant_string(r"'\u00g9'"), None);
+        assert_eq!(gvariant_string(r"'\uD800'"), None);
+    }
+}
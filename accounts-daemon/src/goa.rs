@@ -0,0 +1,135 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use accounts::models::{Provider, Service};
+
+use crate::{Result, glib, paths};
+
+/// An account configured in GNOME Online Accounts.
+#[derive(Debug)]
+pub struct GoaAccount {
+    /// GOA's own identifier, e.g. `account_1700000000_0`.
+    pub id: String,
+    pub provider: Provider,
+    pub identity: String,
+    pub presentation_identity: Option<String>,
+    pub services: HashMap<Service, bool>,
+}
+
+/// Location of the GOA keyfile, `~/.config/goa-1.0/accounts.conf`.
+fn accounts_path() -> Option<PathBuf> {
+    paths::config_home().map(|config| config.join("goa-1.0").join("accounts.conf"))
+}
+
+/// Reads the Google and Microsoft accounts from the GOA keyfile, other providers are skipped.
+///
+/// GOA's tokens were issued to GNOME's OAuth client, so they are never read, the imported
+/// accounts need the user to sign in.
+pub fn read_accounts() -> Result<Vec<GoaAccount>> {
+    let Some(path) = accounts_path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+    Ok(parse_accounts(&std::fs::read_to_string(path)?))
+}
+
+fn parse_accounts(content: &str) -> Vec<GoaAccount> {
+    let mut accounts = Vec::new();
+    for (section, keys) in parse_keyfile(content) {
+        let Some(id) = section.strip_prefix("Account ") else {
+            continue;
+        };
+        let provider = match keys.get("Provider").map(String::as_str) {
+            Some("google") => Provider::Google,
+            Some("ms_graph" | "windows_live") => Provider::Microsoft,
+            _ => continue,
+        };
+        let Some(identity) = keys.get("Identity").cloned() else {
+            continue;
+        };
+
+        let services = [
+            ("MailEnabled", Service::Email),
+            ("CalendarEnabled", Service::Calendar),
+            ("ContactsEnabled", Service::Contacts),
+        ]
+        .into_iter()
+        .filter_map(|(key, service)| {
+            keys.get(key)
+                .map(|enabled| (service, enabled.eq_ignore_ascii_case("true")))
+        })
+        .collect();
+
+        accounts.push(GoaAccount {
+            id: id.to_string(),
+            provider,
+            identity,
+            presentation_identity: keys.get("PresentationIdentity").cloned(),
+            services,
+        });
+    }
+    accounts
+}
+
+/// Splits a GLib keyfile into its groups.
+fn parse_keyfile(content: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut groups: Vec<(String, HashMap<String, String>)> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(group) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            groups.push((group.to_string(), HashMap::new()));
+        } else if let Some((key, value)) = line.split_once('=')
+            && let Some((_, keys)) = groups.last_mut()
+        {
+            keys.insert(key.trim().to_string(), glib::keyfile_value(value.trim()));
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYFILE: &str = r"
+# Written by GOA
+[Account account_1700000000_0]
+Provider=google
+Identity=user@gmail.com
+PresentationIdentity=User\sName
+MailEnabled=true
+CalendarEnabled=false
+
+[Account account_1700000000_1]
+Provider=ms_graph
+Identity=user@outlook.com
+
+[Account account_1700000000_2]
+Provider=owncloud
+Identity=user
+
+[Account account_1700000000_3]
+Provider=google
+";
+
+    #[test]
+    fn reads_google_and_microsoft_accounts() {
+        let accounts = parse_accounts(KEYFILE);
+
+        assert_eq!(accounts.len(), 2);
+        let google = &accounts[0];
+        assert_eq!(google.id, "account_1700000000_0");
+        assert_eq!(google.provider, Provider::Google);
+        assert_eq!(google.identity, "user@gmail.com");
+        assert_eq!(google.presentation_identity.as_deref(), Some("User Name"));
+        assert_eq!(
+            google.services,
+            HashMap::from([(Service::Email, true), (Service::Calendar, false)])
+        );
+        assert_eq!(accounts[1].provider, Provider::Microsoft);
+        assert!(accounts[1].services.is_empty());
+    }
+}
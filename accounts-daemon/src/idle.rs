@@ -0,0 +1,86 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::StreamExt;
+use zbus::{Connection, MessageStream, fdo::DBusProxy, message::Type};
+
+use crate::{BUS_NAME, CONNECTION, account::AccountsInterface, settings};
+
+/// How often the daemon checks the idle timeout while idle exit is disabled.
+const DISABLED_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Activity {
+    /// Unique names of the peers which called the daemon and are still connected.
+    clients: HashSet<String>,
+    last_call: Instant,
+}
+
+/// Exits once the daemon has nothing to do, D-Bus activation starts it again on demand.
+///
+/// The daemon is idle when no accounts exist, no sign in is pending and every client which
+/// called it disconnected at least the `idle_timeout` setting ago.
+pub async fn exit_when_idle(connection: Connection) -> zbus::Result<()> {
+    let activity = Arc::new(Mutex::new(Activity {
+        clients: HashSet::new(),
+        last_call: Instant::now(),
+    }));
+
+    let mut messages = MessageStream::from(&connection);
+    let calls = activity.clone();
+    tokio::spawn(async move {
+        while let Some(Ok(message)) = messages.next().await {
+            if message.message_type() != Type::MethodCall {
+                continue;
+            }
+            if let Some(sender) = message.header().sender() {
+                let mut activity = calls.lock().unwrap();
+                activity.clients.insert(sender.to_string());
+                activity.last_call = Instant::now();
+            }
+        }
+    });
+
+    let mut name_owner_changed = DBusProxy::new(&connection)
+        .await?
+        .receive_name_owner_changed()
+        .await?;
+    let disconnects = activity.clone();
+    tokio::spawn(async move {
+        while let Some(signal) = name_owner_changed.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            if args.new_owner().is_none() {
+                let mut activity = disconnects.lock().unwrap();
+                if activity.clients.remove(args.name().as_str()) {
+                    activity.last_call = Instant::now();
+                }
+            }
+        }
+    });
+
+    loop {
+        let Some(idle_timeout) = settings::get().idle_timeout() else {
+            tokio::time::sleep(DISABLED_CHECK_INTERVAL).await;
+            continue;
+        };
+        tokio::time::sleep(idle_timeout).await;
+        let inactive = {
+            let activity = activity.lock().unwrap();
+            activity.clients.is_empty() && activity.last_call.elapsed() >= idle_timeout
+        };
+        if inactive && AccountsInterface::is_idle().await {
+            break;
+        }
+    }
+
+    tracing::info!("No accounts or clients left, exiting");
+    if let Some(connection) = CONNECTION.get() {
+        connection.release_name(BUS_NAME).await?;
+    }
+    Ok(())
+}
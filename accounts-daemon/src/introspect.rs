@@ -0,0 +1,100 @@
+//! The D-Bus introspection XML of the interfaces, checked in under `data/interfaces` so
+//! packagers and bindings don't need to run the daemon. A test keeps it current, run it
+//! with `UPDATE_INTERFACES=1` to write the XML after changing an interface.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use accounts::models::{Account, Provider};
+use chrono::Utc;
+use uuid::Uuid;
+use zbus::object_server::Interface;
+
+use crate::{
+    account::AccountsInterface,
+    portal::PortalInterface,
+    services::{CalendarService, ContactsService, MailService, TodoService},
+};
+
+const DOCTYPE: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">"#;
+
+/// Where the introspection XML of the interfaces is checked in, installed to
+/// `/usr/share/dbus-1/interfaces`.
+const INTERFACES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/interfaces");
+
+/// The introspection XML of every interface the daemon serves, by `<interface>.xml` file
+/// name, the way `/usr/share/dbus-1/interfaces` expects them.
+///
+/// Nothing is served, so service interfaces which only exist for accounts are included
+/// too. The accounts interface reads no config or credentials to be described.
+fn interfaces() -> BTreeMap<String, String> {
+    let account = placeholder_account();
+    BTreeMap::from([
+        interface_xml(&AccountsInterface::in_memory()),
+        interface_xml(&PortalInterface),
+        interface_xml(&CalendarService::new(account.clone())),
+        interface_xml(&ContactsService::new(account.clone())),
+        interface_xml(&MailService::new(account.clone())),
+        interface_xml(&TodoService::new(account)),
+    ])
+}
+
+fn interface_xml<I: Interface>(interface: &I) -> (String, String) {
+    let mut xml = format!("{DOCTYPE}\n<node>\n");
+    interface.introspect_to_writer(&mut xml, 1);
+    writeln!(xml, "</node>").unwrap();
+    (format!("{}.xml", I::name()), xml)
+}
+
+/// The services only need an account to answer calls, introspecting them doesn't read it.
+fn placeholder_account() -> Account {
+    Account {
+        id: Uuid::nil(),
+        provider: Provider::Google,
+        display_name: String::new(),
+        username: String::new(),
+        email: None,
+        enabled: true,
+        created_at: Utc::now(),
+        last_used: None,
+        services: BTreeMap::new(),
+        attention_needed: false,
+        last_error: None,
+        remote_id: None,
+        label: None,
+        color: None,
+        service_settings: BTreeMap::new(),
+        app_access: BTreeMap::new(),
+        template: None,
+        avatar: None,
+        order: 0,
+        group: None,
+        sync_on_metered: false,
+        locked: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interfaces_match_the_checked_in_xml() {
+        let dir = Path::new(INTERFACES_DIR);
+        let interfaces = interfaces();
+        if std::env::var_os("UPDATE_INTERFACES").is_some() {
+            std::fs::create_dir_all(dir).unwrap();
+            for (file_name, xml) in &interfaces {
+                std::fs::write(dir.join(file_name), xml).unwrap();
+            }
+            return;
+        }
+        for (file_name, xml) in &interfaces {
+            let checked_in = std::fs::read_to_string(dir.join(file_name)).unwrap_or_default();
+            assert!(
+                checked_in == *xml,
+                "{file_name} is out of date, run the tests with UPDATE_INTERFACES=1 to write it"
+            );
+        }
+    }
+}
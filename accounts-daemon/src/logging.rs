@@ -0,0 +1,46 @@
+use std::sync::OnceLock;
+
+use tracing_subscriber::{
+    EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+use crate::{Error, Result, settings};
+
+/// Handle to swap the log filter while the daemon runs.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Sends logs to journald with their span fields, e.g. `ACCOUNT_ID` and `OPERATION`, when
+/// the daemon runs under systemd, and to the terminal otherwise.
+///
+/// The level is read from `RUST_LOG`, falling back to the `log_level` setting.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(settings::get().log_level))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let registry = tracing_subscriber::registry().with(filter);
+
+    let journald = std::env::var_os("JOURNAL_STREAM")
+        .and_then(|_| tracing_journald::layer().ok())
+        .map(|layer| layer.with_syslog_identifier("cosmic-accounts".to_string()));
+    match journald {
+        Some(journald) => registry.with(journald).init(),
+        None => registry.with(tracing_subscriber::fmt::layer()).init(),
+    }
+
+    _ = FILTER.set(handle);
+}
+
+/// Replaces the log filter, e.g. `debug` or `accounts_daemon::auth=trace`.
+pub fn set_level(directives: &str) -> Result<()> {
+    let filter =
+        EnvFilter::try_new(directives).map_err(|e| Error::InvalidArguments(e.to_string()))?;
+    let Some(handle) = FILTER.get() else {
+        return Err(zbus::Error::Failure("Logging is not initialized".to_string()).into());
+    };
+    handle
+        .reload(filter)
+        .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+    tracing::info!("Log level set to {}", directives);
+    Ok(())
+}
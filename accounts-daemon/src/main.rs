@@ -1,67 +1,125 @@
-use crate::{account::AccountsInterface, services::ServiceFactory};
-use accounts::{AccountsClient, models::Account};
-use axum::{Router, extract::Query, http::StatusCode, response::Html, routing::get};
-use serde::Deserialize;
-use tokio::sync::OnceCell;
+use crate::{
+    account::{AccountsInterface, OBJECT_PATH},
+    services::{ServiceFactory, offline},
+};
+use futures_util::StreamExt;
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::OnceCell,
+};
 use tracing::info;
-use tracing_subscriber;
 
+mod access;
 mod account;
 mod auth;
+mod avatar;
+mod callback;
+mod eds;
 mod error;
+mod glib;
+mod goa;
+#[path = "../../accounts-ui/src/i18n.rs"]
+mod i18n;
+mod idle;
+#[cfg(test)]
+mod introspect;
+mod logging;
 mod models;
+mod network;
+mod notify;
+mod oidc;
+mod paths;
+mod polkit;
+mod portal;
+mod power;
+mod ratelimit;
 mod services;
+mod settings;
 mod storage;
+mod templates;
+mod uoa;
+mod usage;
 
 pub use error::{Error, Result};
-use zbus::Connection;
+use zbus::{
+    Connection,
+    fdo::{DBusProxy, RequestNameFlags},
+    names::WellKnownName,
+};
 
-pub static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
+pub const BUS_NAME: &str = "dev.edfloreshz.Accounts";
 
-#[derive(Debug, Deserialize)]
-struct CallbackQuery {
-    code: Option<String>,
-    state: Option<String>,
-    error: Option<String>,
-    error_description: Option<String>,
-}
+pub static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    tracing_subscriber::fmt::init();
+    logging::init();
+    // Notifications are shown in the user's language
+    i18n::init(&i18n_embed::DesktopLanguageRequester::requested_languages());
+    let _settings_watcher = settings::watch();
 
-    info!("Starting Accounts for COSMIC daemon with integrated HTTP server...");
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    let router = Router::new().route("/callback", get(handle_callback));
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080")
-        .await
-        .map_err(|e| Error::Io(e))?;
+    info!("Starting Accounts for COSMIC daemon...");
 
-    info!("HTTP server will listen on http://127.0.0.1:8080");
-    info!("OAuth callback URL: http://127.0.0.1:8080/callback");
+    // `--replace` takes the bus name over from a running daemon instead of exiting
+    let replace = args.iter().any(|arg| arg == "--replace");
 
     info!("Setting up D-Bus connection...");
+    let builder = if polkit::system_bus() {
+        zbus::connection::Builder::system()?
+    } else {
+        zbus::connection::Builder::session()?
+    };
+    let connection = builder.build().await?;
+
+    // Bail out before touching the config or the credentials a running daemon owns
+    let dbus = DBusProxy::new(&connection).await?;
+    if !replace
+        && dbus
+            .name_has_owner(WellKnownName::from_static_str_unchecked(BUS_NAME).into())
+            .await?
+    {
+        tracing::error!(
+            "Accounts for COSMIC daemon is already running, use --replace to replace it"
+        );
+        return Ok(());
+    }
+
     let service = AccountsInterface::new()
         .await
         .map_err(|e| zbus::Error::Failure(e.to_string()))?;
 
-    let accounts: Vec<Account> = service
-        .list_accounts()
-        .await
-        .into_iter()
-        .map(Into::into)
-        .collect();
-
-    CONNECTION
-        .set(
-            zbus::connection::Builder::session()?
-                .name("dev.edfloreshz.Accounts")?
-                .serve_at("/dev/edfloreshz/Accounts/Account", service)?
-                .build()
-                .await?,
-        )
-        .unwrap();
+    let accounts = service.accounts();
+
+    connection.object_server().at(OBJECT_PATH, service).await?;
+    let mut flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
+    if replace {
+        flags |= RequestNameFlags::ReplaceExisting;
+    }
+    match connection.request_name_with_flags(BUS_NAME, flags).await {
+        Ok(_) => {}
+        Err(zbus::Error::NameTaken) => {
+            tracing::error!(
+                "Accounts for COSMIC daemon is already running, use --replace to replace it"
+            );
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    }
+    let mut name_lost = dbus.receive_name_lost().await?;
+    CONNECTION.set(connection.clone()).unwrap();
+
+    let bridged = accounts.clone();
+    let eds_connection = connection.clone();
+    tokio::spawn(async move {
+        if let Err(err) = eds::bridge(eds_connection, bridged).await {
+            tracing::warn!("Evolution Data Server bridge stopped: {}", err);
+        }
+    });
+
+    tokio::spawn(offline::prefetch());
 
     for account in accounts {
         let services = ServiceFactory::create_services(&account);
@@ -70,129 +128,31 @@ async fn main() -> Result<()> {
         }
     }
 
-    info!("D-Bus service started on: dev.edfloreshz.Accounts");
+    let _portal = portal::serve()
+        .await
+        .inspect_err(|err| tracing::warn!("Failed to start the portal: {}", err))
+        .ok();
+
+    info!("D-Bus service started on: {}", BUS_NAME);
     info!("Object path: /dev/edfloreshz/Accounts");
 
     info!("Accounts for COSMIC daemon started successfully");
 
-    axum::serve(listener, router).await.unwrap();
-
-    Ok(())
-}
-
-async fn handle_callback(Query(params): Query<CallbackQuery>) -> (StatusCode, Html<String>) {
-    info!("Received OAuth callback: {:?}", params);
-
-    let Ok(mut client) = AccountsClient::new().await else {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Html("Accounts for COSMIC Client failed to initialize".to_string()),
-        );
-    };
-
-    if let Some(error) = &params.error {
-        let html = format!(
-            r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <title>Authentication Error</title>
-                <style>
-                    body {{ font-family: sans-serif; margin: 40px; text-align: center; }}
-                    .error {{ color: #d73a49; background: #ffeef0; padding: 20px; border-radius: 8px; }}
-                </style>
-            </head>
-            <body>
-                <div class="error">
-                    <h2>Authentication Failed</h2>
-                    <p><strong>Error:</strong> {}</p>
-                    <p><strong>Description:</strong> {}</p>
-                    <p>You can close this window.</p>
-                </div>
-            </body>
-            </html>
-            "#,
-            error,
-            params
-                .error_description
-                .as_deref()
-                .unwrap_or("No description")
-        );
-        (StatusCode::BAD_REQUEST, Html(html))
-    } else if let (Some(authorization_code), Some(csrf_token)) = (params.code, params.state) {
-        let account_id = match client
-            .complete_authentication(&csrf_token, &authorization_code)
-            .await
-        {
-            Ok(account_id) => {
-                match client.account_added(&account_id).await {
-                    Ok(_) => {
-                        tracing::info!("Account added with ID: {}", account_id);
-                    }
-                    Err(err) => {
-                        tracing::error!("Failed to add account: {}", err);
-                    }
-                }
-                account_id
-            }
-            Err(_err) => {
-                if matches!(Error::AccountAlreadyExists, _err) {
-                    match client.account_exists().await {
-                        Ok(_) => {
-                            tracing::info!("Account already exists");
-                        }
-                        Err(err) => {
-                            tracing::error!("Failed to check account existence: {}", err);
-                        }
-                    }
+    // The callback server is started on demand, keep serving D-Bus requests until idle, until
+    // another daemon replaces this one or until stopped
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = idle::exit_when_idle(connection) => result?,
+        _ = async {
+            while let Some(signal) = name_lost.next().await {
+                if signal.args().is_ok_and(|args| args.name().as_str() == BUS_NAME) {
+                    break;
                 }
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Html(format!("Failed to authenticate user: {}", _err)),
-                );
             }
-        };
-
-        tracing::info!("User authenticated with ID: {}", account_id);
-
-        let html = r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <title>Authentication Success</title>
-                <style>
-                    body { font-family: sans-serif; margin: 40px; text-align: center; }
-                    .success { color: #28a745; background: #d4edda; padding: 20px; border-radius: 8px; }
-                </style>
-            </head>
-            <body>
-                <div class="success">
-                    <h2>Authentication Successful!</h2>
-                    <p>You can now close this window.</p>
-                </div>
-            </body>
-            </html>
-        "#;
-        (StatusCode::OK, Html(html.to_string()))
-    } else {
-        let html = r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <title>Invalid Callback</title>
-                <style>
-                    body { font-family: sans-serif; margin: 40px; text-align: center; }
-                    .warning { color: #856404; background: #fff3cd; padding: 20px; border-radius: 8px; }
-                </style>
-            </head>
-            <body>
-                <div class="warning">
-                    <h2>Invalid Callback</h2>
-                    <p>Missing required parameters.</p>
-                </div>
-            </body>
-            </html>
-        "#;
-        (StatusCode::BAD_REQUEST, Html(html.to_string()))
+        } => info!("Replaced by another daemon, exiting"),
+        _ = terminate.recv() => info!("Stopped, exiting"),
     }
+
+    usage::save().await;
+    Ok(())
 }
@@ -1,2 +1,2 @@
 mod provider;
-pub use provider::{AccountProviderConfig, ProviderConfig};
+pub use provider::{AccountProviderConfig, ProviderConfig, ProviderConfigFile};
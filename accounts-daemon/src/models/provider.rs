@@ -1,4 +1,13 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use accounts::{
+    models::{Provider, SecretString, Service},
+    scopes,
+};
 use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::paths;
 
 #[derive(Deserialize)]
 pub struct AccountProviderConfig {
@@ -8,9 +17,193 @@ pub struct AccountProviderConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderConfig {
     pub client_id: String,
-    pub client_secret: String,
+    /// Omitted for public clients which rely on PKCE alone.
+    pub client_secret: Option<SecretString>,
     pub auth_url: String,
     pub token_url: String,
+    /// RFC 8628 device authorization endpoint, if the provider supports it.
+    pub device_auth_url: Option<String>,
+    /// RFC 7009 token revocation endpoint, if the provider supports it.
+    pub revocation_url: Option<String>,
+    /// Loopback callback URI, the port is assigned at runtime. It replaces a `{port}`
+    /// placeholder, e.g. `http://127.0.0.1:{port}/callback`, or else the port of the URI.
     pub redirect_uri: String,
+    /// Private-use URI scheme redirect handled by accounts-ui, e.g.
+    /// `com.system76.CosmicAccounts:/oauth2redirect`, for providers supporting native-app
+    /// redirects. No loopback listener is started for them.
+    pub native_redirect_uri: Option<String>,
     pub scopes: Vec<String>,
+    /// Expected `iss` claim of ID tokens, `{tenantid}` is replaced with the `tid` claim.
+    pub issuer: Option<String>,
+    /// Subset of `scopes` each service needs, used to hand out least-privilege tokens.
+    #[serde(default)]
+    pub service_scopes: HashMap<Service, Vec<String>>,
+}
+
+/// The config file of a provider, with the problems which keep it from being used.
+pub struct ProviderConfigFile {
+    /// The file read, or the file name looked up when there is none.
+    pub path: PathBuf,
+    /// The config, missing when the file is not found or is invalid.
+    pub config: Option<ProviderConfig>,
+    pub errors: Vec<String>,
+}
+
+impl ProviderConfigFile {
+    /// Reads the config of a provider from the first provider directory having it.
+    pub fn load(provider: Provider) -> Self {
+        let provider_dirs = paths::provider_dirs();
+        let Some(path) = provider_dirs
+            .iter()
+            .map(|dir| dir.join(provider.file_name()))
+            .find(|path| path.exists())
+        else {
+            return Self {
+                path: PathBuf::from(provider.file_name()),
+                config: None,
+                errors: vec![format!("Not found in {provider_dirs:?}")],
+            };
+        };
+        let config = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| {
+                toml::from_str::<AccountProviderConfig>(&content).map_err(|err| err.to_string())
+            });
+        match config {
+            Ok(AccountProviderConfig { provider: config }) => {
+                let errors = config.validate();
+                Self {
+                    path,
+                    config: errors.is_empty().then_some(config),
+                    errors,
+                }
+            }
+            Err(err) => Self {
+                path,
+                config: None,
+                errors: vec![err],
+            },
+        }
+    }
+}
+
+impl ProviderConfig {
+    /// Reports the fields the daemon would fail to use.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.client_id.trim().is_empty() {
+            errors.push("client_id is empty".to_string());
+        }
+        if self.scopes.is_empty() {
+            errors.push("scopes is empty".to_string());
+        }
+        let urls = [
+            ("auth_url", Some(&self.auth_url)),
+            ("token_url", Some(&self.token_url)),
+            ("device_auth_url", self.device_auth_url.as_ref()),
+            ("revocation_url", self.revocation_url.as_ref()),
+            ("native_redirect_uri", self.native_redirect_uri.as_ref()),
+        ];
+        for (field, url) in urls {
+            if let Some(url) = url
+                && let Err(err) = Url::parse(url)
+            {
+                errors.push(format!("{field} {url} is invalid: {err}"));
+            }
+        }
+        // The port is only known once the callback server listens
+        if let Err(err) = Url::parse(&self.redirect_uri.replace("{port}", "0")) {
+            errors.push(format!(
+                "redirect_uri {} is invalid: {err}",
+                self.redirect_uri
+            ));
+        }
+        errors
+    }
+
+    /// Fills in the scopes of the services the config doesn't list, for the services whose
+    /// scopes it requests, and returns warnings about service scopes it never requests,
+    /// which the provider would refuse to grant.
+    pub fn validate_scopes(&mut self, provider: Provider) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for service in [
+            Service::Email,
+            Service::Calendar,
+            Service::Contacts,
+            Service::Todo,
+        ] {
+            match self.service_scopes.get(&service) {
+                Some(service_scopes) => {
+                    let unrequested: Vec<&String> = service_scopes
+                        .iter()
+                        .filter(|scope| !self.scopes.contains(scope))
+                        .collect();
+                    if !unrequested.is_empty() {
+                        warnings.push(format!(
+                            "{service} scopes are not requested at sign in: {unrequested:?}"
+                        ));
+                    }
+                }
+                None => {
+                    if scopes::missing(provider, &service, self.scopes.iter().map(String::as_str))
+                        .is_empty()
+                    {
+                        let required = scopes::required(provider, &service);
+                        self.service_scopes.insert(
+                            service,
+                            required.iter().map(|scope| scope.to_string()).collect(),
+                        );
+                    }
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use accounts::scopes::google;
+
+    use super::*;
+
+    fn config(scopes: &[&str], service_scopes: HashMap<Service, Vec<String>>) -> ProviderConfig {
+        ProviderConfig {
+            client_id: "client".to_string(),
+            client_secret: None,
+            auth_url: "https://accounts.example.com/authorize".to_string(),
+            token_url: "https://accounts.example.com/token".to_string(),
+            device_auth_url: None,
+            revocation_url: None,
+            redirect_uri: "http://127.0.0.1:{port}/callback".to_string(),
+            native_redirect_uri: None,
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            issuer: None,
+            service_scopes,
+        }
+    }
+
+    #[test]
+    fn fills_in_the_scopes_of_requested_services() {
+        let mut config = config(&["openid", google::CALENDAR], HashMap::new());
+
+        assert!(config.validate_scopes(Provider::Google).is_empty());
+        assert_eq!(
+            config.service_scopes.get(&Service::Calendar),
+            Some(&vec![google::CALENDAR.to_string()])
+        );
+        assert!(!config.service_scopes.contains_key(&Service::Email));
+    }
+
+    #[test]
+    fn warns_about_service_scopes_never_requested() {
+        let mut config = config(
+            &["openid"],
+            HashMap::from([(Service::Todo, vec![google::TASKS.to_string()])]),
+        );
+
+        let warnings = config.validate_scopes(Provider::Google);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(google::TASKS));
+    }
 }
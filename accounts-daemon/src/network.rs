@@ -0,0 +1,33 @@
+use zbus::{Connection, proxy};
+
+/// `NM_METERED_YES` and `NM_METERED_GUESS_YES`, the connection is metered or likely is, e.g.
+/// a phone hotspot.
+const METERED: [u32; 2] = [1, 3];
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager",
+    interface = "org.freedesktop.NetworkManager"
+)]
+trait NetworkManager {
+    #[zbus(property)]
+    fn metered(&self) -> zbus::Result<u32>;
+}
+
+/// Whether NetworkManager reports the primary connection as metered.
+///
+/// Systems without NetworkManager are taken as unmetered.
+pub async fn is_metered() -> bool {
+    match metered().await {
+        Ok(metered) => METERED.contains(&metered),
+        Err(err) => {
+            tracing::debug!("Failed to read the metered state: {}", err);
+            false
+        }
+    }
+}
+
+async fn metered() -> zbus::Result<u32> {
+    let connection = Connection::system().await?;
+    NetworkManagerProxy::new(&connection).await?.metered().await
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use accounts::models::Account;
+use cosmic_config::{Config, ConfigGet};
+use futures_util::StreamExt;
+use zbus::{Connection, proxy, zvariant::Value};
+
+use crate::{
+    account::{AUTHORIZATION_REQUEST_TIMEOUT, PORTAL_CONSENT_TIMEOUT},
+    fl,
+};
+
+/// Binary of the accounts page, opened when a notification is clicked.
+const ACCOUNTS_APP: &str = "accounts-ui";
+
+/// Desktop entry of the accounts page, which the notifications are shown for.
+const DESKTOP_ENTRY: &str = "dev.edfloreshz.Accounts.App";
+
+/// Config of the COSMIC notifications daemon, which keeps its do not disturb switch.
+const COSMIC_NOTIFICATIONS: &str = "com.system76.CosmicNotifications";
+
+/// How often held back notifications check whether do not disturb ended.
+const DO_NOT_DISTURB_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Authorization requests held back while do not disturb is on, with when they were made.
+static HELD_BACK: Mutex<Vec<(Account, String, Instant)>> = Mutex::new(Vec::new());
+
+#[proxy(
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications",
+    interface = "org.freedesktop.Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+
+    /// Whether the server holds notifications back, not every server has it.
+    #[zbus(property)]
+    fn inhibited(&self) -> zbus::Result<bool>;
+}
+
+/// Tells the user an application waits to be allowed to use an account, which may happen
+/// while no accounts page is open to ask. Clicking the notification opens the account,
+/// until the request would time out.
+///
+/// While do not disturb is on the request is held back, and shown once it ends unless the
+/// request timed out meanwhile.
+pub async fn authorization_requested(account: Account, app: String) {
+    let notifications = match notifications().await {
+        Ok(notifications) => notifications,
+        Err(err) => {
+            tracing::debug!("Failed to notify an authorization request: {}", err);
+            return;
+        }
+    };
+    if do_not_disturb(&notifications).await {
+        hold_back(account, app);
+        return;
+    }
+    if let Err(err) = notify_authorization_requested(&notifications, &account, &app).await {
+        tracing::debug!("Failed to notify an authorization request: {}", err);
+    }
+}
+
+async fn notifications() -> zbus::Result<NotificationsProxy<'static>> {
+    let connection = Connection::session().await?;
+    NotificationsProxy::new(&connection).await
+}
+
+/// Whether the notification server or COSMIC's do not disturb switch holds notifications
+/// back.
+async fn do_not_disturb(notifications: &NotificationsProxy<'_>) -> bool {
+    if let Ok(inhibited) = notifications.inhibited().await {
+        return inhibited;
+    }
+    Config::new(COSMIC_NOTIFICATIONS, 1)
+        .and_then(|config| config.get::<bool>("do_not_disturb"))
+        .unwrap_or_default()
+}
+
+/// Queues a request until do not disturb ends, waiting for it unless a request queued
+/// before already does.
+fn hold_back(account: Account, app: String) {
+    let mut held_back = HELD_BACK.lock().unwrap();
+    let waiting = !held_back.is_empty();
+    held_back.retain(|(queued, queued_app, _)| queued.id != account.id || *queued_app != app);
+    held_back.push((account, app, Instant::now()));
+    drop(held_back);
+    if !waiting {
+        tokio::spawn(notify_held_back());
+    }
+}
+
+/// Waits for do not disturb to end, then shows the requests held back which didn't time
+/// out meanwhile.
+async fn notify_held_back() {
+    let notifications = loop {
+        tokio::time::sleep(DO_NOT_DISTURB_POLL_INTERVAL).await;
+        match notifications().await {
+            Ok(notifications) if !do_not_disturb(&notifications).await => break notifications,
+            Ok(_) => {}
+            Err(err) => tracing::debug!("Failed to check do not disturb: {}", err),
+        }
+    };
+    let held_back = std::mem::take(&mut *HELD_BACK.lock().unwrap());
+    for (account, app, requested_at) in held_back {
+        if requested_at.elapsed() >= AUTHORIZATION_REQUEST_TIMEOUT {
+            continue;
+        }
+        if let Err(err) = notify_authorization_requested(&notifications, &account, &app).await {
+            tracing::debug!("Failed to notify an authorization request: {}", err);
+        }
+    }
+}
+
+async fn notify_authorization_requested(
+    notifications: &NotificationsProxy<'_>,
+    account: &Account,
+    app: &str,
+) -> zbus::Result<()> {
+    // Subscribed before notifying, so a quick click isn't missed
+    let mut invoked = notifications.receive_action_invoked().await?;
+    let mut closed = notifications.receive_notification_closed().await?;
+
+    let account_name = account.email.as_deref().unwrap_or(&account.username);
+    let id = notifications
+        .notify(
+            &fl!("app-name"),
+            0,
+            "dev.edfloreshz.Accounts",
+            &fl!("authorization-requested"),
+            &fl!(
+                "authorization-requested-body",
+                app = app,
+                account = account_name
+            ),
+            &["default", &fl!("open-accounts")],
+            HashMap::from([("desktop-entry", Value::from(DESKTOP_ENTRY))]),
+            -1,
+        )
+        .await?;
+
+    let account_id = account.id;
+    let answered = async move {
+        loop {
+            tokio::select! {
+                Some(signal) = invoked.next() => {
+                    if signal.args().is_ok_and(|args| args.id == id) {
+                        let opened = tokio::process::Command::new(ACCOUNTS_APP)
+                            .arg("--account")
+                            .arg(account_id.to_string())
+                            .spawn();
+                        if let Err(err) = opened {
+                            tracing::warn!("Failed to open {}: {}", ACCOUNTS_APP, err);
+                        }
+                        break;
+                    }
+                }
+                Some(signal) = closed.next() => {
+                    if signal.args().is_ok_and(|args| args.id == id) {
+                        break;
+                    }
+                }
+                else => break,
+            }
+        }
+    };
+    // Nobody waits for the answer anymore once the request times out
+    tokio::spawn(tokio::time::timeout(PORTAL_CONSENT_TIMEOUT, answered));
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use oauth2::{
+    Client, ExtraTokenFields, StandardRevocableToken, StandardTokenResponse,
+    basic::{
+        BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+        BasicTokenType,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, models::ProviderConfig};
+
+/// Token response fields added by OpenID Connect.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdTokenFields {
+    pub id_token: Option<String>,
+}
+
+impl ExtraTokenFields for IdTokenFields {}
+
+pub type OidcTokenResponse = StandardTokenResponse<IdTokenFields, BasicTokenType>;
+
+pub type OidcClient = Client<
+    BasicErrorResponse,
+    OidcTokenResponse,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// The claims of an ID token used to identify the account.
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    aud: Audience,
+    exp: i64,
+    /// Microsoft tenant the account belongs to, part of its issuer.
+    tid: Option<String>,
+    /// Microsoft object ID of the user.
+    pub oid: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub preferred_username: Option<String>,
+    /// URL of the profile picture, only sent by Google.
+    pub picture: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::Single(audience) => audience == client_id,
+            Audience::Multiple(audiences) => audiences.iter().any(|audience| audience == client_id),
+        }
+    }
+}
+
+/// Decodes an ID token and validates its claims against the provider configuration.
+///
+/// The token is received directly from the token endpoint over TLS, so its signature is
+/// not checked, as allowed by OpenID Connect Core 3.1.3.7.
+pub fn decode_id_token(id_token: &str, config: &ProviderConfig) -> Result<IdTokenClaims> {
+    let invalid = |reason: &str| Error::AuthenticationFailed {
+        reason: format!("Invalid ID token: {reason}"),
+    };
+
+    let mut parts = id_token.split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("malformed token"));
+    };
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| invalid("payload is not base64url"))?;
+    let claims: IdTokenClaims = serde_json::from_slice(&payload)?;
+
+    if !claims.aud.contains(&config.client_id) {
+        return Err(invalid("issued for another client"));
+    }
+    if claims.exp <= Utc::now().timestamp() {
+        return Err(invalid("expired"));
+    }
+    if let Some(issuer) = &config.issuer {
+        let issuer = match &claims.tid {
+            Some(tenant_id) => issuer.replace("{tenantid}", tenant_id),
+            None => issuer.clone(),
+        };
+        if claims.iss != issuer {
+            return Err(invalid("unexpected issuer"));
+        }
+    }
+
+    Ok(claims)
+}
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+/// Overrides every other provider directory, meant for running from the source tree with
+/// `COSMIC_ACCOUNTS_PROVIDERS_DIR=accounts-daemon/data/providers`.
+const PROVIDERS_DIR_ENV: &str = "COSMIC_ACCOUNTS_PROVIDERS_DIR";
+
+/// The directories provider configs are looked up in, the first one having a provider's
+/// file wins:
+///
+/// 1. `$COSMIC_ACCOUNTS_PROVIDERS_DIR`, when set.
+/// 2. `$XDG_CONFIG_HOME/accounts/providers`, for the user's own client registrations.
+/// 3. `/etc/accounts/providers`, for the administrator's.
+/// 4. `<dir>/accounts/providers` for each of `$XDG_DATA_DIRS`, where packages install them.
+pub fn provider_dirs() -> Vec<PathBuf> {
+    if let Some(dir) = std::env::var_os(PROVIDERS_DIR_ENV) {
+        return vec![PathBuf::from(dir)];
+    }
+
+    let mut dirs = Vec::new();
+    if let Some(config_home) = config_home() {
+        dirs.push(config_home.join("accounts").join("providers"));
+    }
+    dirs.push(PathBuf::from("/etc/accounts/providers"));
+    dirs.extend(
+        data_dirs()
+            .into_iter()
+            .map(|dir| dir.join("accounts").join("providers")),
+    );
+    dirs
+}
+
+/// Where the daemon keeps its own data, `$XDG_DATA_HOME/cosmic-accounts`.
+pub fn data_dir() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".local/share")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("cosmic-accounts")
+}
+
+/// Where the daemon caches downloaded data, `$XDG_CACHE_HOME/cosmic-accounts`.
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("cosmic-accounts")
+}
+
+/// Where the Evolution Data Server registry loads user sources from,
+/// `$XDG_CONFIG_HOME/evolution/sources`.
+pub fn evolution_sources_dir() -> Option<PathBuf> {
+    config_home().map(|config_home| config_home.join("evolution").join("sources"))
+}
+
+/// Files marking a directory for backup tools to skip, and their content.
+const BACKUP_MARKERS: [(&str, &str); 2] = [
+    (
+        "CACHEDIR.TAG",
+        "Signature: 8a477f597d28d172789f06886806bc55\n\
+         # This directory holds credentials or caches of the COSMIC accounts daemon.\n\
+         # For information about cache directory tags see https://bford.info/cachedir/\n",
+    ),
+    (".nobackup", ""),
+];
+
+/// Marks a directory for backup tools to skip, with a `CACHEDIR.TAG` as most of them
+/// understand and a `.nobackup` file for those configured to look for one.
+///
+/// Used for the credentials and caches, which must not end up in backups; the account
+/// structure can be kept with `ExportManifest` instead.
+pub fn exclude_from_backups(dir: &Path) {
+    for (name, content) in BACKUP_MARKERS {
+        let path = dir.join(name);
+        if !path.exists()
+            && let Err(err) = std::fs::write(&path, content)
+        {
+            tracing::debug!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Removes the markers [`exclude_from_backups`] wrote, leaving ones with other content.
+pub fn include_in_backups(dir: &Path) {
+    for (name, content) in BACKUP_MARKERS {
+        let path = dir.join(name);
+        if std::fs::read_to_string(&path).is_ok_and(|written| written == content)
+            && let Err(err) = std::fs::remove_file(&path)
+        {
+            tracing::debug!("Failed to remove {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, defaulting to `~/.config`.
+pub fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".config")))
+}
+
+/// `$XDG_DATA_DIRS`, defaulting to `/usr/local/share:/usr/share` as the spec requires.
+fn data_dirs() -> Vec<PathBuf> {
+    let dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
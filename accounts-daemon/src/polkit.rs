@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use accounts::AccountsError;
+use zbus::{
+    Connection,
+    fdo::DBusProxy,
+    message::{Flags, Header},
+    names::BusName,
+    proxy,
+    zvariant::Value,
+};
+
+/// Action checked before an account is removed.
+pub const REMOVE_ACCOUNT: &str = "dev.edfloreshz.Accounts.remove-account";
+/// Action checked before an account is unlocked, which allows removing it again.
+pub const UNLOCK_ACCOUNT: &str = "dev.edfloreshz.Accounts.unlock-account";
+/// Action checked before a refresh token leaves the daemon.
+pub const GET_REFRESH_TOKEN: &str = "dev.edfloreshz.Accounts.get-refresh-token";
+/// Action checked before the log level of the daemon changes.
+pub const SET_LOG_LEVEL: &str = "dev.edfloreshz.Accounts.set-log-level";
+/// Action checked before the accounts are exported.
+pub const EXPORT_ACCOUNTS: &str = "dev.edfloreshz.Accounts.export-accounts";
+/// Action checked before accounts are restored from a manifest.
+pub const IMPORT_ACCOUNTS: &str = "dev.edfloreshz.Accounts.import-accounts";
+/// Action checked before an application is allowed or denied the tokens of an account.
+pub const SET_APP_ACCESS: &str = "dev.edfloreshz.Accounts.set-app-access";
+/// Action checked before the settings of a service change, which may redirect its requests.
+pub const SET_SERVICE_SETTING: &str = "dev.edfloreshz.Accounts.set-service-setting";
+/// Action checked before paused accounts are resumed, so no application undoes a pause.
+pub const RESUME_ACCOUNTS: &str = "dev.edfloreshz.Accounts.resume-accounts";
+/// Action checked before access tokens or the accounts leave the daemon, which every user
+/// may reach on the system bus, so it needs an administrator.
+pub const ACCESS_ACCOUNTS: &str = "dev.edfloreshz.Accounts.access-accounts";
+
+/// Lets the user authenticate, e.g. by typing their password, instead of failing right away.
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+#[proxy(
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority",
+    interface = "org.freedesktop.PolicyKit1.Authority"
+)]
+trait Authority {
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// Whether the daemon serves the system bus, set with `COSMIC_ACCOUNTS_BUS=system`.
+///
+/// Every user owns their session bus, so actions are only checked on the system bus.
+pub fn system_bus() -> bool {
+    accounts::clients::system_bus()
+}
+
+/// Checks the caller of a method is authorized to perform an action.
+///
+/// The user is only prompted when the caller allowed interactive authorization, otherwise
+/// actions which need it fail with `InteractiveAuthorizationRequired`.
+pub async fn check(
+    connection: &Connection,
+    header: &Header<'_>,
+    action_id: &str,
+) -> Result<(), AccountsError> {
+    if !system_bus() {
+        return Ok(());
+    }
+    let Some(sender) = header.sender() else {
+        return Err(AccountsError::NotAuthorized(
+            "The caller has no bus name".to_string(),
+        ));
+    };
+
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender.as_str()))]),
+    );
+    authorize(connection, header, &subject, action_id).await
+}
+
+/// Checks the caller of a method is authorized to perform an action, on the session bus
+/// too.
+///
+/// For actions other applications of the user must not take on their own, e.g. allowing
+/// themselves to read tokens. polkit can't resolve session bus names, so the caller is
+/// identified by its process there.
+pub async fn check_caller(
+    connection: &Connection,
+    header: &Header<'_>,
+    action_id: &str,
+) -> Result<(), AccountsError> {
+    if system_bus() {
+        return check(connection, header, action_id).await;
+    }
+    let Some(sender) = header.sender() else {
+        return Err(AccountsError::NotAuthorized(
+            "The caller has no bus name".to_string(),
+        ));
+    };
+    let pid = DBusProxy::new(connection)
+        .await?
+        .get_connection_unix_process_id(BusName::from(sender.to_owned()))
+        .await?;
+    let Some(start_time) = process_start_time(pid) else {
+        return Err(AccountsError::NotAuthorized(format!(
+            "The process of {sender} could not be identified"
+        )));
+    };
+
+    let subject = (
+        "unix-process",
+        HashMap::from([
+            ("pid", Value::from(pid)),
+            ("start-time", Value::from(start_time)),
+        ]),
+    );
+    let connection = Connection::system().await.map_err(unavailable)?;
+    authorize(&connection, header, &subject, action_id).await
+}
+
+/// When a process started, in clock ticks since boot, which tells it apart from a later
+/// process reusing its PID.
+fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may contain spaces, the fields after it don't
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Asks polkit, on the system bus `connection`, whether the subject may perform an action.
+async fn authorize(
+    connection: &Connection,
+    header: &Header<'_>,
+    subject: &(&str, HashMap<&str, Value<'_>>),
+    action_id: &str,
+) -> Result<(), AccountsError> {
+    let flags = if header
+        .primary()
+        .flags()
+        .contains(Flags::AllowInteractiveAuth)
+    {
+        ALLOW_USER_INTERACTION
+    } else {
+        0
+    };
+
+    let authority = AuthorityProxy::new(connection).await.map_err(unavailable)?;
+    let (authorized, challenge, _) = authority
+        .check_authorization(subject, action_id, HashMap::new(), flags, "")
+        .await
+        .map_err(unavailable)?;
+
+    if authorized {
+        Ok(())
+    } else if challenge {
+        Err(zbus::fdo::Error::InteractiveAuthorizationRequired(format!(
+            "Authentication is required to perform {action_id}"
+        ))
+        .into())
+    } else {
+        Err(AccountsError::NotAuthorized(format!(
+            "Not authorized to perform {action_id}"
+        )))
+    }
+}
+
+/// Refuses the action when polkit can't be asked, e.g. without a system bus, the same way
+/// polkit refuses it.
+fn unavailable(error: zbus::Error) -> AccountsError {
+    AccountsError::NotAuthorized(format!("polkit is unavailable: {error}"))
+}
@@ -0,0 +1,50 @@
+use accounts::{
+    AccountsError,
+    models::{Provider, Service},
+};
+use zbus::{Connection, interface, message::Header};
+
+use crate::{account::AccountsInterface, polkit};
+
+/// Name sandboxed applications are allowed to talk to, e.g. with Flatpak's
+/// `--talk-name=dev.edfloreshz.Accounts.Portal`.
+pub const PORTAL_BUS_NAME: &str = "dev.edfloreshz.Accounts.Portal";
+
+pub const PORTAL_PATH: &str = "/dev/edfloreshz/Accounts/Portal";
+
+/// The only way into the daemon for sandboxed applications.
+///
+/// It is served on its own connection, so being allowed to talk to its name doesn't give
+/// access to the accounts interface, and it only ever hands out tokens limited to a service
+/// after asking the user.
+pub struct PortalInterface;
+
+#[interface(name = "dev.edfloreshz.Accounts.Portal")]
+impl PortalInterface {
+    /// Request a token for a service of an account of the provider
+    ///
+    /// Returns the ID of the account and the token once the user allowed the application.
+    async fn request_token(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        provider: Provider,
+        service: Service,
+    ) -> Result<(String, String), AccountsError> {
+        AccountsInterface::portal_token(connection, &header, provider, service).await
+    }
+}
+
+/// Serves the portal on a connection of its own, which must be kept alive.
+pub async fn serve() -> zbus::Result<Connection> {
+    let builder = if polkit::system_bus() {
+        zbus::connection::Builder::system()?
+    } else {
+        zbus::connection::Builder::session()?
+    };
+    builder
+        .name(PORTAL_BUS_NAME)?
+        .serve_at(PORTAL_PATH, PortalInterface)?
+        .build()
+        .await
+}
@@ -0,0 +1,74 @@
+use zbus::{Connection, proxy};
+
+#[proxy(
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower",
+    interface = "org.freedesktop.UPower"
+)]
+trait UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+/// power-profiles-daemon, which took this name over from `net.hadess.PowerProfiles`.
+#[proxy(
+    default_service = "org.freedesktop.UPower.PowerProfiles",
+    default_path = "/org/freedesktop/UPower/PowerProfiles",
+    interface = "org.freedesktop.UPower.PowerProfiles"
+)]
+trait PowerProfiles {
+    #[zbus(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+}
+
+#[proxy(
+    default_service = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles",
+    interface = "net.hadess.PowerProfiles"
+)]
+trait LegacyPowerProfiles {
+    #[zbus(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+}
+
+/// Whether UPower reports the system running on battery.
+///
+/// Systems without UPower are taken as plugged in.
+pub async fn on_battery() -> bool {
+    let on_battery = async {
+        let connection = Connection::system().await?;
+        UPowerProxy::new(&connection).await?.on_battery().await
+    };
+    on_battery.await.unwrap_or_else(|err| {
+        tracing::debug!("Failed to read the battery state: {}", err);
+        false
+    })
+}
+
+/// Whether the user picked the power saver profile.
+pub async fn saving_power() -> bool {
+    match active_profile().await {
+        Ok(profile) => profile == "power-saver",
+        Err(err) => {
+            tracing::debug!("Failed to read the power profile: {}", err);
+            false
+        }
+    }
+}
+
+async fn active_profile() -> zbus::Result<String> {
+    let connection = Connection::system().await?;
+    match PowerProfilesProxy::new(&connection)
+        .await?
+        .active_profile()
+        .await
+    {
+        Ok(profile) => Ok(profile),
+        Err(_) => {
+            LegacyPowerProfilesProxy::new(&connection)
+                .await?
+                .active_profile()
+                .await
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use accounts::models::Provider;
+use chrono::Utc;
+
+use crate::{Error, Result, settings};
+
+/// The longest a request waits for its turn, those which would wait longer fail instead
+/// of queueing up behind a client calling in a loop.
+const MAX_WAIT: Duration = Duration::from_secs(5);
+
+/// Who a request to a provider is sent for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Priority {
+    /// Signing in, which the user waits for. It is never held back, so a client calling in
+    /// a loop can't keep the user from signing in, but it takes a token when there is one.
+    SignIn,
+    /// Everything else, which waits for its turn.
+    Queued,
+}
+
+/// A token bucket, refilled at the configured rate up to the burst.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    /// Refills the bucket for the time passed since it was last used, then takes a token
+    /// for a request. Returns how long the request waits for it, or fails with the wait
+    /// when it is longer than [`MAX_WAIT`], taking nothing.
+    fn take(
+        &mut self,
+        now: Instant,
+        rate: f64,
+        burst: f64,
+        priority: Priority,
+    ) -> std::result::Result<Duration, Duration> {
+        self.tokens = (self.tokens
+            + now.saturating_duration_since(self.updated).as_secs_f64() * rate)
+            .min(burst);
+        self.updated = now;
+
+        if priority == Priority::SignIn {
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+            }
+            return Ok(Duration::ZERO);
+        }
+        // Requests which have to wait take their token right away, the ones after them
+        // wait for the next
+        let wait = Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / rate);
+        if wait > MAX_WAIT {
+            return Err(wait);
+        }
+        self.tokens -= 1.0;
+        Ok(wait)
+    }
+}
+
+static BUCKETS: LazyLock<Mutex<HashMap<Provider, Bucket>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Waits until a request may be sent to the provider, every account of the provider shares
+/// the same limit.
+///
+/// Fails with [`Error::RateLimited`] when the request would have to wait longer than
+/// [`MAX_WAIT`], so the provider never sees more than the configured rate.
+pub async fn acquire(provider: Provider, priority: Priority) -> Result<()> {
+    let settings = settings::get();
+    let Some(rate) = settings.provider_rate_limit() else {
+        return Ok(());
+    };
+    let burst = f64::from(settings.provider_burst.max(1));
+
+    let wait = {
+        let mut buckets = BUCKETS.lock().unwrap();
+        let now = Instant::now();
+        buckets
+            .entry(provider)
+            .or_insert_with(|| Bucket::new(burst, now))
+            .take(now, rate, burst, priority)
+            .map_err(|wait| Error::RateLimited {
+                provider,
+                until: Utc::now() + chrono::Duration::from_std(wait - MAX_WAIT).unwrap_or_default(),
+            })?
+    };
+
+    if !wait.is_zero() {
+        tracing::debug!(
+            "Holding back a request to {} for {}ms",
+            provider,
+            wait.as_millis()
+        );
+        tokio::time::sleep(wait).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 2.0;
+    const BURST: f64 = 3.0;
+
+    #[test]
+    fn lets_a_burst_through_at_once() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(BURST, now);
+        for _ in 0..3 {
+            assert_eq!(
+                bucket.take(now, RATE, BURST, Priority::Queued),
+                Ok(Duration::ZERO)
+            );
+        }
+        assert_eq!(
+            bucket.take(now, RATE, BURST, Priority::Queued),
+            Ok(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn refills_at_the_rate_up_to_the_burst() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(BURST, now);
+        for _ in 0..3 {
+            bucket.take(now, RATE, BURST, Priority::Queued).unwrap();
+        }
+
+        let later = now + Duration::from_secs(1);
+        for _ in 0..2 {
+            assert_eq!(
+                bucket.take(later, RATE, BURST, Priority::Queued),
+                Ok(Duration::ZERO)
+            );
+        }
+        assert!(bucket.take(later, RATE, BURST, Priority::Queued).unwrap() > Duration::ZERO);
+
+        let idle = later + Duration::from_secs(60);
+        bucket.take(idle, RATE, BURST, Priority::Queued).unwrap();
+        assert_eq!(bucket.tokens, BURST - 1.0);
+    }
+
+    #[test]
+    fn rejects_requests_waiting_longer_than_max_wait() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(BURST, now);
+        let mut waits = Vec::new();
+        loop {
+            match bucket.take(now, RATE, BURST, Priority::Queued) {
+                Ok(wait) => waits.push(wait),
+                Err(wait) => {
+                    assert!(wait > MAX_WAIT);
+                    break;
+                }
+            }
+        }
+        assert!(waits.iter().all(|wait| *wait <= MAX_WAIT));
+        // Rejected requests take nothing, the next one is rejected the same way
+        let tokens = bucket.tokens;
+        assert!(bucket.take(now, RATE, BURST, Priority::Queued).is_err());
+        assert_eq!(bucket.tokens, tokens);
+    }
+
+    #[test]
+    fn never_holds_back_signing_in() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(BURST, now);
+        while bucket.take(now, RATE, BURST, Priority::Queued).is_ok() {}
+
+        assert_eq!(
+            bucket.take(now, RATE, BURST, Priority::SignIn),
+            Ok(Duration::ZERO)
+        );
+        assert!(bucket.take(now, RATE, BURST, Priority::Queued).is_err());
+    }
+}
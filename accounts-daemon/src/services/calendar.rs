@@ -1,17 +1,23 @@
 use std::collections::HashMap;
 
 use accounts::{
-    AccountService, ServiceConfig,
-    models::{Account, Provider, Service},
+    AccountService, AccountsError, ServiceConfig,
+    models::{Account, Provider, SecretString, Service},
 };
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use zbus::{
+    Connection,
     fdo::{Error, Result},
     interface,
+    message::Header,
 };
 
-use crate::CONNECTION;
+use super::{
+    ServiceObject,
+    events::{self, EventStore},
+    offline,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CalendarService {
@@ -22,13 +28,31 @@ impl CalendarService {
     pub fn new(account: Account) -> Self {
         Self { account }
     }
+
+    pub(super) fn events<'a>(&'a self, access_token: &'a SecretString) -> EventStore<'a> {
+        EventStore::new(
+            &self.account,
+            access_token,
+            self.setting("uri").map(String::as_str),
+        )
+    }
+}
+
+impl ServiceObject for CalendarService {
+    const SERVICE: Service = Service::Calendar;
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
 }
 
 #[interface(name = "dev.edfloreshz.Accounts.Calendar")]
 impl CalendarService {
     #[zbus(property)]
     async fn uri(&self) -> Result<String> {
-        if self.account.provider == Provider::Google {
+        if let Some(uri) = self.setting("uri") {
+            Ok(uri.clone())
+        } else if self.account.provider == Provider::Google {
             Ok("https://apidata.googleusercontent.com/caldav/v2/".to_string())
         } else if self.account.provider == Provider::Microsoft {
             Ok("https://outlook.office365.com/".to_string())
@@ -41,6 +65,74 @@ impl CalendarService {
     async fn accept_ssl_errors(&self) -> Result<bool> {
         Ok(false)
     }
+
+    /// The events of the default calendar between two Unix timestamps, as iCalendar objects
+    async fn list_events(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        start: i64,
+        end: i64,
+    ) -> std::result::Result<Vec<String>, AccountsError> {
+        let (start, end) = (events::timestamp(start)?, events::timestamp(end)?);
+        let access_token = self.access_token(connection, &header, "ListEvents").await?;
+        if let Some(events) = offline::cached_events(&self.account.id, start, end, false) {
+            return Ok(events);
+        }
+        match self.events(&access_token).list(start, end).await {
+            Ok(events) => Ok(events),
+            Err(err) if offline::unreachable(&err) => {
+                offline::cached_events(&self.account.id, start, end, true).ok_or_else(|| err.into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Create an event from an iCalendar object, returning its UID
+    async fn create_event(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        ics: &str,
+    ) -> std::result::Result<String, AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "CreateEvent")
+            .await?;
+        let uid = self.events(&access_token).create(ics).await?;
+        offline::invalidate_events(&self.account.id);
+        Ok(uid)
+    }
+
+    /// Replace the event with the UID
+    async fn update_event(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        uid: &str,
+        ics: &str,
+    ) -> std::result::Result<(), AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "UpdateEvent")
+            .await?;
+        self.events(&access_token).update(uid, ics).await?;
+        offline::invalidate_events(&self.account.id);
+        Ok(())
+    }
+
+    /// Delete the event with the UID
+    async fn delete_event(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        uid: &str,
+    ) -> std::result::Result<(), AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "DeleteEvent")
+            .await?;
+        self.events(&access_token).delete(uid).await?;
+        offline::invalidate_events(&self.account.id);
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -74,6 +166,17 @@ impl AccountService for CalendarService {
 
         settings.insert("accept_ssl_errors".to_string(), false.into());
 
+        // Settings overridden by the user replace the provider defaults
+        if let Some(overrides) = account.service_settings.get(&Service::Calendar) {
+            for (key, value) in overrides {
+                let value = match value.parse::<bool>() {
+                    Ok(flag) => flag.into(),
+                    Err(_) => value.clone().into(),
+                };
+                settings.insert(key.clone(), value);
+            }
+        }
+
         Ok(ServiceConfig {
             service_type: "Calendar".to_string(),
             provider_type: account.provider.to_string(),
@@ -82,40 +185,11 @@ impl AccountService for CalendarService {
     }
 
     async fn add_service(&self) -> Result<bool> {
-        tracing::info!(
-            "Adding a calendar service for account {}",
-            self.account.dbus_id()
-        );
-        if let Some(connection) = CONNECTION.get() {
-            connection
-                .object_server()
-                .at(
-                    format!(
-                        "/dev/edfloreshz/Accounts/Calendar/{}",
-                        self.account.dbus_id()
-                    ),
-                    self.clone(),
-                )
-                .await?;
-        }
-        Ok(false)
+        self.register().await
     }
 
     async fn remove_service(&self) -> Result<bool> {
-        tracing::info!(
-            "Removing calendar service for account {}",
-            self.account.dbus_id()
-        );
-        if let Some(connection) = CONNECTION.get() {
-            connection
-                .object_server()
-                .remove::<CalendarService, String>(format!(
-                    "/dev/edfloreshz/Accounts/Calendar/{}",
-                    self.account.dbus_id()
-                ))
-                .await?;
-        }
-        Ok(false)
+        self.unregister().await
     }
 
     async fn ensure_credentials(&self, _account: &mut Account) -> Result<()> {
@@ -1,23 +1,31 @@
 use std::collections::HashMap;
 
-use async_trait::async_trait;
-use zbus::{
-    fdo::{Error, Result},
-    interface,
-};
-
-use crate::{
+use accounts::{
+    AccountService, AccountsError, ServiceConfig,
     models::{Account, Provider, Service},
-    services::{Service, ServiceConfig},
 };
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use zbus::{Connection, fdo::Result, interface, message::Header};
+
+use super::{ServiceObject, offline, people::ContactStore};
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ContactsService {
-    account_id: String,
+    account: Account,
 }
 
 impl ContactsService {
-    pub fn new(account_id: String) -> Self {
-        Self { account_id }
+    pub fn new(account: Account) -> Self {
+        Self { account }
+    }
+}
+
+impl ServiceObject for ContactsService {
+    const SERVICE: Service = Service::Contacts;
+
+    fn account(&self) -> &Account {
+        &self.account
     }
 }
 
@@ -25,13 +33,13 @@ impl ContactsService {
 impl ContactsService {
     #[zbus(property)]
     async fn uri(&self) -> Result<String> {
-        if self.account_id.contains("google") {
-            Ok("https://www.googleapis.com/.well-known/carddav".to_string())
-        } else if self.account_id.contains("microsoft") {
-            Ok("https://outlook.office365.com/".to_string())
-        } else {
-            Err(Error::Failed("Unsupported provider".to_string()))
+        if let Some(uri) = self.setting("uri") {
+            return Ok(uri.clone());
         }
+        Ok(match self.account.provider {
+            Provider::Google => "https://www.googleapis.com/.well-known/carddav".to_string(),
+            Provider::Microsoft => "https://outlook.office365.com/".to_string(),
+        })
     }
 
     /// Whether to accept SSL errors - matches GOA's AcceptSslErrors
@@ -39,10 +47,46 @@ impl ContactsService {
     async fn accept_ssl_errors(&self) -> Result<bool> {
         Ok(false)
     }
+
+    /// Up to `limit` contacts whose name or address matches the query, as vCards
+    async fn search_contacts(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        query: &str,
+        limit: u32,
+    ) -> std::result::Result<Vec<String>, AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "SearchContacts")
+            .await?;
+        match ContactStore::new(&self.account, &access_token)
+            .search(query, limit)
+            .await
+        {
+            Ok(contacts) => Ok(contacts),
+            Err(err) if offline::unreachable(&err) => {
+                offline::search_contacts(&self.account.id, query, limit).ok_or_else(|| err.into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The contact with the UID, as a vCard
+    async fn get_contact(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        uid: &str,
+    ) -> std::result::Result<String, AccountsError> {
+        let access_token = self.access_token(connection, &header, "GetContact").await?;
+        Ok(ContactStore::new(&self.account, &access_token)
+            .contact(uid)
+            .await?)
+    }
 }
 
 #[async_trait]
-impl Service for ContactsService {
+impl AccountService for ContactsService {
     fn name(&self) -> &str {
         "Contacts"
     }
@@ -72,6 +116,17 @@ impl Service for ContactsService {
 
         settings.insert("accept_ssl_errors".to_string(), false.into());
 
+        // Settings overridden by the user replace the provider defaults
+        if let Some(overrides) = account.service_settings.get(&Service::Contacts) {
+            for (key, value) in overrides {
+                let value = match value.parse::<bool>() {
+                    Ok(flag) => flag.into(),
+                    Err(_) => value.clone().into(),
+                };
+                settings.insert(key.clone(), value);
+            }
+        }
+
         Ok(ServiceConfig {
             service_type: "Contacts".to_string(),
             provider_type: account.provider.to_string(),
@@ -79,6 +134,14 @@ impl Service for ContactsService {
         })
     }
 
+    async fn add_service(&self) -> Result<bool> {
+        self.register().await
+    }
+
+    async fn remove_service(&self) -> Result<bool> {
+        self.unregister().await
+    }
+
     async fn ensure_credentials(&self, _account: &mut Account) -> Result<()> {
         Ok(())
     }
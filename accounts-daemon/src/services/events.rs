@@ -0,0 +1,532 @@
+use accounts::models::{Account, Provider, SecretString};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde_json::{Value, json};
+use url::Url;
+
+use super::ical::{self, Event, EventTime};
+use crate::{
+    Error, Result,
+    usage::{self, MeteredSend},
+};
+
+const GOOGLE_CALDAV_URL: &str = "https://apidata.googleusercontent.com/caldav/v2/";
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0/me";
+
+/// The events of the default calendar of an account, as iCalendar objects.
+///
+/// Google is reached over CalDAV, which speaks iCalendar already. Microsoft only offers
+/// Graph, whose events are translated, keeping the summary, description, location and
+/// times.
+pub struct EventStore<'a> {
+    account: &'a Account,
+    access_token: &'a SecretString,
+    /// Overrides the CalDAV server, from the `uri` calendar setting. Only other paths on
+    /// Google's server are honoured, as the account's token is sent to it.
+    caldav_url: Option<&'a str>,
+    client: Client,
+}
+
+impl<'a> EventStore<'a> {
+    pub fn new(
+        account: &'a Account,
+        access_token: &'a SecretString,
+        caldav_url: Option<&'a str>,
+    ) -> Self {
+        Self {
+            account,
+            access_token,
+            caldav_url,
+            client: Client::new(),
+        }
+    }
+
+    /// Returns the events overlapping the range.
+    pub async fn list(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<String>> {
+        match self.account.provider {
+            Provider::Google => {
+                let body = calendar_query(&format!(
+                    r#"<C:comp-filter name="VEVENT"><C:time-range start="{}" end="{}"/></C:comp-filter>"#,
+                    start.format("%Y%m%dT%H%M%SZ"),
+                    end.format("%Y%m%dT%H%M%SZ"),
+                ));
+                let response = self.report(&body).await?;
+                Ok(xml_elements(&response, "calendar-data"))
+            }
+            Provider::Microsoft => {
+                let mut url = Url::parse(&format!("{GRAPH_URL}/calendarView"))?;
+                url.query_pairs_mut()
+                    .append_pair("startDateTime", &start.to_rfc3339())
+                    .append_pair("endDateTime", &end.to_rfc3339())
+                    .append_pair("$top", "100");
+                let mut events = Vec::new();
+                let mut next = Some(url.to_string());
+                while let Some(url) = next {
+                    let page: Value = self
+                        .graph(Method::GET, &url)
+                        .send_metered(self.account, usage::CALENDAR)
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await?;
+                    events.extend(
+                        page["value"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(graph_to_event)
+                            .map(|event| event.to_ics()),
+                    );
+                    next = page["@odata.nextLink"].as_str().map(str::to_string);
+                }
+                Ok(events)
+            }
+        }
+    }
+
+    /// Creates an event and returns its UID, Microsoft assigns its own.
+    pub async fn create(&self, ics: &str) -> Result<String> {
+        match self.account.provider {
+            Provider::Google => {
+                let (uid, ics) = ical::ensure_uid(ics)?;
+                let url = self.collection()?.join(&format!("{}.ics", encode(&uid)))?;
+                self.client
+                    .put(url)
+                    .bearer_auth(self.access_token.expose())
+                    .header("Content-Type", "text/calendar; charset=utf-8")
+                    .header("If-None-Match", "*")
+                    .body(ics)
+                    .send_metered(self.account, usage::CALENDAR)
+                    .await?
+                    .error_for_status()?;
+                Ok(uid)
+            }
+            Provider::Microsoft => {
+                let event = ical::parse(ics)?;
+                let created: Value = self
+                    .graph(Method::POST, &format!("{GRAPH_URL}/events"))
+                    .json(&event_to_graph(&event))
+                    .send_metered(self.account, usage::CALENDAR)
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                created["iCalUId"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| Error::StorageError("Graph returned no event UID".to_string()))
+            }
+        }
+    }
+
+    /// Replaces the event with the UID.
+    pub async fn update(&self, uid: &str, ics: &str) -> Result<()> {
+        match self.account.provider {
+            Provider::Google => {
+                let url = self.caldav_href(uid).await?;
+                self.client
+                    .put(url)
+                    .bearer_auth(self.access_token.expose())
+                    .header("Content-Type", "text/calendar; charset=utf-8")
+                    .body(ics.to_string())
+                    .send_metered(self.account, usage::CALENDAR)
+                    .await?
+                    .error_for_status()?;
+            }
+            Provider::Microsoft => {
+                let id = self.graph_id(uid).await?;
+                self.graph(Method::PATCH, &format!("{GRAPH_URL}/events/{id}"))
+                    .json(&event_to_graph(&ical::parse(ics)?))
+                    .send_metered(self.account, usage::CALENDAR)
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the event with the UID.
+    pub async fn delete(&self, uid: &str) -> Result<()> {
+        let request = match self.account.provider {
+            Provider::Google => self
+                .client
+                .delete(self.caldav_href(uid).await?)
+                .bearer_auth(self.access_token.expose()),
+            Provider::Microsoft => {
+                let id = self.graph_id(uid).await?;
+                self.graph(Method::DELETE, &format!("{GRAPH_URL}/events/{id}"))
+            }
+        };
+        request
+            .send_metered(self.account, usage::CALENDAR)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// The events collection of the primary calendar, named after the account's address.
+    fn collection(&self) -> Result<Url> {
+        let server = caldav_server(self.caldav_url)?;
+        let calendar = self
+            .account
+            .email
+            .as_deref()
+            .unwrap_or(&self.account.username);
+        Ok(server.join(&format!("{}/events/", encode(calendar)))?)
+    }
+
+    async fn report(&self, body: &str) -> Result<String> {
+        let report = Method::from_bytes(b"REPORT").expect("REPORT is a valid method");
+        Ok(self
+            .client
+            .request(report, self.collection()?)
+            .bearer_auth(self.access_token.expose())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body.to_string())
+            .send_metered(self.account, usage::CALENDAR)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    /// Resources are not always named after the UID of their event, so they are looked up.
+    async fn caldav_href(&self, uid: &str) -> Result<Url> {
+        let body = calendar_query(&format!(
+            r#"<C:comp-filter name="VEVENT"><C:prop-filter name="UID"><C:text-match collation="i;octet">{}</C:text-match></C:prop-filter></C:comp-filter>"#,
+            xml_escape(uid)
+        ));
+        let response = self.report(&body).await?;
+        let href = xml_elements(&response, "href")
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::InvalidArguments(format!("Event {uid} not found")))?;
+        Ok(self.collection()?.join(&href)?)
+    }
+
+    async fn graph_id(&self, uid: &str) -> Result<String> {
+        let mut url = Url::parse(&format!("{GRAPH_URL}/events"))?;
+        url.query_pairs_mut()
+            .append_pair(
+                "$filter",
+                &format!("iCalUId eq '{}'", uid.replace('\'', "''")),
+            )
+            .append_pair("$select", "id");
+        let response = self
+            .graph(Method::GET, url.as_str())
+            .send_metered(self.account, usage::CALENDAR)
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::InvalidArguments(format!("Event {uid} not found")));
+        }
+        let events: Value = response.error_for_status()?.json().await?;
+        events["value"][0]["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| Error::InvalidArguments(format!("Event {uid} not found")))
+    }
+
+    fn graph(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .bearer_auth(self.access_token.expose())
+            .header(
+                "Prefer",
+                r#"outlook.timezone="UTC", outlook.body-content-type="text""#,
+            )
+    }
+}
+
+/// The CalDAV server of the account, which the `uri` setting may only move to another path
+/// on Google's server.
+fn caldav_server(uri: Option<&str>) -> Result<Url> {
+    let default = Url::parse(GOOGLE_CALDAV_URL)?;
+    let Some(uri) = uri else {
+        return Ok(default);
+    };
+    let server = Url::parse(uri)?;
+    if server.origin() != default.origin() {
+        return Err(Error::InvalidArguments(format!(
+            "{uri} is not a Google CalDAV server"
+        )));
+    }
+    Ok(server)
+}
+
+fn calendar_query(filter: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><D:getetag/><C:calendar-data/></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR">{filter}</C:comp-filter></C:filter>
+</C:calendar-query>"#
+    )
+}
+
+/// Converts a Graph event, whose times are in UTC and body in plain text as requested with
+/// `Prefer`.
+fn graph_to_event(event: &Value) -> Option<Event> {
+    let all_day = event["isAllDay"].as_bool().unwrap_or(false);
+    let time = |time: &Value| -> Option<EventTime> {
+        let time = time["dateTime"].as_str()?;
+        // Graph sends seven digits of fractional seconds
+        let time = time.split('.').next()?;
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S").ok()?;
+        Some(if all_day {
+            EventTime::Date(time.date())
+        } else {
+            EventTime::Utc(time.and_utc())
+        })
+    };
+    Some(Event {
+        uid: event["iCalUId"].as_str()?.to_string(),
+        summary: event["subject"].as_str().map(str::to_string),
+        // The preview is truncated, it is only read if the body is not in plain text
+        description: Some(&event["body"])
+            .filter(|body| body["contentType"] == "text")
+            .map_or(&event["bodyPreview"], |body| &body["content"])
+            .as_str()
+            .filter(|body| !body.is_empty())
+            .map(str::to_string),
+        location: event["location"]["displayName"]
+            .as_str()
+            .filter(|location| !location.is_empty())
+            .map(str::to_string),
+        start: time(&event["start"])?,
+        end: time(&event["end"]),
+    })
+}
+
+fn event_to_graph(event: &Event) -> Value {
+    let time = |time: &EventTime| match time {
+        EventTime::Date(date) => {
+            json!({ "dateTime": format!("{date}T00:00:00"), "timeZone": "UTC" })
+        }
+        EventTime::Utc(time) => {
+            json!({ "dateTime": time.format("%Y-%m-%dT%H:%M:%S").to_string(), "timeZone": "UTC" })
+        }
+        EventTime::Local { time, tzid } => json!({
+            "dateTime": time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timeZone": tzid.as_deref().unwrap_or("UTC"),
+        }),
+    };
+    let end = match (&event.end, &event.start) {
+        (Some(end), _) => end.clone(),
+        // All day events without an end last the day, others are instants
+        (None, EventTime::Date(date)) => EventTime::Date(date.succ_opt().unwrap_or(*date)),
+        (None, start) => start.clone(),
+    };
+
+    let mut body = json!({
+        "subject": event.summary.clone().unwrap_or_default(),
+        "start": time(&event.start),
+        "end": time(&end),
+        "isAllDay": matches!(event.start, EventTime::Date(_)),
+    });
+    if let Some(description) = &event.description {
+        body["body"] = json!({ "contentType": "text", "content": description });
+    }
+    if let Some(location) = &event.location {
+        body["location"] = json!({ "displayName": location });
+    }
+    body
+}
+
+/// Returns the text of the elements with the local name, whatever their namespace prefix.
+fn xml_elements(xml: &str, name: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        if tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let tag_name = tag.split([' ', '\t', '\r', '\n', '/']).next().unwrap_or("");
+        let local_name = tag_name.rsplit(':').next().unwrap_or(tag_name);
+        if local_name != name {
+            continue;
+        }
+        if tag.ends_with('/') {
+            texts.push(String::new());
+            continue;
+        }
+        let Some(close) = rest.find(&format!("</{tag_name}>")) else {
+            break;
+        };
+        let text = rest[..close].trim();
+        texts.push(
+            match text
+                .strip_prefix("<![CDATA[")
+                .and_then(|text| text.strip_suffix("]]>"))
+            {
+                Some(cdata) => cdata.to_string(),
+                None => xml_unescape(text),
+            },
+        );
+        rest = &rest[close..];
+    }
+    texts
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
+
+/// Percent-encodes a path segment.
+pub(super) fn encode(segment: &str) -> String {
+    url::form_urlencoded::byte_serialize(segment.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
+
+/// Converts a range received over D-Bus, as Unix timestamps.
+pub fn timestamp(seconds: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| Error::InvalidArguments(format!("Invalid timestamp {seconds}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn reads_elements_whatever_their_prefix() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response>
+    <d:href>/caldav/v2/user%40gmail.com/events/a.ics</d:href>
+    <d:propstat><d:prop>
+      <cal:calendar-data>BEGIN:VCALENDAR&#13;
+SUMMARY:Fish &amp; chips &lt;3&#13;
+END:VCALENDAR</cal:calendar-data>
+    </d:prop></d:propstat>
+  </d:response>
+  <response xmlns="DAV:">
+    <href>b.ics</href>
+    <calendar-data xmlns="urn:ietf:params:xml:ns:caldav"><![CDATA[<raw> &amp;]]></calendar-data>
+    <calendar-data/>
+  </response>
+</d:multistatus>"#;
+
+        assert_eq!(
+            xml_elements(xml, "href"),
+            vec!["/caldav/v2/user%40gmail.com/events/a.ics", "b.ics"]
+        );
+        assert_eq!(
+            xml_elements(xml, "calendar-data"),
+            vec![
+                "BEGIN:VCALENDAR\r\nSUMMARY:Fish & chips <3\r\nEND:VCALENDAR",
+                "<raw> &amp;",
+                "",
+            ]
+        );
+        assert!(xml_elements(xml, "getetag").is_empty());
+    }
+
+    #[test]
+    fn escapes_xml_text() {
+        let text = r#"a<b & "c"'"#;
+        assert_eq!(xml_escape(text), "a&lt;b &amp; &quot;c&quot;'");
+        assert_eq!(xml_unescape(&xml_escape(text)), text);
+        assert_eq!(xml_unescape("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn converts_graph_events() {
+        let event = graph_to_event(&json!({
+            "iCalUId": "event-1",
+            "subject": "Review",
+            "bodyPreview": "Agenda: the first…",
+            "body": { "contentType": "text", "content": "Agenda: the first items\nand more" },
+            "location": { "displayName": "" },
+            "isAllDay": false,
+            "start": { "dateTime": "2024-01-01T10:00:00.0000000", "timeZone": "UTC" },
+            "end": { "dateTime": "2024-01-01T11:30:00.0000000", "timeZone": "UTC" },
+        }))
+        .unwrap();
+
+        let time = |hour, minute| {
+            EventTime::Utc(
+                NaiveDate::from_ymd_opt(2024, 1, 1)
+                    .unwrap()
+                    .and_hms_opt(hour, minute, 0)
+                    .unwrap()
+                    .and_utc(),
+            )
+        };
+        assert_eq!(
+            event,
+            Event {
+                uid: "event-1".to_string(),
+                summary: Some("Review".to_string()),
+                description: Some("Agenda: the first items\nand more".to_string()),
+                location: None,
+                start: time(10, 0),
+                end: Some(time(11, 30)),
+            }
+        );
+    }
+
+    #[test]
+    fn converts_all_day_graph_events() {
+        let event = graph_to_event(&json!({
+            "iCalUId": "event-2",
+            "bodyPreview": "Preview",
+            "body": { "contentType": "html", "content": "<p>Preview</p>" },
+            "isAllDay": true,
+            "start": { "dateTime": "2024-01-01T00:00:00.0000000", "timeZone": "UTC" },
+            "end": { "dateTime": "2024-01-02T00:00:00.0000000", "timeZone": "UTC" },
+        }))
+        .unwrap();
+
+        let date = |day| EventTime::Date(NaiveDate::from_ymd_opt(2024, 1, day).unwrap());
+        assert_eq!(event.start, date(1));
+        assert_eq!(event.end, Some(date(2)));
+        assert_eq!(event.description.as_deref(), Some("Preview"));
+        assert_eq!(event_to_graph(&event)["isAllDay"], true);
+
+        assert!(graph_to_event(&json!({ "subject": "No UID" })).is_none());
+    }
+
+    #[test]
+    fn sends_tokens_to_google_only() {
+        assert_eq!(caldav_server(None).unwrap().as_str(), GOOGLE_CALDAV_URL);
+        assert_eq!(
+            caldav_server(Some(
+                "https://apidata.googleusercontent.com/caldav/v2/other/"
+            ))
+            .unwrap()
+            .path(),
+            "/caldav/v2/other/"
+        );
+        for uri in [
+            "https://attacker.example.com/caldav/v2/",
+            "http://apidata.googleusercontent.com/caldav/v2/",
+            "https://apidata.googleusercontent.com:8443/caldav/v2/",
+            "https://apidata.googleusercontent.com.example.com/",
+            "not a url",
+        ] {
+            assert!(caldav_server(Some(uri)).is_err(), "{uri}");
+        }
+    }
+}
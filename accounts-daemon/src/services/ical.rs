@@ -0,0 +1,439 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use uuid::Uuid;
+
+use crate::{Error, Result};
+
+/// The parts of an iCalendar (RFC 5545) event the calendar service translates for
+/// providers without CalDAV.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start: EventTime,
+    pub end: Option<EventTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventTime {
+    /// An all day event, `DTSTART;VALUE=DATE:20240101`.
+    Date(NaiveDate),
+    /// `DTSTART:20240101T100000Z`.
+    Utc(DateTime<Utc>),
+    /// `DTSTART;TZID=Europe/Paris:20240101T100000`, floating without a time zone.
+    Local {
+        time: NaiveDateTime,
+        tzid: Option<String>,
+    },
+}
+
+/// A content line, `NAME;PARAM=value:VALUE`.
+struct Property<'a> {
+    name: &'a str,
+    params: Vec<(&'a str, &'a str)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim_matches('"'))
+    }
+}
+
+/// Joins the lines folded by a leading space or tab back together.
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_line(line: &str) -> Option<Property<'_>> {
+    // Parameter values may be quoted and hold colons
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?;
+    let params = parts.filter_map(|param| param.split_once('=')).collect();
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+/// Reads the first event of an iCalendar object.
+pub fn parse(ics: &str) -> Result<Event> {
+    let invalid = |reason: &str| Error::InvalidArguments(format!("Invalid iCalendar: {reason}"));
+
+    let lines = unfold(ics);
+    let mut in_event = false;
+    let mut uid = None;
+    let mut event = Event {
+        uid: String::new(),
+        summary: None,
+        description: None,
+        location: None,
+        start: EventTime::Date(NaiveDate::default()),
+        end: None,
+    };
+    let mut start = None;
+    for property in lines.iter().filter_map(|line| parse_line(line)) {
+        match (property.name.to_ascii_uppercase().as_str(), property.value) {
+            ("BEGIN", "VEVENT") => in_event = true,
+            ("END", "VEVENT") => break,
+            _ if !in_event => {}
+            ("UID", value) => uid = Some(value.to_string()),
+            ("SUMMARY", value) => event.summary = Some(unescape(value)),
+            ("DESCRIPTION", value) => event.description = Some(unescape(value)),
+            ("LOCATION", value) => event.location = Some(unescape(value)),
+            ("DTSTART", _) => {
+                start = Some(parse_time(&property).ok_or_else(|| invalid("DTSTART"))?)
+            }
+            ("DTEND", _) => {
+                event.end = Some(parse_time(&property).ok_or_else(|| invalid("DTEND"))?)
+            }
+            _ => {}
+        }
+    }
+    if !in_event {
+        return Err(invalid("no event"));
+    }
+    event.uid = uid.ok_or_else(|| invalid("no UID"))?;
+    event.start = start.ok_or_else(|| invalid("no DTSTART"))?;
+    Ok(event)
+}
+
+/// Returns the UID of the first event, adding a new one when it has none.
+pub fn ensure_uid(ics: &str) -> Result<(String, String)> {
+    let lines = unfold(ics);
+    let mut in_event = false;
+    for property in lines.iter().filter_map(|line| parse_line(line)) {
+        match (property.name.to_ascii_uppercase().as_str(), property.value) {
+            ("BEGIN", "VEVENT") => in_event = true,
+            ("END", "VEVENT") => break,
+            ("UID", uid) if in_event => return Ok((uid.to_string(), ics.to_string())),
+            _ => {}
+        }
+    }
+    if !in_event {
+        return Err(Error::InvalidArguments(
+            "Invalid iCalendar: no event".to_string(),
+        ));
+    }
+
+    let uid = Uuid::new_v4().to_string();
+    let ics = ics.replacen("BEGIN:VEVENT", &format!("BEGIN:VEVENT\r\nUID:{uid}"), 1);
+    Ok((uid, ics))
+}
+
+fn parse_time(property: &Property<'_>) -> Option<EventTime> {
+    let value = property.value.trim();
+    if property
+        .param("VALUE")
+        .is_some_and(|kind| kind.eq_ignore_ascii_case("DATE"))
+        || value.len() == 8
+    {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(EventTime::Date);
+    }
+    match value.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|time| EventTime::Utc(time.and_utc())),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|time| EventTime::Local {
+                time,
+                tzid: property.param("TZID").map(str::to_string),
+            }),
+    }
+}
+
+impl EventTime {
+    /// The time in UTC. Local times are taken as UTC, which is close enough to select a
+    /// range of days.
+    fn to_utc(&self) -> DateTime<Utc> {
+        match self {
+            EventTime::Date(date) => date.and_time(NaiveTime::MIN).and_utc(),
+            EventTime::Utc(time) => *time,
+            EventTime::Local { time, .. } => time.and_utc(),
+        }
+    }
+}
+
+impl Event {
+    /// Whether the event overlaps the range, like a CalDAV time-range filter.
+    pub fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        let event_start = self.start.to_utc();
+        let event_end = match (&self.end, &self.start) {
+            (Some(end), _) => end.to_utc(),
+            // All day events without an end last the day
+            (None, EventTime::Date(date)) => {
+                EventTime::Date(date.succ_opt().unwrap_or(*date)).to_utc()
+            }
+            (None, start) => start.to_utc(),
+        };
+        if event_start == event_end {
+            start <= event_start && event_start < end
+        } else {
+            event_start < end && start < event_end
+        }
+    }
+
+    /// Serializes the event as a calendar object.
+    pub fn to_ics(&self) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//COSMIC//Accounts//EN".to_string(),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", self.uid),
+            format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+            format_time("DTSTART", &self.start),
+        ];
+        if let Some(end) = &self.end {
+            lines.push(format_time("DTEND", end));
+        }
+        for (name, value) in [
+            ("SUMMARY", &self.summary),
+            ("DESCRIPTION", &self.description),
+            ("LOCATION", &self.location),
+        ] {
+            if let Some(value) = value {
+                lines.push(format!("{name}:{}", escape(value)));
+            }
+        }
+        lines.push("END:VEVENT".to_string());
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+fn format_time(name: &str, time: &EventTime) -> String {
+    match time {
+        EventTime::Date(date) => format!("{name};VALUE=DATE:{}", date.format("%Y%m%d")),
+        EventTime::Utc(time) => format!("{name}:{}", time.format("%Y%m%dT%H%M%SZ")),
+        EventTime::Local {
+            time,
+            tzid: Some(tzid),
+        } => format!("{name};TZID={tzid}:{}", time.format("%Y%m%dT%H%M%S")),
+        EventTime::Local { time, tzid: None } => {
+            format!("{name}:{}", time.format("%Y%m%dT%H%M%S"))
+        }
+    }
+}
+
+/// Folds a content line at 75 octets and terminates it with CRLF.
+pub(super) fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => {}
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:event-1\r\n\
+        SUMMARY:Lunch\\, then a walk\r\n\
+        DESCRIPTION:First line\\nsecond li\r\n ne\r\n\
+        LOCATION;ALTREP=\"http://example.com/a:b\":Park\r\n\
+        DTSTART;TZID=\"Europe/Paris\":20240101T120000\r\n\
+        DTEND:20240101T130000Z\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:event-2\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_the_first_event() {
+        let event = parse(ICS).unwrap();
+
+        assert_eq!(event.uid, "event-1");
+        assert_eq!(event.summary.as_deref(), Some("Lunch, then a walk"));
+        assert_eq!(
+            event.description.as_deref(),
+            Some("First line\nsecond line")
+        );
+        assert_eq!(event.location.as_deref(), Some("Park"));
+        assert_eq!(
+            event.start,
+            EventTime::Local {
+                time: date(2024, 1, 1).and_hms_opt(12, 0, 0).unwrap(),
+                tzid: Some("Europe/Paris".to_string()),
+            }
+        );
+        assert_eq!(
+            event.end,
+            Some(EventTime::Utc(
+                date(2024, 1, 1).and_hms_opt(13, 0, 0).unwrap().and_utc()
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_all_day_events() {
+        let event = parse(
+            "BEGIN:VEVENT\nUID:day\nDTSTART;VALUE=DATE:20240229\nDTEND:20240301\nEND:VEVENT\n",
+        )
+        .unwrap();
+
+        assert_eq!(event.start, EventTime::Date(date(2024, 2, 29)));
+        assert_eq!(event.end, Some(EventTime::Date(date(2024, 3, 1))));
+    }
+
+    #[test]
+    fn rejects_incomplete_events() {
+        assert!(parse("BEGIN:VCALENDAR\nEND:VCALENDAR\n").is_err());
+        assert!(parse("BEGIN:VEVENT\nDTSTART:20240101\nEND:VEVENT\n").is_err());
+        assert!(parse("BEGIN:VEVENT\nUID:a\nEND:VEVENT\n").is_err());
+        assert!(parse("BEGIN:VEVENT\nUID:a\nDTSTART:tomorrow\nEND:VEVENT\n").is_err());
+    }
+
+    #[test]
+    fn keeps_or_adds_the_uid() {
+        assert_eq!(
+            ensure_uid(ICS).unwrap(),
+            ("event-1".to_string(), ICS.to_string())
+        );
+
+        let (uid, ics) = ensure_uid("BEGIN:VEVENT\r\nDTSTART:20240101\r\nEND:VEVENT\r\n").unwrap();
+        assert_eq!(parse(&ics).unwrap().uid, uid);
+        assert!(ensure_uid("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").is_err());
+    }
+
+    #[test]
+    fn serializes_events_which_parse_back() {
+        let event = Event {
+            uid: "event-1".to_string(),
+            summary: Some("Plan; review, ship".to_string()),
+            description: Some(format!("{}\nback\\slash", "long ".repeat(30))),
+            location: None,
+            start: EventTime::Date(date(2024, 1, 1)),
+            end: Some(EventTime::Local {
+                time: date(2024, 1, 2).and_hms_opt(9, 30, 0).unwrap(),
+                tzid: None,
+            }),
+        };
+
+        let ics = event.to_ics();
+        assert!(
+            ics.lines()
+                .all(|line| line.trim_end_matches('\r').len() <= 75)
+        );
+        assert_eq!(parse(&ics).unwrap(), event);
+    }
+
+    #[test]
+    fn overlaps_ranges_like_caldav() {
+        let at = |day, hour| {
+            date(2024, 1, day)
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let event = |start, end| Event {
+            uid: "event".to_string(),
+            summary: None,
+            description: None,
+            location: None,
+            start,
+            end,
+        };
+
+        let meeting = event(EventTime::Utc(at(2, 10)), Some(EventTime::Utc(at(2, 11))));
+        assert!(meeting.overlaps(at(2, 0), at(3, 0)));
+        assert!(meeting.overlaps(at(2, 10), at(2, 11)));
+        // Ranges end before their end, events as well
+        assert!(!meeting.overlaps(at(2, 11), at(2, 12)));
+        assert!(!meeting.overlaps(at(2, 9), at(2, 10)));
+
+        let reminder = event(EventTime::Utc(at(2, 10)), None);
+        assert!(reminder.overlaps(at(2, 10), at(2, 11)));
+        assert!(!reminder.overlaps(at(2, 9), at(2, 10)));
+
+        let holiday = event(EventTime::Date(date(2024, 1, 2)), None);
+        assert!(holiday.overlaps(at(2, 23), at(3, 1)));
+        assert!(!holiday.overlaps(at(3, 0), at(4, 0)));
+        assert!(!holiday.overlaps(at(1, 0), at(2, 0)));
+
+        let local = event(
+            EventTime::Local {
+                time: date(2024, 1, 2).and_hms_opt(10, 0, 0).unwrap(),
+                tzid: Some("Europe/Paris".to_string()),
+            },
+            Some(EventTime::Date(date(2024, 1, 3))),
+        );
+        assert!(local.overlaps(at(2, 12), at(2, 13)));
+        assert!(!local.overlaps(at(3, 0), at(3, 1)));
+    }
+
+    #[test]
+    fn folds_lines_between_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+
+        assert!(folded.ends_with("\r\n"));
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(unfold(&folded), vec![line]);
+    }
+}
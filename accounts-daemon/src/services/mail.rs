@@ -1,23 +1,39 @@
 use std::collections::HashMap;
 
-use async_trait::async_trait;
-use zbus::{
-    fdo::{Error, Result},
-    interface,
-};
-
-use crate::{
+use accounts::{
+    AccountService, ServiceConfig,
     models::{Account, Provider, Service},
-    services::{Service, ServiceConfig},
 };
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use zbus::{fdo::Result, interface};
+
+use super::ServiceObject;
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MailService {
-    account_id: String,
+    account: Account,
 }
 
 impl MailService {
-    pub fn new(account_id: String) -> Self {
-        Self { account_id }
+    pub fn new(account: Account) -> Self {
+        Self { account }
+    }
+
+    /// The address mail is sent from, the login name when the provider gave none
+    fn address(&self) -> String {
+        self.account
+            .email
+            .clone()
+            .unwrap_or_else(|| self.account.username.clone())
+    }
+}
+
+impl ServiceObject for MailService {
+    const SERVICE: Service = Service::Email;
+
+    fn account(&self) -> &Account {
+        &self.account
     }
 }
 
@@ -26,14 +42,13 @@ impl MailService {
     /// Email address - matches GOA's EmailAddress property
     #[zbus(property)]
     async fn email_address(&self) -> Result<String> {
-        // In a real implementation, this would fetch from storage
-        Ok("user@example.com".to_string())
+        Ok(self.address())
     }
 
     /// Display name - matches GOA's Name property
     #[zbus(property)]
     async fn name(&self) -> Result<String> {
-        Ok("User Name".to_string())
+        Ok(self.account.display_name.clone())
     }
 
     // IMAP Properties - matching GOA exactly
@@ -41,20 +56,17 @@ impl MailService {
     /// IMAP hostname - matches GOA's ImapHost
     #[zbus(property)]
     async fn imap_host(&self) -> Result<String> {
-        if self.account_id.contains("google") {
-            Ok("imap.gmail.com".to_string())
-        } else if self.account_id.contains("microsoft") {
-            Ok("outlook.office365.com".to_string())
-        } else {
-            Err(Error::Failed("Unsupported provider".to_string()))
-        }
+        Ok(match self.account.provider {
+            Provider::Google => "imap.gmail.com".to_string(),
+            Provider::Microsoft => "outlook.office365.com".to_string(),
+        })
     }
 
     /// IMAP username - matches GOA's ImapUserName
     #[zbus(property)]
     async fn imap_user_name(&self) -> Result<String> {
         // Usually the email address for OAuth2
-        self.email_address().await
+        Ok(self.address())
     }
 
     /// Whether IMAP is supported - matches GOA's ImapSupported
@@ -86,19 +98,16 @@ impl MailService {
     /// SMTP hostname - matches GOA's SmtpHost
     #[zbus(property)]
     async fn smtp_host(&self) -> Result<String> {
-        if self.account_id.contains("google") {
-            Ok("smtp.gmail.com".to_string())
-        } else if self.account_id.contains("microsoft") {
-            Ok("smtp.office365.com".to_string())
-        } else {
-            Err(Error::Failed("Unsupported provider".to_string()))
-        }
+        Ok(match self.account.provider {
+            Provider::Google => "smtp.gmail.com".to_string(),
+            Provider::Microsoft => "smtp.office365.com".to_string(),
+        })
     }
 
     /// SMTP username - matches GOA's SmtpUserName
     #[zbus(property)]
     async fn smtp_user_name(&self) -> Result<String> {
-        self.email_address().await
+        Ok(self.address())
     }
 
     /// Whether SMTP is supported - matches GOA's SmtpSupported
@@ -151,7 +160,7 @@ impl MailService {
 }
 
 #[async_trait]
-impl Service for MailService {
+impl AccountService for MailService {
     fn name(&self) -> &str {
         "Mail"
     }
@@ -184,11 +193,10 @@ impl Service for MailService {
             }
         }
 
-        if let Some(email) = &account.email {
-            settings.insert("email_address".to_string(), email.clone().into());
-            settings.insert("imap_user_name".to_string(), email.clone().into());
-            settings.insert("smtp_user_name".to_string(), email.clone().into());
-        }
+        let address = account.email.as_ref().unwrap_or(&account.username);
+        settings.insert("email_address".to_string(), address.clone().into());
+        settings.insert("imap_user_name".to_string(), address.clone().into());
+        settings.insert("smtp_user_name".to_string(), address.clone().into());
 
         settings.insert("name".to_string(), account.display_name.clone().into());
 
@@ -199,6 +207,14 @@ impl Service for MailService {
         })
     }
 
+    async fn add_service(&self) -> Result<bool> {
+        self.register().await
+    }
+
+    async fn remove_service(&self) -> Result<bool> {
+        self.unregister().await
+    }
+
     async fn ensure_credentials(&self, _account: &mut Account) -> Result<()> {
         Ok(())
     }
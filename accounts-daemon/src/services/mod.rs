@@ -1,16 +1,93 @@
 mod calendar;
-// mod contacts;
-// pub use contacts::*;
-// mod mail;
-// pub use mail::*;
-// mod todo;
-// pub use todo::*;
+mod contacts;
+mod events;
+mod ical;
+mod mail;
+pub mod offline;
+mod people;
+mod tasks;
+mod todo;
+mod vcard;
 
 use accounts::{
-    AccountService,
-    models::{Account, Service},
+    AccountService, AccountsError,
+    clients::service_path,
+    models::{Account, SecretString, Service},
 };
+use async_trait::async_trait;
 pub use calendar::*;
+pub use contacts::*;
+pub use mail::*;
+pub use todo::*;
+use zbus::{Connection, message::Header, object_server::Interface};
+
+use crate::{CONNECTION, account::AccountsInterface};
+
+/// The D-Bus object of a service of an account, served at its [`service_path`].
+#[async_trait]
+trait ServiceObject: Interface + Clone {
+    const SERVICE: Service;
+
+    fn account(&self) -> &Account;
+
+    /// A token for a method of the service, once the caller is checked like for
+    /// `GetAccessTokenForService`.
+    async fn access_token(
+        &self,
+        connection: &Connection,
+        header: &Header<'_>,
+        method: &str,
+    ) -> Result<SecretString, AccountsError> {
+        AccountsInterface::service_access_token(
+            connection,
+            header,
+            &self.account().id,
+            Self::SERVICE,
+            method,
+        )
+        .await
+    }
+
+    /// A setting of the service overridden by the user.
+    fn setting(&self, key: &str) -> Option<&String> {
+        self.account()
+            .service_settings
+            .get(&Self::SERVICE)
+            .and_then(|settings| settings.get(key))
+    }
+
+    async fn register(&self) -> zbus::fdo::Result<bool> {
+        let account = self.account();
+        tracing::info!(
+            "Adding the {} service for account {}",
+            Self::SERVICE,
+            account.dbus_id()
+        );
+        if let Some(connection) = CONNECTION.get() {
+            connection
+                .object_server()
+                .at(service_path(&Self::SERVICE, &account.id), self.clone())
+                .await?;
+        }
+        Ok(false)
+    }
+
+    async fn unregister(&self) -> zbus::fdo::Result<bool> {
+        let account = self.account();
+        tracing::info!(
+            "Removing the {} service for account {}",
+            Self::SERVICE,
+            account.dbus_id()
+        );
+        if let Some(connection) = CONNECTION.get() {
+            connection
+                .object_server()
+                .remove::<Self, String>(service_path(&Self::SERVICE, &account.id))
+                .await?;
+        }
+        Ok(false)
+    }
+}
 
 pub struct ServiceFactory;
 
@@ -24,13 +101,33 @@ impl ServiceFactory {
             services.push(Box::new(CalendarService::new(account.clone())));
         }
 
+        if let Some((_, value)) = account.services.get_key_value(&Service::Contacts)
+            && *value
+        {
+            services.push(Box::new(ContactsService::new(account.clone())));
+        }
+
+        if let Some((_, value)) = account.services.get_key_value(&Service::Email)
+            && *value
+        {
+            services.push(Box::new(MailService::new(account.clone())));
+        }
+
+        if let Some((_, value)) = account.services.get_key_value(&Service::Todo)
+            && *value
+        {
+            services.push(Box::new(TodoService::new(account.clone())));
+        }
+
         services
     }
 
     pub fn create_service(account: &Account, service: &Service) -> Option<Box<dyn AccountService>> {
         match service {
             Service::Calendar => Some(Box::new(CalendarService::new(account.clone()))),
-            _ => None,
+            Service::Contacts => Some(Box::new(ContactsService::new(account.clone()))),
+            Service::Email => Some(Box::new(MailService::new(account.clone()))),
+            Service::Todo => Some(Box::new(TodoService::new(account.clone()))),
         }
     }
 }
@@ -0,0 +1,320 @@
+use std::{fs, os::unix::fs::DirBuilderExt, path::PathBuf, time::Duration};
+
+use accounts::models::{Account, Service};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::{CalendarService, ical, people::ContactStore};
+use crate::{Error, Result, account::AccountsInterface, network, paths, power, settings};
+
+/// How long after the daemon starts data is prefetched, leaving the session time to settle.
+const PREFETCH_DELAY: Duration = Duration::from_secs(30);
+
+/// How long the prefetch waits instead on battery, with the `battery_aware` setting on.
+const BATTERY_PREFETCH_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How often the data is prefetched again, keeping it fresh.
+const PREFETCH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often instead on battery, with the `battery_aware` setting on.
+const BATTERY_PREFETCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How many days of events are prefetched, starting the day before so today is covered
+/// whatever the time zone.
+const PREFETCH_DAYS: i64 = 14;
+
+/// How many of the most recently changed contacts are prefetched.
+const PREFETCH_CONTACTS: u32 = 50;
+
+/// How long prefetched events are served without asking the provider, a little longer than
+/// the time until the next prefetch.
+const FRESH_FOR: chrono::Duration = chrono::Duration::minutes(20);
+
+const EVENTS_FILE: &str = "events.json";
+const CONTACTS_FILE: &str = "contacts.json";
+
+#[derive(Serialize, Deserialize)]
+struct CachedEvents {
+    fetched_at: DateTime<Utc>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    events: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedContacts {
+    fetched_at: DateTime<Utc>,
+    contacts: Vec<String>,
+}
+
+fn dir(account_id: &Uuid) -> PathBuf {
+    paths::cache_dir()
+        .join("services")
+        .join(account_id.to_string())
+}
+
+/// Caches the upcoming events and the recent contacts of the enabled accounts shortly
+/// after the daemon starts and then periodically, while the `prefetch` setting is on, so
+/// the calendar applet and contact completion don't wait for the providers when they are
+/// opened. On a metered connection only accounts allowed to sync on one are prefetched,
+/// and nothing is while the power saver profile is active or all accounts are paused.
+/// Turning the setting off deletes the cached data.
+///
+/// Failures are logged, the services ask the providers as usual then.
+pub async fn prefetch() {
+    let mut changes = settings::subscribe();
+    let mut enabled = changes.borrow_and_update().prefetch;
+    let mut next = Instant::now() + wait(PREFETCH_DELAY, BATTERY_PREFETCH_DELAY).await;
+    loop {
+        tokio::select! {
+            () = tokio::time::sleep_until(next) => {
+                prefetch_accounts().await;
+                next = Instant::now() + wait(PREFETCH_INTERVAL, BATTERY_PREFETCH_INTERVAL).await;
+            }
+            Ok(()) = changes.changed() => {
+                let prefetch = changes.borrow_and_update().prefetch;
+                if enabled && !prefetch {
+                    remove_all();
+                }
+                enabled = prefetch;
+            }
+        }
+    }
+}
+
+/// How long to wait, longer on battery with the `battery_aware` setting on.
+async fn wait(delay: Duration, battery_delay: Duration) -> Duration {
+    if settings::get().battery_aware && power::on_battery().await {
+        battery_delay
+    } else {
+        delay
+    }
+}
+
+async fn prefetch_accounts() {
+    let settings = settings::get();
+    if !settings.prefetch {
+        return;
+    }
+    if AccountsInterface::daemon_paused().await {
+        tracing::debug!("Not prefetching while all accounts are paused");
+        return;
+    }
+    if settings.battery_aware && power::saving_power().await {
+        tracing::debug!("Not prefetching while the power saver profile is active");
+        return;
+    }
+
+    let metered = network::is_metered().await;
+    for account in AccountsInterface::daemon_accounts()
+        .await
+        .iter()
+        .filter(|account| account.enabled && (account.sync_on_metered || !metered))
+    {
+        if enabled(account, Service::Calendar)
+            && let Err(err) = prefetch_events(account).await
+        {
+            tracing::debug!("Failed to prefetch the events of {}: {}", account.id, err);
+        }
+        if enabled(account, Service::Contacts)
+            && let Err(err) = prefetch_contacts(account).await
+        {
+            tracing::debug!("Failed to prefetch the contacts of {}: {}", account.id, err);
+        }
+    }
+}
+
+fn enabled(account: &Account, service: Service) -> bool {
+    matches!(account.services.get(&service), Some(true))
+}
+
+async fn prefetch_events(account: &Account) -> Result<()> {
+    let access_token =
+        AccountsInterface::daemon_access_token(&account.id, Service::Calendar).await?;
+    let now = Utc::now();
+    let (start, end) = (
+        now - chrono::Duration::days(1),
+        now + chrono::Duration::days(PREFETCH_DAYS),
+    );
+    let events = CalendarService::new(account.clone())
+        .events(&access_token)
+        .list(start, end)
+        .await?;
+    tracing::debug!("Prefetched {} events of {}", events.len(), account.id);
+    write(
+        &account.id,
+        EVENTS_FILE,
+        &CachedEvents {
+            fetched_at: now,
+            start,
+            end,
+            events,
+        },
+    )
+}
+
+async fn prefetch_contacts(account: &Account) -> Result<()> {
+    let access_token =
+        AccountsInterface::daemon_access_token(&account.id, Service::Contacts).await?;
+    let contacts = ContactStore::new(account, &access_token)
+        .recent(PREFETCH_CONTACTS)
+        .await?;
+    tracing::debug!("Prefetched {} contacts of {}", contacts.len(), account.id);
+    write(
+        &account.id,
+        CONTACTS_FILE,
+        &CachedContacts {
+            fetched_at: Utc::now(),
+            contacts,
+        },
+    )
+}
+
+/// Returns the prefetched events overlapping the range, when the prefetched range covers
+/// it. Unless `stale` is set, events prefetched more than a few minutes ago are ignored.
+pub fn cached_events(
+    account_id: &Uuid,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    stale: bool,
+) -> Option<Vec<String>> {
+    let cached: CachedEvents = read(account_id, EVENTS_FILE)?;
+    if start < cached.start || cached.end < end {
+        return None;
+    }
+    if !stale && Utc::now() - cached.fetched_at > FRESH_FOR {
+        return None;
+    }
+    Some(
+        cached
+            .events
+            .into_iter()
+            .filter(|ics| ical::parse(ics).is_ok_and(|event| event.overlaps(start, end)))
+            .collect(),
+    )
+}
+
+/// Drops the prefetched events once the calendar changed.
+pub fn invalidate_events(account_id: &Uuid) {
+    let _ = fs::remove_file(dir(account_id).join(EVENTS_FILE));
+}
+
+/// Searches the prefetched contacts by name and address, for when the provider can't be
+/// reached.
+pub fn search_contacts(account_id: &Uuid, query: &str, limit: u32) -> Option<Vec<String>> {
+    let cached: CachedContacts = read(account_id, CONTACTS_FILE)?;
+    Some(matching_contacts(cached.contacts, query, limit))
+}
+
+/// The vCards whose name or address contains the query, ignoring case.
+fn matching_contacts(contacts: Vec<String>, query: &str, limit: u32) -> Vec<String> {
+    let query = query.trim().to_lowercase();
+    contacts
+        .into_iter()
+        .filter(|vcard| {
+            vcard.lines().any(|line| {
+                ["FN:", "N:", "EMAIL:"]
+                    .iter()
+                    .any(|name| line.starts_with(name))
+                    && line.to_lowercase().contains(&query)
+            })
+        })
+        .take(limit as usize)
+        .collect()
+}
+
+/// Whether a request failed because the provider could not be reached, rather than
+/// refused it.
+pub fn unreachable(err: &Error) -> bool {
+    matches!(err, Error::Network(err) if err.is_connect() || err.is_timeout())
+}
+
+/// Deletes the data prefetched for a removed account.
+pub fn remove(account_id: &Uuid) {
+    let dir = dir(account_id);
+    if dir.exists()
+        && let Err(err) = fs::remove_dir_all(&dir)
+    {
+        tracing::warn!("Failed to remove {}: {}", dir.display(), err);
+    }
+}
+
+/// Deletes the data prefetched for every account.
+fn remove_all() {
+    let dir = paths::cache_dir().join("services");
+    if dir.exists()
+        && let Err(err) = fs::remove_dir_all(&dir)
+    {
+        tracing::warn!("Failed to remove {}: {}", dir.display(), err);
+    }
+}
+
+/// Reads a cache file, nothing is served while the `prefetch` setting is off.
+fn read<T: DeserializeOwned>(account_id: &Uuid, name: &str) -> Option<T> {
+    if !settings::get().prefetch {
+        return None;
+    }
+    let data = fs::read(dir(account_id).join(name)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn write<T: Serialize>(account_id: &Uuid, name: &str, value: &T) -> Result<()> {
+    let dir = dir(account_id);
+    // Events and contacts are private, only the user may list them
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    paths::exclude_from_backups(&paths::cache_dir());
+    fs::write(dir.join(name), serde_json::to_vec(value)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::vcard::Contact;
+
+    fn vcard(uid: &str, name: Option<&str>, email: &str) -> String {
+        Contact {
+            uid: uid.to_string(),
+            full_name: name.map(str::to_string),
+            emails: vec![email.to_string()],
+            phones: vec!["+1 555 0100".to_string()],
+            ..Contact::default()
+        }
+        .to_vcard()
+    }
+
+    #[test]
+    fn searches_contacts_by_name_and_address() {
+        let contacts = vec![
+            vcard("1", Some("Ada Lovelace"), "ada@example.com"),
+            vcard("2", Some("Grace Hopper"), "grace@navy.example.com"),
+            vcard("3", None, "alan@example.com"),
+        ];
+
+        let uids = |found: Vec<String>| -> Vec<String> {
+            found
+                .iter()
+                .filter_map(|vcard| vcard.lines().find_map(|line| line.strip_prefix("UID:")))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(
+            uids(matching_contacts(contacts.clone(), " LOVE ", 10)),
+            ["1"]
+        );
+        assert_eq!(uids(matching_contacts(contacts.clone(), "navy", 10)), ["2"]);
+        assert_eq!(uids(matching_contacts(contacts.clone(), "alan", 10)), ["3"]);
+        assert_eq!(
+            uids(matching_contacts(contacts.clone(), "example", 2)),
+            ["1", "2"]
+        );
+        // Phone numbers and UIDs are not searched
+        assert!(matching_contacts(contacts.clone(), "555", 10).is_empty());
+        assert!(matching_contacts(contacts, "UID", 10).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use accounts::models::{Account, Provider, SecretString};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::Value;
+use url::Url;
+
+use super::{events::encode, vcard::Contact};
+use crate::{
+    Error, Result,
+    usage::{self, MeteredSend},
+};
+
+const PEOPLE_URL: &str = "https://people.googleapis.com/v1";
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0/me";
+
+/// The fields of a Google person the contacts service reads.
+const PERSON_FIELDS: &str = "names,emailAddresses,phoneNumbers,organizations";
+
+/// The most results Google returns for a search.
+const GOOGLE_SEARCH_LIMIT: u32 = 30;
+const GRAPH_SEARCH_LIMIT: u32 = 100;
+
+/// The contacts of an account, as vCards.
+///
+/// Searching needs a server side index CardDAV does not offer, so Google is reached
+/// through the People API and Microsoft through Graph. Contacts are identified by the
+/// resource name of the person on Google and the contact ID on Microsoft.
+pub struct ContactStore<'a> {
+    account: &'a Account,
+    access_token: &'a SecretString,
+    client: Client,
+}
+
+impl<'a> ContactStore<'a> {
+    pub fn new(account: &'a Account, access_token: &'a SecretString) -> Self {
+        Self {
+            account,
+            access_token,
+            client: Client::new(),
+        }
+    }
+
+    /// Returns the contacts whose name or address matches the query, the best ones first.
+    pub async fn search(&self, query: &str, limit: u32) -> Result<Vec<String>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let contacts = match self.account.provider {
+            Provider::Google => {
+                let mut url = Url::parse(&format!("{PEOPLE_URL}/people:searchContacts"))?;
+                url.query_pairs_mut()
+                    .append_pair("query", query)
+                    .append_pair("readMask", PERSON_FIELDS)
+                    .append_pair("pageSize", &limit.clamp(1, GOOGLE_SEARCH_LIMIT).to_string());
+                let results: Value = self
+                    .get(url.as_str())
+                    .send_metered(self.account, usage::CONTACTS)
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                results["results"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|result| person_to_contact(&result["person"]))
+                    .collect::<Vec<_>>()
+            }
+            Provider::Microsoft => {
+                let query = query.replace('\'', "''");
+                let mut url = Url::parse(&format!("{GRAPH_URL}/contacts"))?;
+                url.query_pairs_mut()
+                    .append_pair(
+                        "$filter",
+                        &format!(
+                            "startswith(displayName,'{query}') or startswith(givenName,'{query}') \
+                             or startswith(surname,'{query}') \
+                             or emailAddresses/any(a:startswith(a/address,'{query}'))"
+                        ),
+                    )
+                    .append_pair("$top", &limit.clamp(1, GRAPH_SEARCH_LIMIT).to_string());
+                let results: Value = self
+                    .get(url.as_str())
+                    .send_metered(self.account, usage::CONTACTS)
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                results["value"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(graph_to_contact)
+                    .collect::<Vec<_>>()
+            }
+        };
+        Ok(contacts.iter().map(Contact::to_vcard).collect())
+    }
+
+    /// Returns the contacts changed most recently, the ones an address book shows first.
+    pub async fn recent(&self, limit: u32) -> Result<Vec<String>> {
+        let contacts = match self.account.provider {
+            Provider::Google => {
+                let mut url = Url::parse(&format!("{PEOPLE_URL}/people/me/connections"))?;
+                url.query_pairs_mut()
+                    .append_pair("personFields", PERSON_FIELDS)
+                    .append_pair("sortOrder", "LAST_MODIFIED_DESCENDING")
+                    .append_pair("pageSize", &limit.to_string());
+                let results: Value = self
+                    .get(url.as_str())
+                    .send_metered(self.account, usage::CONTACTS)
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                results["connections"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(person_to_contact)
+                    .collect::<Vec<_>>()
+            }
+            Provider::Microsoft => {
+                let mut url = Url::parse(&format!("{GRAPH_URL}/contacts"))?;
+                url.query_pairs_mut()
+                    .append_pair("$orderby", "lastModifiedDateTime desc")
+                    .append_pair("$top", &limit.to_string());
+                let results: Value = self
+                    .get(url.as_str())
+                    .send_metered(self.account, usage::CONTACTS)
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                results["value"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(graph_to_contact)
+                    .collect::<Vec<_>>()
+            }
+        };
+        Ok(contacts.iter().map(Contact::to_vcard).collect())
+    }
+
+    /// Returns the contact with the UID.
+    pub async fn contact(&self, uid: &str) -> Result<String> {
+        let not_found = || Error::InvalidArguments(format!("Contact {uid} not found"));
+        let url = match self.account.provider {
+            Provider::Google => {
+                // Only resource names are accepted, not arbitrary API paths
+                let id = uid.strip_prefix("people/").ok_or_else(not_found)?;
+                let mut url = Url::parse(&format!("{PEOPLE_URL}/people/"))?.join(&encode(id))?;
+                url.query_pairs_mut()
+                    .append_pair("personFields", PERSON_FIELDS);
+                url
+            }
+            Provider::Microsoft => {
+                Url::parse(&format!("{GRAPH_URL}/contacts/"))?.join(&encode(uid))?
+            }
+        };
+        let response = self
+            .get(url.as_str())
+            .send_metered(self.account, usage::CONTACTS)
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(not_found());
+        }
+        let contact: Value = response.error_for_status()?.json().await?;
+        let contact = match self.account.provider {
+            Provider::Google => person_to_contact(&contact),
+            Provider::Microsoft => graph_to_contact(&contact),
+        };
+        contact
+            .map(|contact| contact.to_vcard())
+            .ok_or_else(not_found)
+    }
+
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url).bearer_auth(self.access_token.expose())
+    }
+}
+
+/// Converts a Google person, whose primary values come first.
+fn person_to_contact(person: &Value) -> Option<Contact> {
+    let values = |field: &str, key: &str| -> Vec<String> {
+        person[field]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| text(&value[key]))
+            .collect()
+    };
+    let name = &person["names"][0];
+    Some(Contact {
+        uid: person["resourceName"].as_str()?.to_string(),
+        full_name: text(&name["displayName"]),
+        given_name: text(&name["givenName"]),
+        family_name: text(&name["familyName"]),
+        emails: values("emailAddresses", "value"),
+        phones: values("phoneNumbers", "value"),
+        organization: text(&person["organizations"][0]["name"]),
+    })
+}
+
+fn graph_to_contact(contact: &Value) -> Option<Contact> {
+    let phones = ["mobilePhone", "businessPhones", "homePhones"]
+        .iter()
+        .flat_map(|field| match &contact[field] {
+            Value::Array(phones) => phones.iter().filter_map(text).collect(),
+            phone => text(phone).into_iter().collect::<Vec<_>>(),
+        })
+        .collect();
+    Some(Contact {
+        uid: contact["id"].as_str()?.to_string(),
+        full_name: text(&contact["displayName"]),
+        given_name: text(&contact["givenName"]),
+        family_name: text(&contact["surname"]),
+        emails: contact["emailAddresses"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|address| text(&address["address"]))
+            .collect(),
+        phones,
+        organization: text(&contact["companyName"]),
+    })
+}
+
+/// A string value, missing when empty.
+fn text(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn converts_google_people() {
+        let contact = person_to_contact(&json!({
+            "resourceName": "people/c123",
+            "names": [
+                { "displayName": "Ada Lovelace", "givenName": "Ada", "familyName": "Lovelace" },
+                { "displayName": "Countess of Lovelace" },
+            ],
+            "emailAddresses": [
+                { "value": "ada@example.com" },
+                { "value": "" },
+                { "value": "countess@example.com" },
+            ],
+            "phoneNumbers": [{ "value": "+44 20 7946 0000" }],
+            "organizations": [{ "name": "Analytical Engines" }],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            contact,
+            Contact {
+                uid: "people/c123".to_string(),
+                full_name: Some("Ada Lovelace".to_string()),
+                given_name: Some("Ada".to_string()),
+                family_name: Some("Lovelace".to_string()),
+                emails: vec![
+                    "ada@example.com".to_string(),
+                    "countess@example.com".to_string()
+                ],
+                phones: vec!["+44 20 7946 0000".to_string()],
+                organization: Some("Analytical Engines".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn converts_google_people_with_few_fields() {
+        let contact = person_to_contact(&json!({ "resourceName": "people/c1" })).unwrap();
+
+        assert_eq!(
+            contact,
+            Contact {
+                uid: "people/c1".to_string(),
+                ..Contact::default()
+            }
+        );
+        assert!(person_to_contact(&json!({ "names": [] })).is_none());
+    }
+
+    #[test]
+    fn converts_graph_contacts() {
+        let contact = graph_to_contact(&json!({
+            "id": "AAMkAD",
+            "displayName": "Grace Hopper",
+            "givenName": "Grace",
+            "surname": "Hopper",
+            "emailAddresses": [{ "name": "Grace", "address": "grace@example.com" }],
+            "mobilePhone": "+1 555 0100",
+            "businessPhones": ["+1 555 0101", ""],
+            "homePhones": [],
+            "companyName": "",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            contact,
+            Contact {
+                uid: "AAMkAD".to_string(),
+                full_name: Some("Grace Hopper".to_string()),
+                given_name: Some("Grace".to_string()),
+                family_name: Some("Hopper".to_string()),
+                emails: vec!["grace@example.com".to_string()],
+                phones: vec!["+1 555 0100".to_string(), "+1 555 0101".to_string()],
+                organization: None,
+            }
+        );
+        assert!(graph_to_contact(&json!({ "displayName": "No ID" })).is_none());
+    }
+}
@@ -0,0 +1,320 @@
+use accounts::models::{Account, Provider, SecretString, Task, TaskList};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::{Value, json};
+use url::Url;
+
+use super::events::encode;
+use crate::{
+    Error, Result,
+    usage::{self, MeteredSend},
+};
+
+const GOOGLE_TASKS_URL: &str = "https://tasks.googleapis.com/tasks/v1";
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0/me/todo";
+
+/// The task lists of an account, from Google Tasks or Microsoft To Do.
+pub struct TaskStore<'a> {
+    account: &'a Account,
+    access_token: &'a SecretString,
+    client: Client,
+}
+
+impl<'a> TaskStore<'a> {
+    pub fn new(account: &'a Account, access_token: &'a SecretString) -> Self {
+        Self {
+            account,
+            access_token,
+            client: Client::new(),
+        }
+    }
+
+    pub async fn lists(&self) -> Result<Vec<TaskList>> {
+        let url = match self.account.provider {
+            Provider::Google => format!("{GOOGLE_TASKS_URL}/users/@me/lists"),
+            Provider::Microsoft => format!("{GRAPH_URL}/lists"),
+        };
+        Ok(self
+            .items(Url::parse(&url)?)
+            .await?
+            .iter()
+            .filter_map(|list| {
+                let title = match self.account.provider {
+                    Provider::Google => &list["title"],
+                    Provider::Microsoft => &list["displayName"],
+                };
+                Some(TaskList {
+                    id: list["id"].as_str()?.to_string(),
+                    title: title.as_str().unwrap_or_default().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Returns the tasks of the list, completed ones included.
+    pub async fn tasks(&self, list_id: &str) -> Result<Vec<Task>> {
+        let mut url = self.tasks_url(list_id)?;
+        if self.account.provider == Provider::Google {
+            url.query_pairs_mut()
+                .append_pair("showCompleted", "true")
+                .append_pair("showHidden", "true")
+                .append_pair("maxResults", "100");
+        }
+        Ok(self
+            .items(url)
+            .await?
+            .iter()
+            .filter_map(|task| to_task(&self.account.provider, task, &Local))
+            .collect())
+    }
+
+    pub async fn complete(&self, list_id: &str, task_id: &str) -> Result<()> {
+        let url = Url::parse(&format!("{}/{}", self.tasks_url(list_id)?, encode(task_id)))?;
+        self.request(Method::PATCH, url)
+            .json(&json!({ "status": "completed" }))
+            .send_metered(self.account, usage::TODO)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Adds a task to the list and returns it. `due` is RFC 3339.
+    pub async fn create(
+        &self,
+        list_id: &str,
+        title: &str,
+        notes: Option<&str>,
+        due: Option<&str>,
+    ) -> Result<Task> {
+        let due = due
+            .map(|due| {
+                DateTime::parse_from_rfc3339(due)
+                    .map(|due| due.with_timezone(&Local))
+                    .map_err(|e| Error::InvalidArguments(format!("Invalid due date {due}: {e}")))
+            })
+            .transpose()?;
+        let created: Value = self
+            .request(Method::POST, self.tasks_url(list_id)?)
+            .json(&new_task(&self.account.provider, title, notes, due))
+            .send_metered(self.account, usage::TODO)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        to_task(&self.account.provider, &created, &Local)
+            .ok_or_else(|| Error::StorageError("The provider returned no task".to_string()))
+    }
+
+    /// The tasks collection of a list.
+    fn tasks_url(&self, list_id: &str) -> Result<Url> {
+        let url = match self.account.provider {
+            Provider::Google => format!("{GOOGLE_TASKS_URL}/lists/{}/tasks", encode(list_id)),
+            Provider::Microsoft => format!("{GRAPH_URL}/lists/{}/tasks", encode(list_id)),
+        };
+        Ok(Url::parse(&url)?)
+    }
+
+    /// Fetches every page of a collection.
+    async fn items(&self, url: Url) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut next = Some(url.clone());
+        while let Some(page_url) = next {
+            let page: Value = self
+                .request(Method::GET, page_url)
+                .send_metered(self.account, usage::TODO)
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let (key, next_page) = match self.account.provider {
+                Provider::Google => (
+                    "items",
+                    page["nextPageToken"].as_str().map(|token| {
+                        let mut next = url.clone();
+                        next.query_pairs_mut().append_pair("pageToken", token);
+                        next
+                    }),
+                ),
+                Provider::Microsoft => (
+                    "value",
+                    page["@odata.nextLink"]
+                        .as_str()
+                        .and_then(|link| Url::parse(link).ok()),
+                ),
+            };
+            items.extend(page[key].as_array().into_iter().flatten().cloned());
+            next = next_page;
+        }
+        Ok(items)
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .bearer_auth(self.access_token.expose())
+    }
+}
+
+/// The body creating a task. Google only keeps the date of `due`, which is taken in its time
+/// zone.
+fn new_task<Tz: TimeZone>(
+    provider: &Provider,
+    title: &str,
+    notes: Option<&str>,
+    due: Option<DateTime<Tz>>,
+) -> Value {
+    let mut body = json!({ "title": title });
+    match provider {
+        Provider::Google => {
+            if let Some(notes) = notes {
+                body["notes"] = json!(notes);
+            }
+            if let Some(due) = due {
+                body["due"] = json!(
+                    due.date_naive()
+                        .format("%Y-%m-%dT00:00:00.000Z")
+                        .to_string()
+                );
+            }
+        }
+        Provider::Microsoft => {
+            if let Some(notes) = notes {
+                body["body"] = json!({ "content": notes, "contentType": "text" });
+            }
+            if let Some(due) = due {
+                body["dueDateTime"] = json!({
+                    "dateTime": due.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    "timeZone": "UTC",
+                });
+            }
+        }
+    }
+    body
+}
+
+/// Converts a task of the provider. Google's due dates are returned as the start of the day
+/// in `tz`, as the day is all they keep.
+fn to_task<Tz: TimeZone>(provider: &Provider, task: &Value, tz: &Tz) -> Option<Task>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let (notes, due) = match provider {
+        Provider::Google => (
+            &task["notes"],
+            task["due"]
+                .as_str()
+                .and_then(|due| NaiveDate::parse_from_str(due.get(..10)?, "%Y-%m-%d").ok())
+                .and_then(|date| {
+                    tz.from_local_datetime(&date.and_time(NaiveTime::MIN))
+                        .earliest()
+                })
+                .map(|due| due.to_rfc3339()),
+        ),
+        Provider::Microsoft => (
+            &task["body"]["content"],
+            task["dueDateTime"]["dateTime"]
+                .as_str()
+                // Graph sends seven digits of fractional seconds
+                .and_then(|time| time.split('.').next())
+                .and_then(|time| NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S").ok())
+                .map(|time| time.and_utc().to_rfc3339()),
+        ),
+    };
+    Some(Task {
+        id: task["id"].as_str()?.to_string(),
+        title: task["title"].as_str().unwrap_or_default().to_string(),
+        notes: notes
+            .as_str()
+            .filter(|notes| !notes.trim().is_empty())
+            .map(str::to_string),
+        due,
+        completed: task["status"].as_str() == Some("completed"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn new_york() -> FixedOffset {
+        FixedOffset::west_opt(5 * 3600).unwrap()
+    }
+
+    #[test]
+    fn google_due_dates_keep_the_local_day() {
+        // Late in the evening in New York is the next day in UTC
+        let due = DateTime::parse_from_rfc3339("2024-03-10T21:00:00-05:00").unwrap();
+        let body = new_task(&Provider::Google, "Taxes", Some("Forms"), Some(due));
+
+        assert_eq!(
+            body,
+            json!({ "title": "Taxes", "notes": "Forms", "due": "2024-03-10T00:00:00.000Z" })
+        );
+
+        let task = to_task(
+            &Provider::Google,
+            &json!({ "id": "t1", "title": "Taxes", "due": "2024-03-10T00:00:00.000Z" }),
+            &new_york(),
+        )
+        .unwrap();
+        assert_eq!(task.due.as_deref(), Some("2024-03-10T00:00:00-05:00"));
+    }
+
+    #[test]
+    fn microsoft_due_times_are_sent_in_utc() {
+        let due = DateTime::parse_from_rfc3339("2024-03-10T21:00:00-05:00").unwrap();
+        let body = new_task(&Provider::Microsoft, "Taxes", Some("Forms"), Some(due));
+
+        assert_eq!(
+            body,
+            json!({
+                "title": "Taxes",
+                "body": { "content": "Forms", "contentType": "text" },
+                "dueDateTime": { "dateTime": "2024-03-11T02:00:00", "timeZone": "UTC" },
+            })
+        );
+        assert_eq!(
+            new_task::<Utc>(&Provider::Microsoft, "Taxes", None, None),
+            json!({ "title": "Taxes" })
+        );
+    }
+
+    #[test]
+    fn converts_tasks() {
+        let google = to_task(
+            &Provider::Google,
+            &json!({ "id": "t1", "title": "Call", "notes": " ", "status": "completed" }),
+            &Utc,
+        )
+        .unwrap();
+        assert_eq!(
+            google,
+            Task {
+                id: "t1".to_string(),
+                title: "Call".to_string(),
+                notes: None,
+                due: None,
+                completed: true,
+            }
+        );
+
+        let microsoft = to_task(
+            &Provider::Microsoft,
+            &json!({
+                "id": "AAMk",
+                "title": "Call",
+                "status": "notStarted",
+                "body": { "content": "Ask about the invoice", "contentType": "text" },
+                "dueDateTime": { "dateTime": "2024-03-11T02:00:00.0000000", "timeZone": "UTC" },
+            }),
+            &Utc,
+        )
+        .unwrap();
+        assert_eq!(microsoft.notes.as_deref(), Some("Ask about the invoice"));
+        assert_eq!(microsoft.due.as_deref(), Some("2024-03-11T02:00:00+00:00"));
+        assert!(!microsoft.completed);
+
+        assert!(to_task(&Provider::Google, &json!({ "title": "No ID" }), &Utc).is_none());
+    }
+}
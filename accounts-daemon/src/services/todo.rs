@@ -1,23 +1,31 @@
 use std::collections::HashMap;
 
-use async_trait::async_trait;
-use zbus::{
-    fdo::{Error, Result},
-    interface,
+use accounts::{
+    AccountService, AccountsError, ServiceConfig,
+    models::{Account, Provider, Service, Task, TaskList},
 };
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use zbus::{Connection, fdo::Result, interface, message::Header};
 
-use crate::{
-    models::{Account, Provider},
-    services::{Service, ServiceConfig},
-};
+use super::{ServiceObject, tasks::TaskStore};
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TodoService {
-    account_id: String,
+    account: Account,
 }
 
 impl TodoService {
-    pub fn new(account_id: String) -> Self {
-        Self { account_id }
+    pub fn new(account: Account) -> Self {
+        Self { account }
+    }
+}
+
+impl ServiceObject for TodoService {
+    const SERVICE: Service = Service::Todo;
+
+    fn account(&self) -> &Account {
+        &self.account
     }
 }
 
@@ -26,18 +34,78 @@ impl TodoService {
     /// ToDo API URI - following GOA's Uri pattern
     #[zbus(property)]
     async fn uri(&self) -> Result<String> {
-        if self.account_id.contains("google") {
-            Ok("https://tasks.googleapis.com/tasks/v1/".to_string())
-        } else if self.account_id.contains("microsoft") {
-            Ok("https://graph.microsoft.com/v1.0/me/todo".to_string())
-        } else {
-            Err(Error::Failed("Unsupported provider".to_string()))
-        }
+        Ok(match self.account.provider {
+            Provider::Google => "https://tasks.googleapis.com/tasks/v1/".to_string(),
+            Provider::Microsoft => "https://graph.microsoft.com/v1.0/me/todo".to_string(),
+        })
+    }
+
+    /// The task lists of the account
+    async fn list_task_lists(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> std::result::Result<Vec<TaskList>, AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "ListTaskLists")
+            .await?;
+        Ok(TaskStore::new(&self.account, &access_token).lists().await?)
+    }
+
+    /// The tasks of a list, completed ones included
+    async fn list_tasks(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        list_id: &str,
+    ) -> std::result::Result<Vec<Task>, AccountsError> {
+        let access_token = self.access_token(connection, &header, "ListTasks").await?;
+        Ok(TaskStore::new(&self.account, &access_token)
+            .tasks(list_id)
+            .await?)
+    }
+
+    /// Mark a task as completed
+    async fn complete_task(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        list_id: &str,
+        task_id: &str,
+    ) -> std::result::Result<(), AccountsError> {
+        let access_token = self
+            .access_token(connection, &header, "CompleteTask")
+            .await?;
+        Ok(TaskStore::new(&self.account, &access_token)
+            .complete(list_id, task_id)
+            .await?)
+    }
+
+    /// Add a task to a list, `notes` and `due` are left out when empty
+    async fn create_task(
+        &self,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        list_id: &str,
+        title: &str,
+        notes: &str,
+        due: &str,
+    ) -> std::result::Result<Task, AccountsError> {
+        let access_token = self.access_token(connection, &header, "CreateTask").await?;
+        let optional = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+        Ok(TaskStore::new(&self.account, &access_token)
+            .create(
+                list_id,
+                title,
+                optional(notes).as_deref(),
+                optional(due).as_deref(),
+            )
+            .await?)
     }
 }
 
 #[async_trait]
-impl Service for TodoService {
+impl AccountService for TodoService {
     fn name(&self) -> &str {
         "Todo"
     }
@@ -47,8 +115,7 @@ impl Service for TodoService {
     }
 
     fn is_supported(&self, account: &Account) -> bool {
-        // Check if the account has todo services
-        matches!(account.provider, Provider::Google | Provider::Microsoft)
+        account.services.contains_key(&Service::Todo)
     }
 
     async fn get_config(&self, account: &Account) -> Result<ServiceConfig> {
@@ -76,6 +143,14 @@ impl Service for TodoService {
         })
     }
 
+    async fn add_service(&self) -> Result<bool> {
+        self.register().await
+    }
+
+    async fn remove_service(&self) -> Result<bool> {
+        self.unregister().await
+    }
+
     async fn ensure_credentials(&self, _account: &mut Account) -> Result<()> {
         Ok(())
     }
@@ -0,0 +1,114 @@
+use super::ical::{escape, fold};
+
+/// The parts of a contact the contacts service serializes as a vCard (RFC 6350).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contact {
+    pub uid: String,
+    pub full_name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub organization: Option<String>,
+}
+
+impl Contact {
+    /// Serializes the contact as a vCard 4.0.
+    pub fn to_vcard(&self) -> String {
+        // FN is required, fall back to the first address like address books do
+        let full_name = self
+            .full_name
+            .as_deref()
+            .or(self.emails.first().map(String::as_str))
+            .unwrap_or_default();
+        let mut lines = vec![
+            "BEGIN:VCARD".to_string(),
+            "VERSION:4.0".to_string(),
+            format!("UID:{}", escape(&self.uid)),
+            format!("FN:{}", escape(full_name)),
+        ];
+        if self.given_name.is_some() || self.family_name.is_some() {
+            lines.push(format!(
+                "N:{};{};;;",
+                escape(self.family_name.as_deref().unwrap_or_default()),
+                escape(self.given_name.as_deref().unwrap_or_default()),
+            ));
+        }
+        lines.extend(
+            self.emails
+                .iter()
+                .map(|email| format!("EMAIL:{}", escape(email))),
+        );
+        lines.extend(
+            self.phones
+                .iter()
+                .map(|phone| format!("TEL;VALUE=text:{}", escape(phone))),
+        );
+        if let Some(organization) = &self.organization {
+            lines.push(format!("ORG:{}", escape(organization)));
+        }
+        lines.push("END:VCARD".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_contacts() {
+        let contact = Contact {
+            uid: "people/c123".to_string(),
+            full_name: Some("Lovelace, Ada".to_string()),
+            given_name: Some("Ada".to_string()),
+            family_name: None,
+            emails: vec!["ada@example.com".to_string()],
+            phones: vec!["+44 20 7946 0000".to_string()],
+            organization: Some("Engines; Analytical".to_string()),
+        };
+
+        assert_eq!(
+            contact.to_vcard(),
+            "BEGIN:VCARD\r\n\
+             VERSION:4.0\r\n\
+             UID:people/c123\r\n\
+             FN:Lovelace\\, Ada\r\n\
+             N:;Ada;;;\r\n\
+             EMAIL:ada@example.com\r\n\
+             TEL;VALUE=text:+44 20 7946 0000\r\n\
+             ORG:Engines\\; Analytical\r\n\
+             END:VCARD\r\n"
+        );
+    }
+
+    #[test]
+    fn names_contacts_after_their_address() {
+        let contact = Contact {
+            uid: "1".to_string(),
+            emails: vec![
+                "first@example.com".to_string(),
+                "second@example.com".to_string(),
+            ],
+            ..Contact::default()
+        };
+
+        let vcard = contact.to_vcard();
+        assert!(vcard.contains("\r\nFN:first@example.com\r\n"));
+        assert!(!vcard.contains("\r\nN:"));
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let contact = Contact {
+            uid: "1".to_string(),
+            full_name: Some("N".repeat(100)),
+            ..Contact::default()
+        };
+
+        let vcard = contact.to_vcard();
+        assert!(vcard.split("\r\n").all(|line| line.len() <= 75));
+        assert!(vcard.contains(&format!("FN:{}\r\n {}\r\n", "N".repeat(72), "N".repeat(28))));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use cosmic_config::{Config, CosmicConfigEntry, cosmic_config_derive::CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::logging;
+
+pub const SETTINGS_VERSION: u64 = 1;
+
+/// The daemon settings, `~/.config/cosmic/dev.edfloreshz.AccountsDaemon.Settings/v1`.
+///
+/// Changes are applied while the daemon runs.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, CosmicConfigEntry)]
+pub struct DaemonSettings {
+    /// Address the OAuth callback server listens on, it only ever serves loopback clients.
+    pub callback_address: IpAddr,
+    /// Inclusive range of ports the OAuth callback server may listen on, the same port twice
+    /// for a fixed one. `(0, 0)` lets the system pick a free port.
+    pub callback_ports: (u16, u16),
+    /// Seconds without clients after which a daemon without accounts exits, `0` to never exit.
+    pub idle_timeout: u64,
+    /// Seconds before expiry at which access tokens are refreshed.
+    pub refresh_lead_time: u64,
+    /// Log filter used when `RUST_LOG` is not set, e.g. `info` or `accounts_daemon=debug`.
+    pub log_level: String,
+    /// Whether accounts can be imported from GNOME Online Accounts.
+    pub goa_import: bool,
+    /// Whether accounts can be imported from Ubuntu Online Accounts (libaccounts-glib).
+    pub uoa_import: bool,
+    /// Whether applications must be allowed by the user before reading tokens.
+    pub app_access_control: bool,
+    /// Whether accounts with calendars, contacts or mail enabled are added to Evolution Data
+    /// Server, for applications using it.
+    pub eds_bridge: bool,
+    /// Whether upcoming events and recent contacts of enabled accounts are cached shortly
+    /// after the daemon starts and every few minutes after that, so the calendar applet
+    /// opens instantly and both are served while the providers can't be reached. Turning it
+    /// off deletes the cache.
+    pub prefetch: bool,
+    /// Whether background work waits longer on battery and is skipped while the power
+    /// saver profile is active.
+    pub battery_aware: bool,
+    /// Where credentials are stored: `secret-service`, `kwallet`, `tpm2` or `file`. Empty
+    /// picks the Secret Service, falling back to an encrypted file, which the accounts page
+    /// warns about. `keyring` and `memory` lose the credentials on reboot and are meant for
+    /// testing. Only read at startup, `COSMIC_ACCOUNTS_STORAGE` overrides it.
+    pub credential_store: String,
+    /// Requests per minute sent to each provider, for all of its accounts together, `0` for
+    /// no limit. Keeps a client asking for tokens in a loop from getting the accounts
+    /// throttled by the provider.
+    pub provider_rate_limit: u32,
+    /// Requests which may be sent to a provider at once before the rate limit applies.
+    pub provider_burst: u32,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        Self {
+            callback_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            callback_ports: (0, 0),
+            idle_timeout: 60,
+            refresh_lead_time: 60,
+            log_level: "info".to_string(),
+            goa_import: true,
+            uoa_import: true,
+            app_access_control: true,
+            eds_bridge: false,
+            prefetch: false,
+            battery_aware: true,
+            credential_store: String::new(),
+            provider_rate_limit: 120,
+            provider_burst: 30,
+        }
+    }
+}
+
+impl DaemonSettings {
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout > 0).then(|| Duration::from_secs(self.idle_timeout))
+    }
+
+    pub fn refresh_lead_time(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.refresh_lead_time.min(i64::MAX as u64) as i64)
+    }
+
+    /// Requests per second sent to each provider.
+    pub fn provider_rate_limit(&self) -> Option<f64> {
+        (self.provider_rate_limit > 0).then(|| f64::from(self.provider_rate_limit) / 60.0)
+    }
+}
+
+static SETTINGS: LazyLock<RwLock<DaemonSettings>> = LazyLock::new(|| RwLock::new(load()));
+
+/// Sends the settings once they changed.
+static CHANGES: LazyLock<watch::Sender<DaemonSettings>> = LazyLock::new(|| watch::channel(get()).0);
+
+fn config_handler() -> Option<Config> {
+    Config::new("dev.edfloreshz.AccountsDaemon.Settings", SETTINGS_VERSION).ok()
+}
+
+fn load() -> DaemonSettings {
+    match config_handler() {
+        Some(config_handler) => {
+            DaemonSettings::get_entry(&config_handler).unwrap_or_else(|(errs, settings)| {
+                tracing::info!("errors loading settings: {:?}", errs);
+                settings
+            })
+        }
+        None => DaemonSettings::default(),
+    }
+}
+
+/// The current settings.
+pub fn get() -> DaemonSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+/// Receives the settings each time they change, while the watcher of [`watch()`] lives.
+pub fn subscribe() -> watch::Receiver<DaemonSettings> {
+    CHANGES.subscribe()
+}
+
+/// Reloads the settings whenever they change, for as long as the returned watcher lives.
+pub fn watch() -> Option<impl Sized> {
+    let config_handler = config_handler()?;
+    let watcher = config_handler.watch(|config_handler, keys| {
+        let mut settings = SETTINGS.write().unwrap();
+        let (errs, changed) = settings.update_keys(config_handler, keys);
+        if !errs.is_empty() {
+            tracing::warn!("errors reloading settings: {:?}", errs);
+        }
+        if changed.contains(&"log_level")
+            && std::env::var_os("RUST_LOG").is_none()
+            && let Err(err) = logging::set_level(&settings.log_level)
+        {
+            tracing::warn!("Invalid log level {}: {}", settings.log_level, err);
+        }
+        if !changed.is_empty() {
+            tracing::info!("Settings changed: {:?}", changed);
+            let current = settings.clone();
+            // Subscribers read the settings, so they are only notified once unlocked
+            drop(settings);
+            CHANGES.send_replace(current);
+        }
+    });
+    match watcher {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            tracing::warn!(
+                "Settings changes will not be applied until restart: {}",
+                err
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{Attributes, CredentialStore};
+use crate::{Error, Result};
+
+/// How the fake Secret Service answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeState {
+    Unlocked,
+    /// The collection is locked, like after the session keyring timed out.
+    Locked,
+    /// No Secret Service is running on the bus.
+    Unavailable,
+}
+
+/// Stands in for the Secret Service in tests, keeping the secrets in a file so they
+/// outlive the store like they outlive the daemon.
+///
+/// Clones share their state, so a test can lock the store it handed to the storage.
+#[derive(Clone)]
+pub struct FakeSecretService {
+    path: PathBuf,
+    state: Arc<Mutex<FakeState>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Secrets(Vec<(Attributes, Vec<u8>)>);
+
+impl FakeSecretService {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Arc::new(Mutex::new(FakeState::Unlocked)),
+        }
+    }
+
+    pub fn set_state(&self, state: FakeState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// Fails the way the Secret Service does when it can't serve the request.
+    fn check(&self) -> Result<()> {
+        match *self.state.lock().unwrap() {
+            FakeState::Unlocked => Ok(()),
+            FakeState::Locked => Err(Error::CredentialStorage(secret_service::Error::Locked)),
+            FakeState::Unavailable => {
+                Err(Error::CredentialStorage(secret_service::Error::Unavailable))
+            }
+        }
+    }
+
+    fn read(&self) -> Result<Secrets> {
+        match std::fs::read(&self.path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Secrets::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&self, secrets: &Secrets) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_vec(secrets)?)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CredentialStore for FakeSecretService {
+    fn name(&self) -> &'static str {
+        "secret-service"
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        self.check()?;
+        Ok(self
+            .read()?
+            .0
+            .into_iter()
+            .find(|(stored, _)| stored == attributes)
+            .map(|(_, secret)| secret))
+    }
+
+    async fn set(&self, _label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        self.check()?;
+        let mut secrets = self.read()?;
+        secrets.0.retain(|(stored, _)| stored != attributes);
+        secrets.0.push((attributes.clone(), secret.to_vec()));
+        self.write(&secrets)
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        self.check()?;
+        let mut secrets = self.read()?;
+        secrets
+            .0
+            .retain(|(stored, _)| stored.get(attribute).is_none_or(|v| v != value));
+        self.write(&secrets)
+    }
+}
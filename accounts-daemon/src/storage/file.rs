@@ -0,0 +1,198 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::{Attributes, CredentialStore};
+use crate::{Error, Result, paths};
+
+const NONCE_LEN: usize = 12;
+/// Name of the encrypted file in its directory.
+const FILE_NAME: &str = "credentials.bin";
+/// Name of the key of the file in its directory.
+const KEY_FILE_NAME: &str = "credentials.key";
+
+/// Stores secrets in a file encrypted with ChaCha20-Poly1305, for headless systems.
+///
+/// The key is kept next to the file, readable only by the user, so this protects the
+/// secrets from being read out of backups or copies of the file alone.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+    /// Serializes read-modify-write cycles of the file.
+    lock: Mutex<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSecret {
+    label: String,
+    attributes: Attributes,
+    secret: String,
+}
+
+impl EncryptedFileStore {
+    pub fn new(directory: &Path) -> Result<Self> {
+        fs::create_dir_all(directory)?;
+        fs::set_permissions(directory, fs::Permissions::from_mode(0o700))?;
+        paths::exclude_from_backups(directory);
+
+        let key_path = directory.join(KEY_FILE_NAME);
+        let key = match fs::read(&key_path) {
+            Ok(key) if key.len() == 32 => *Key::from_slice(&key),
+            Ok(_) => {
+                return Err(Error::StorageError(format!(
+                    "Invalid key in {}",
+                    key_path.display()
+                )));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&key_path, &key)?;
+                key
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self::with_key(directory, &key))
+    }
+
+    /// Whether secrets were stored in the directory.
+    pub fn exists(directory: &Path) -> bool {
+        directory.join(FILE_NAME).exists()
+    }
+
+    /// Moves the file and its key out of a directory they were kept in before, unless the
+    /// new directory has a file already. The key goes first, so an interrupted move is
+    /// finished on the next start.
+    pub fn migrate(from: &Path, to: &Path) -> Result<()> {
+        if !Self::exists(from) || Self::exists(to) {
+            return Ok(());
+        }
+        fs::create_dir_all(to)?;
+        if let Err(err) = fs::rename(from.join(KEY_FILE_NAME), to.join(KEY_FILE_NAME))
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(err.into());
+        }
+        fs::rename(from.join(FILE_NAME), to.join(FILE_NAME))?;
+        paths::include_in_backups(from);
+        Ok(())
+    }
+
+    /// Uses a key kept elsewhere, the directory must exist.
+    pub fn with_key(directory: &Path, key: &Key) -> Self {
+        Self {
+            path: directory.join(FILE_NAME),
+            cipher: ChaCha20Poly1305::new(key),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Every secret in the file, with its attributes.
+    pub async fn all(&self) -> Result<Vec<(Attributes, Vec<u8>)>> {
+        let _guard = self.lock.lock().await;
+        self.load()?
+            .into_iter()
+            .map(|stored| {
+                let secret = STANDARD
+                    .decode(stored.secret)
+                    .map_err(|e| Error::StorageError(e.to_string()))?;
+                Ok((stored.attributes, secret))
+            })
+            .collect()
+    }
+
+    fn load(&self) -> Result<Vec<StoredSecret>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        if content.len() < NONCE_LEN {
+            return Err(Error::StorageError(
+                "Credentials file is truncated".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = content.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::StorageError("Failed to decrypt credentials".to_string()))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn save(&self, secrets: &[StoredSecret]) -> Result<()> {
+        let plaintext = serde_json::to_vec(secrets)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| Error::StorageError("Failed to encrypt credentials".to_string()))?;
+        write_private(&self.path, &[nonce.as_slice(), &ciphertext].concat())
+    }
+}
+
+#[async_trait]
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        let _guard = self.lock.lock().await;
+        self.load()?
+            .into_iter()
+            .find(|stored| stored.attributes == *attributes)
+            .map(|stored| {
+                STANDARD
+                    .decode(stored.secret)
+                    .map_err(|e| Error::StorageError(e.to_string()))
+            })
+            .transpose()
+    }
+
+    async fn set(&self, label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut secrets = self.load()?;
+        secrets.retain(|stored| stored.attributes != *attributes);
+        secrets.push(StoredSecret {
+            label: label.to_string(),
+            attributes: attributes.clone(),
+            secret: STANDARD.encode(secret),
+        });
+        self.save(&secrets)
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut secrets = self.load()?;
+        secrets.retain(|stored| stored.attributes.get(attribute).is_none_or(|v| v != value));
+        self.save(&secrets)
+    }
+}
+
+/// Writes a file only the user can read, replacing it atomically.
+pub(super) fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temporary)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
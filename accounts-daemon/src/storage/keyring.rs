@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use keyring::Entry;
+
+use super::{Attributes, CredentialStore};
+use crate::{Error, Result};
+
+const SERVICE: &str = "dev.edfloreshz.Accounts";
+/// Entry listing the attributes of every stored secret, the kernel keyring can't search.
+const INDEX: &str = "index";
+
+/// Stores secrets in the kernel keyring, for systems without a Secret Service.
+///
+/// The kernel keyring doesn't persist across reboots, accounts have to be signed in again.
+pub struct KeyringStore;
+
+impl KeyringStore {
+    pub fn new() -> Result<Self> {
+        // Fails early when the platform has no usable keyring
+        Self::index()?;
+        Ok(Self)
+    }
+
+    fn entry(user: &str) -> Result<Entry> {
+        Entry::new(SERVICE, user).map_err(keyring_error)
+    }
+
+    fn index() -> Result<Vec<Attributes>> {
+        match Self::entry(INDEX)?.get_password() {
+            Ok(index) => Ok(serde_json::from_str(&index)?),
+            Err(keyring::Error::NoEntry) => Ok(Vec::new()),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+
+    fn set_index(index: &[Attributes]) -> Result<()> {
+        Self::entry(INDEX)?
+            .set_password(&serde_json::to_string(index)?)
+            .map_err(keyring_error)
+    }
+}
+
+#[async_trait]
+impl CredentialStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        match Self::entry(&entry_name(attributes))?.get_secret() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+
+    async fn set(&self, _label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        Self::entry(&entry_name(attributes))?
+            .set_secret(secret)
+            .map_err(keyring_error)?;
+
+        let mut index = Self::index()?;
+        if !index.contains(attributes) {
+            index.push(attributes.clone());
+            Self::set_index(&index)?;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        let mut index = Self::index()?;
+        for attributes in index
+            .iter()
+            .filter(|attributes| attributes.get(attribute).is_some_and(|v| v == value))
+        {
+            match Self::entry(&entry_name(attributes))?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(err) => return Err(keyring_error(err)),
+            }
+        }
+        index.retain(|attributes| attributes.get(attribute).is_none_or(|v| v != value));
+        Self::set_index(&index)
+    }
+}
+
+/// Attributes are sorted, so the same set always names the same entry.
+pub(super) fn entry_name(attributes: &Attributes) -> String {
+    attributes
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn keyring_error(error: keyring::Error) -> Error {
+    Error::StorageError(format!("Keyring error: {error}"))
+}
@@ -0,0 +1,186 @@
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use zbus::{Connection, proxy};
+
+use super::{Attributes, CredentialStore, keyring::entry_name};
+use crate::{Error, Result};
+
+const APP_ID: &str = "dev.edfloreshz.Accounts";
+/// Folder of the wallet the secrets are kept in.
+const FOLDER: &str = "COSMIC Accounts";
+
+#[proxy(
+    interface = "org.kde.KWallet",
+    default_service = "org.kde.kwalletd6",
+    default_path = "/modules/kwalletd6"
+)]
+trait KWallet {
+    #[zbus(name = "networkWallet")]
+    fn network_wallet(&self) -> zbus::Result<String>;
+
+    fn open(&self, wallet: &str, w_id: i64, appid: &str) -> zbus::Result<i32>;
+
+    #[zbus(name = "hasFolder")]
+    fn has_folder(&self, handle: i32, folder: &str, appid: &str) -> zbus::Result<bool>;
+
+    #[zbus(name = "createFolder")]
+    fn create_folder(&self, handle: i32, folder: &str, appid: &str) -> zbus::Result<bool>;
+
+    #[zbus(name = "hasEntry")]
+    fn has_entry(&self, handle: i32, folder: &str, key: &str, appid: &str) -> zbus::Result<bool>;
+
+    #[zbus(name = "entryList")]
+    fn entry_list(&self, handle: i32, folder: &str, appid: &str) -> zbus::Result<Vec<String>>;
+
+    #[zbus(name = "readPassword")]
+    fn read_password(
+        &self,
+        handle: i32,
+        folder: &str,
+        key: &str,
+        appid: &str,
+    ) -> zbus::Result<String>;
+
+    #[zbus(name = "writePassword")]
+    fn write_password(
+        &self,
+        handle: i32,
+        folder: &str,
+        key: &str,
+        value: &str,
+        appid: &str,
+    ) -> zbus::Result<i32>;
+
+    #[zbus(name = "removeEntry")]
+    fn remove_entry(&self, handle: i32, folder: &str, key: &str, appid: &str) -> zbus::Result<i32>;
+}
+
+/// Stores secrets in the network wallet of KWallet, for users running KDE applications.
+///
+/// Entries are named after their attributes, as in the kernel keyring, and hold the secret
+/// as base64 since wallet passwords are strings.
+pub struct KWalletStore {
+    proxy: KWalletProxy<'static>,
+    wallet: String,
+}
+
+impl KWalletStore {
+    pub async fn new() -> Result<Self> {
+        let connection = Connection::session().await?;
+        let mut proxy = KWalletProxy::new(&connection).await?;
+        if proxy.network_wallet().await.is_err() {
+            // Plasma 5 runs kwalletd5
+            proxy = KWalletProxy::builder(&connection)
+                .destination("org.kde.kwalletd5")?
+                .path("/modules/kwalletd5")?
+                .build()
+                .await?;
+        }
+        let wallet = proxy.network_wallet().await.map_err(kwallet_error)?;
+        Ok(Self { proxy, wallet })
+    }
+
+    /// Opens the wallet, asking the user to unlock it when needed, and its folder.
+    ///
+    /// KWallet hands out the same handle while the wallet stays open, it is requested on
+    /// every use since the user may close the wallet at any time.
+    async fn handle(&self) -> Result<i32> {
+        let handle = self
+            .proxy
+            .open(&self.wallet, 0, APP_ID)
+            .await
+            .map_err(kwallet_error)?;
+        if handle < 0 {
+            return Err(Error::StorageError(format!(
+                "KWallet refused to open {}",
+                self.wallet
+            )));
+        }
+        if !self
+            .proxy
+            .has_folder(handle, FOLDER, APP_ID)
+            .await
+            .map_err(kwallet_error)?
+        {
+            self.proxy
+                .create_folder(handle, FOLDER, APP_ID)
+                .await
+                .map_err(kwallet_error)?;
+        }
+        Ok(handle)
+    }
+}
+
+#[async_trait]
+impl CredentialStore for KWalletStore {
+    fn name(&self) -> &'static str {
+        "kwallet"
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        let handle = self.handle().await?;
+        let key = entry_name(attributes);
+        if !self
+            .proxy
+            .has_entry(handle, FOLDER, &key, APP_ID)
+            .await
+            .map_err(kwallet_error)?
+        {
+            return Ok(None);
+        }
+        let secret = self
+            .proxy
+            .read_password(handle, FOLDER, &key, APP_ID)
+            .await
+            .map_err(kwallet_error)?;
+        STANDARD
+            .decode(secret)
+            .map(Some)
+            .map_err(|e| Error::StorageError(e.to_string()))
+    }
+
+    async fn set(&self, _label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        let handle = self.handle().await?;
+        let result = self
+            .proxy
+            .write_password(
+                handle,
+                FOLDER,
+                &entry_name(attributes),
+                &STANDARD.encode(secret),
+                APP_ID,
+            )
+            .await
+            .map_err(kwallet_error)?;
+        if result != 0 {
+            return Err(Error::StorageError(
+                "KWallet failed to store the secret".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        let handle = self.handle().await?;
+        let entries = self
+            .proxy
+            .entry_list(handle, FOLDER, APP_ID)
+            .await
+            .map_err(kwallet_error)?;
+        let pair = format!("{attribute}={value}");
+        for key in entries
+            .iter()
+            .filter(|key| key.split(';').any(|entry| entry == pair))
+        {
+            self.proxy
+                .remove_entry(handle, FOLDER, key, APP_ID)
+                .await
+                .map_err(kwallet_error)?;
+        }
+        Ok(())
+    }
+}
+
+fn kwallet_error(error: zbus::Error) -> Error {
+    Error::StorageError(format!("KWallet error: {error}"))
+}
@@ -0,0 +1,48 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{Attributes, CredentialStore};
+use crate::Result;
+
+/// Keeps secrets in memory only, for tests and CI.
+#[derive(Default)]
+pub struct MemoryStore {
+    secrets: Mutex<Vec<(Attributes, Vec<u8>)>>,
+}
+
+#[async_trait]
+impl CredentialStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .secrets
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(stored, _)| stored == attributes)
+            .map(|(_, secret)| secret.clone()))
+    }
+
+    async fn set(&self, _label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        let mut secrets = self.secrets.lock().unwrap();
+        secrets.retain(|(stored, _)| stored != attributes);
+        secrets.push((attributes.clone(), secret.to_vec()));
+        Ok(())
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        self.secrets
+            .lock()
+            .unwrap()
+            .retain(|(stored, _)| stored.get(attribute).is_none_or(|v| v != value));
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{Error, Result, paths::data_dir, settings};
+use accounts::models::{Credential, Provider, SecretString};
+use async_trait::async_trait;
+use uuid::Uuid;
+
+#[cfg(test)]
+pub mod fake;
+mod file;
+mod keyring;
+mod kwallet;
+mod memory;
+mod secret_service;
+mod tpm2;
+
+pub use self::file::EncryptedFileStore;
+pub use self::keyring::KeyringStore;
+pub use self::kwallet::KWalletStore;
+pub use self::memory::MemoryStore;
+pub use self::secret_service::SecretServiceStore;
+pub use self::tpm2::Tpm2Store;
+
+/// A reason to tell the user where their credentials are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageWarning {
+    /// The Secret Service was unavailable, the credentials are in the encrypted file.
+    Fallback,
+    /// The backend loses the credentials on reboot.
+    NotPersistent,
+}
+
+impl StorageWarning {
+    /// Code the warning is sent as over D-Bus.
+    pub fn code(self) -> &'static str {
+        match self {
+            StorageWarning::Fallback => "fallback",
+            StorageWarning::NotPersistent => "not-persistent",
+        }
+    }
+}
+
+/// Attributes identifying a stored secret.
+pub type Attributes = BTreeMap<String, String>;
+
+/// A backend able to keep secrets, looked up by their attributes.
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Name of the backend, as picked in the `credential_store` setting.
+    fn name(&self) -> &'static str;
+
+    /// Whether secrets survive a reboot.
+    fn persistent(&self) -> bool {
+        true
+    }
+
+    /// Returns the secret stored under the attributes, if any.
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>>;
+
+    /// Stores a secret, replacing the one stored under the same attributes.
+    async fn set(&self, label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()>;
+
+    /// Deletes every secret with the attribute set to the value.
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()>;
+}
+
+pub struct CredentialStorage {
+    store: Box<dyn CredentialStore>,
+    /// Whether the Secret Service was unavailable and the encrypted file is used instead.
+    fallback: bool,
+    /// Directory of the encrypted file keeping credentials the store failed to save.
+    rescue_dir: PathBuf,
+}
+
+impl CredentialStorage {
+    /// Selects the backend from `COSMIC_ACCOUNTS_STORAGE` or the `credential_store` setting
+    /// (`secret-service`, `kwallet`, `tpm2`, `keyring`, `file` or `memory`), falling back to
+    /// the encrypted file when no Secret Service is running. The kernel keyring and memory
+    /// backends lose the credentials on reboot and are never picked on their own.
+    pub async fn new() -> Result<Self> {
+        let backend = std::env::var("COSMIC_ACCOUNTS_STORAGE")
+            .ok()
+            .or_else(|| Some(settings::get().credential_store).filter(|b| !b.is_empty()));
+        let store: Box<dyn CredentialStore> = match backend.as_deref() {
+            Some("secret-service") => Box::new(SecretServiceStore::new().await?),
+            Some("kwallet") => Box::new(KWalletStore::new().await?),
+            Some("tpm2") => Box::new(Tpm2Store::new(&data_dir().join("tpm2"))?),
+            Some("keyring") => Box::new(KeyringStore::new()?),
+            Some("file") => Box::new(file_store()?),
+            Some("memory") => Box::new(MemoryStore::default()),
+            Some(backend) => {
+                return Err(Error::StorageError(format!(
+                    "Unknown storage backend: {backend}"
+                )));
+            }
+            None => match SecretServiceStore::new().await {
+                Ok(store) => Box::new(store),
+                Err(err) => {
+                    tracing::warn!(
+                        "Secret Service unavailable, storing credentials in a file: {}",
+                        err
+                    );
+                    return Ok(Self {
+                        store: Box::new(file_store()?),
+                        fallback: true,
+                        rescue_dir: rescue_dir(),
+                    });
+                }
+            },
+        };
+        if !store.persistent() {
+            tracing::warn!(
+                "Credentials are stored in the {} backend and are lost on reboot",
+                store.name()
+            );
+        }
+        Ok(Self::with_store(store))
+    }
+
+    pub fn with_store(store: Box<dyn CredentialStore>) -> Self {
+        Self {
+            store,
+            fallback: false,
+            rescue_dir: rescue_dir(),
+        }
+    }
+
+    /// Keeps rescued credentials in another directory, for tests.
+    #[cfg(test)]
+    pub fn with_rescue_dir(mut self, rescue_dir: PathBuf) -> Self {
+        self.rescue_dir = rescue_dir;
+        self
+    }
+
+    /// Name of the backend the credentials are stored in.
+    pub fn backend(&self) -> &'static str {
+        self.store.name()
+    }
+
+    /// Why the user should know where their credentials are stored, if they should: the
+    /// Secret Service was unavailable, or the backend loses them on reboot.
+    pub fn warning(&self) -> Option<StorageWarning> {
+        if self.fallback {
+            Some(StorageWarning::Fallback)
+        } else if !self.store.persistent() {
+            Some(StorageWarning::NotPersistent)
+        } else {
+            None
+        }
+    }
+
+    pub async fn get_account_credentials(&self, account_id: &Uuid) -> Result<Credential> {
+        self.find_account_credentials(account_id)
+            .await?
+            .ok_or_else(|| {
+                Error::StorageError(format!("Credentials not found for account {}", account_id))
+            })
+    }
+
+    /// The credentials of an account, `None` when none are stored.
+    pub async fn find_account_credentials(&self, account_id: &Uuid) -> Result<Option<Credential>> {
+        let Some(secret) = self.store.get(&account_attributes(account_id)).await? else {
+            return Ok(None);
+        };
+        let serialized = std::str::from_utf8(&secret).map_err(Error::Utf8)?;
+        Ok(Some(serde_json::from_str(serialized)?))
+    }
+
+    pub async fn set_account_credentials(
+        &self,
+        account_id: &Uuid,
+        credential: &Credential,
+    ) -> Result<()> {
+        let serialized = serde_json::to_string(credential)?;
+        self.store
+            .set(
+                &format!("Account: {}", account_id),
+                &account_attributes(account_id),
+                serialized.as_bytes(),
+            )
+            .await
+    }
+
+    pub async fn get_client_secret(&self, provider: &Provider) -> Result<Option<SecretString>> {
+        let Some(secret) = self.store.get(&client_secret_attributes(provider)).await? else {
+            return Ok(None);
+        };
+        let secret = std::str::from_utf8(&secret).map_err(Error::Utf8)?;
+        Ok(Some(SecretString::from(secret)))
+    }
+
+    pub async fn set_client_secret(
+        &self,
+        provider: &Provider,
+        secret: &SecretString,
+    ) -> Result<()> {
+        self.store
+            .set(
+                &format!("{} client secret", provider),
+                &client_secret_attributes(provider),
+                secret.expose().as_bytes(),
+            )
+            .await
+    }
+
+    /// Credentials limited to a set of scopes are stored next to the account credentials
+    /// under a separate attribute so they never shadow them.
+    pub async fn get_scoped_credentials(
+        &self,
+        account_id: &Uuid,
+        scopes: &[String],
+    ) -> Result<Option<Credential>> {
+        let Some(secret) = self
+            .store
+            .get(&scoped_attributes(account_id, scopes))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let serialized = std::str::from_utf8(&secret).map_err(Error::Utf8)?;
+        Ok(Some(serde_json::from_str(serialized)?))
+    }
+
+    pub async fn set_scoped_credentials(
+        &self,
+        account_id: &Uuid,
+        scopes: &[String],
+        credential: &Credential,
+    ) -> Result<()> {
+        let serialized = serde_json::to_string(credential)?;
+        self.store
+            .set(
+                &format!("Account: {} ({})", account_id, scope_key(scopes)),
+                &scoped_attributes(account_id, scopes),
+                serialized.as_bytes(),
+            )
+            .await
+    }
+
+    /// Saves the credentials of an account to an encrypted file of their own after the
+    /// store failed to, so a refresh token the provider rotated survives a restart.
+    pub async fn rescue_account_credentials(
+        &self,
+        account_id: &Uuid,
+        credential: &Credential,
+    ) -> Result<()> {
+        let rescue = Self::with_store(Box::new(EncryptedFileStore::new(&self.rescue_dir)?));
+        rescue.set_account_credentials(account_id, credential).await
+    }
+
+    /// The credentials rescued from failed saves, by account.
+    pub async fn rescued_account_credentials(&self) -> Result<Vec<(Uuid, Credential)>> {
+        if !EncryptedFileStore::exists(&self.rescue_dir) {
+            return Ok(Vec::new());
+        }
+        let store = EncryptedFileStore::new(&self.rescue_dir)?;
+        let mut rescued = Vec::new();
+        for (attributes, secret) in store.all().await? {
+            let Some(account_id) = attributes
+                .get("account_id")
+                .and_then(|id| Uuid::parse_str(id).ok())
+            else {
+                continue;
+            };
+            let serialized = std::str::from_utf8(&secret).map_err(Error::Utf8)?;
+            rescued.push((account_id, serde_json::from_str(serialized)?));
+        }
+        Ok(rescued)
+    }
+
+    /// Deletes the rescued credentials of an account, once the store has them.
+    pub async fn forget_rescued_account_credentials(&self, account_id: &Uuid) -> Result<()> {
+        match self.rescued()? {
+            Some(rescue) => rescue.delete_account_credentials(account_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// The store of rescued credentials, `None` while nothing was ever rescued.
+    fn rescued(&self) -> Result<Option<Self>> {
+        if !EncryptedFileStore::exists(&self.rescue_dir) {
+            return Ok(None);
+        }
+        let store = EncryptedFileStore::new(&self.rescue_dir)?;
+        Ok(Some(Self::with_store(Box::new(store))))
+    }
+
+    pub async fn delete_account_credentials(&self, account_id: &Uuid) -> Result<()> {
+        for attribute in ["account_id", "scoped_account_id"] {
+            self.store
+                .delete(attribute, &account_id.to_string())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Where credentials the store failed to save are kept, apart from the encrypted file the
+/// store may be.
+fn rescue_dir() -> PathBuf {
+    data_dir().join("rescued")
+}
+
+/// The encrypted file store, in a directory of its own so only it is kept out of backups.
+fn file_store() -> Result<EncryptedFileStore> {
+    let dir = data_dir().join("credentials");
+    // Earlier versions kept the file in the data directory itself
+    EncryptedFileStore::migrate(&data_dir(), &dir)?;
+    EncryptedFileStore::new(&dir)
+}
+
+fn account_attributes(account_id: &Uuid) -> Attributes {
+    Attributes::from([("account_id".to_string(), account_id.to_string())])
+}
+
+fn client_secret_attributes(provider: &Provider) -> Attributes {
+    Attributes::from([("client_secret".to_string(), provider.to_string())])
+}
+
+fn scoped_attributes(account_id: &Uuid, scopes: &[String]) -> Attributes {
+    Attributes::from([
+        ("scoped_account_id".to_string(), account_id.to_string()),
+        ("scopes".to_string(), scope_key(scopes)),
+    ])
+}
+
+/// Scope sets are order independent, so they are sorted before being used as a key.
+fn scope_key(scopes: &[String]) -> String {
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+    scopes.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    use super::fake::{FakeSecretService, FakeState};
+    use super::*;
+
+    fn credential(access_token: &str) -> Credential {
+        Credential {
+            access_token: access_token.into(),
+            refresh_token: Some("refresh-token".into()),
+            expires_at: Some(Utc::now()),
+            scope: vec!["openid".to_string(), "email".to_string()],
+            token_type: "Bearer".to_string(),
+        }
+    }
+
+    fn storage(dir: &TempDir) -> (FakeSecretService, CredentialStorage) {
+        let store = FakeSecretService::new(dir.path().join("secrets.json"));
+        let storage = CredentialStorage::with_store(Box::new(store.clone()));
+        (store, storage)
+    }
+
+    #[tokio::test]
+    async fn credentials_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let (_, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+        let credential = credential("access-token");
+
+        storage
+            .set_account_credentials(&account_id, &credential)
+            .await
+            .unwrap();
+
+        let stored = storage.get_account_credentials(&account_id).await.unwrap();
+        assert_eq!(stored, credential);
+    }
+
+    #[test]
+    fn warns_about_backends_losing_credentials_on_reboot() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(storage(&dir).1.warning(), None);
+
+        let storage = CredentialStorage::with_store(Box::new(MemoryStore::default()));
+        assert_eq!(storage.backend(), "memory");
+        assert_eq!(storage.warning(), Some(StorageWarning::NotPersistent));
+    }
+
+    #[tokio::test]
+    async fn credentials_outlive_the_store() {
+        let dir = TempDir::new().unwrap();
+        let account_id = Uuid::new_v4();
+        let credential = credential("access-token");
+        storage(&dir)
+            .1
+            .set_account_credentials(&account_id, &credential)
+            .await
+            .unwrap();
+
+        let (_, storage) = storage(&dir);
+        let stored = storage.get_account_credentials(&account_id).await.unwrap();
+        assert_eq!(stored, credential);
+    }
+
+    #[tokio::test]
+    async fn scoped_credentials_never_shadow_the_account_credentials() {
+        let dir = TempDir::new().unwrap();
+        let (_, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+        let scopes = vec!["email".to_string(), "openid".to_string()];
+        storage
+            .set_account_credentials(&account_id, &credential("account"))
+            .await
+            .unwrap();
+
+        storage
+            .set_scoped_credentials(&account_id, &scopes, &credential("scoped"))
+            .await
+            .unwrap();
+
+        let account = storage.get_account_credentials(&account_id).await.unwrap();
+        assert_eq!(account.access_token.expose(), "account");
+        // Scope sets are order independent
+        let reversed: Vec<String> = scopes.into_iter().rev().collect();
+        let scoped = storage
+            .get_scoped_credentials(&account_id, &reversed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(scoped.access_token.expose(), "scoped");
+    }
+
+    #[tokio::test]
+    async fn removing_an_account_deletes_all_its_credentials() {
+        let dir = TempDir::new().unwrap();
+        let (_, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+        let scopes = vec!["email".to_string()];
+        for id in [&account_id, &other_id] {
+            storage
+                .set_account_credentials(id, &credential("access-token"))
+                .await
+                .unwrap();
+        }
+        storage
+            .set_scoped_credentials(&account_id, &scopes, &credential("scoped"))
+            .await
+            .unwrap();
+
+        storage
+            .delete_account_credentials(&account_id)
+            .await
+            .unwrap();
+
+        assert!(storage.get_account_credentials(&account_id).await.is_err());
+        assert!(
+            storage
+                .get_scoped_credentials(&account_id, &scopes)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(storage.get_account_credentials(&other_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn missing_credentials_are_an_error() {
+        let dir = TempDir::new().unwrap();
+        let (_, storage) = storage(&dir);
+
+        let result = storage.get_account_credentials(&Uuid::new_v4()).await;
+
+        assert!(matches!(result, Err(Error::StorageError(_))));
+    }
+
+    #[tokio::test]
+    async fn locked_store_fails_until_unlocked() {
+        let dir = TempDir::new().unwrap();
+        let (store, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+        storage
+            .set_account_credentials(&account_id, &credential("access-token"))
+            .await
+            .unwrap();
+
+        store.set_state(FakeState::Locked);
+        let result = storage.get_account_credentials(&account_id).await;
+        assert!(matches!(
+            result,
+            Err(Error::CredentialStorage(secret_service::Error::Locked))
+        ));
+
+        store.set_state(FakeState::Unlocked);
+        assert!(storage.get_account_credentials(&account_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn finding_credentials_tells_missing_from_locked() {
+        let dir = TempDir::new().unwrap();
+        let (store, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+
+        let missing = storage.find_account_credentials(&account_id).await;
+        assert!(matches!(missing, Ok(None)));
+
+        store.set_state(FakeState::Locked);
+        let locked = storage.find_account_credentials(&account_id).await;
+        assert!(matches!(
+            locked,
+            Err(Error::CredentialStorage(secret_service::Error::Locked))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unavailable_store_keeps_nothing() {
+        let dir = TempDir::new().unwrap();
+        let (store, storage) = storage(&dir);
+        let account_id = Uuid::new_v4();
+        store.set_state(FakeState::Unavailable);
+
+        let result = storage
+            .set_account_credentials(&account_id, &credential("access-token"))
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::CredentialStorage(secret_service::Error::Unavailable))
+        ));
+        assert!(storage.get_client_secret(&Provider::Google).await.is_err());
+
+        store.set_state(FakeState::Unlocked);
+        assert!(storage.get_account_credentials(&account_id).await.is_err());
+    }
+}
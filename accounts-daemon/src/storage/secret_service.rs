@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use secret_service::{EncryptionType, SecretService};
+
+use super::{Attributes, CredentialStore};
+use crate::{Error, Result};
+
+/// Stores secrets in the default collection of the Secret Service.
+pub struct SecretServiceStore {
+    service: SecretService<'static>,
+}
+
+impl SecretServiceStore {
+    pub async fn new() -> Result<Self> {
+        Ok(Self {
+            service: SecretService::connect(EncryptionType::Dh)
+                .await
+                .map_err(Error::CredentialStorage)?,
+        })
+    }
+}
+
+#[async_trait]
+impl CredentialStore for SecretServiceStore {
+    fn name(&self) -> &'static str {
+        "secret-service"
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        let search_items = self
+            .service
+            .search_items(as_search(attributes))
+            .await
+            .map_err(Error::CredentialStorage)?;
+        let Some(item) = search_items.unlocked.first() else {
+            return Ok(None);
+        };
+        let secret = item.get_secret().await.map_err(Error::CredentialStorage)?;
+        Ok(Some(secret))
+    }
+
+    async fn set(&self, label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        let collection = self
+            .service
+            .get_default_collection()
+            .await
+            .map_err(Error::CredentialStorage)?;
+
+        collection
+            .create_item(
+                label,
+                as_search(attributes),
+                secret,
+                true, // replace existing
+                "text/plain",
+            )
+            .await
+            .map_err(|e| Error::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        let collection = self
+            .service
+            .get_default_collection()
+            .await
+            .map_err(Error::CredentialStorage)?;
+
+        let search_items = collection
+            .search_items(HashMap::from([(attribute, value)]))
+            .await
+            .map_err(Error::CredentialStorage)?;
+
+        for item in search_items {
+            item.delete().await.map_err(Error::CredentialStorage)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn as_search(attributes: &Attributes) -> HashMap<&str, &str> {
+    attributes
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect()
+}
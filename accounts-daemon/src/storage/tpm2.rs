@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use async_trait::async_trait;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, aead::OsRng};
+
+use super::{
+    Attributes, CredentialStore,
+    file::{EncryptedFileStore, write_private},
+};
+use crate::{Error, Result, paths};
+
+/// Name the key is sealed under, `systemd-creds` refuses to unseal it under another one.
+const CREDENTIAL_NAME: &str = "cosmic-accounts";
+
+/// Stores secrets in an encrypted file whose key is sealed to the TPM2 of the machine.
+///
+/// The key is sealed with `systemd-creds`, so copies of the files are useless on any other
+/// machine. The user needs access to `/dev/tpmrm0`, usually through the `tss` group.
+pub struct Tpm2Store(EncryptedFileStore);
+
+impl Tpm2Store {
+    pub fn new(directory: &Path) -> Result<Self> {
+        fs::create_dir_all(directory)?;
+        fs::set_permissions(directory, fs::Permissions::from_mode(0o700))?;
+        paths::exclude_from_backups(directory);
+
+        let sealed_path = directory.join("credentials.key.cred");
+        let key = match fs::read(&sealed_path) {
+            Ok(sealed) => {
+                let key = systemd_creds(&["decrypt"], &sealed)?;
+                if key.len() != 32 {
+                    return Err(Error::StorageError(format!(
+                        "Invalid key sealed in {}",
+                        sealed_path.display()
+                    )));
+                }
+                *Key::from_slice(&key)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                let sealed = systemd_creds(&["encrypt", "--with-key=tpm2"], &key)?;
+                write_private(&sealed_path, &sealed)?;
+                key
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self(EncryptedFileStore::with_key(directory, &key)))
+    }
+}
+
+#[async_trait]
+impl CredentialStore for Tpm2Store {
+    fn name(&self) -> &'static str {
+        "tpm2"
+    }
+
+    async fn get(&self, attributes: &Attributes) -> Result<Option<Vec<u8>>> {
+        self.0.get(attributes).await
+    }
+
+    async fn set(&self, label: &str, attributes: &Attributes, secret: &[u8]) -> Result<()> {
+        self.0.set(label, attributes, secret).await
+    }
+
+    async fn delete(&self, attribute: &str, value: &str) -> Result<()> {
+        self.0.delete(attribute, value).await
+    }
+}
+
+/// Runs `systemd-creds` on the input, from standard input to standard output.
+fn systemd_creds(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new("systemd-creds")
+        .args(args)
+        .arg(format!("--name={CREDENTIAL_NAME}"))
+        .args(["-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::StorageError(format!("Failed to run systemd-creds: {e}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::StorageError(format!(
+            "TPM2 sealing failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
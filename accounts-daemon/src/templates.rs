@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, path::Path};
+
+use accounts::models::{Account, Provider, Service};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Where administrators drop account templates.
+const TEMPLATES_DIR: &str = "/etc/accounts/templates";
+
+#[derive(Deserialize)]
+struct AccountTemplateFile {
+    template: AccountTemplate,
+}
+
+/// An account an administrator provisions for every user, e.g. `work.toml`:
+///
+/// ```toml
+/// [template]
+/// provider = "Microsoft"
+/// email_pattern = "{user}@example.com"
+/// display_name = "Work"
+/// locked = true
+///
+/// [template.services]
+/// Calendar = true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountTemplate {
+    /// File stem of the template, stored on the accounts created from it.
+    #[serde(skip)]
+    pub name: String,
+    pub provider: Provider,
+    /// Email of the account, `{user}` is replaced with the login name.
+    pub email_pattern: String,
+    pub display_name: Option<String>,
+    /// Services the user cannot turn on or off.
+    #[serde(default)]
+    pub services: BTreeMap<Service, bool>,
+    /// Whether the user is kept from removing or disabling the account.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl AccountTemplate {
+    pub fn email(&self) -> Option<String> {
+        let user = std::env::var("USER").ok()?;
+        Some(self.email_pattern.replace("{user}", &user))
+    }
+
+    /// Whether the template forces a service into another state.
+    pub fn enforces(&self, service: &Service, enabled: bool) -> bool {
+        self.services
+            .get(service)
+            .is_some_and(|enforced| *enforced != enabled)
+    }
+
+    /// Creates the pending account, which the user only needs to sign in to.
+    pub fn account(&self) -> Option<Account> {
+        let email = self.email()?;
+        let mut services = self.provider.services();
+        services.extend(self.services.clone());
+
+        Some(Account {
+            id: Uuid::new_v4(),
+            provider: self.provider,
+            display_name: self.display_name.clone().unwrap_or_else(|| email.clone()),
+            username: email.clone(),
+            email: Some(email),
+            enabled: true,
+            created_at: Utc::now(),
+            last_used: None,
+            services,
+            attention_needed: true,
+            last_error: None,
+            remote_id: None,
+            label: None,
+            color: None,
+            service_settings: BTreeMap::new(),
+            app_access: BTreeMap::new(),
+            template: Some(self.name.clone()),
+            avatar: None,
+            order: 0,
+            group: None,
+            sync_on_metered: false,
+            locked: self.locked,
+        })
+    }
+}
+
+/// Reads the templates installed by the administrator, skipping invalid ones.
+pub fn load() -> Vec<AccountTemplate> {
+    let Ok(entries) = std::fs::read_dir(TEMPLATES_DIR) else {
+        return Vec::new();
+    };
+
+    let mut templates: Vec<AccountTemplate> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .filter_map(|path| match read(&path) {
+            Ok(template) => Some(template),
+            Err(err) => {
+                tracing::warn!("Ignoring account template {}: {}", path.display(), err);
+                None
+            }
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+fn read(path: &Path) -> crate::Result<AccountTemplate> {
+    let content = std::fs::read_to_string(path)?;
+    let mut template = toml::from_str::<AccountTemplateFile>(&content)?.template;
+    template.name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(template)
+}
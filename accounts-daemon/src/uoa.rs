@@ -0,0 +1,176 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use accounts::models::{Provider, Service};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+use crate::{Error, Result, glib, paths};
+
+/// An account configured in libaccounts-glib, as used by Ubuntu Online Accounts.
+#[derive(Debug)]
+pub struct UoaAccount {
+    pub id: u32,
+    pub provider: Provider,
+    pub identity: String,
+    pub display_name: Option<String>,
+    pub services: HashMap<Service, bool>,
+}
+
+/// The account database, `~/.config/libaccounts-glib/accounts.db`.
+fn accounts_path() -> Option<PathBuf> {
+    paths::config_home().map(|config| config.join("libaccounts-glib").join("accounts.db"))
+}
+
+fn open(path: Option<PathBuf>) -> Result<Option<Connection>> {
+    let Some(path) = path.filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map(Some)
+        .map_err(sqlite_error)
+}
+
+/// Reads the Google and Microsoft accounts from the libaccounts-glib database, other
+/// providers are skipped.
+pub fn read_accounts() -> Result<Vec<UoaAccount>> {
+    let Some(db) = open(accounts_path())? else {
+        return Ok(Vec::new());
+    };
+
+    let mut statement = db
+        .prepare("SELECT id, name, provider FROM Accounts")
+        .map_err(sqlite_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(sqlite_error)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(sqlite_error)?;
+
+    let mut accounts = Vec::new();
+    for (id, name, provider) in rows {
+        let provider = match provider.as_str() {
+            "google" => Provider::Google,
+            "microsoft" | "windows-live" | "outlook" => Provider::Microsoft,
+            _ => continue,
+        };
+        let settings = account_settings(&db, id)?;
+        let Some(identity) = settings
+            .get("username")
+            .and_then(|value| glib::gvariant_string(value))
+            .or(name.clone())
+            .filter(|identity| !identity.is_empty())
+        else {
+            continue;
+        };
+
+        accounts.push(UoaAccount {
+            id,
+            provider,
+            display_name: name.filter(|name| *name != identity),
+            identity,
+            services: account_services(&db, id)?,
+        });
+    }
+    Ok(accounts)
+}
+
+/// The global settings of an account, as serialized GVariants.
+fn account_settings(db: &Connection, account: u32) -> Result<HashMap<String, String>> {
+    let mut statement = db
+        .prepare("SELECT key, value FROM Settings WHERE account = ?1 AND service = 0")
+        .map_err(sqlite_error)?;
+    statement
+        .query_map([account], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(sqlite_error)?
+        .collect::<rusqlite::Result<_>>()
+        .map_err(sqlite_error)
+}
+
+/// Whether each service of the account is enabled, by the type of the service.
+fn account_services(db: &Connection, account: u32) -> Result<HashMap<Service, bool>> {
+    let mut statement = db
+        .prepare(
+            "SELECT Services.type, Settings.value FROM Settings \
+             JOIN Services ON Services.id = Settings.service \
+             WHERE Settings.account = ?1 AND Settings.key = 'enabled'",
+        )
+        .map_err(sqlite_error)?;
+    let rows = statement
+        .query_map([account], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(sqlite_error)?;
+
+    let mut services = HashMap::new();
+    for row in rows {
+        let (service_type, enabled) = row.map_err(sqlite_error)?;
+        let service = match service_type.as_str() {
+            "mail" | "e-mail" => Service::Email,
+            "calendar" => Service::Calendar,
+            "contacts" => Service::Contacts,
+            "tasks" => Service::Todo,
+            _ => continue,
+        };
+        let enabled = enabled.trim() == "true";
+        // Providers may have several services of a type, any of them enables it
+        *services.entry(service).or_insert(false) |= enabled;
+    }
+    Ok(services)
+}
+
+fn sqlite_error(error: rusqlite::Error) -> Error {
+    Error::StorageError(format!("libaccounts-glib database error: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_the_services_of_each_type() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE Services (id INTEGER PRIMARY KEY, name TEXT, type TEXT);
+             CREATE TABLE Settings (account INTEGER, service INTEGER, key TEXT, value TEXT);
+             INSERT INTO Services VALUES
+                 (1, 'google-gmail', 'mail'),
+                 (2, 'google-calendar', 'calendar'),
+                 (3, 'google-tasks', 'tasks'),
+                 (4, 'google-im', 'IM'),
+                 (5, 'google-contacts', 'contacts'),
+                 (6, 'google-contacts-sync', 'contacts');
+             INSERT INTO Settings VALUES
+                 (1, 0, 'username', '''user@gmail.com'''),
+                 (1, 1, 'enabled', 'true'),
+                 (1, 2, 'enabled', 'false'),
+                 (1, 3, 'enabled', ' true '),
+                 (1, 4, 'enabled', 'true'),
+                 (1, 5, 'enabled', 'false'),
+                 (1, 6, 'enabled', 'true'),
+                 (2, 2, 'enabled', 'true');",
+        )
+        .unwrap();
+
+        assert_eq!(
+            account_services(&db, 1).unwrap(),
+            HashMap::from([
+                (Service::Email, true),
+                (Service::Calendar, false),
+                (Service::Todo, true),
+                (Service::Contacts, true),
+            ])
+        );
+        assert_eq!(
+            account_settings(&db, 1)
+                .unwrap()
+                .get("username")
+                .map(String::as_str),
+            Some("'user@gmail.com'")
+        );
+    }
+}
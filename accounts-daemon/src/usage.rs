@@ -0,0 +1,332 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use accounts::models::{Account, Provider, UsageStats};
+use chrono::{DateTime, Utc};
+use oauth2::{HttpRequest, HttpResponse, reqwest::async_http_client};
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{Result, paths, ratelimit};
+
+/// Token refreshes and revocations.
+pub const AUTHENTICATION: &str = "Authentication";
+/// Profile pictures.
+pub const PROFILE: &str = "Profile";
+pub const CALENDAR: &str = "Calendar";
+pub const CONTACTS: &str = "Contacts";
+pub const TODO: &str = "Todo";
+
+/// What the daemon sent to the providers for an account, by service.
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountUsage {
+    since: DateTime<Utc>,
+    services: BTreeMap<String, Counter>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Counter {
+    requests: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// How long counted requests wait to be saved, so a burst of them is written once.
+const SAVE_DELAY: Duration = Duration::from_secs(30);
+
+/// The usage of every account, saved [`SAVE_DELAY`] after it changed and when the daemon
+/// exits.
+#[derive(Default)]
+struct Usage {
+    accounts: HashMap<Uuid, AccountUsage>,
+    /// Whether changes wait to be saved, a save is scheduled then.
+    unsaved: bool,
+}
+
+static USAGE: LazyLock<Mutex<Usage>> = LazyLock::new(|| {
+    Mutex::new(Usage {
+        accounts: load(&path()),
+        unsaved: false,
+    })
+});
+
+/// Serializes saves, so the one at exit doesn't race a scheduled one.
+static SAVING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn path() -> PathBuf {
+    paths::data_dir().join("usage.json")
+}
+
+/// Reads the saved usage. A corrupt file is moved aside instead of being overwritten, so
+/// the counts can still be recovered from it.
+fn load(path: &Path) -> HashMap<Uuid, AccountUsage> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(err) => {
+            tracing::warn!("Failed to read the usage from {}: {}", path.display(), err);
+            return HashMap::new();
+        }
+    };
+    match serde_json::from_slice(&data) {
+        Ok(usage) => usage,
+        Err(err) => {
+            let corrupt = path.with_extension("json.corrupt");
+            tracing::warn!(
+                "The usage in {} is corrupt, moving it to {}: {}",
+                path.display(),
+                corrupt.display(),
+                err
+            );
+            if let Err(err) = std::fs::rename(path, &corrupt) {
+                tracing::warn!("Failed to move {}: {}", path.display(), err);
+            }
+            HashMap::new()
+        }
+    }
+}
+
+/// Counts a request made for a service of an account, with the bytes of its body and of
+/// the body of its response.
+pub fn record(account_id: Uuid, service: &str, bytes_sent: u64, bytes_received: u64) {
+    let mut usage = USAGE.lock().unwrap();
+    count(
+        &mut usage.accounts,
+        account_id,
+        service,
+        bytes_sent,
+        bytes_received,
+    );
+    changed(&mut usage);
+}
+
+fn count(
+    accounts: &mut HashMap<Uuid, AccountUsage>,
+    account_id: Uuid,
+    service: &str,
+    bytes_sent: u64,
+    bytes_received: u64,
+) {
+    let counter = accounts
+        .entry(account_id)
+        .or_insert_with(|| AccountUsage {
+            since: Utc::now(),
+            services: BTreeMap::new(),
+        })
+        .services
+        .entry(service.to_string())
+        .or_default();
+    counter.requests += 1;
+    counter.bytes_sent += bytes_sent;
+    counter.bytes_received += bytes_received;
+}
+
+/// The usage of an account, one entry per service it was used for.
+pub fn stats(account_id: &Uuid) -> Vec<UsageStats> {
+    USAGE
+        .lock()
+        .unwrap()
+        .accounts
+        .get(account_id)
+        .map(account_stats)
+        .unwrap_or_default()
+}
+
+fn account_stats(account: &AccountUsage) -> Vec<UsageStats> {
+    account
+        .services
+        .iter()
+        .map(|(service, counter)| UsageStats {
+            service: service.clone(),
+            requests: counter.requests,
+            bytes_sent: counter.bytes_sent,
+            bytes_received: counter.bytes_received,
+            since: account.since.to_rfc3339(),
+        })
+        .collect()
+}
+
+/// Forgets the usage of a removed account.
+pub fn remove(account_id: &Uuid) {
+    let mut usage = USAGE.lock().unwrap();
+    if usage.accounts.remove(account_id).is_some() {
+        changed(&mut usage);
+    }
+}
+
+/// Schedules a save after a change, unless one is already scheduled.
+fn changed(usage: &mut Usage) {
+    if std::mem::replace(&mut usage.unsaved, true) {
+        return;
+    }
+    tokio::spawn(async {
+        tokio::time::sleep(SAVE_DELAY).await;
+        save().await;
+    });
+}
+
+/// Writes the changes of the usage which wait to be saved, called by the daemon before it
+/// exits too.
+pub async fn save() {
+    let _saving = SAVING.lock().await;
+    let data = {
+        let mut usage = USAGE.lock().unwrap();
+        if !std::mem::take(&mut usage.unsaved) {
+            return;
+        }
+        serde_json::to_vec(&usage.accounts)
+    };
+    let path = path();
+    let result = match data {
+        Ok(data) => write(&path, &data).await,
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+        tracing::warn!("Failed to save the usage to {}: {}", path.display(), err);
+    }
+}
+
+/// Replaces a file atomically, so a crash while saving leaves the previous one.
+async fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let temporary = path.with_extension("json.tmp");
+    tokio::fs::write(&temporary, data).await?;
+    tokio::fs::rename(&temporary, path).await
+}
+
+/// Sends a request made for a service of an account and counts it, once the rate limit
+/// of the provider allows it.
+///
+/// The response body is read to count it, even when the provider didn't announce its
+/// length, and handed back in a response the caller reads as usual.
+pub async fn send(
+    request: RequestBuilder,
+    account_id: Uuid,
+    provider: Provider,
+    service: &'static str,
+) -> Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    ratelimit::acquire(provider, ratelimit::Priority::Queued).await?;
+    let bytes_sent = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map_or(0, <[u8]>::len);
+
+    let response = client.execute(request).await?;
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    let body = response.bytes().await?;
+    record(account_id, service, bytes_sent as u64, body.len() as u64);
+
+    Ok(builder
+        .body(body)
+        .expect("the parts come from a valid response")
+        .into())
+}
+
+/// Sends requests through [`send`], in place of [`RequestBuilder::send`].
+pub trait MeteredSend {
+    fn send_metered(
+        self,
+        account: &Account,
+        service: &'static str,
+    ) -> impl Future<Output = Result<Response>> + Send;
+}
+
+impl MeteredSend for RequestBuilder {
+    fn send_metered(
+        self,
+        account: &Account,
+        service: &'static str,
+    ) -> impl Future<Output = Result<Response>> + Send {
+        send(self, account.id, account.provider, service)
+    }
+}
+
+/// The HTTP client of oauth2 for requests made for an account, counted as
+/// [`AUTHENTICATION`]. Callers wait for the rate limit with [`ratelimit::acquire`] first,
+/// oauth2 has no room for its errors.
+pub async fn oauth_http_client(
+    account_id: Uuid,
+    request: HttpRequest,
+) -> Result<HttpResponse, oauth2::reqwest::Error<reqwest::Error>> {
+    let bytes_sent = request.body.len() as u64;
+    let response = async_http_client(request).await?;
+    record(
+        account_id,
+        AUTHENTICATION,
+        bytes_sent,
+        response.body.len() as u64,
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn counts_requests_by_service() {
+        let mut accounts = HashMap::new();
+        let account_id = Uuid::new_v4();
+        count(&mut accounts, account_id, CALENDAR, 10, 100);
+        count(&mut accounts, account_id, CALENDAR, 5, 50);
+        count(&mut accounts, account_id, AUTHENTICATION, 1, 2);
+
+        let stats = account_stats(&accounts[&account_id]);
+        assert_eq!(stats.len(), 2);
+        let calendar = stats.iter().find(|s| s.service == CALENDAR).unwrap();
+        assert_eq!(calendar.requests, 2);
+        assert_eq!(calendar.bytes_sent, 15);
+        assert_eq!(calendar.bytes_received, 150);
+        assert!(accounts.get(&Uuid::new_v4()).is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_back_what_it_writes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data").join("usage.json");
+        let mut accounts = HashMap::new();
+        let account_id = Uuid::new_v4();
+        count(&mut accounts, account_id, TODO, 3, 30);
+
+        write(&path, &serde_json::to_vec(&accounts).unwrap())
+            .await
+            .unwrap();
+
+        let loaded = load(&path);
+        assert_eq!(loaded[&account_id].services[TODO].bytes_received, 30);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn moves_a_corrupt_file_aside() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("usage.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        assert!(load(&path).is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read_to_string(path.with_extension("json.corrupt")).unwrap(),
+            "{ not json"
+        );
+        assert!(load(&dir.path().join("missing.json")).is_empty());
+    }
+}
@@ -0,0 +1,448 @@
+//! Drives the daemon through `AccountsClient` on a private session bus.
+//!
+//! Each test starts its own `dbus-daemon`, a daemon keeping accounts and credentials in
+//! memory, and a stand-in token endpoint for the Google provider. The tests fail when
+//! `dbus-daemon` isn't installed, unless `COSMIC_ACCOUNTS_SKIP_DBUS_TESTS` is set to skip
+//! them.
+
+use std::{
+    io::{BufRead, BufReader},
+    os::unix::fs::MetadataExt,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use accounts::{
+    AccountsClient, AccountsError,
+    models::{Account, AccountManifest, Provider, Service},
+    zbus::{self, Connection, fdo::DBusProxy, names::BusName},
+};
+use axum::{Json, Router, routing::post};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use tempfile::TempDir;
+use url::Url;
+use uuid::Uuid;
+
+const CLIENT_ID: &str = "integration-test";
+const EMAIL: &str = "user@example.com";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Harness {
+    client: AccountsClient,
+    bus: Child,
+    daemon: Child,
+    // Removed once the processes using it are gone
+    _dir: TempDir,
+}
+
+impl Harness {
+    async fn start() -> Option<Self> {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join("bus");
+        let mut bus = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .arg(format!("--address=unix:path={}", socket.display()))
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(bus) => bus,
+            Err(err) if std::env::var_os("COSMIC_ACCOUNTS_SKIP_DBUS_TESTS").is_some() => {
+                eprintln!("Skipping, dbus-daemon is unavailable: {err}");
+                return None;
+            }
+            Err(err) => panic!(
+                "dbus-daemon is unavailable, set COSMIC_ACCOUNTS_SKIP_DBUS_TESTS to skip: {err}"
+            ),
+        };
+        let mut address = String::new();
+        BufReader::new(bus.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let token_url = token_endpoint().await;
+        let providers = dir.path().join("providers");
+        std::fs::create_dir_all(&providers).unwrap();
+        std::fs::write(
+            providers.join("google.toml"),
+            format!(
+                r#"[provider]
+client_id = "{CLIENT_ID}"
+auth_url = "https://accounts.example.com/authorize"
+token_url = "{token_url}"
+redirect_uri = "http://localhost/callback"
+scopes = ["openid", "email", "profile"]
+"#
+            ),
+        )
+        .unwrap();
+
+        let daemon = Command::new(env!("CARGO_BIN_EXE_accounts-daemon"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .env("COSMIC_ACCOUNTS_STORAGE", "memory")
+            .env("COSMIC_ACCOUNTS_PROVIDERS_DIR", &providers)
+            .env("HOME", dir.path())
+            .env("XDG_CONFIG_HOME", dir.path().join("config"))
+            .env("XDG_DATA_HOME", dir.path().join("data"))
+            .env("XDG_CACHE_HOME", dir.path().join("cache"))
+            .spawn()
+            .unwrap();
+
+        let connection = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        wait_for_daemon(&connection).await;
+        let client = AccountsClient::with_connection(&connection).await.unwrap();
+
+        Some(Self {
+            client,
+            bus,
+            daemon,
+            _dir: dir,
+        })
+    }
+
+    /// Signs in to the Google provider, answering the flow like the browser would.
+    async fn sign_in(&mut self) -> zbus::fdo::Result<Uuid> {
+        let auth_url = self.client.start_authentication(&Provider::Google).await?;
+        let auth_url = Url::parse(&auth_url).unwrap();
+        let (_, csrf_token) = auth_url
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .unwrap();
+        self.client
+            .complete_authentication(&csrf_token, "authorization-code")
+            .await
+    }
+
+    async fn add_account(&mut self) -> Uuid {
+        self.sign_in().await.unwrap()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = self.bus.kill();
+        let _ = self.bus.wait();
+    }
+}
+
+/// Serves a token endpoint issuing an ID token for [`EMAIL`], returning its URL.
+async fn token_endpoint() -> String {
+    async fn token() -> Json<Value> {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "iss": "https://accounts.example.com",
+                "sub": "1234",
+                "aud": CLIENT_ID,
+                "exp": chrono::Utc::now().timestamp() + 3600,
+                "email": EMAIL,
+                "name": "Test User",
+            })
+            .to_string(),
+        );
+        Json(json!({
+            "access_token": "access-token",
+            "refresh_token": "refresh-token",
+            "token_type": "Bearer",
+            "expires_in": 3600,
+            "id_token": format!("{header}.{claims}.signature"),
+        }))
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, Router::new().route("/token", post(token)))
+            .await
+            .unwrap();
+    });
+    format!("http://{address}/token")
+}
+
+async fn wait_for_daemon(connection: &Connection) {
+    let dbus = DBusProxy::new(connection).await.unwrap();
+    let name = BusName::try_from("dev.edfloreshz.Accounts").unwrap();
+    tokio::time::timeout(TIMEOUT, async {
+        while !dbus.name_has_owner(name.clone()).await.unwrap() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The daemon did not start");
+}
+
+#[tokio::test]
+async fn adds_an_account() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let mut added = harness.client.receive_account_added().await.unwrap();
+
+    let account_id = harness.add_account().await;
+
+    let signal = tokio::time::timeout(TIMEOUT, added.next())
+        .await
+        .unwrap()
+        .unwrap();
+    let account = Account::try_from(signal.args().unwrap().account().clone()).unwrap();
+    assert_eq!(account.id, account_id);
+    assert_eq!(account.email.as_deref(), Some(EMAIL));
+
+    let accounts = harness.client.list_accounts().await.unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].username, EMAIL);
+}
+
+#[tokio::test]
+async fn toggles_an_account() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+    let mut changed = harness.client.receive_account_changed().await.unwrap();
+
+    harness
+        .client
+        .set_account_enabled(&account_id, false)
+        .await
+        .unwrap();
+
+    let signal = tokio::time::timeout(TIMEOUT, changed.next())
+        .await
+        .unwrap()
+        .unwrap();
+    let account = Account::try_from(signal.args().unwrap().account().clone()).unwrap();
+    assert!(!account.enabled);
+
+    let account = harness
+        .client
+        .get_account(&account_id.to_string())
+        .await
+        .unwrap();
+    assert!(!account.enabled);
+}
+
+#[tokio::test]
+async fn removes_an_account() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+    let mut removed = harness.client.receive_account_removed().await.unwrap();
+
+    harness.client.remove_account(&account_id).await.unwrap();
+
+    let signal = tokio::time::timeout(TIMEOUT, removed.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(*signal.args().unwrap().account_id(), account_id.to_string());
+    assert!(harness.client.list_accounts().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn keeps_a_locked_account() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+
+    harness
+        .client
+        .set_account_locked(&account_id, true)
+        .await
+        .unwrap();
+
+    assert!(harness.client.remove_account(&account_id).await.is_err());
+    assert!(
+        harness
+            .client
+            .set_account_enabled(&account_id, false)
+            .await
+            .is_err()
+    );
+    assert_eq!(harness.client.list_accounts().await.unwrap().len(), 1);
+
+    harness
+        .client
+        .set_account_locked(&account_id, false)
+        .await
+        .unwrap();
+    harness.client.remove_account(&account_id).await.unwrap();
+    assert!(harness.client.list_accounts().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn refuses_tokens_while_paused() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+
+    harness.client.set_all_accounts_paused(true).await.unwrap();
+    assert!(harness.client.all_accounts_paused().await.unwrap());
+    assert!(
+        harness
+            .client
+            .ensure_credentials(&account_id)
+            .await
+            .is_err()
+    );
+
+    // Resuming needs polkit, which refuses other users than root, as nobody is there to
+    // type a password
+    match harness.client.set_all_accounts_paused(false).await {
+        Ok(()) => {
+            assert!(running_as_root(), "Resumed without authorization");
+            assert!(harness.client.ensure_credentials(&account_id).await.is_ok());
+        }
+        Err(err) => {
+            assert!(is_not_authorized(&err), "Unexpected error: {err}");
+            assert!(harness.client.all_accounts_paused().await.unwrap());
+        }
+    }
+}
+
+fn running_as_root() -> bool {
+    std::fs::metadata("/proc/self").unwrap().uid() == 0
+}
+
+/// Whether polkit refused the caller, or asked for authentication nobody can give.
+fn is_not_authorized(error: &zbus::fdo::Error) -> bool {
+    match error {
+        zbus::fdo::Error::InteractiveAuthorizationRequired(_) => true,
+        zbus::fdo::Error::ZBus(error) => matches!(
+            AccountsError::from(error.clone()),
+            AccountsError::NotAuthorized(_)
+        ),
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn rejects_a_second_sign_in_to_the_same_account() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    harness.add_account().await;
+
+    assert!(harness.sign_in().await.is_err());
+    assert_eq!(harness.client.list_accounts().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn reports_provider_config_problems() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    let report = harness.client.validate_providers().await.unwrap();
+
+    let errors = |file: &str| {
+        report
+            .iter()
+            .find(|(path, _)| path.ends_with(file))
+            .map(|(_, errors)| errors.clone())
+            .unwrap()
+    };
+    assert!(errors("google.toml").is_empty());
+    // Only the Google config is written
+    assert_eq!(errors("microsoft.toml").len(), 1);
+}
+
+#[tokio::test]
+async fn restores_an_exported_manifest() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+    let manifest = harness.client.export_manifest().await.unwrap();
+    assert_eq!(manifest.accounts.len(), 1);
+    assert_eq!(
+        AccountManifest::from_json(&manifest.to_json().unwrap()).unwrap(),
+        manifest
+    );
+
+    // Accounts which exist are skipped
+    assert!(
+        harness
+            .client
+            .import_manifest(&manifest)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    harness.client.remove_account(&account_id).await.unwrap();
+    let restored = harness.client.import_manifest(&manifest).await.unwrap();
+    assert_eq!(restored, vec![account_id]);
+    let account = harness
+        .client
+        .get_account(&account_id.to_string())
+        .await
+        .unwrap();
+    assert_eq!(account.username, EMAIL);
+    assert!(account.attention_needed);
+}
+
+#[tokio::test]
+async fn restores_no_access_or_settings_from_a_manifest() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+    let mut manifest = harness.client.export_manifest().await.unwrap();
+    harness.client.remove_account(&account_id).await.unwrap();
+
+    let account = &mut manifest.accounts[0];
+    account
+        .app_access
+        .insert("org.example.App".to_string(), true);
+    account.service_settings.insert(
+        Service::Calendar,
+        [(
+            "uri".to_string(),
+            "https://attacker.example.com".to_string(),
+        )]
+        .into(),
+    );
+    account.locked = true;
+    account.template = Some("managed".to_string());
+    harness.client.import_manifest(&manifest).await.unwrap();
+
+    let account = harness
+        .client
+        .get_account(&account_id.to_string())
+        .await
+        .unwrap();
+    assert!(account.app_access.is_empty());
+    assert!(account.service_settings.is_empty());
+    assert!(!account.locked);
+    assert_eq!(account.template, None);
+}
+
+#[tokio::test]
+async fn rejects_manifests_of_other_versions() {
+    let Some(mut harness) = Harness::start().await else {
+        return;
+    };
+    let account_id = harness.add_account().await;
+    let mut manifest = harness.client.export_manifest().await.unwrap();
+    harness.client.remove_account(&account_id).await.unwrap();
+
+    manifest.version += 1;
+    assert!(matches!(
+        harness.client.import_manifest(&manifest).await,
+        Err(zbus::fdo::Error::ZBus(zbus::Error::MethodError(name, _, _)))
+            if name.as_str() == "dev.edfloreshz.Accounts.Error.InvalidArguments"
+    ));
+    assert!(harness.client.list_accounts().await.unwrap().is_empty());
+}
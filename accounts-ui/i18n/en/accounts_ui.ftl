@@ -17,23 +17,125 @@ no-email = No Email
 created-at = Created At
 last-used = Last Used
 no-usage = No usage
+attention-needed = Attention needed
+sign-in-again = Sign in again
+last-error = Last error
+status = Status
+state-active = Active
+state-disabled = Disabled
+state-needs-reauth = Needs sign in
+state-error = Error
+group = Group
+no-group = None
+sync-on-metered = Sync on metered connections
+locked = Locked
+pause-all = Pause all accounts
+token-expires = Access token expires
+token-expired = Expired
+token-expiry-unknown = Unknown
+refresh-now = Refresh now
+configure = Configure
+service-settings = { $service } settings
+reset = Reset to default
+permissions = Permissions
+request-permissions = Request permissions
+revoke-access = Revoke access
+data-usage = Data usage
+usage-since = Counted since
+no-data-usage = Nothing was downloaded for this account yet
+usage-summary = { $requests } requests, { $received } received, { $sent } sent
+usage-authentication = Sign in
+usage-profile = Profile picture
+usage-calendar = Calendar
+usage-contacts = Contacts
+usage-todo = Tasks
+storage-fallback = No Secret Service is running, credentials are stored in an encrypted file instead
+storage-not-persistent = Credentials are only kept in memory and are lost on reboot
 
 # Toaster
 account-exists = The account you are trying to add already exists
+signed-in = Signed in successfully
+authentication-failed = Authentication failed: { $reason }
+access-revoked = Access to the account was revoked, sign in again to use it
+revoke-failed = Failed to revoke access: { $reason }
+credentials-valid = The account credentials are valid
+refresh-failed = Failed to refresh the credentials: { $reason }
+group-failed = Failed to update the groups: { $reason }
+bulk-action-failed = Failed to update the selected accounts: { $reason }
+accounts-imported = { $count ->
+    [0] No accounts were found to import
+    [one] Imported one account, sign in to use it
+   *[other] Imported { $count } accounts, sign in to use them
+}
+import-failed = Failed to import the accounts: { $reason }
+
+# Daemon errors
+error-account-not-found = The account no longer exists
+error-invalid-arguments = Invalid request: { $detail }
+error-invalid-provider = { $provider } is not a supported provider
+error-invalid-service = The { $service } service is not available for this account
+error-authentication-failed = { $detail }
+error-reauthentication-required = Sign in again to keep using this account
+error-token-expired = The sign-in for this account expired
+error-token-refresh-failed = The sign-in for this account could not be renewed
+error-token-revocation-failed = The provider did not revoke access: { $detail }
+error-network = Could not reach the provider: { $detail }
+error-throttled = The provider keeps failing, trying again after { $time }
+error-rate-limited = Too many requests to the provider, try again after { $time }
+error-account-locked = The account is locked, unlock it first
+error-paused = All accounts are paused
+error-access-denied = The application was denied access to this account
+error-authorization-pending = The application is waiting to be allowed access to this account
+error-not-authorized = You are not allowed to do this
+error-storage = The credentials could not be stored: { $detail }
+error-failed = Something went wrong: { $detail }
+error-oauth = The provider rejected the request ({ $code })
+error-oauth-access-denied = Access was not granted
+error-oauth-invalid-grant = The sign-in is no longer valid, sign in again
+error-oauth-invalid-scope = The provider refused the requested permissions
+error-oauth-invalid-client = The provider does not accept this application
+error-oauth-unavailable = The provider is unavailable, try again later
+error-sign-in = The sign in did not complete
 
 # Dialog
 add-account-title = Add an account
 add-account-body = Click on a provider above to get started, or use the menu to add an account
+authenticating-title = Waiting for sign in
+authenticating-body = Finish signing in to your { $provider } account in the browser window that just opened
+authorize-app-title = Allow access to your account?
+authorize-app-body = { $app } wants to use your { $account } account
+allow = Allow
+deny = Deny
 error-title = An error occurred
 ok = Ok
+new-group = New group
+group-name = Group name
+create = Create
 save = Save
 close = Close
+cancel = Cancel
+
+# Group Page
+accounts = Accounts
+enable-all = Enable all
+disable-all = Disable all
+no-accounts-in-group = No accounts in this group yet
+remove-group = Remove group
 
 # Footer
 remove = Remove
+enable = Enable
+disable = Disable
+
+# Nav bar
+search-accounts = Search accounts
+move-up = Move up
+move-down = Move down
 
 # Menu
 add-account = Add Account
+import-accounts = Import Accounts
+select-accounts = Select Accounts
 about = About
 view = View
 file = File
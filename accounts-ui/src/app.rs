@@ -1,18 +1,18 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use crate::fl;
-use accounts::models::{Account, Provider, Service};
-use accounts::{AccountsClient, Local, Uuid, zbus};
+use crate::{describe, fl, icons};
+use accounts::models::{Account, AccountEvent, AccountState, Provider, Service, UsageStats};
+use accounts::{AccountsClient, DateTime, Local, Utc, Uuid, capabilities, scopes, zbus};
 use cosmic::app::context_drawer;
 use cosmic::iced::alignment::{Horizontal, Vertical};
 use cosmic::iced::{Alignment, Length, Subscription, stream};
 use cosmic::prelude::*;
 use cosmic::theme::spacing;
-use cosmic::widget::image::Handle;
 use cosmic::widget::{self, ToastId, menu, nav_bar};
 use cosmic::{cosmic_theme, theme};
 use futures_util::{SinkExt, StreamExt};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use url::Url;
 
 const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 const APP_ICON: &[u8] = include_bytes!("../resources/icons/hicolor/scalable/apps/icon.svg");
@@ -39,6 +39,46 @@ pub struct AppModel {
     // Providers list.
     providers: Vec<Provider>,
     selected_account: Option<Account>,
+    // Display name being edited for the selected account.
+    display_name_draft: Option<String>,
+    // Cached avatar images by account, `None` while loading or when there is none. They are
+    // decoded once, when loaded, instead of on every view.
+    avatars: HashMap<Uuid, Option<widget::icon::Handle>>,
+    // Resolved settings of the service shown in the context drawer, edited in place.
+    service_config: BTreeMap<String, String>,
+    // OAuth scopes granted to the selected account.
+    granted_scopes: Vec<String>,
+    // When the access token of the selected account expires, if known.
+    token_expires_at: Option<DateTime<Utc>>,
+    // Requests made to the provider for the selected account, by service.
+    usage_stats: Vec<UsageStats>,
+    // Account to select once the accounts are loaded, passed on the command line.
+    startup_account: Option<Uuid>,
+    // Native-app redirect the provider sent the browser to, completed once connected.
+    pending_redirect: Option<String>,
+    // Applications asking for accounts which weren't listed yet, asked once they are.
+    pending_requests: Vec<(Uuid, String)>,
+    // Text the accounts in the nav bar are filtered by.
+    search_query: String,
+    // Provider the accounts in the nav bar are filtered by.
+    provider_filter: Option<Provider>,
+    // Whether every account is paused.
+    paused: bool,
+    // Warning about where the daemon stores credentials, e.g. in a file instead of the
+    // Secret Service.
+    storage_warning: Option<String>,
+    // Groups accounts can be filed under.
+    groups: Vec<String>,
+    // Choices of the group dropdown, no group followed by the groups.
+    group_options: Vec<String>,
+    // Group whose page is shown instead of an account.
+    selected_group: Option<String>,
+    // Name being edited for the selected group.
+    group_name_draft: Option<String>,
+    // Accounts picked for a bulk action, `Some` while selecting accounts.
+    selection: Option<HashSet<Uuid>>,
+    // What the nav bar lists, in order, to update its items in place while it stays the same.
+    nav_layout: Vec<NavItem>,
 }
 
 /// Messages emitted by the application and its widgets.
@@ -49,7 +89,6 @@ pub enum Message {
     SubscriptionChannel,
     ToggleContextPage(ContextPage),
     ToggleDialog(DialogPage),
-    #[allow(unused)]
     UpdateDialog(DialogPage),
     CloseDialog,
     LaunchUrl(String),
@@ -57,19 +96,74 @@ pub enum Message {
     CloseToast(ToastId),
     // Accounts
     LoadAccounts,
-    AddAccount(Uuid),
+    AddAccount(Account),
+    UpdateAccount(Account),
     DeleteAccount(Uuid),
     RemoveAccount(Uuid),
     ToggleService(Service, bool),
+    SetSyncOnMetered(bool),
+    SetAccountLocked(bool),
+    ShowServiceSettings(Service),
+    ServiceConfigLoaded(Service, BTreeMap<String, String>),
+    EditServiceSetting(String, String),
+    SaveServiceSetting(String, String),
+    ResetServiceSetting(String),
     EnableAccount(bool),
+    EditDisplayName(String),
+    SaveDisplayName,
     AccountSelected(Account),
     SetAccounts(Vec<Account>),
+    Search(String),
+    MoveAccount(Uuid, MoveDirection),
+    LoadPaused,
+    SetPaused(bool),
+    LoadStorageWarning,
+    SetStorageWarning(Option<String>),
+    PauseAll(bool),
+    // Groups
+    LoadGroups,
+    SetGroups(Vec<String>),
+    GroupSelected(String),
+    CreateGroup(String),
+    EditGroupName(String),
+    RenameGroup,
+    RemoveGroup(String),
+    SetGroupEnabled(String, bool),
+    SetAccountGroup(usize),
+    GroupUpdateFailed(String),
+    // Selection
+    StartSelection,
+    CancelSelection,
+    SelectAccount(Uuid, bool),
+    SetSelectedEnabled(bool),
+    RemoveSelected,
+    BulkActionFailed(String),
+    FilterProvider(Option<Provider>),
+    AvatarLoaded(Uuid, Option<Vec<u8>>),
     AccountExists,
     // Client
     CreateClient,
     SetClient(Option<AccountsClient>),
     // Auth
     StartAuth(Provider),
+    CancelAuth(Provider),
+    AuthCancelled(Provider),
+    AuthCompleted,
+    AuthFailed(Option<Provider>, String),
+    CompleteRedirect(String),
+    RedirectCompleted(Uuid),
+    Reauthenticate(Uuid),
+    RequestScopes(Uuid, Service),
+    GrantedScopesLoaded(Uuid, Vec<String>),
+    TokenExpiryLoaded(Uuid, Option<DateTime<Utc>>),
+    UsageStatsLoaded(Uuid, Vec<UsageStats>),
+    RefreshCredentials(Uuid),
+    CredentialsRefreshed(Uuid),
+    RevokeCredentials(Uuid),
+    ImportAccounts,
+    // Access control
+    AuthorizationRequested(Uuid, String),
+    SetAppAccess(Uuid, String, bool),
 }
 
 impl<'a> AppModel {
@@ -121,11 +215,7 @@ impl<'a> AppModel {
                     .spacing(spacing().space_xxs)
                     .padding(spacing().space_m)
                     .align_y(Alignment::Center)
-                    .push(
-                        widget::image(Self::provider_icon(provider))
-                            .width(24)
-                            .height(24),
-                    )
+                    .push(widget::icon(icons::provider(provider)).size(24))
                     .push(widget::text(provider.to_string()))
                     .apply(widget::button::custom)
                     .on_press(Message::StartAuth(provider.clone()));
@@ -192,11 +282,7 @@ impl<'a> AppModel {
                     .spacing(spacing().space_xxs)
                     .padding(spacing().space_m)
                     .align_y(Alignment::Center)
-                    .push(
-                        widget::image(Self::provider_icon(provider))
-                            .width(24)
-                            .height(24),
-                    )
+                    .push(widget::icon(icons::provider(provider)).size(24))
                     .push(widget::text(provider.to_string()))
                     .apply(widget::button::custom)
                     .on_press(Message::StartAuth(provider.clone()));
@@ -240,7 +326,7 @@ impl<'a> AppModel {
         };
 
         let provider_header = widget::row()
-            .push(widget::image(Self::provider_icon(&account.provider)).width(60))
+            .push(widget::icon(self.avatar(account)).size(60))
             .push(
                 widget::column()
                     .push(widget::text::title1(account.provider.to_string()))
@@ -249,13 +335,57 @@ impl<'a> AppModel {
             .spacing(spacing().space_xs)
             .align_y(Vertical::Center);
 
-        let account_state =
-            widget::settings::section()
-                .title(fl!("account"))
-                .add(widget::settings::flex_item(
-                    fl!("enabled"),
-                    widget::toggler(account.enabled).on_toggle(Message::EnableAccount),
-                ));
+        let mut account_state = widget::settings::section()
+            .title(fl!("account"))
+            .add(widget::settings::flex_item(
+                fl!("enabled"),
+                // A locked account can still be enabled, but not disabled
+                widget::toggler(account.enabled).on_toggle_maybe(
+                    (!account.locked || !account.enabled).then_some(Message::EnableAccount),
+                ),
+            ))
+            .add(widget::settings::flex_item(
+                fl!("status"),
+                widget::text::body(match account.state() {
+                    AccountState::Active => fl!("state-active"),
+                    AccountState::Disabled => fl!("state-disabled"),
+                    AccountState::NeedsReauth => fl!("state-needs-reauth"),
+                    AccountState::Error(_) => fl!("state-error"),
+                }),
+            ))
+            .add(widget::settings::flex_item(
+                fl!("token-expires"),
+                widget::row()
+                    .push(widget::text::body(match self.token_expires_at {
+                        Some(expires_at) if expires_at <= Utc::now() => fl!("token-expired"),
+                        Some(expires_at) => expires_at
+                            .with_timezone(&Local)
+                            .format("%B %d, %Y at %I:%M %p")
+                            .to_string(),
+                        None => fl!("token-expiry-unknown"),
+                    }))
+                    .push(
+                        widget::button::standard(fl!("refresh-now"))
+                            .on_press(Message::RefreshCredentials(account.id)),
+                    )
+                    .spacing(spacing().space_xs)
+                    .align_y(Vertical::Center),
+            ));
+
+        if account.attention_needed {
+            account_state = account_state.add(widget::settings::flex_item(
+                fl!("attention-needed"),
+                widget::button::suggested(fl!("sign-in-again"))
+                    .on_press(Message::Reauthenticate(account.id)),
+            ));
+        }
+
+        if let Some(last_error) = &account.last_error {
+            account_state = account_state.add(widget::settings::flex_item(
+                fl!("last-error"),
+                widget::text::body(last_error),
+            ));
+        }
 
         let account_details = widget::settings::section()
             .title(fl!("details"))
@@ -265,12 +395,41 @@ impl<'a> AppModel {
             ))
             .add(widget::settings::flex_item(
                 fl!("display-name"),
-                widget::text::body(&account.display_name),
+                widget::text_input(
+                    fl!("display-name"),
+                    self.display_name_draft
+                        .as_deref()
+                        .unwrap_or(&account.display_name),
+                )
+                .on_input(Message::EditDisplayName)
+                .on_submit(|_| Message::SaveDisplayName),
             ))
             .add(widget::settings::flex_item(
                 fl!("email"),
                 widget::text::body(account.email.clone().unwrap_or(fl!("no-email"))),
             ))
+            .add(widget::settings::flex_item(
+                fl!("group"),
+                widget::dropdown(
+                    &self.group_options,
+                    Some(
+                        account
+                            .group
+                            .as_ref()
+                            .and_then(|group| self.groups.iter().position(|g| g == group))
+                            .map_or(0, |position| position + 1),
+                    ),
+                    Message::SetAccountGroup,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("sync-on-metered"),
+                widget::toggler(account.sync_on_metered).on_toggle(Message::SetSyncOnMetered),
+            ))
+            .add(widget::settings::item(
+                fl!("locked"),
+                widget::toggler(account.locked).on_toggle(Message::SetAccountLocked),
+            ))
             .add(widget::settings::flex_item(
                 fl!("created-at"),
                 widget::text::body(
@@ -298,10 +457,87 @@ impl<'a> AppModel {
 
         let mut services = widget::settings::section().title(fl!("services"));
         for (service, enabled) in &account.services {
-            services = services.add(widget::settings::item(
-                service.to_string(),
+            let mut controls = widget::row()
+                .spacing(spacing().space_xs)
+                .align_y(Vertical::Center);
+            if *enabled {
+                controls = controls.push(
+                    widget::button::icon(widget::icon::from_name("emblem-system-symbolic"))
+                        .tooltip(fl!("configure"))
+                        .on_press(Message::ShowServiceSettings(service.clone())),
+                );
+            }
+            controls = controls.push(
                 widget::toggler(*enabled)
                     .on_toggle(|enabled| Message::ToggleService(service.clone(), enabled)),
+            );
+            services = services.add(widget::settings::item(service.to_string(), controls));
+        }
+
+        let mut permissions = widget::settings::section().title(fl!("permissions"));
+        for scope in &self.granted_scopes {
+            permissions = permissions.add(widget::settings::item_row(vec![
+                widget::text::body(scope.as_str()).into(),
+            ]));
+        }
+        let service_missing_scopes = account
+            .services
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(service, _)| service)
+            .find(|service| {
+                !scopes::missing(
+                    account.provider,
+                    service,
+                    self.granted_scopes.iter().map(String::as_str),
+                )
+                .is_empty()
+            })
+            .filter(|_| !self.granted_scopes.is_empty());
+        let mut permission_actions = widget::row()
+            .push(widget::horizontal_space())
+            .spacing(spacing().space_xs);
+        if let Some(service) = service_missing_scopes {
+            permission_actions = permission_actions.push(
+                widget::button::suggested(fl!("request-permissions"))
+                    .on_press(Message::RequestScopes(account.id, service.clone())),
+            );
+        }
+        if !self.granted_scopes.is_empty() {
+            permission_actions = permission_actions.push(
+                widget::button::standard(fl!("revoke-access"))
+                    .class(cosmic::style::Button::Destructive)
+                    .on_press(Message::RevokeCredentials(account.id)),
+            );
+        }
+        permissions = permissions.add(permission_actions);
+
+        let mut usage = widget::settings::section().title(fl!("data-usage"));
+        match self.usage_stats.first() {
+            Some(stats) => {
+                let since = DateTime::parse_from_rfc3339(&stats.since)
+                    .map(|since| since.with_timezone(&Local).format("%B %d, %Y").to_string())
+                    .unwrap_or_else(|_| stats.since.clone());
+                usage = usage.add(widget::settings::flex_item(
+                    fl!("usage-since"),
+                    widget::text::body(since),
+                ));
+            }
+            None => {
+                usage = usage.add(widget::settings::item_row(vec![
+                    widget::text::body(fl!("no-data-usage")).into(),
+                ]));
+            }
+        }
+        for stats in &self.usage_stats {
+            usage = usage.add(widget::settings::flex_item(
+                describe::usage_service(&stats.service),
+                widget::text::body(fl!(
+                    "usage-summary",
+                    requests = stats.requests,
+                    received = describe::bytes(stats.bytes_received),
+                    sent = describe::bytes(stats.bytes_sent)
+                )),
             ));
         }
 
@@ -310,18 +546,252 @@ impl<'a> AppModel {
             .push(account_state)
             .push(account_details)
             .push(services)
+            .push(permissions)
+            .push(usage)
+            .spacing(spacing().space_xxs)
+    }
+
+    /// The account avatar, falling back to the provider icon until it is loaded.
+    fn avatar(&self, account: &Account) -> widget::icon::Handle {
+        match self.avatars.get(&account.id) {
+            Some(Some(avatar)) => avatar.clone(),
+            _ => icons::provider(&account.provider),
+        }
+    }
+
+    fn nav_icon(&self, account: &Account) -> widget::icon::Icon {
+        widget::icon::icon(self.avatar(account))
+    }
+
+    /// Whether the account matches the search query and the provider filter.
+    fn matches_filter(&self, account: &Account) -> bool {
+        if self
+            .provider_filter
+            .is_some_and(|provider| provider != account.provider)
+        {
+            return false;
+        }
+        let query = self.search_query.trim().to_lowercase();
+        let provider = account.provider.to_string();
+        query.is_empty()
+            || [
+                Some(account.display_name.as_str()),
+                Some(account.username.as_str()),
+                account.email.as_deref(),
+                Some(provider.as_str()),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// The group an account is listed under in the nav bar, `None` for the ungrouped ones.
+    fn nav_group<'a>(&self, account: &'a Account) -> Option<&'a String> {
+        account
+            .group
+            .as_ref()
+            .filter(|group| self.groups.contains(group))
+    }
+
+    /// Fills the nav bar with the accounts matching the filters, keeping the selection.
+    ///
+    /// Ungrouped accounts come first, followed by each group under a header. While the
+    /// listed groups and accounts stay the same, the items are updated in place instead.
+    fn update_nav(&mut self) {
+        let selected = self.selected_account.as_ref().map(|account| account.id);
+        let entries: Vec<(Account, widget::icon::Icon)> = self
+            .accounts
+            .iter()
+            .filter(|account| self.matches_filter(account))
+            .map(|account| (account.clone(), self.nav_icon(account)))
+            .collect();
+        let filtering = !self.search_query.trim().is_empty() || self.provider_filter.is_some();
+
+        let mut items: Vec<(NavItem, Option<&(Account, widget::icon::Icon)>)> = Vec::new();
+        for group in std::iter::once(None).chain(self.groups.iter().map(Some)) {
+            let members: Vec<&(Account, widget::icon::Icon)> = entries
+                .iter()
+                .filter(|(account, _)| self.nav_group(account) == group)
+                .collect();
+            if let Some(group) = group {
+                if filtering && members.is_empty() {
+                    continue;
+                }
+                items.push((NavItem::Group(group.clone()), None));
+            }
+            for entry in members {
+                items.push((NavItem::Account(entry.0.id), Some(entry)));
+            }
+        }
+
+        let layout: Vec<NavItem> = items.iter().map(|(item, _)| item.clone()).collect();
+        let rebuild = layout != self.nav_layout;
+        if rebuild {
+            self.nav.clear();
+        }
+        let entities: Vec<_> = self.nav.iter().collect();
+        for (index, (item, entry)) in items.into_iter().enumerate() {
+            let entity = match (rebuild, &item, entry) {
+                (true, NavItem::Group(group), _) => self
+                    .nav
+                    .insert()
+                    .text(group.clone())
+                    .icon(widget::icon::from_name("folder-symbolic").icon())
+                    .data(GroupHeader(group.clone()))
+                    .divider_above(true)
+                    .id(),
+                (true, NavItem::Account(_), Some((account, icon))) => self
+                    .nav
+                    .insert()
+                    .text(account.username.clone())
+                    .icon(icon.clone())
+                    .data(account.clone())
+                    .id(),
+                (false, NavItem::Account(_), Some((account, icon))) => {
+                    let entity = entities[index];
+                    self.nav.text_set(entity, account.username.clone());
+                    self.nav.icon_set(entity, icon.clone());
+                    self.nav.data_set(entity, account.clone());
+                    entity
+                }
+                _ => entities[index],
+            };
+            let active = match &item {
+                NavItem::Group(group) => self.selected_group.as_ref() == Some(group),
+                NavItem::Account(id) => selected == Some(*id),
+            };
+            if active {
+                self.nav.activate(entity);
+            }
+        }
+        self.nav_layout = layout;
+    }
+
+    /// The page of a group, to rename it and enable or disable its accounts at once.
+    fn group_view<'a>(&'a self, group: &'a str) -> Element<'a, Message> {
+        let details = widget::settings::section()
+            .title(fl!("group"))
+            .add(widget::settings::flex_item(
+                fl!("group-name"),
+                widget::text_input(
+                    fl!("group-name"),
+                    self.group_name_draft.as_deref().unwrap_or(group),
+                )
+                .on_input(Message::EditGroupName)
+                .on_submit(|_| Message::RenameGroup),
+            ))
+            .add(widget::settings::flex_item(
+                fl!("accounts"),
+                widget::row()
+                    .push(
+                        widget::button::standard(fl!("enable-all"))
+                            .on_press(Message::SetGroupEnabled(group.to_string(), true)),
+                    )
+                    .push(
+                        widget::button::standard(fl!("disable-all"))
+                            .on_press(Message::SetGroupEnabled(group.to_string(), false)),
+                    )
+                    .spacing(spacing().space_xs),
+            ));
+
+        let mut members = widget::settings::section().title(fl!("accounts"));
+        let mut empty = true;
+        for account in self
+            .accounts
+            .iter()
+            .filter(|account| account.group.as_deref() == Some(group))
+        {
+            empty = false;
+            members = members.add(widget::settings::flex_item(
+                account.username.as_str(),
+                widget::toggler(account.enabled),
+            ));
+        }
+        if empty {
+            members = members.add(widget::settings::item_row(vec![
+                widget::text::body(fl!("no-accounts-in-group")).into(),
+            ]));
+        }
+
+        let actions = widget::row().push(widget::horizontal_space()).push(
+            widget::button::standard(fl!("remove-group"))
+                .class(cosmic::style::Button::Destructive)
+                .on_press(Message::RemoveGroup(group.to_string())),
+        );
+
+        widget::column()
+            .push(widget::text::title1(group))
+            .push(details)
+            .push(members)
+            .push(actions)
             .spacing(spacing().space_xxs)
+            .into()
+    }
+
+    /// The accounts with a checkbox each, to apply an action to several of them.
+    fn selection_view(&self, selection: &HashSet<Uuid>) -> Element<'_, Message> {
+        let mut accounts = widget::settings::section().title(fl!("select-accounts"));
+        for account in &self.accounts {
+            let id = account.id;
+            accounts = accounts.add(widget::settings::item_row(vec![
+                widget::checkbox(account.username.as_str(), selection.contains(&id))
+                    .on_toggle(move |selected| Message::SelectAccount(id, selected))
+                    .into(),
+                widget::horizontal_space().into(),
+                widget::text::body(if account.enabled {
+                    fl!("state-active")
+                } else {
+                    fl!("state-disabled")
+                })
+                .into(),
+            ]));
+        }
+        widget::column().push(accounts).into()
     }
 
-    fn provider_icon(provider: &Provider) -> Handle {
-        match provider {
-            Provider::Google => {
-                Handle::from_bytes(include_bytes!("../resources/img/google.png").to_vec())
+    /// Reloads the groups after a change, or reports why it failed.
+    fn group_updated(result: zbus::fdo::Result<()>) -> cosmic::Action<Message> {
+        match result {
+            Ok(_) => cosmic::Action::App(Message::LoadGroups),
+            Err(err) => {
+                tracing::error!("Failed to update groups: {}", err);
+                cosmic::Action::App(Message::GroupUpdateFailed(describe::daemon_error(&err)))
             }
-            Provider::Microsoft => {
-                Handle::from_bytes(include_bytes!("../resources/img/microsoft.png").to_vec())
+        }
+    }
+
+    /// Refreshes what is derived from the accounts after they changed.
+    fn accounts_changed(&mut self) -> Vec<Task<cosmic::Action<Message>>> {
+        self.core.nav_bar_set_toggled(!self.accounts.is_empty());
+        if let Some(selected) = self.selected_account.clone()
+            && let Some(account) = self.accounts.iter().find(|a| a.id == selected.id)
+        {
+            self.selected_account = Some(account.clone());
+        }
+        self.update_nav();
+        self.avatars
+            .retain(|id, _| self.accounts.iter().any(|account| account.id == *id));
+        self.load_avatars()
+    }
+
+    /// Reads the cached avatars of the accounts which aren't loaded yet.
+    fn load_avatars(&mut self) -> Vec<Task<cosmic::Action<Message>>> {
+        let mut tasks = Vec::new();
+        for account in &self.accounts {
+            let Some(path) = account.avatar.clone() else {
+                continue;
+            };
+            if self.avatars.contains_key(&account.id) {
+                continue;
             }
+            self.avatars.insert(account.id, None);
+            let id = account.id;
+            tasks.push(Task::perform(
+                async move { tokio::fs::read(path).await.ok() },
+                move |avatar| cosmic::Action::App(Message::AvatarLoaded(id, avatar)),
+            ));
         }
+        tasks
     }
 }
 
@@ -330,14 +800,16 @@ impl<'a> cosmic::Application for AppModel {
     /// The async executor that will be used to run your application's commands.
     type Executor = cosmic::executor::Default;
 
-    /// Data that your application receives to its init method.
-    type Flags = ();
+    /// What to show on startup.
+    type Flags = Flags;
 
     /// Messages which the application and its widgets will emit.
     type Message = Message;
 
     /// Unique identifier in RDNN (reverse domain name notation) format.
-    const APP_ID: &'static str = "dev.edfloreshz.Accounts";
+    ///
+    /// The running instance owns it on the session bus, so it differs from the daemon's.
+    const APP_ID: &'static str = "dev.edfloreshz.Accounts.App";
 
     fn core(&self) -> &cosmic::Core {
         &self.core
@@ -348,10 +820,7 @@ impl<'a> cosmic::Application for AppModel {
     }
 
     /// Initializes the application with any given flags and startup commands.
-    fn init(
-        core: cosmic::Core,
-        _flags: Self::Flags,
-    ) -> (Self, Task<cosmic::Action<Self::Message>>) {
+    fn init(core: cosmic::Core, flags: Self::Flags) -> (Self, Task<cosmic::Action<Self::Message>>) {
         // Construct the app model with the runtime's core.
         let mut app = AppModel {
             core,
@@ -364,16 +833,50 @@ impl<'a> cosmic::Application for AppModel {
             accounts: Vec::new(),
             providers: Provider::list().to_vec(),
             selected_account: None,
+            display_name_draft: None,
+            avatars: HashMap::new(),
+            service_config: BTreeMap::new(),
+            granted_scopes: Vec::new(),
+            token_expires_at: None,
+            usage_stats: Vec::new(),
+            startup_account: None,
+            pending_redirect: None,
+            pending_requests: Vec::new(),
+            search_query: String::new(),
+            provider_filter: None,
+            paused: false,
+            storage_warning: None,
+            groups: Vec::new(),
+            group_options: vec![fl!("no-group")],
+            selected_group: None,
+            nav_layout: Vec::new(),
+            group_name_draft: None,
+            selection: None,
         };
 
-        let tasks = vec![
+        let mut tasks = vec![
             app.update_title(),
             cosmic::task::message(Message::CreateClient),
         ];
+        tasks.extend(app.activate(flags));
 
         (app, Task::batch(tasks))
     }
 
+    /// Shows what a second launch was started for, e.g. the account the applet opens.
+    fn dbus_activation(
+        &mut self,
+        msg: cosmic::dbus_activation::Message,
+    ) -> cosmic::app::Task<Self::Message> {
+        match msg.msg {
+            cosmic::dbus_activation::Details::ActivateAction { action, .. } => {
+                let args = action.split(' ').map(str::to_string).collect();
+                Task::batch(self.activate(crate::parse_args(args)))
+            }
+            _ => Task::none(),
+        }
+    }
+
     /// Elements to pack at the start of the header bar.
     fn header_start(&self) -> Vec<Element<'_, Self::Message>> {
         let menu_bar = menu::bar(vec![
@@ -381,11 +884,20 @@ impl<'a> cosmic::Application for AppModel {
                 menu::root(fl!("file")).apply(Element::from),
                 menu::items(
                     &self.key_binds,
-                    vec![menu::Item::Button(
-                        fl!("add-account"),
-                        None,
-                        MenuAction::AddAccount,
-                    )],
+                    vec![
+                        menu::Item::Button(fl!("add-account"), None, MenuAction::AddAccount),
+                        menu::Item::Button(
+                            fl!("import-accounts"),
+                            None,
+                            MenuAction::ImportAccounts,
+                        ),
+                        menu::Item::Button(fl!("new-group"), None, MenuAction::NewGroup),
+                        menu::Item::Button(
+                            fl!("select-accounts"),
+                            None,
+                            MenuAction::SelectAccounts,
+                        ),
+                    ],
                 ),
             ),
             menu::Tree::with_children(
@@ -400,6 +912,18 @@ impl<'a> cosmic::Application for AppModel {
         vec![menu_bar.into()]
     }
 
+    /// Elements to pack at the end of the header bar.
+    fn header_end(&self) -> Vec<Element<'_, Self::Message>> {
+        vec![
+            widget::row()
+                .push(widget::text::body(fl!("pause-all")))
+                .push(widget::toggler(self.paused).on_toggle(Message::PauseAll))
+                .spacing(spacing().space_xxs)
+                .align_y(Vertical::Center)
+                .into(),
+        ]
+    }
+
     /// Enables the COSMIC application to create a nav bar with this model.
     fn nav_model(&self) -> Option<&nav_bar::Model> {
         Some(&self.nav)
@@ -411,15 +935,99 @@ impl<'a> cosmic::Application for AppModel {
         Some(dialog.into())
     }
 
+    /// The nav bar, with a search field and provider filters above the accounts.
+    fn nav_bar(&self) -> Option<Element<'_, cosmic::Action<Self::Message>>> {
+        if !self.core().nav_bar_active() {
+            return None;
+        }
+        let nav_model = self.nav_model()?;
+
+        let mut filters = widget::column()
+            .spacing(spacing().space_xxs)
+            .padding([spacing().space_xxs, spacing().space_xs])
+            .push(
+                widget::search_input(fl!("search-accounts"), &self.search_query)
+                    .on_input(Message::Search)
+                    .on_clear(Message::Search(String::new())),
+            );
+
+        let mut providers: Vec<Provider> = Vec::new();
+        for account in &self.accounts {
+            if !providers.contains(&account.provider) {
+                providers.push(account.provider);
+            }
+        }
+        if providers.len() > 1 {
+            let mut chips = widget::row().spacing(spacing().space_xxs);
+            for provider in providers {
+                let chip = if self.provider_filter == Some(provider) {
+                    widget::button::suggested(provider.to_string())
+                        .on_press(Message::FilterProvider(None))
+                } else {
+                    widget::button::standard(provider.to_string())
+                        .on_press(Message::FilterProvider(Some(provider)))
+                };
+                chips = chips.push(chip);
+            }
+            filters = filters.push(chips);
+        }
+
+        let mut nav = widget::nav_bar(nav_model, |id| {
+            cosmic::Action::Cosmic(cosmic::app::Action::NavBar(id))
+        })
+        .on_context(|id| cosmic::Action::Cosmic(cosmic::app::Action::NavBarContext(id)))
+        .context_menu(self.nav_context_menu(self.core().nav_bar_context()))
+        .into_container()
+        .width(Length::Shrink)
+        .height(Length::Shrink);
+        if !self.core().is_condensed() {
+            nav = nav.max_width(280);
+        }
+
+        Some(
+            widget::column()
+                .push(Element::from(filters).map(cosmic::Action::App))
+                .push(nav)
+                .into(),
+        )
+    }
+
+    /// Context menu of the nav bar accounts, to move them up or down the list.
+    fn nav_context_menu(
+        &self,
+        id: nav_bar::Id,
+    ) -> Option<Vec<menu::Tree<cosmic::Action<Self::Message>>>> {
+        let account = self.nav.data::<Account>(id)?;
+        let position = self.accounts.iter().position(|a| a.id == account.id)?;
+
+        let mut items = Vec::new();
+        if position > 0 {
+            items.push(menu::Item::Button(
+                fl!("move-up"),
+                None,
+                NavMenuAction::Move(account.id, MoveDirection::Up),
+            ));
+        }
+        if position + 1 < self.accounts.len() {
+            items.push(menu::Item::Button(
+                fl!("move-down"),
+                None,
+                NavMenuAction::Move(account.id, MoveDirection::Down),
+            ));
+        }
+        Some(menu::items(&HashMap::new(), items))
+    }
+
     /// Called when a nav item is selected.
     fn on_nav_select(&mut self, id: nav_bar::Id) -> Task<cosmic::Action<Self::Message>> {
         // Activate the page in the model.
         self.nav.activate(id);
 
         let mut tasks = vec![self.update_title()];
-        let account = self.nav.active_data::<Account>();
-        if let Some(account) = account {
+        if let Some(account) = self.nav.active_data::<Account>() {
             tasks.push(self.update(Message::AccountSelected(account.clone())));
+        } else if let Some(GroupHeader(group)) = self.nav.active_data::<GroupHeader>() {
+            tasks.push(self.update(Message::GroupSelected(group.clone())));
         }
         Task::batch(tasks)
     }
@@ -440,23 +1048,60 @@ impl<'a> cosmic::Application for AppModel {
             return None;
         }
 
-        Some(match self.context_page {
+        Some(match &self.context_page {
             ContextPage::About => context_drawer::context_drawer(
                 self.about(),
                 Message::ToggleContextPage(ContextPage::About),
             )
             .title(fl!("about")),
+            ContextPage::ServiceSettings(service) => context_drawer::context_drawer(
+                self.service_settings(service),
+                Message::ToggleContextPage(ContextPage::ServiceSettings(service.clone())),
+            )
+            .title(fl!("service-settings", service = service.to_string())),
         })
     }
 
     fn footer(&self) -> Option<Element<'_, Self::Message>> {
+        if let Some(selection) = &self.selection {
+            let mut actions = widget::row()
+                .push(widget::button::standard(fl!("cancel")).on_press(Message::CancelSelection))
+                .push(widget::horizontal_space());
+            if !selection.is_empty() {
+                actions = actions
+                    .push(
+                        widget::button::standard(fl!("enable"))
+                            .on_press(Message::SetSelectedEnabled(true)),
+                    )
+                    .push(
+                        widget::button::standard(fl!("disable"))
+                            .on_press(Message::SetSelectedEnabled(false)),
+                    )
+                    .push(
+                        widget::button::standard(fl!("remove"))
+                            .class(cosmic::style::Button::Destructive)
+                            .on_press(Message::RemoveSelected),
+                    );
+            }
+            return Some(
+                actions
+                    .spacing(spacing().space_xxs)
+                    .apply(widget::container)
+                    .class(cosmic::style::Container::Card)
+                    .padding(spacing().space_xxs)
+                    .into(),
+            );
+        }
+
         self.selected_account.as_ref().map(|account| {
             widget::row()
                 .push(widget::horizontal_space())
                 .push(
                     widget::button::standard(fl!("remove"))
                         .class(cosmic::style::Button::Destructive)
-                        .on_press(Message::DeleteAccount(account.id)),
+                        .on_press_maybe(
+                            (!account.locked).then_some(Message::DeleteAccount(account.id)),
+                        ),
                 )
                 .spacing(spacing().space_xxs)
                 .apply(widget::container)
@@ -471,7 +1116,11 @@ impl<'a> cosmic::Application for AppModel {
     /// Application events will be processed through the view. Any messages emitted by
     /// events received by widgets will be passed to the update method.
     fn view(&self) -> Element<'_, Self::Message> {
-        let content = if self.selected_account.is_some() {
+        let content = if let Some(selection) = &self.selection {
+            self.selection_view(selection)
+        } else if let Some(group) = &self.selected_group {
+            self.group_view(group).into()
+        } else if self.selected_account.is_some() {
             self.account_view().into()
         } else {
             self.welcome_view().into()
@@ -481,6 +1130,7 @@ impl<'a> cosmic::Application for AppModel {
             widget::row::row().push(widget::toaster(&self.toasts, widget::horizontal_space()));
 
         widget::column()
+            .push_maybe(self.storage_warning.as_deref().map(widget::warning))
             .push(widget::scrollable(content))
             .push(toaster)
             .padding(spacing().space_xxs)
@@ -499,9 +1149,12 @@ impl<'a> cosmic::Application for AppModel {
         let Some(client) = self.client.clone() else {
             return Subscription::none();
         };
-        let account_changed_client = client.clone();
-        let account_removed_client = client.clone();
         let account_exists_client = client.clone();
+        let authentication_cancelled_client = client.clone();
+        let authentication_completed_client = client.clone();
+        let authentication_failed_client = client.clone();
+        let authorization_requested_client = client.clone();
+        let paused_client = client.clone();
 
         Subscription::batch(vec![
             // Create a subscription which emits updates through a channel.
@@ -514,17 +1167,65 @@ impl<'a> cosmic::Application for AppModel {
                 }),
             ),
             Subscription::run_with_id(
-                "account_added",
+                "account_events",
                 stream::channel(1, move |mut output| async move {
-                    if let Ok(mut account_added_stream) = client.receive_account_added().await {
-                        while let Some(account_added) = account_added_stream.next().await {
-                            let args = account_added.args().expect("Error parsing arguments");
-                            if let Err(err) = output
-                                .send(Message::AddAccount(
-                                    Uuid::parse_str(args.account_id())
-                                        .expect("Expected account id to be UUID"),
-                                ))
-                                .await
+                    if let Ok(mut events) = client.watch().await {
+                        while let Some(event) = events.next().await {
+                            let message = match event {
+                                AccountEvent::Added(account) => Message::AddAccount(account),
+                                AccountEvent::Changed(account)
+                                | AccountEvent::AttentionNeeded(account) => {
+                                    Message::UpdateAccount(account)
+                                }
+                                AccountEvent::Removed(id) => Message::RemoveAccount(id),
+                                AccountEvent::DaemonRestarted => Message::LoadAccounts,
+                            };
+                            if let Err(err) = output.send(message).await {
+                                tracing::warn!("failed to send message from subscription: {}", err);
+                            }
+                        }
+                    }
+                }),
+            ),
+            Subscription::run_with_id(
+                "all_accounts_paused",
+                stream::channel(1, move |mut output| async move {
+                    let mut changes =
+                        std::pin::pin!(paused_client.receive_all_accounts_paused_changed().await);
+                    while let Some(paused) = changes.next().await {
+                        if let Err(err) = output.send(Message::SetPaused(paused)).await {
+                            tracing::warn!("failed to send message from subscription: {}", err);
+                        }
+                    }
+                }),
+            ),
+            Subscription::run_with_id(
+                "account_exists",
+                stream::channel(1, move |mut output| async move {
+                    if let Ok(mut account_exists_stream) =
+                        account_exists_client.receive_account_exists().await
+                    {
+                        while let Some(_) = account_exists_stream.next().await {
+                            if let Err(err) = output.send(Message::AccountExists).await {
+                                tracing::warn!("failed to send message from subscription: {}", err);
+                            }
+                        }
+                    }
+                }),
+            ),
+            Subscription::run_with_id(
+                "authentication_cancelled",
+                stream::channel(1, move |mut output| async move {
+                    if let Ok(mut authentication_cancelled_stream) = authentication_cancelled_client
+                        .receive_authentication_cancelled()
+                        .await
+                    {
+                        while let Some(cancelled) = authentication_cancelled_stream.next().await {
+                            let Ok(args) = cancelled.args() else {
+                                continue;
+                            };
+                            if let Err(err) =
+                                output.send(Message::AuthCancelled(*args.provider())).await
                             {
                                 tracing::warn!("failed to send message from subscription: {}", err);
                             }
@@ -533,13 +1234,14 @@ impl<'a> cosmic::Application for AppModel {
                 }),
             ),
             Subscription::run_with_id(
-                "account_changed",
+                "authentication_completed",
                 stream::channel(1, move |mut output| async move {
-                    if let Ok(mut account_changed_stream) =
-                        account_changed_client.receive_account_changed().await
+                    if let Ok(mut authentication_completed_stream) = authentication_completed_client
+                        .receive_authentication_completed()
+                        .await
                     {
-                        while let Some(_) = account_changed_stream.next().await {
-                            if let Err(err) = output.send(Message::LoadAccounts).await {
+                        while let Some(_) = authentication_completed_stream.next().await {
+                            if let Err(err) = output.send(Message::AuthCompleted).await {
                                 tracing::warn!("failed to send message from subscription: {}", err);
                             }
                         }
@@ -547,13 +1249,19 @@ impl<'a> cosmic::Application for AppModel {
                 }),
             ),
             Subscription::run_with_id(
-                "account_removed",
+                "authentication_failed",
                 stream::channel(1, move |mut output| async move {
-                    if let Ok(mut account_removed_stream) =
-                        account_removed_client.receive_account_removed().await
+                    if let Ok(mut authentication_failed_stream) = authentication_failed_client
+                        .receive_authentication_failed()
+                        .await
                     {
-                        while let Some(_) = account_removed_stream.next().await {
-                            if let Err(err) = output.send(Message::LoadAccounts).await {
+                        while let Some(failed) = authentication_failed_stream.next().await {
+                            let args = failed.args().expect("Error parsing arguments");
+                            let provider = Provider::from_str(args.provider());
+                            let reason = describe::error_message(args.error(), args.detail());
+                            if let Err(err) =
+                                output.send(Message::AuthFailed(provider, reason)).await
+                            {
                                 tracing::warn!("failed to send message from subscription: {}", err);
                             }
                         }
@@ -561,13 +1269,31 @@ impl<'a> cosmic::Application for AppModel {
                 }),
             ),
             Subscription::run_with_id(
-                "account_exists",
+                "authorization_requested",
                 stream::channel(1, move |mut output| async move {
-                    if let Ok(mut account_exists_stream) =
-                        account_exists_client.receive_account_exists().await
+                    if let Ok(mut authorization_requested_stream) = authorization_requested_client
+                        .receive_authorization_requested()
+                        .await
                     {
-                        while let Some(_) = account_exists_stream.next().await {
-                            if let Err(err) = output.send(Message::AccountExists).await {
+                        // Requests made while the page was closed are asked again
+                        if let Err(err) = authorization_requested_client
+                            .replay_authorization_requests()
+                            .await
+                        {
+                            tracing::warn!("Failed to replay authorization requests: {}", err);
+                        }
+                        while let Some(requested) = authorization_requested_stream.next().await {
+                            let args = requested.args().expect("Error parsing arguments");
+                            let Ok(account_id) = Uuid::parse_str(args.account_id()) else {
+                                continue;
+                            };
+                            if let Err(err) = output
+                                .send(Message::AuthorizationRequested(
+                                    account_id,
+                                    args.app().to_string(),
+                                ))
+                                .await
+                            {
                                 tracing::warn!("failed to send message from subscription: {}", err);
                             }
                         }
@@ -636,6 +1362,57 @@ impl<'a> cosmic::Application for AppModel {
                         },
                     ));
                 }
+                tasks.push(self.update(Message::LoadGroups));
+                tasks.push(self.update(Message::LoadPaused));
+                tasks.push(self.update(Message::LoadStorageWarning));
+            }
+            Message::LoadStorageWarning => {
+                if let Some(client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.credential_store_warning().await },
+                        |warning| match warning {
+                            Ok(warning) => cosmic::Action::App(Message::SetStorageWarning(warning)),
+                            Err(err) => {
+                                tracing::error!("Failed to load the credential store: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::SetStorageWarning(warning) => {
+                self.storage_warning = warning.as_deref().and_then(describe::storage_warning);
+            }
+            Message::LoadPaused => {
+                if let Some(client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.all_accounts_paused().await },
+                        |paused| match paused {
+                            Ok(paused) => cosmic::Action::App(Message::SetPaused(paused)),
+                            Err(err) => {
+                                tracing::error!("Failed to load the pause state: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::SetPaused(paused) => {
+                self.paused = paused;
+            }
+            Message::PauseAll(paused) => {
+                if let Some(mut client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.set_all_accounts_paused(paused).await },
+                        move |result| match result {
+                            Ok(_) => cosmic::Action::App(Message::SetPaused(paused)),
+                            Err(err) => {
+                                tracing::error!("Failed to pause accounts: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
             }
             Message::EnableAccount(enable) => {
                 if let (Some(mut client), Some(account)) =
@@ -647,7 +1424,7 @@ impl<'a> cosmic::Application for AppModel {
                             Ok(())
                         },
                         |result: Result<(), zbus::fdo::Error>| match result {
-                            Ok(_) => cosmic::action::app(Message::LoadAccounts),
+                            Ok(_) => cosmic::action::none(),
                             Err(err) => {
                                 tracing::error!("Failed to toggle account: {}", err);
                                 cosmic::action::none()
@@ -668,7 +1445,7 @@ impl<'a> cosmic::Application for AppModel {
                             Ok(())
                         },
                         |result: Result<(), zbus::fdo::Error>| match result {
-                            Ok(_) => cosmic::action::app(Message::LoadAccounts),
+                            Ok(_) => cosmic::action::none(),
                             Err(err) => {
                                 tracing::error!("Failed to set service: {}", err);
                                 cosmic::action::none()
@@ -677,22 +1454,99 @@ impl<'a> cosmic::Application for AppModel {
                     ));
                 }
             }
-            Message::AddAccount(id) => {
-                let client = self.client.clone();
-                if let Some(client) = client {
+            Message::SetAccountLocked(locked) => {
+                if let (Some(mut client), Some(account)) =
+                    (self.client.clone(), self.selected_account.clone())
+                {
                     tasks.push(Task::perform(
-                        async move { client.get_account(&id.to_string()).await },
-                        |account| match account {
-                            Ok(account) => cosmic::action::app(Message::AccountSelected(account)),
+                        async move { client.set_account_locked(&account.id, locked).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
                             Err(err) => {
-                                tracing::error!("{err}");
+                                tracing::error!("Failed to lock account: {}", err);
                                 cosmic::action::none()
                             }
                         },
                     ));
                 }
-                tasks.push(self.update(Message::CloseDialog));
-                tasks.push(self.update(Message::LoadAccounts));
+            }
+            Message::SetSyncOnMetered(enabled) => {
+                if let (Some(mut client), Some(account)) =
+                    (self.client.clone(), self.selected_account.clone())
+                {
+                    tasks.push(Task::perform(
+                        async move { client.set_sync_on_metered(&account.id, enabled).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to set metered sync: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::ShowServiceSettings(service) => {
+                self.context_page = ContextPage::ServiceSettings(service.clone());
+                self.core.window.show_context = true;
+                self.service_config.clear();
+                tasks.push(self.load_service_config(service));
+            }
+            Message::ServiceConfigLoaded(service, config) => {
+                if self.context_page == ContextPage::ServiceSettings(service) {
+                    self.service_config = config;
+                }
+            }
+            Message::EditServiceSetting(key, value) => {
+                self.service_config.insert(key, value);
+            }
+            Message::SaveServiceSetting(key, value) => {
+                tasks.push(self.set_service_setting(key, Some(value)));
+            }
+            Message::ResetServiceSetting(key) => {
+                tasks.push(self.set_service_setting(key, None));
+            }
+            Message::EditDisplayName(name) => self.display_name_draft = Some(name),
+            Message::SaveDisplayName => {
+                if let (Some(mut client), Some(account), Some(name)) = (
+                    self.client.clone(),
+                    self.selected_account.clone(),
+                    self.display_name_draft.take(),
+                ) {
+                    tasks.push(Task::perform(
+                        async move { client.set_display_name(&account.id, &name).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to rename account: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::AddAccount(account) => {
+                self.accounts.retain(|a| a.id != account.id);
+                self.accounts.push(account.clone());
+                self.selected_account = Some(account);
+                self.dialog_pages.clear();
+                tasks.extend(self.accounts_changed());
+            }
+            Message::UpdateAccount(account) => {
+                // The avatar may have been replaced
+                self.avatars.remove(&account.id);
+                // Signing in again changes the granted scopes and the token expiry
+                if self.selected_account.as_ref().map(|a| a.id) == Some(account.id) {
+                    tasks.push(self.load_granted_scopes(account.id));
+                    tasks.push(self.load_token_expiry(account.id));
+                }
+                if let Some(existing) = self.accounts.iter_mut().find(|a| a.id == account.id) {
+                    *existing = account;
+                } else {
+                    self.accounts.push(account);
+                }
+                self.accounts.sort_by_key(|account| account.order);
+                tasks.extend(self.accounts_changed());
             }
             Message::DeleteAccount(account_id) => {
                 tracing::info!("Removing account: {}", account_id);
@@ -700,7 +1554,6 @@ impl<'a> cosmic::Application for AppModel {
                     tasks.push(Task::perform(
                         async move {
                             client.remove_account(&account_id).await?;
-                            client.account_removed(&account_id).await?;
                             Ok(account_id)
                         },
                         |result: Result<Uuid, zbus::fdo::Error>| match result {
@@ -717,54 +1570,361 @@ impl<'a> cosmic::Application for AppModel {
             }
             Message::RemoveAccount(account_id) => {
                 self.accounts.retain(|account| account.id != account_id);
-                self.selected_account = None;
+                if self.selected_account.as_ref().map(|a| a.id) == Some(account_id) {
+                    self.selected_account = None;
+                }
+                tasks.extend(self.accounts_changed());
             }
             Message::AccountExists => {
                 tasks.push(self.update(Message::ShowToast(fl!("account-exists"))));
             }
-            Message::AccountSelected(account) => self.selected_account = Some(account),
-            Message::SetAccounts(accounts) => {
-                self.core.nav_bar_set_toggled(!accounts.is_empty());
-                self.accounts.clear();
-                self.nav.clear();
-
-                self.accounts = accounts;
-                if let Some(selected) = self.selected_account.clone()
-                    && let Some(account) = self.accounts.iter().find(|a| a.id == selected.id)
+            Message::AccountSelected(account) => {
+                if matches!(self.context_page, ContextPage::ServiceSettings(_))
+                    && self.selected_account.as_ref().map(|a| a.id) != Some(account.id)
                 {
-                    self.selected_account = Some(account.clone());
-                    for account in &self.accounts {
-                        let account = account.clone();
-
-                        if account.id == selected.id {
-                            self.nav
-                                .insert()
-                                .activate()
-                                .text(account.username.clone())
-                                .data(account);
-                        } else {
-                            self.nav
-                                .insert()
-                                .text(account.username.clone())
-                                .data(account);
-                        }
-                    }
-                } else {
-                    for account in &self.accounts {
-                        let account = account.clone();
-
-                        self.nav
-                            .insert()
-                            .text(account.username.clone())
-                            .data(account);
-                    }
+                    self.core.window.show_context = false;
+                }
+                if self.selected_account.as_ref().map(|a| a.id) != Some(account.id) {
+                    self.granted_scopes.clear();
+                    self.token_expires_at = None;
+                    self.usage_stats.clear();
                 }
+                tasks.push(self.load_granted_scopes(account.id));
+                tasks.push(self.load_token_expiry(account.id));
+                tasks.push(self.load_usage_stats(account.id));
+                self.selected_account = Some(account);
+                self.selected_group = None;
+                self.display_name_draft = None;
             }
-            Message::CreateClient => {
-                tasks.push(Task::perform(
-                    async {
-                        match AccountsClient::new().await {
-                            Ok(client) => Some(client),
+            Message::GrantedScopesLoaded(account_id, scopes) => {
+                if self.selected_account.as_ref().map(|a| a.id) == Some(account_id) {
+                    self.granted_scopes = scopes;
+                }
+            }
+            Message::TokenExpiryLoaded(account_id, expires_at) => {
+                if self.selected_account.as_ref().map(|a| a.id) == Some(account_id) {
+                    self.token_expires_at = expires_at;
+                }
+            }
+            Message::UsageStatsLoaded(account_id, stats) => {
+                if self.selected_account.as_ref().map(|a| a.id) == Some(account_id) {
+                    self.usage_stats = stats;
+                }
+            }
+            Message::RefreshCredentials(account_id) => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move { client.ensure_credentials(&account_id).await },
+                    move |result| match result {
+                        Ok(_) => cosmic::Action::App(Message::CredentialsRefreshed(account_id)),
+                        Err(err) => {
+                            tracing::error!("Failed to refresh credentials: {}", err);
+                            cosmic::Action::App(Message::ShowToast(fl!(
+                                "refresh-failed",
+                                reason = describe::daemon_error(&err)
+                            )))
+                        }
+                    },
+                ));
+            }
+            Message::CredentialsRefreshed(account_id) => {
+                tasks.push(self.update(Message::ShowToast(fl!("credentials-valid"))));
+                tasks.push(self.load_token_expiry(account_id));
+            }
+            Message::RevokeCredentials(account_id) => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move { client.revoke_credentials(&account_id).await },
+                    |result| match result {
+                        Ok(_) => cosmic::Action::App(Message::ShowToast(fl!("access-revoked"))),
+                        Err(err) => {
+                            tracing::error!("Failed to revoke access: {}", err);
+                            cosmic::Action::App(Message::ShowToast(fl!(
+                                "revoke-failed",
+                                reason = describe::daemon_error(&err)
+                            )))
+                        }
+                    },
+                ));
+            }
+            Message::ImportAccounts => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move {
+                        // Imported accounts arrive as `AccountAdded` like any other
+                        let available = client.capabilities().await?;
+                        let mut imported = 0;
+                        if available.iter().any(|c| c == capabilities::GOA_IMPORT) {
+                            imported += client.import_goa_accounts().await?.len();
+                        }
+                        if available.iter().any(|c| c == capabilities::UOA_IMPORT) {
+                            imported += client.import_uoa_accounts().await?.len();
+                        }
+                        Ok(imported)
+                    },
+                    |result: Result<usize, zbus::fdo::Error>| match result {
+                        Ok(count) => cosmic::Action::App(Message::ShowToast(fl!(
+                            "accounts-imported",
+                            count = count
+                        ))),
+                        Err(err) => {
+                            tracing::error!("Failed to import accounts: {}", err);
+                            cosmic::Action::App(Message::ShowToast(fl!(
+                                "import-failed",
+                                reason = describe::daemon_error(&err)
+                            )))
+                        }
+                    },
+                ));
+            }
+            Message::SetAccounts(accounts) => {
+                self.accounts = accounts;
+                for (account_id, app) in std::mem::take(&mut self.pending_requests) {
+                    if self.accounts.iter().any(|account| account.id == account_id) {
+                        tasks.push(self.update(Message::AuthorizationRequested(account_id, app)));
+                    }
+                }
+                if let Some(id) = self.startup_account.take()
+                    && let Some(account) = self.accounts.iter().find(|a| a.id == id).cloned()
+                {
+                    tasks.push(self.update(Message::AccountSelected(account)));
+                }
+                tasks.extend(self.accounts_changed());
+            }
+            Message::MoveAccount(account_id, direction) => {
+                let Some(position) = self.accounts.iter().position(|a| a.id == account_id) else {
+                    return Task::none();
+                };
+                // Swapped with the neighbour shown in the nav bar, hidden accounts stay put
+                let section = self.nav_group(&self.accounts[position]);
+                let mut shown = self.accounts.iter().enumerate().filter(|(_, account)| {
+                    self.matches_filter(account) && self.nav_group(account) == section
+                });
+                let target = match direction {
+                    MoveDirection::Up => shown
+                        .take_while(|(index, _)| *index < position)
+                        .last()
+                        .map(|(index, _)| index),
+                    MoveDirection::Down => shown
+                        .find(|(index, _)| *index > position)
+                        .map(|(index, _)| index),
+                };
+                let Some(target) = target else {
+                    return Task::none();
+                };
+                self.accounts.swap(position, target);
+                for (order, account) in self.accounts.iter_mut().enumerate() {
+                    account.order = order as u32;
+                }
+                self.update_nav();
+
+                if let Some(mut client) = self.client.clone() {
+                    let ids: Vec<Uuid> = self.accounts.iter().map(|a| a.id).collect();
+                    tasks.push(Task::perform(
+                        async move { client.set_account_order(&ids).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to reorder accounts: {}", err);
+                                cosmic::Action::App(Message::LoadAccounts)
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::LoadGroups => {
+                if let Some(client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.groups().await },
+                        |groups| match groups {
+                            Ok(groups) => cosmic::Action::App(Message::SetGroups(groups)),
+                            Err(err) => {
+                                tracing::error!("Failed to load groups: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::SetGroups(groups) => {
+                self.group_options = std::iter::once(fl!("no-group"))
+                    .chain(groups.iter().cloned())
+                    .collect();
+                if self
+                    .selected_group
+                    .as_ref()
+                    .is_some_and(|group| !groups.contains(group))
+                {
+                    self.selected_group = None;
+                }
+                self.groups = groups;
+                self.update_nav();
+            }
+            Message::GroupSelected(group) => {
+                self.selected_group = Some(group);
+                self.selected_account = None;
+                self.group_name_draft = None;
+            }
+            Message::CreateGroup(name) => {
+                self.dialog_pages.pop_front();
+                if let Some(mut client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.create_group(&name).await },
+                        Self::group_updated,
+                    ));
+                }
+            }
+            Message::EditGroupName(name) => self.group_name_draft = Some(name),
+            Message::RenameGroup => {
+                if let (Some(mut client), Some(group), Some(new_name)) = (
+                    self.client.clone(),
+                    self.selected_group.clone(),
+                    self.group_name_draft.take(),
+                ) {
+                    // Follow the group to its new name, the groups are reloaded on failure
+                    self.selected_group = Some(new_name.trim().to_string());
+                    tasks.push(Task::perform(
+                        async move { client.rename_group(&group, &new_name).await },
+                        Self::group_updated,
+                    ));
+                }
+            }
+            Message::RemoveGroup(group) => {
+                if let Some(mut client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.remove_group(&group).await },
+                        Self::group_updated,
+                    ));
+                }
+            }
+            Message::SetGroupEnabled(group, enabled) => {
+                if let Some(mut client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.set_group_enabled(&group, enabled).await },
+                        Self::group_updated,
+                    ));
+                }
+            }
+            Message::SetAccountGroup(index) => {
+                let group = index
+                    .checked_sub(1)
+                    .and_then(|index| self.groups.get(index).cloned());
+                if let (Some(mut client), Some(account)) =
+                    (self.client.clone(), self.selected_account.clone())
+                {
+                    tasks.push(Task::perform(
+                        async move {
+                            client
+                                .set_account_group(&account.id, group.as_deref())
+                                .await
+                        },
+                        Self::group_updated,
+                    ));
+                }
+            }
+            Message::GroupUpdateFailed(reason) => {
+                tasks.push(self.update(Message::ShowToast(fl!("group-failed", reason = reason))));
+                tasks.push(self.update(Message::LoadGroups));
+            }
+            Message::StartSelection => self.selection = Some(HashSet::new()),
+            Message::CancelSelection => self.selection = None,
+            Message::SelectAccount(account_id, selected) => {
+                if let Some(selection) = &mut self.selection {
+                    if selected {
+                        selection.insert(account_id);
+                    } else {
+                        selection.remove(&account_id);
+                    }
+                }
+            }
+            Message::SetSelectedEnabled(enabled) => {
+                if let (Some(mut client), Some(selection)) =
+                    (self.client.clone(), self.selection.clone())
+                {
+                    let ids: Vec<Uuid> = selection.into_iter().collect();
+                    tasks.push(Task::perform(
+                        async move { client.set_accounts_enabled(&ids, enabled).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to toggle accounts: {}", err);
+                                cosmic::Action::App(Message::BulkActionFailed(
+                                    describe::daemon_error(&err),
+                                ))
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::RemoveSelected => {
+                if let (Some(mut client), Some(selection)) =
+                    (self.client.clone(), self.selection.take())
+                {
+                    tracing::info!("Removing {} accounts", selection.len());
+                    if self
+                        .selected_account
+                        .as_ref()
+                        .is_some_and(|account| selection.contains(&account.id))
+                    {
+                        self.selected_account = None;
+                    }
+                    tasks.push(Task::perform(
+                        async move {
+                            for account_id in selection {
+                                client.remove_account(&account_id).await?;
+                            }
+                            Ok(())
+                        },
+                        |result: Result<(), zbus::fdo::Error>| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to remove accounts: {}", err);
+                                cosmic::Action::App(Message::BulkActionFailed(
+                                    describe::daemon_error(&err),
+                                ))
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::BulkActionFailed(reason) => {
+                tasks.push(self.update(Message::ShowToast(fl!(
+                    "bulk-action-failed",
+                    reason = reason
+                ))));
+            }
+            Message::Search(query) => {
+                self.search_query = query;
+                self.update_nav();
+            }
+            Message::FilterProvider(provider) => {
+                self.provider_filter = provider;
+                self.update_nav();
+            }
+            Message::AvatarLoaded(id, avatar) => {
+                if let Some(avatar) = avatar {
+                    self.avatars
+                        .insert(id, Some(widget::icon::from_raster_bytes(avatar)));
+                    self.update_nav();
+                }
+            }
+            Message::CreateClient => {
+                tasks.push(Task::perform(
+                    async {
+                        match AccountsClient::new().await {
+                            Ok(client) => Some(client.with_cache()),
                             Err(err) => {
                                 tracing::error!("{err}");
                                 None
@@ -777,6 +1937,9 @@ impl<'a> cosmic::Application for AppModel {
             Message::SetClient(client) => {
                 self.client = client;
                 tasks.push(cosmic::task::message(Message::LoadAccounts));
+                if let Some(uri) = self.pending_redirect.take() {
+                    tasks.push(self.update(Message::CompleteRedirect(uri)));
+                }
             }
             Message::StartAuth(provider) => {
                 tracing::info!(
@@ -789,9 +1952,161 @@ impl<'a> cosmic::Application for AppModel {
                     return Task::none();
                 };
 
+                // The waiting dialog replaces the provider picker
+                self.dialog_pages
+                    .retain(|page| *page != DialogPage::AddAccount);
+
                 tasks.push(Task::perform(
                     async move {
                         let url = client.start_authentication(&provider).await?;
+                        open::that_detached(url)
+                            .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+                        Ok(provider)
+                    },
+                    |result: Result<Provider, zbus::Error>| match result {
+                        Ok(provider) => cosmic::action::app(Message::ToggleDialog(
+                            DialogPage::Authenticating(provider),
+                        )),
+                        Err(err) => {
+                            tracing::error!("Failed to start authentication: {}", err);
+                            cosmic::action::none()
+                        }
+                    },
+                ));
+            }
+            Message::CancelAuth(provider) => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move { client.cancel_authentication(&provider).await },
+                    |result| match result {
+                        Ok(_) => cosmic::action::none(),
+                        Err(err) => {
+                            tracing::error!("Failed to cancel authentication: {}", err);
+                            cosmic::action::none()
+                        }
+                    },
+                ));
+            }
+            Message::AuthCancelled(provider) => {
+                self.dialog_pages
+                    .retain(|page| *page != DialogPage::Authenticating(provider));
+            }
+            Message::AuthCompleted => {
+                self.dialog_pages
+                    .retain(|page| !matches!(page, DialogPage::Authenticating(_)));
+            }
+            Message::AuthFailed(provider, reason) => {
+                self.dialog_pages.retain(|page| match (page, provider) {
+                    (DialogPage::Authenticating(pending), Some(provider)) => *pending != provider,
+                    _ => true,
+                });
+                tasks.push(self.update(Message::ShowToast(fl!(
+                    "authentication-failed",
+                    reason = reason
+                ))));
+            }
+            Message::CompleteRedirect(uri) => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+                let Ok(uri) = Url::parse(&uri) else {
+                    tracing::error!("Invalid redirect URI: {}", uri);
+                    return Task::none();
+                };
+                let query: HashMap<String, String> = uri.query_pairs().into_owned().collect();
+                let Some(csrf_token) = query.get("state").cloned() else {
+                    tracing::error!("Redirect without a state");
+                    return Task::none();
+                };
+
+                // The daemon notifies the failure, which is shown like any other
+                if let Some(error) = query.get("error").cloned() {
+                    tasks.push(Task::perform(
+                        async move { client.fail_authentication(&csrf_token, &error).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to report authentication error: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                } else if let Some(code) = query.get("code").cloned() {
+                    tasks.push(Task::perform(
+                        async move { client.complete_authentication(&csrf_token, &code).await },
+                        |result| match result {
+                            Ok(account_id) => {
+                                cosmic::Action::App(Message::RedirectCompleted(account_id))
+                            }
+                            Err(err) => {
+                                tracing::error!("Failed to complete authentication: {}", err);
+                                cosmic::Action::App(Message::ShowToast(fl!(
+                                    "authentication-failed",
+                                    reason = describe::daemon_error(&err)
+                                )))
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::RedirectCompleted(account_id) => {
+                self.startup_account = Some(account_id);
+                tasks.push(self.update(Message::ShowToast(fl!("signed-in"))));
+                tasks.push(self.update(Message::LoadAccounts));
+            }
+            Message::AuthorizationRequested(account_id, app) => {
+                let Some(account) = self.accounts.iter().find(|a| a.id == account_id) else {
+                    self.pending_requests.push((account_id, app));
+                    return Task::none();
+                };
+                // Replayed requests may already be asked
+                let asked = self.dialog_pages.iter().any(|page| {
+                    matches!(page, DialogPage::AuthorizeApp { account_id: id, app: pending, .. }
+                        if *id == account_id && *pending == app)
+                });
+                if asked {
+                    return Task::none();
+                }
+                self.dialog_pages.push_back(DialogPage::AuthorizeApp {
+                    account_id,
+                    account: account.display_name.clone(),
+                    app,
+                });
+            }
+            Message::SetAppAccess(account_id, app, allowed) => {
+                self.dialog_pages.retain(|page| {
+                    !matches!(page, DialogPage::AuthorizeApp { account_id: id, app: pending, .. }
+                        if *id == account_id && *pending == app)
+                });
+                if let Some(mut client) = self.client.clone() {
+                    tasks.push(Task::perform(
+                        async move { client.set_app_access(&account_id, &app, allowed).await },
+                        |result| match result {
+                            Ok(_) => cosmic::action::none(),
+                            Err(err) => {
+                                tracing::error!("Failed to save application access: {}", err);
+                                cosmic::action::none()
+                            }
+                        },
+                    ));
+                }
+            }
+            Message::Reauthenticate(account_id) => {
+                tracing::info!("Starting re-authentication for account: {}", account_id);
+
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move {
+                        let url = client.reauthenticate_account(&account_id).await?;
                         open::that_detached(url)
                             .map_err(|e| zbus::Error::Failure(e.to_string()))?;
                         Ok(())
@@ -799,7 +2114,31 @@ impl<'a> cosmic::Application for AppModel {
                     |result: Result<(), zbus::Error>| match result {
                         Ok(_) => cosmic::action::none(),
                         Err(err) => {
-                            tracing::error!("Failed to start authentication: {}", err);
+                            tracing::error!("Failed to start re-authentication: {}", err);
+                            cosmic::action::none()
+                        }
+                    },
+                ));
+            }
+            Message::RequestScopes(account_id, service) => {
+                let Some(mut client) = self.client.clone() else {
+                    tracing::error!("No client available");
+                    return Task::none();
+                };
+
+                tasks.push(Task::perform(
+                    async move {
+                        let url = client
+                            .request_additional_scopes(&account_id, &service)
+                            .await?;
+                        open::that_detached(url)
+                            .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+                        Ok(())
+                    },
+                    |result: Result<(), zbus::Error>| match result {
+                        Ok(_) => cosmic::action::none(),
+                        Err(err) => {
+                            tracing::error!("Failed to request additional scopes: {}", err);
                             cosmic::action::none()
                         }
                     },
@@ -811,6 +2150,36 @@ impl<'a> cosmic::Application for AppModel {
 }
 
 impl AppModel {
+    /// Shows what the application was launched for.
+    fn activate(&mut self, flags: Flags) -> Vec<Task<cosmic::Action<Message>>> {
+        let mut tasks = Vec::new();
+        if let Some(id) = flags.account {
+            // Selected once the accounts are listed
+            self.startup_account = Some(id);
+            if self.client.is_some() {
+                tasks.push(cosmic::task::message(Message::LoadAccounts));
+            }
+        }
+        // A redirect reaching the running instance is completed right away
+        if let Some(uri) = flags.redirect {
+            match self.client {
+                Some(_) => tasks.push(cosmic::task::message(Message::CompleteRedirect(uri))),
+                None => self.pending_redirect = Some(uri),
+            }
+        }
+        // Anyone can open the link, so it only picks among fixed messages
+        if let Some((code, detail)) = flags.error {
+            let message = fl!(
+                "authentication-failed",
+                reason = describe::link_error(&code, &detail)
+            );
+            tasks.push(cosmic::task::message(Message::ShowToast(message)));
+        } else if flags.signed_in {
+            tasks.push(cosmic::task::message(Message::ShowToast(fl!("signed-in"))));
+        }
+        tasks
+    }
+
     /// The about page for this app.
     pub fn about(&self) -> Element<'_, Message> {
         let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
@@ -845,6 +2214,155 @@ impl AppModel {
             .into()
     }
 
+    /// The settings of a service of the selected account, overridden settings can be reset.
+    fn service_settings(&self, service: &Service) -> Element<'_, Message> {
+        let overrides = self
+            .selected_account
+            .as_ref()
+            .and_then(|account| account.service_settings.get(service));
+
+        let mut section = widget::settings::section();
+        for (key, value) in &self.service_config {
+            let mut controls = widget::row()
+                .spacing(spacing().space_xs)
+                .align_y(Vertical::Center);
+            controls =
+                match value.parse::<bool>() {
+                    Ok(flag) => controls.push(widget::toggler(flag).on_toggle(|flag| {
+                        Message::SaveServiceSetting(key.clone(), flag.to_string())
+                    })),
+                    Err(_) => controls.push(
+                        widget::text_input(key.as_str(), value.as_str())
+                            .on_input(|value| Message::EditServiceSetting(key.clone(), value))
+                            .on_submit(|value| Message::SaveServiceSetting(key.clone(), value)),
+                    ),
+                };
+            if overrides.is_some_and(|overrides| overrides.contains_key(key)) {
+                controls = controls.push(
+                    widget::button::icon(widget::icon::from_name("edit-undo-symbolic"))
+                        .tooltip(fl!("reset"))
+                        .on_press(Message::ResetServiceSetting(key.clone())),
+                );
+            }
+            section = section.add(widget::settings::flex_item(key.as_str(), controls));
+        }
+
+        widget::column().push(section).into()
+    }
+
+    /// Fetches the OAuth scopes granted to an account.
+    fn load_granted_scopes(&self, account_id: Uuid) -> Task<cosmic::Action<Message>> {
+        let Some(client) = self.client.clone() else {
+            return Task::none();
+        };
+        Task::perform(
+            async move { client.granted_scopes(&account_id).await },
+            move |result| match result {
+                Ok(scopes) => cosmic::Action::App(Message::GrantedScopesLoaded(account_id, scopes)),
+                Err(err) => {
+                    tracing::error!("Failed to load granted scopes: {}", err);
+                    cosmic::action::none()
+                }
+            },
+        )
+    }
+
+    /// Fetches the requests made to the provider for an account.
+    fn load_usage_stats(&self, account_id: Uuid) -> Task<cosmic::Action<Message>> {
+        let Some(client) = self.client.clone() else {
+            return Task::none();
+        };
+        Task::perform(
+            async move { client.usage_stats(&account_id).await },
+            move |result| match result {
+                Ok(stats) => cosmic::Action::App(Message::UsageStatsLoaded(account_id, stats)),
+                Err(err) => {
+                    tracing::error!("Failed to load usage statistics: {}", err);
+                    cosmic::action::none()
+                }
+            },
+        )
+    }
+
+    /// Fetches when the access token of an account expires.
+    fn load_token_expiry(&self, account_id: Uuid) -> Task<cosmic::Action<Message>> {
+        let Some(client) = self.client.clone() else {
+            return Task::none();
+        };
+        Task::perform(
+            async move { client.token_expiry(&account_id).await },
+            move |result| match result {
+                Ok(expires_at) => {
+                    cosmic::Action::App(Message::TokenExpiryLoaded(account_id, expires_at))
+                }
+                Err(err) => {
+                    tracing::error!("Failed to load token expiry: {}", err);
+                    cosmic::action::none()
+                }
+            },
+        )
+    }
+
+    /// Fetches the resolved settings of a service of the selected account.
+    fn load_service_config(&self, service: Service) -> Task<cosmic::Action<Message>> {
+        let (Some(client), Some(account)) = (self.client.clone(), self.selected_account.clone())
+        else {
+            return Task::none();
+        };
+        Task::perform(
+            async move {
+                let config = client.get_service_config(&account.id, &service).await?;
+                Ok((service, config))
+            },
+            |result: Result<_, zbus::fdo::Error>| match result {
+                Ok((service, config)) => cosmic::Action::App(Message::ServiceConfigLoaded(
+                    service,
+                    config.into_iter().collect(),
+                )),
+                Err(err) => {
+                    tracing::error!("Failed to load service settings: {}", err);
+                    cosmic::action::none()
+                }
+            },
+        )
+    }
+
+    /// Overrides a setting of the service shown in the context drawer, `None` restores
+    /// its default.
+    fn set_service_setting(
+        &self,
+        key: String,
+        value: Option<String>,
+    ) -> Task<cosmic::Action<Message>> {
+        let ContextPage::ServiceSettings(service) = self.context_page.clone() else {
+            return Task::none();
+        };
+        let (Some(mut client), Some(account)) =
+            (self.client.clone(), self.selected_account.clone())
+        else {
+            return Task::none();
+        };
+        Task::perform(
+            async move {
+                client
+                    .set_service_setting(&account.id, &service, &key, value.as_deref())
+                    .await?;
+                let config = client.get_service_config(&account.id, &service).await?;
+                Ok((service, config))
+            },
+            |result: Result<_, zbus::fdo::Error>| match result {
+                Ok((service, config)) => cosmic::Action::App(Message::ServiceConfigLoaded(
+                    service,
+                    config.into_iter().collect(),
+                )),
+                Err(err) => {
+                    tracing::error!("Failed to save service setting: {}", err);
+                    cosmic::action::none()
+                }
+            },
+        )
+    }
+
     /// Updates the header and window titles.
     pub fn update_title(&mut self) -> Task<cosmic::Action<Message>> {
         let mut window_title = fl!("app-title");
@@ -862,17 +2380,81 @@ impl AppModel {
     }
 }
 
+/// What the application is launched to show.
+#[derive(Debug, Clone, Default)]
+pub struct Flags {
+    /// The account to select once the accounts are loaded.
+    pub account: Option<Uuid>,
+    /// Whether the browser handed back a successful sign in.
+    pub signed_in: bool,
+    /// Code and detail of the error a sign in failed with.
+    pub error: Option<(String, String)>,
+    /// `com.system76.CosmicAccounts:/oauth2redirect` URI the provider redirected to.
+    pub redirect: Option<String>,
+    /// The arguments, handed to the instance already running.
+    pub action: Option<String>,
+}
+
+impl cosmic::app::CosmicFlags for Flags {
+    type SubCommand = String;
+    type Args = Vec<String>;
+
+    fn action(&self) -> Option<&String> {
+        self.action.as_ref()
+    }
+}
+
 /// The context page to display in the context drawer.
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub enum ContextPage {
     #[default]
     About,
+    ServiceSettings(Service),
+}
+
+/// An item of the nav bar, a group header or an account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum NavItem {
+    Group(String),
+    Account(Uuid),
+}
+
+/// Data of the nav bar items heading the accounts of a group.
+#[derive(Clone, Debug)]
+struct GroupHeader(String);
+
+/// Where to move an account in the list.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+/// Actions of the nav bar context menu.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NavMenuAction {
+    Move(Uuid, MoveDirection),
+}
+
+impl menu::action::MenuAction for NavMenuAction {
+    type Message = cosmic::Action<Message>;
+
+    fn message(&self) -> Self::Message {
+        match self {
+            NavMenuAction::Move(account_id, direction) => {
+                cosmic::Action::App(Message::MoveAccount(*account_id, *direction))
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MenuAction {
     About,
     AddAccount,
+    ImportAccounts,
+    NewGroup,
+    SelectAccounts,
 }
 
 impl menu::action::MenuAction for MenuAction {
@@ -882,6 +2464,9 @@ impl menu::action::MenuAction for MenuAction {
         match self {
             MenuAction::About => Message::ToggleContextPage(ContextPage::About),
             MenuAction::AddAccount => Message::ToggleDialog(DialogPage::AddAccount),
+            MenuAction::ImportAccounts => Message::ImportAccounts,
+            MenuAction::NewGroup => Message::ToggleDialog(DialogPage::NewGroup(String::new())),
+            MenuAction::SelectAccounts => Message::StartSelection,
         }
     }
 }
@@ -889,6 +2474,13 @@ impl menu::action::MenuAction for MenuAction {
 #[derive(Clone, Debug, PartialEq)]
 pub enum DialogPage {
     AddAccount,
+    Authenticating(Provider),
+    AuthorizeApp {
+        account_id: Uuid,
+        account: String,
+        app: String,
+    },
+    NewGroup(String),
 }
 
 impl<'a> DialogPage {
@@ -899,6 +2491,52 @@ impl<'a> DialogPage {
                 .body(fl!("add-account-body"))
                 .primary_action(widget::button::text(fl!("close")).on_press(Message::CloseDialog))
                 .control(AppModel::add_account_dialog()),
+            DialogPage::Authenticating(provider) => widget::dialog()
+                .title(fl!("authenticating-title"))
+                .body(fl!("authenticating-body", provider = provider.to_string()))
+                .primary_action(
+                    widget::button::standard(fl!("cancel"))
+                        .on_press(Message::CancelAuth(*provider)),
+                ),
+            DialogPage::AuthorizeApp {
+                account_id,
+                account,
+                app,
+            } => widget::dialog()
+                .title(fl!("authorize-app-title"))
+                .body(fl!(
+                    "authorize-app-body",
+                    app = app.as_str(),
+                    account = account.as_str()
+                ))
+                .primary_action(
+                    widget::button::suggested(fl!("allow")).on_press(Message::SetAppAccess(
+                        *account_id,
+                        app.clone(),
+                        true,
+                    )),
+                )
+                .secondary_action(
+                    widget::button::standard(fl!("deny")).on_press(Message::SetAppAccess(
+                        *account_id,
+                        app.clone(),
+                        false,
+                    )),
+                ),
+            DialogPage::NewGroup(name) => widget::dialog()
+                .title(fl!("new-group"))
+                .control(
+                    widget::text_input(fl!("group-name"), name.as_str())
+                        .on_input(|name| Message::UpdateDialog(DialogPage::NewGroup(name)))
+                        .on_submit(Message::CreateGroup),
+                )
+                .primary_action(
+                    widget::button::suggested(fl!("create"))
+                        .on_press(Message::CreateGroup(name.clone())),
+                )
+                .secondary_action(
+                    widget::button::standard(fl!("cancel")).on_press(Message::CloseDialog),
+                ),
         }
     }
 }
@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Localized descriptions of what the accounts daemon reports.
+
+use std::sync::LazyLock;
+
+use accounts::{DateTime, ERROR_NAME_PREFIX, Local, zbus};
+use chrono::Locale;
+use i18n_embed::DesktopLanguageRequester;
+
+/// The locale times are formatted in, the first requested language chrono knows.
+static LOCALE: LazyLock<Locale> = LazyLock::new(|| {
+    DesktopLanguageRequester::requested_languages()
+        .iter()
+        .find_map(|language| Locale::try_from(language.to_string().replace('-', "_").as_str()).ok())
+        .unwrap_or(Locale::POSIX)
+});
+
+/// Describes an error returned by a call to the accounts daemon in the user's language.
+pub fn daemon_error(error: &zbus::fdo::Error) -> String {
+    match error {
+        zbus::fdo::Error::ZBus(zbus::Error::MethodError(name, detail, _)) => {
+            match name.as_str().strip_prefix(ERROR_NAME_PREFIX) {
+                Some(code) => error_message(code, detail.as_deref().unwrap_or_default()),
+                None => error.to_string(),
+            }
+        }
+        error => error.to_string(),
+    }
+}
+
+/// Describes an error from its code and detail, as sent by the accounts daemon.
+pub fn error_message(code: &str, detail: &str) -> String {
+    match code {
+        "AccountNotFound" => crate::fl!("error-account-not-found"),
+        "AccountAlreadyExists" => crate::fl!("account-exists"),
+        "InvalidArguments" => crate::fl!("error-invalid-arguments", detail = detail),
+        "InvalidProvider" => crate::fl!("error-invalid-provider", provider = detail),
+        "InvalidService" => crate::fl!("error-invalid-service", service = detail),
+        "AuthenticationFailed" => crate::fl!("error-authentication-failed", detail = detail),
+        "ReauthenticationRequired" => crate::fl!("error-reauthentication-required"),
+        "TokenExpired" => crate::fl!("error-token-expired"),
+        "TokenRefreshFailed" => crate::fl!("error-token-refresh-failed"),
+        "TokenRevocationFailed" => crate::fl!("error-token-revocation-failed", detail = detail),
+        "OAuth" => oauth_error(detail),
+        "Network" => crate::fl!("error-network", detail = detail),
+        "Throttled" => crate::fl!("error-throttled", time = local_time(detail)),
+        "RateLimited" => crate::fl!("error-rate-limited", time = local_time(detail)),
+        "AccountLocked" => crate::fl!("error-account-locked"),
+        "Paused" => crate::fl!("error-paused"),
+        "AccessDenied" => crate::fl!("error-access-denied"),
+        "AuthorizationPending" => crate::fl!("error-authorization-pending"),
+        "NotAuthorized" => crate::fl!("error-not-authorized"),
+        "Storage" => crate::fl!("error-storage", detail = detail),
+        _ => crate::fl!("error-failed", detail = detail),
+    }
+}
+
+/// The local time of day of an RFC 3339 time sent with an error, written the way the
+/// user's locale writes it.
+fn local_time(detail: &str) -> String {
+    DateTime::parse_from_rfc3339(detail)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format_localized("%X", *LOCALE)
+                .to_string()
+        })
+        .unwrap_or_else(|_| detail.to_string())
+}
+
+/// Describes a warning the daemon sends about where it stores credentials.
+pub fn storage_warning(code: &str) -> Option<String> {
+    match code {
+        "fallback" => Some(crate::fl!("storage-fallback")),
+        "not-persistent" => Some(crate::fl!("storage-not-persistent")),
+        _ => None,
+    }
+}
+
+/// Describes the error of a `cosmic-accounts://done` link without repeating any of it,
+/// the detail only picks the message of an OAuth error.
+pub fn link_error(code: &str, detail: &str) -> String {
+    match code {
+        "OAuth" => known_oauth_error(detail).unwrap_or_else(|| crate::fl!("error-sign-in")),
+        "AccountAlreadyExists" => crate::fl!("account-exists"),
+        "AccountNotFound" => crate::fl!("error-account-not-found"),
+        "ReauthenticationRequired" => crate::fl!("error-reauthentication-required"),
+        "AccessDenied" => crate::fl!("error-access-denied"),
+        "NotAuthorized" => crate::fl!("error-not-authorized"),
+        _ => crate::fl!("error-sign-in"),
+    }
+}
+
+/// Describes the OAuth error codes of RFC 6749 and RFC 8628 users can act on.
+fn oauth_error(code: &str) -> String {
+    known_oauth_error(code).unwrap_or_else(|| crate::fl!("error-oauth", code = code))
+}
+
+fn known_oauth_error(code: &str) -> Option<String> {
+    match code {
+        "access_denied" => Some(crate::fl!("error-oauth-access-denied")),
+        "invalid_grant" | "expired_token" => Some(crate::fl!("error-oauth-invalid-grant")),
+        "invalid_scope" => Some(crate::fl!("error-oauth-invalid-scope")),
+        "invalid_client" | "unauthorized_client" => Some(crate::fl!("error-oauth-invalid-client")),
+        "temporarily_unavailable" | "server_error" => Some(crate::fl!("error-oauth-unavailable")),
+        _ => None,
+    }
+}
+
+/// Names a service the daemon counts data usage for in the user's language, unknown ones
+/// as they are sent.
+pub fn usage_service(service: &str) -> String {
+    match service {
+        "Authentication" => crate::fl!("usage-authentication"),
+        "Profile" => crate::fl!("usage-profile"),
+        "Calendar" => crate::fl!("usage-calendar"),
+        "Contacts" => crate::fl!("usage-contacts"),
+        "Todo" => crate::fl!("usage-todo"),
+        service => service.to_string(),
+    }
+}
+
+/// Describes a number of bytes in the largest unit it reaches, e.g. `1.5 MB`.
+pub fn bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_bytes_in_the_largest_unit() {
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(999), "999 B");
+        assert_eq!(bytes(1000), "1.0 kB");
+        assert_eq!(bytes(1_500_000), "1.5 MB");
+        assert_eq!(bytes(2_000_000_000_000_000), "2000.0 TB");
+    }
+}
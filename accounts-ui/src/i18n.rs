@@ -1,13 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 //! Provides localization support for this crate.
+//!
+//! The applet and the daemon compile this file too, each crate embeds its own `i18n/`
+//! directory.
 
 use std::sync::LazyLock;
 
 use i18n_embed::{
-    fluent::{fluent_language_loader, FluentLanguageLoader},
-    unic_langid::LanguageIdentifier,
     DefaultLocalizer, LanguageLoader, Localizer,
+    fluent::{FluentLanguageLoader, fluent_language_loader},
+    unic_langid::LanguageIdentifier,
 };
 use rust_embed::RustEmbed;
 
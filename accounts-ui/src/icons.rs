@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::LazyLock;
+
+use accounts::models::Provider;
+use cosmic::widget::icon;
+
+static GOOGLE: LazyLock<icon::Handle> = LazyLock::new(|| {
+    themed(
+        "dev.edfloreshz.Accounts.google",
+        include_bytes!("../resources/icons/hicolor/scalable/apps/google.svg"),
+    )
+});
+
+static MICROSOFT: LazyLock<icon::Handle> = LazyLock::new(|| {
+    themed(
+        "dev.edfloreshz.Accounts.microsoft",
+        include_bytes!("../resources/icons/hicolor/scalable/apps/microsoft.svg"),
+    )
+});
+
+/// The logo of a provider, resolved once and shared by every view.
+pub fn provider(provider: &Provider) -> icon::Handle {
+    match provider {
+        Provider::Google => GOOGLE.clone(),
+        Provider::Microsoft => MICROSOFT.clone(),
+    }
+}
+
+/// The icon of the theme, falling back to the embedded one when the theme and hicolor
+/// don't have it, e.g. when the app runs without being installed.
+fn themed(name: &'static str, fallback: &'static [u8]) -> icon::Handle {
+    match icon::from_name(name).path() {
+        Some(path) => icon::from_path(path),
+        None => icon::from_svg_bytes(fallback),
+    }
+}
@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use accounts::Uuid;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use url::Url;
 
 mod app;
+mod describe;
 mod i18n;
+mod icons;
 
 fn main() -> cosmic::iced::Result {
     // Get the system's preferred languages.
@@ -29,6 +33,53 @@ fn main() -> cosmic::iced::Result {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Starts the application's event loop with `()` as the application's flags.
-    cosmic::app::run::<app::AppModel>(settings, ())
+    // A second launch hands its arguments to the running instance instead of opening a
+    // window of its own.
+    let flags = parse_args(std::env::args().skip(1).collect());
+    cosmic::app::run_single_instance::<app::AppModel>(settings, flags)
+}
+
+/// Reads what to show from the arguments: the account passed with `--account <id>`, e.g.
+/// by the status applet, or the result of a sign in the browser hands back as a
+/// `cosmic-accounts://done` link.
+pub fn parse_args(args: Vec<String>) -> app::Flags {
+    let mut flags = app::Flags::default();
+    let action = (!args.is_empty()).then(|| args.join(" "));
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--account" {
+            flags.account = args.next().and_then(|id| Uuid::parse_str(&id).ok());
+        } else if let Ok(uri) = Url::parse(&arg) {
+            match uri.scheme() {
+                "cosmic-accounts" => flags = deep_link(&uri),
+                // The scheme of native-app redirects, which the daemon can't listen for
+                "com.system76.cosmicaccounts" => flags.redirect = Some(arg),
+                _ => {}
+            }
+        }
+    }
+    flags.action = action;
+    flags
+}
+
+/// Reads the `cosmic-accounts://done` link the daemon redirects the browser to, either with
+/// `?account=<id>` or with `?error=<code>&detail=<detail>`.
+fn deep_link(uri: &Url) -> app::Flags {
+    let mut flags = app::Flags::default();
+    let mut detail = String::new();
+    for (key, value) in uri.query_pairs() {
+        match key.as_ref() {
+            "account" => {
+                flags.account = Uuid::parse_str(&value).ok();
+                flags.signed_in = true;
+            }
+            "error" => flags.error = Some((value.into_owned(), String::new())),
+            "detail" => detail = value.into_owned(),
+            _ => {}
+        }
+    }
+    if let Some((_, error_detail)) = &mut flags.error {
+        *error_detail = detail;
+    }
+    flags
 }
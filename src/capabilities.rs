@@ -0,0 +1,19 @@
+//! Optional features of the daemon, as listed by `GetCapabilities`, and its API level.
+//!
+//! Clients check them before calling methods older daemons don't have, instead of failing
+//! with `UnknownMethod`.
+
+/// The API level of the daemon, raised whenever methods, properties or signals are added.
+/// Changes which break existing clients need a new interface name instead.
+pub const API_LEVEL: u32 = 3;
+
+/// Signing in with a code entered on another device, `StartDeviceAuthentication`.
+pub const DEVICE_FLOW: &str = "device-flow";
+/// Importing the accounts of GNOME Online Accounts, `ImportGoaAccounts`.
+pub const GOA_IMPORT: &str = "goa-import";
+/// Importing the accounts of Ubuntu Online Accounts, `ImportUoaAccounts`.
+pub const UOA_IMPORT: &str = "uoa-import";
+/// Tokens of several accounts in one call, `GetAccessTokens`.
+pub const BATCH_TOKENS: &str = "batch-tokens";
+/// Pages of accounts with only some of their fields, `ListAccountsPaged`.
+pub const PAGED_LISTING: &str = "paged-listing";
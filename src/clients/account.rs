@@ -1,48 +1,217 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    models::{Account, Provider, Service},
+    AccountsError,
+    models::{
+        AccessLogEntry, Account, AccountEvent, AccountManifest, AccountState, DaemonState,
+        DbusAccount, InvalidAccount, Provider, Service, UsageStats,
+    },
     proxy::{
-        AccountAddedStream, AccountChangedStream, AccountExistsStream, AccountRemovedStream,
-        AccountsProxy,
+        AccountAddedStream, AccountAttentionNeededStream, AccountChangedStream,
+        AccountExistsStream, AccountRemovedStream, AccountsProxy, AuthenticationCancelledStream,
+        AuthenticationCompletedStream, AuthenticationFailedStream, AuthenticationStartedStream,
+        AuthorizationRequestedStream,
     },
 };
+use chrono::{DateTime, Duration, Utc};
+use futures_util::{Stream, StreamExt, future, stream};
 use uuid::Uuid;
-use zbus::{Connection, fdo::Result};
+use zbus::{Connection, fdo::Result, zvariant::OwnedValue};
 
 #[derive(Debug, Clone)]
 pub struct AccountsClient {
     proxy: AccountsProxy<'static>,
+    cache: Option<Arc<Mutex<AccountCache>>>,
+}
+
+/// The accounts last listed, updated from the events of the [`AccountsClient::watch`]
+/// streams.
+#[derive(Debug, Default)]
+struct AccountCache {
+    accounts: Option<Vec<Account>>,
+    /// Events received before the accounts were listed, replayed on top of the list.
+    pending: Vec<AccountEvent>,
+    /// Live watch streams, the cache can only be trusted while one keeps it up to date.
+    watchers: usize,
+}
+
+impl AccountCache {
+    /// Stores the listed accounts and replays the events the list may have missed.
+    fn fill(&mut self, accounts: Vec<Account>) {
+        self.accounts = Some(accounts);
+        for event in std::mem::take(&mut self.pending) {
+            self.apply(&event);
+        }
+    }
+
+    fn apply(&mut self, event: &AccountEvent) {
+        if let AccountEvent::DaemonRestarted = event {
+            self.accounts = None;
+            self.pending.clear();
+            return;
+        }
+        let Some(accounts) = &mut self.accounts else {
+            self.pending.push(event.clone());
+            return;
+        };
+        match event {
+            AccountEvent::Added(account)
+            | AccountEvent::Changed(account)
+            | AccountEvent::AttentionNeeded(account) => {
+                match accounts
+                    .iter_mut()
+                    .find(|existing| existing.id == account.id)
+                {
+                    Some(existing) => existing.clone_from(account),
+                    None => accounts.push(account.clone()),
+                }
+                accounts.sort_by_key(|account| account.order);
+            }
+            AccountEvent::Removed(id) => accounts.retain(|account| account.id != *id),
+            AccountEvent::DaemonRestarted => {}
+        }
+    }
+}
+
+/// Stops trusting the cache once the last watch stream is dropped.
+struct CacheWatcher(Arc<Mutex<AccountCache>>);
+
+impl Drop for CacheWatcher {
+    fn drop(&mut self) {
+        let mut cache = self.0.lock().unwrap();
+        cache.watchers -= 1;
+        if cache.watchers == 0 {
+            cache.accounts = None;
+            cache.pending.clear();
+        }
+    }
 }
 
 impl AccountsClient {
     pub async fn new() -> Result<Self> {
-        let connection = Connection::session().await?;
-        let proxy = AccountsProxy::new(&connection).await?;
-        Ok(Self { proxy })
+        let connection = super::connection().await?;
+        Self::with_connection(&connection).await
+    }
+
+    /// Talks to the daemon over an existing connection, e.g. to a private bus.
+    pub async fn with_connection(connection: &Connection) -> Result<Self> {
+        let proxy = AccountsProxy::new(connection).await?;
+        Ok(Self { proxy, cache: None })
+    }
+
+    /// Keeps the listed accounts in memory while a [`watch`](Self::watch) stream is
+    /// polled, listing them once the stream is subscribed, so [`list_accounts`](Self::list_accounts) doesn't ask the daemon again and
+    /// [`cached_accounts`](Self::cached_accounts) answers instantly.
+    pub fn with_cache(mut self) -> Self {
+        self.cache = Some(Arc::default());
+        self
     }
 }
 
 impl AccountsClient {
     pub async fn list_accounts(&self) -> Result<Vec<Account>> {
+        if let Some(accounts) = self.cached_accounts() {
+            return Ok(accounts);
+        }
+        let accounts = self.proxy.list_accounts().await.map(valid_accounts)?;
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            if cache.watchers > 0 && cache.accounts.is_none() {
+                cache.fill(accounts.clone());
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Lists up to `limit` accounts from `offset` on, with only the given fields of the
+    /// account dicts, e.g. `["id", "display_name"]`, or all of them when none are given.
+    pub async fn list_accounts_paged(
+        &self,
+        offset: u32,
+        limit: u32,
+        fields: &[&str],
+    ) -> Result<Vec<HashMap<String, OwnedValue>>> {
+        self.proxy.list_accounts_paged(offset, limit, fields).await
+    }
+
+    /// The accounts from the cache, `None` when caching is disabled or the cache isn't
+    /// populated yet.
+    pub fn cached_accounts(&self) -> Option<Vec<Account>> {
+        let cache = self.cache.as_ref()?.lock().unwrap();
+        cache.accounts.clone().filter(|_| cache.watchers > 0)
+    }
+
+    pub async fn list_enabled_accounts(&self, service: Service) -> Result<Vec<Account>> {
         self.proxy
-            .list_accounts()
+            .list_accounts_by_service(service)
             .await
-            .map(|accounts| accounts.into_iter().map(Into::into).collect())
+            .map(valid_accounts)
     }
 
-    pub async fn list_enabled_accounts(&self, service: Service) -> Result<Vec<Account>> {
-        self.proxy.list_accounts().await.map(|accounts| {
-            accounts
-                .into_iter()
-                .filter(|a| a.enabled && matches!(a.services.get(&service.to_string()), Some(true)))
-                .map(Into::into)
-                .collect()
-        })
+    pub async fn list_accounts_by_provider(&self, provider: &Provider) -> Result<Vec<Account>> {
+        self.proxy
+            .list_accounts_by_provider(*provider)
+            .await
+            .map(valid_accounts)
+    }
+
+    pub async fn find_account(&self, email_or_username: &str) -> Result<Account> {
+        let account = self.proxy.find_account(email_or_username).await?;
+        account
+            .try_into()
+            .map_err(|e: InvalidAccount| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    pub async fn get_account_by_email(&self, email: &str) -> Result<Account> {
+        let account = self.proxy.get_account_by_email(email).await?;
+        account
+            .try_into()
+            .map_err(|e: InvalidAccount| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    pub async fn get_accounts_by_domain(&self, domain: &str) -> Result<Vec<Account>> {
+        self.proxy
+            .get_accounts_by_domain(domain)
+            .await
+            .map(valid_accounts)
     }
 
     pub async fn start_authentication(&mut self, provider: &Provider) -> Result<String> {
-        self.proxy.start_authentication(&provider.to_string()).await
+        self.proxy.start_authentication(*provider).await
+    }
+
+    /// Returns the user code and the verification URL to enter it at.
+    pub async fn start_device_authentication(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(String, String)> {
+        self.proxy.start_device_authentication(*provider).await
+    }
+
+    /// Cancels the pending authentication flows for a provider.
+    pub async fn cancel_authentication(&mut self, provider: &Provider) -> Result<()> {
+        self.proxy
+            .cancel_authentication(&provider.to_string())
+            .await
+    }
+
+    pub async fn reauthenticate_account(&mut self, id: &Uuid) -> Result<String> {
+        self.proxy.reauthenticate_account(&id.to_string()).await
+    }
+
+    /// Returns the URL of a sign in which also grants the scopes the service needs.
+    pub async fn request_additional_scopes(
+        &mut self,
+        id: &Uuid,
+        service: &Service,
+    ) -> Result<String> {
+        self.proxy
+            .request_additional_scopes(&id.to_string(), service.clone())
+            .await
     }
 
     pub async fn complete_authentication(
@@ -57,18 +226,199 @@ impl AccountsClient {
         Uuid::from_str(&account_id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
     }
 
+    /// Fails the flow started with the CSRF token with the OAuth error code the provider
+    /// redirected back with.
+    pub async fn fail_authentication(&mut self, csrf_token: &str, error: &str) -> Result<()> {
+        self.proxy.fail_authentication(csrf_token, error).await
+    }
+
     pub async fn get_account(&self, id: &str) -> Result<Account> {
-        self.proxy.get_account(id).await.map(Into::into)
+        let account = self.proxy.get_account(id).await?;
+        account
+            .try_into()
+            .map_err(|e: InvalidAccount| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// The problems found in each provider config file, by path, none for the usable ones.
+    pub async fn validate_providers(&self) -> Result<HashMap<String, Vec<String>>> {
+        self.proxy.validate_providers().await
+    }
+
+    /// Whether the account is active, disabled or needs attention.
+    pub async fn account_state(&self, id: &Uuid) -> Result<AccountState> {
+        self.get_account(&id.to_string())
+            .await
+            .map(|account| account.state())
+    }
+
+    /// Imports the Google and Microsoft accounts of GNOME Online Accounts.
+    pub async fn import_goa_accounts(&mut self) -> Result<Vec<Uuid>> {
+        let ids = self.proxy.import_goa_accounts().await?;
+        ids.iter()
+            .map(|id| Uuid::from_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string())))
+            .collect()
+    }
+
+    /// Returns the accounts and groups without their credentials, to keep in backups.
+    pub async fn export_manifest(&self) -> Result<AccountManifest> {
+        let json = self.proxy.export_manifest().await?;
+        AccountManifest::from_json(&json).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Restores the accounts and groups of a manifest, the accounts need signing in again.
+    pub async fn import_manifest(&mut self, manifest: &AccountManifest) -> Result<Vec<Uuid>> {
+        let json = manifest
+            .to_json()
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+        let ids = self.proxy.import_manifest(&json).await?;
+        ids.iter()
+            .map(|id| Uuid::from_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string())))
+            .collect()
+    }
+
+    /// Imports the Google and Microsoft accounts of Ubuntu Online Accounts.
+    pub async fn import_uoa_accounts(&mut self) -> Result<Vec<Uuid>> {
+        let ids = self.proxy.import_uoa_accounts().await?;
+        ids.iter()
+            .map(|id| Uuid::from_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string())))
+            .collect()
     }
 
     pub async fn remove_account(&mut self, id: &Uuid) -> Result<()> {
         self.proxy.remove_account(&id.to_string()).await
     }
 
+    pub async fn set_display_name(&mut self, id: &Uuid, name: &str) -> Result<()> {
+        self.proxy.set_display_name(&id.to_string(), name).await
+    }
+
+    /// Sets the account label, `None` removes it.
+    pub async fn set_account_label(&mut self, id: &Uuid, label: Option<&str>) -> Result<()> {
+        self.proxy
+            .set_account_label(&id.to_string(), label.unwrap_or_default())
+            .await
+    }
+
+    /// Sets the account color as `#rrggbb`, `None` removes it.
+    pub async fn set_account_color(&mut self, id: &Uuid, color: Option<&str>) -> Result<()> {
+        self.proxy
+            .set_account_color(&id.to_string(), color.unwrap_or_default())
+            .await
+    }
+
+    /// Sets whether background work such as prefetching runs for the account while the
+    /// connection is metered.
+    pub async fn set_sync_on_metered(&mut self, id: &Uuid, enabled: bool) -> Result<()> {
+        self.proxy
+            .set_sync_on_metered(&id.to_string(), enabled)
+            .await
+    }
+
+    /// Locks the account so it can't be removed or disabled until it is unlocked.
+    pub async fn set_account_locked(&mut self, id: &Uuid, locked: bool) -> Result<()> {
+        self.proxy.set_account_locked(&id.to_string(), locked).await
+    }
+
     pub async fn set_account_enabled(&mut self, id: &Uuid, enabled: bool) -> Result<()> {
-        let id = id.to_string();
-        self.proxy.set_account_enabled(&id, enabled).await?;
-        self.proxy.emit_account_changed(&id).await
+        self.proxy
+            .set_account_enabled(&id.to_string(), enabled)
+            .await
+    }
+
+    /// Enables or disables several accounts with a single call.
+    pub async fn set_accounts_enabled(&mut self, ids: &[Uuid], enabled: bool) -> Result<()> {
+        let ids: Vec<String> = ids.iter().map(Uuid::to_string).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.proxy.set_accounts_enabled(&ids, enabled).await
+    }
+
+    /// Lists the accounts in the given order, the accounts left out are listed after them.
+    pub async fn set_account_order(&mut self, ids: &[Uuid]) -> Result<()> {
+        let ids: Vec<String> = ids.iter().map(Uuid::to_string).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.proxy.set_account_order(&ids).await
+    }
+
+    /// The version of the daemon.
+    pub async fn version(&self) -> Result<String> {
+        Ok(self.proxy.version().await?)
+    }
+
+    /// The API level of the daemon, 0 for daemons older than API levels.
+    pub async fn api_level(&self) -> Result<u32> {
+        match self.proxy.api_level().await {
+            Err(zbus::Error::FDO(err)) if matches!(*err, zbus::fdo::Error::UnknownProperty(_)) => {
+                Ok(0)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// The optional features of the daemon, see [`crate::capabilities`]. Daemons older than
+    /// capabilities have none.
+    pub async fn capabilities(&self) -> Result<Vec<String>> {
+        match self.proxy.get_capabilities().await {
+            Err(zbus::fdo::Error::UnknownMethod(_)) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Whether the daemon has an optional feature, e.g. [`crate::capabilities::DEVICE_FLOW`].
+    pub async fn has_capability(&self, capability: &str) -> Result<bool> {
+        Ok(self
+            .capabilities()
+            .await?
+            .iter()
+            .any(|available| available == capability))
+    }
+
+    /// The groups accounts can be filed under, in the order they are listed.
+    pub async fn groups(&self) -> Result<Vec<String>> {
+        Ok(self.proxy.groups().await?)
+    }
+
+    /// Whether every account is paused, in which case tokens are refused with `Paused`.
+    pub async fn all_accounts_paused(&self) -> Result<bool> {
+        Ok(self.proxy.all_accounts_paused().await?)
+    }
+
+    /// Yields whether every account is paused each time it changes.
+    pub async fn receive_all_accounts_paused_changed(&self) -> impl Stream<Item = bool> + '_ {
+        self.proxy
+            .receive_all_accounts_paused_changed()
+            .await
+            .filter_map(|change| async move { change.get().await.ok() })
+    }
+
+    /// Pauses or resumes every account at once.
+    pub async fn set_all_accounts_paused(&mut self, paused: bool) -> Result<()> {
+        self.proxy.set_all_accounts_paused(paused).await
+    }
+
+    pub async fn create_group(&mut self, name: &str) -> Result<()> {
+        self.proxy.create_group(name).await
+    }
+
+    /// Renames a group, its accounts move to the new name.
+    pub async fn rename_group(&mut self, name: &str, new_name: &str) -> Result<()> {
+        self.proxy.rename_group(name, new_name).await
+    }
+
+    /// Removes a group, its accounts are left ungrouped.
+    pub async fn remove_group(&mut self, name: &str) -> Result<()> {
+        self.proxy.remove_group(name).await
+    }
+
+    /// Files the account under a group, `None` removes it from its group.
+    pub async fn set_account_group(&mut self, id: &Uuid, group: Option<&str>) -> Result<()> {
+        self.proxy
+            .set_account_group(&id.to_string(), group.unwrap_or_default())
+            .await
+    }
+
+    /// Enables or disables every account of a group.
+    pub async fn set_group_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        self.proxy.set_group_enabled(name, enabled).await
     }
 
     pub async fn set_service_enabled(
@@ -77,29 +427,225 @@ impl AccountsClient {
         service: &Service,
         enabled: bool,
     ) -> Result<()> {
-        let id = id.to_string();
         self.proxy
-            .set_service_enabled(&id, &service.to_string(), enabled)
+            .set_service_enabled(&id.to_string(), service.clone(), enabled)
+            .await
+    }
+
+    /// Returns the setting overriding a service default, `None` if it isn't set.
+    pub async fn get_service_setting(
+        &self,
+        id: &Uuid,
+        service: &Service,
+        key: &str,
+    ) -> Result<Option<String>> {
+        let value = self
+            .proxy
+            .get_service_setting(&id.to_string(), service.clone(), key)
             .await?;
-        self.proxy.emit_account_changed(&id).await
+        Ok((!value.is_empty()).then_some(value))
+    }
+
+    /// Overrides a service default, `None` restores it.
+    pub async fn set_service_setting(
+        &mut self,
+        id: &Uuid,
+        service: &Service,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        self.proxy
+            .set_service_setting(
+                &id.to_string(),
+                service.clone(),
+                key,
+                value.unwrap_or_default(),
+            )
+            .await
     }
 
-    pub async fn ensure_credentials(&mut self, id: &Uuid) -> Result<()> {
+    /// Returns the settings a service is configured with, its defaults with the overrides
+    /// applied.
+    pub async fn get_service_config(
+        &self,
+        id: &Uuid,
+        service: &Service,
+    ) -> Result<HashMap<String, String>> {
+        self.proxy
+            .get_service_config(&id.to_string(), service.clone())
+            .await
+    }
+
+    /// Returns whether the account credentials are valid and the seconds until they expire.
+    pub async fn ensure_credentials(&mut self, id: &Uuid) -> Result<(bool, i64)> {
         self.proxy.ensure_credentials(&id.to_string()).await
     }
 
+    /// Returns an access token and when it expires, `None` if the provider didn't say.
+    ///
+    /// The credentials are refreshed first when needed, and fetched again once if they
+    /// expire before the token is returned. When they are still invalid, fails with
+    /// [`AccountsError::ReauthenticationRequired`] carrying the error the daemon recorded
+    /// on the account, or its id if there is none.
+    pub async fn get_valid_access_token(
+        &mut self,
+        id: &Uuid,
+    ) -> Result<(String, Option<DateTime<Utc>>)> {
+        for _ in 0..2 {
+            let requested_at = Utc::now();
+            let (valid, expires_in) = self.ensure_credentials(id).await?;
+            if !valid {
+                continue;
+            }
+            match self.get_access_token(id).await {
+                Ok(access_token) => {
+                    let expires_at =
+                        (expires_in > 0).then(|| requested_at + Duration::seconds(expires_in));
+                    return Ok((access_token, expires_at));
+                }
+                Err(err) if is_token_expired(&err) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        let account = self.get_account(&id.to_string()).await?;
+        let reason = account.last_error.unwrap_or_else(|| id.to_string());
+        Err(zbus::fdo::Error::ZBus(
+            AccountsError::ReauthenticationRequired(reason).into(),
+        ))
+    }
+
     pub async fn get_access_token(&mut self, id: &Uuid) -> Result<String> {
         let id = id.to_string();
         let access_token = self.proxy.get_access_token(&id).await?;
         Ok(access_token)
     }
 
+    /// Returns the access tokens of several accounts in a single call. Accounts whose token
+    /// can't be had are left out, [`Self::get_access_token`] returns why.
+    pub async fn get_access_tokens(&mut self, ids: &[Uuid]) -> Result<HashMap<Uuid, String>> {
+        let ids: Vec<String> = ids.iter().map(Uuid::to_string).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let tokens = self.proxy.get_access_tokens(&ids).await?;
+        tokens
+            .into_iter()
+            .map(|(id, token)| {
+                Uuid::from_str(&id)
+                    .map(|id| (id, token))
+                    .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns an access token limited to the scopes the service needs.
+    pub async fn get_access_token_for_service(
+        &mut self,
+        id: &Uuid,
+        service: &Service,
+    ) -> Result<String> {
+        self.proxy
+            .get_access_token_for_service(&id.to_string(), service.clone())
+            .await
+    }
+
     pub async fn get_refresh_token(&mut self, id: &Uuid) -> Result<String> {
         let id = id.to_string();
         let refresh_token = self.proxy.get_refresh_token(&id).await?;
         Ok(refresh_token)
     }
 
+    /// Returns the peers which recently requested the tokens of the account, oldest first.
+    pub async fn access_log(&self, id: &Uuid) -> Result<Vec<AccessLogEntry>> {
+        self.proxy.get_access_log(&id.to_string()).await
+    }
+
+    /// Returns the requests made to the provider for the account and the bytes they moved,
+    /// one entry per service.
+    pub async fn usage_stats(&self, id: &Uuid) -> Result<Vec<UsageStats>> {
+        self.proxy.get_usage_stats(&id.to_string()).await
+    }
+
+    /// Returns when the access token of the account expires, `None` if the provider didn't say.
+    pub async fn token_expiry(&self, id: &Uuid) -> Result<Option<DateTime<Utc>>> {
+        let timestamp = self.proxy.get_token_expiry(&id.to_string()).await?;
+        Ok((timestamp != 0)
+            .then(|| DateTime::from_timestamp(timestamp, 0))
+            .flatten())
+    }
+
+    /// Returns the OAuth scopes granted to the account, empty once its tokens are revoked.
+    pub async fn granted_scopes(&self, id: &Uuid) -> Result<Vec<String>> {
+        self.proxy.get_granted_scopes(&id.to_string()).await
+    }
+
+    /// Revokes the tokens of the account at the provider, it needs to sign in again.
+    pub async fn revoke_credentials(&mut self, id: &Uuid) -> Result<()> {
+        self.proxy.revoke_credentials(&id.to_string()).await
+    }
+
+    /// A snapshot of the daemon state for scripts and bug reports, without credentials.
+    pub async fn dump_state(&self) -> Result<DaemonState> {
+        let json = self.proxy.dump_state().await?;
+        DaemonState::from_json(&json).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Allows or denies an application, by Flatpak app ID or executable path, access to the
+    /// account tokens.
+    pub async fn set_app_access(&mut self, id: &Uuid, app: &str, allowed: bool) -> Result<()> {
+        self.proxy
+            .set_app_access(&id.to_string(), app, allowed)
+            .await
+    }
+
+    /// Forgets the decision made for an application, so the user is asked again.
+    pub async fn reset_app_access(&mut self, id: &Uuid, app: &str) -> Result<()> {
+        self.proxy.reset_app_access(&id.to_string(), app).await
+    }
+
+    /// Changes which logs the daemon emits, using `RUST_LOG` syntax.
+    pub async fn set_log_level(&self, level: &str) -> Result<()> {
+        self.proxy.set_log_level(level).await
+    }
+
+    pub async fn touch_account(&mut self, id: &Uuid) -> Result<()> {
+        self.proxy.touch_account(&id.to_string()).await
+    }
+
+    pub async fn accounts_needing_attention(&self) -> Result<Vec<Uuid>> {
+        let ids = self.proxy.accounts_needing_attention().await?;
+        ids.iter()
+            .map(|id| Uuid::from_str(id).map_err(|e| zbus::fdo::Error::Failed(e.to_string())))
+            .collect()
+    }
+
+    /// When the tokens of each account whose provider failed repeatedly are refreshed
+    /// again. Until then, the daemon doesn't refresh them on its own, while token requests
+    /// still reach the provider.
+    pub async fn throttled_until(&self) -> Result<HashMap<Uuid, DateTime<Utc>>> {
+        let throttled = self.proxy.throttled_until().await?;
+        throttled
+            .into_iter()
+            .map(|(id, until)| {
+                let id =
+                    Uuid::from_str(&id).map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+                let until = DateTime::parse_from_rfc3339(&until)
+                    .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+                Ok((id, until.with_timezone(&Utc)))
+            })
+            .collect()
+    }
+
+    /// Backend the daemon stores credentials in, e.g. `secret-service` or `file`.
+    pub async fn credential_store(&self) -> Result<String> {
+        Ok(self.proxy.credential_store().await?)
+    }
+
+    /// Why the user should be told where their credentials are stored, `fallback` when the
+    /// Secret Service was unavailable or `not-persistent` when they are lost on reboot.
+    pub async fn credential_store_warning(&self) -> Result<Option<String>> {
+        let warning = self.proxy.credential_store_warning().await?;
+        Ok(Some(warning).filter(|warning| !warning.is_empty()))
+    }
+
     /// Signals
     pub async fn account_added(&self, account_id: &Uuid) -> Result<()> {
         self.proxy.emit_account_added(&account_id.to_string()).await
@@ -121,6 +667,77 @@ impl AccountsClient {
         self.proxy.emit_account_exists().await
     }
 
+    /// Merges the account signals into a single stream.
+    ///
+    /// The subscriptions follow the daemon's bus name, so they keep working when the
+    /// daemon restarts, which is reported as [`AccountEvent::DaemonRestarted`].
+    pub async fn watch(&self) -> zbus::Result<impl Stream<Item = AccountEvent> + Send + Unpin> {
+        let added = self
+            .proxy
+            .receive_account_added()
+            .await?
+            .filter_map(|signal| {
+                let account = signal_account(signal.args().map(|args| args.account().clone()));
+                future::ready(account.map(AccountEvent::Added))
+            });
+        let changed = self
+            .proxy
+            .receive_account_changed()
+            .await?
+            .filter_map(|signal| {
+                let account = signal_account(signal.args().map(|args| args.account().clone()));
+                future::ready(account.map(AccountEvent::Changed))
+            });
+        let attention_needed = self
+            .proxy
+            .receive_account_attention_needed()
+            .await?
+            .filter_map(|signal| {
+                let account = signal_account(signal.args().map(|args| args.account().clone()));
+                future::ready(account.map(AccountEvent::AttentionNeeded))
+            });
+        let removed = self
+            .proxy
+            .receive_account_removed()
+            .await?
+            .filter_map(|signal| {
+                let id = signal
+                    .args()
+                    .ok()
+                    .and_then(|args| Uuid::parse_str(args.account_id()).ok());
+                future::ready(id.map(AccountEvent::Removed))
+            });
+        let restarted = self
+            .proxy
+            .inner()
+            .receive_owner_changed()
+            .await?
+            .filter_map(|owner| future::ready(owner.map(|_| AccountEvent::DaemonRestarted)));
+
+        let events = stream::select_all([
+            added.boxed(),
+            changed.boxed(),
+            attention_needed.boxed(),
+            removed.boxed(),
+            restarted.boxed(),
+        ]);
+
+        let watcher = self.cache.clone().map(|cache| {
+            cache.lock().unwrap().watchers += 1;
+            CacheWatcher(cache)
+        });
+        // Listed only now that the signals are subscribed, so no change falls between
+        // the list and the first event. A failed list is retried by `list_accounts`.
+        if watcher.is_some() && self.cached_accounts().is_none() {
+            let _ = self.list_accounts().await;
+        }
+        Ok(events.inspect(move |event| {
+            if let Some(CacheWatcher(cache)) = &watcher {
+                cache.lock().unwrap().apply(event);
+            }
+        }))
+    }
+
     pub async fn receive_account_added(&self) -> zbus::Result<AccountAddedStream> {
         self.proxy.receive_account_added().await
     }
@@ -136,4 +753,79 @@ impl AccountsClient {
     pub async fn receive_account_exists(&self) -> zbus::Result<AccountExistsStream> {
         self.proxy.receive_account_exists().await
     }
+
+    pub async fn receive_authentication_started(
+        &self,
+    ) -> zbus::Result<AuthenticationStartedStream> {
+        self.proxy.receive_authentication_started().await
+    }
+
+    pub async fn receive_authentication_completed(
+        &self,
+    ) -> zbus::Result<AuthenticationCompletedStream> {
+        self.proxy.receive_authentication_completed().await
+    }
+
+    pub async fn receive_authentication_failed(&self) -> zbus::Result<AuthenticationFailedStream> {
+        self.proxy.receive_authentication_failed().await
+    }
+
+    pub async fn receive_authentication_cancelled(
+        &self,
+    ) -> zbus::Result<AuthenticationCancelledStream> {
+        self.proxy.receive_authentication_cancelled().await
+    }
+
+    /// Has the daemon emit `AuthorizationRequested` again for the unanswered requests, to
+    /// call once subscribed to them.
+    pub async fn replay_authorization_requests(&self) -> Result<()> {
+        self.proxy.replay_authorization_requests().await
+    }
+
+    pub async fn receive_authorization_requested(
+        &self,
+    ) -> zbus::Result<AuthorizationRequestedStream> {
+        self.proxy.receive_authorization_requested().await
+    }
+
+    pub async fn receive_account_attention_needed(
+        &self,
+    ) -> zbus::Result<AccountAttentionNeededStream> {
+        self.proxy.receive_account_attention_needed().await
+    }
+}
+
+fn is_token_expired(error: &zbus::fdo::Error) -> bool {
+    match error {
+        zbus::fdo::Error::ZBus(error) => {
+            matches!(
+                AccountsError::from(error.clone()),
+                AccountsError::TokenExpired(_)
+            )
+        }
+        _ => false,
+    }
+}
+
+/// Converts the account carried by a signal, reporting malformed ones.
+fn signal_account(account: zbus::Result<DbusAccount>) -> Option<Account> {
+    let account = account
+        .inspect_err(|err| tracing::warn!("Ignoring malformed signal: {}", err))
+        .ok()?;
+    Account::try_from(account)
+        .inspect_err(|err| tracing::warn!("Ignoring malformed account: {}", err))
+        .ok()
+}
+
+/// Converts the accounts sent by the daemon, skipping and reporting malformed entries.
+fn valid_accounts(accounts: Vec<DbusAccount>) -> Vec<Account> {
+    accounts
+        .into_iter()
+        .filter_map(|account| {
+            let id = account.id.clone();
+            Account::try_from(account)
+                .inspect_err(|err| tracing::warn!("Skipping malformed account {}: {}", id, err))
+                .ok()
+        })
+        .collect()
 }
@@ -1,31 +1,60 @@
-use crate::{models::Account, proxy::CalendarProxy};
-use zbus::{Connection, fdo::Result};
+use crate::{
+    clients::service_path,
+    models::{Account, Service},
+    proxy::CalendarProxy,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use zbus::fdo::Result;
 
+/// The CalDAV settings and the events of the default calendar of an account.
 #[derive(Debug, Clone)]
 pub struct CalendarClient {
     proxy: CalendarProxy<'static>,
-    account: Account,
 }
 
 impl CalendarClient {
     pub async fn new(account: &Account) -> Result<Self> {
-        let connection = Connection::session().await?;
-        let proxy = CalendarProxy::new(
-            &connection,
-            format!("/dev/edfloreshz/Accounts/Calendar/{}", account.dbus_id()),
-        )
-        .await?;
-        Ok(Self {
-            proxy,
-            account: account.clone(),
-        })
+        Self::for_account(&account.id).await
+    }
+
+    pub async fn for_account(id: &Uuid) -> Result<Self> {
+        let connection = super::connection().await?;
+        let proxy = CalendarProxy::new(&connection, service_path(&Service::Calendar, id)).await?;
+        Ok(Self { proxy })
     }
 
     pub async fn uri(&self) -> Result<String> {
-        self.proxy.uri().await
+        Ok(self.proxy.uri().await?)
     }
 
     pub async fn accept_ssl_errors(&self) -> Result<bool> {
-        self.proxy.accept_ssl_errors().await
+        Ok(self.proxy.accept_ssl_errors().await?)
+    }
+
+    /// Returns the events overlapping the range, as iCalendar objects.
+    pub async fn list_events(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        self.proxy
+            .list_events(start.timestamp(), end.timestamp())
+            .await
+    }
+
+    /// Creates an event from an iCalendar object and returns its UID.
+    ///
+    /// Microsoft assigns events a UID of its own, the one of the object is ignored.
+    pub async fn create_event(&self, ics: &str) -> Result<String> {
+        self.proxy.create_event(ics).await
+    }
+
+    pub async fn update_event(&self, uid: &str, ics: &str) -> Result<()> {
+        self.proxy.update_event(uid, ics).await
+    }
+
+    pub async fn delete_event(&self, uid: &str) -> Result<()> {
+        self.proxy.delete_event(uid).await
     }
 }
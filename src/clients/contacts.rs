@@ -0,0 +1,45 @@
+use crate::{
+    clients::service_path,
+    models::{Account, Service},
+    proxy::ContactsProxy,
+};
+use uuid::Uuid;
+use zbus::fdo::Result;
+
+/// The CardDAV settings and the contacts of an account.
+#[derive(Debug, Clone)]
+pub struct ContactsClient {
+    proxy: ContactsProxy<'static>,
+}
+
+impl ContactsClient {
+    pub async fn new(account: &Account) -> Result<Self> {
+        Self::for_account(&account.id).await
+    }
+
+    pub async fn for_account(id: &Uuid) -> Result<Self> {
+        let connection = super::connection().await?;
+        let proxy = ContactsProxy::new(&connection, service_path(&Service::Contacts, id)).await?;
+        Ok(Self { proxy })
+    }
+
+    pub async fn uri(&self) -> Result<String> {
+        Ok(self.proxy.uri().await?)
+    }
+
+    pub async fn accept_ssl_errors(&self) -> Result<bool> {
+        Ok(self.proxy.accept_ssl_errors().await?)
+    }
+
+    /// Returns up to `limit` contacts whose name or address matches the query, as vCards.
+    pub async fn search_contacts(&self, query: &str, limit: u32) -> Result<Vec<String>> {
+        self.proxy.search_contacts(query, limit).await
+    }
+
+    /// Returns the contact with the UID, as a vCard.
+    ///
+    /// UIDs are the ones of the vCards returned by `search_contacts`.
+    pub async fn get_contact(&self, uid: &str) -> Result<String> {
+        self.proxy.get_contact(uid).await
+    }
+}
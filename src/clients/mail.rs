@@ -0,0 +1,97 @@
+use crate::{
+    clients::service_path,
+    models::{Account, Service},
+    proxy::MailProxy,
+};
+use uuid::Uuid;
+use zbus::fdo::Result;
+
+/// The IMAP and SMTP settings of an account, following GOA's `Mail` interface.
+#[derive(Debug, Clone)]
+pub struct MailClient {
+    proxy: MailProxy<'static>,
+}
+
+impl MailClient {
+    pub async fn new(account: &Account) -> Result<Self> {
+        Self::for_account(&account.id).await
+    }
+
+    pub async fn for_account(id: &Uuid) -> Result<Self> {
+        let connection = super::connection().await?;
+        let proxy = MailProxy::new(&connection, service_path(&Service::Email, id)).await?;
+        Ok(Self { proxy })
+    }
+
+    pub async fn email_address(&self) -> Result<String> {
+        Ok(self.proxy.email_address().await?)
+    }
+
+    pub async fn name(&self) -> Result<String> {
+        Ok(self.proxy.name().await?)
+    }
+
+    pub async fn imap_host(&self) -> Result<String> {
+        Ok(self.proxy.imap_host().await?)
+    }
+
+    pub async fn imap_user_name(&self) -> Result<String> {
+        Ok(self.proxy.imap_user_name().await?)
+    }
+
+    pub async fn imap_supported(&self) -> Result<bool> {
+        Ok(self.proxy.imap_supported().await?)
+    }
+
+    pub async fn imap_use_ssl(&self) -> Result<bool> {
+        Ok(self.proxy.imap_use_ssl().await?)
+    }
+
+    pub async fn imap_use_tls(&self) -> Result<bool> {
+        Ok(self.proxy.imap_use_tls().await?)
+    }
+
+    pub async fn imap_accept_ssl_errors(&self) -> Result<bool> {
+        Ok(self.proxy.imap_accept_ssl_errors().await?)
+    }
+
+    pub async fn smtp_host(&self) -> Result<String> {
+        Ok(self.proxy.smtp_host().await?)
+    }
+
+    pub async fn smtp_user_name(&self) -> Result<String> {
+        Ok(self.proxy.smtp_user_name().await?)
+    }
+
+    pub async fn smtp_supported(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_supported().await?)
+    }
+
+    pub async fn smtp_use_auth(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_use_auth().await?)
+    }
+
+    pub async fn smtp_use_ssl(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_use_ssl().await?)
+    }
+
+    pub async fn smtp_use_tls(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_use_tls().await?)
+    }
+
+    pub async fn smtp_accept_ssl_errors(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_accept_ssl_errors().await?)
+    }
+
+    pub async fn smtp_auth_login(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_auth_login().await?)
+    }
+
+    pub async fn smtp_auth_plain(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_auth_plain().await?)
+    }
+
+    pub async fn smtp_auth_xoauth2(&self) -> Result<bool> {
+        Ok(self.proxy.smtp_auth_xoauth2().await?)
+    }
+}
@@ -2,6 +2,46 @@
 
 mod account;
 mod calendar;
+mod contacts;
+mod mail;
+mod todo;
 
 pub use account::AccountsClient;
 pub use calendar::CalendarClient;
+pub use contacts::ContactsClient;
+pub use mail::MailClient;
+pub use todo::TodoClient;
+
+use uuid::Uuid;
+use zbus::Connection;
+
+use crate::models::Service;
+
+/// Whether the daemon serves the system bus, set with `COSMIC_ACCOUNTS_BUS=system`.
+pub fn system_bus() -> bool {
+    std::env::var("COSMIC_ACCOUNTS_BUS").is_ok_and(|bus| bus == "system")
+}
+
+/// Connects to the bus the daemon serves, see [`system_bus`].
+pub async fn connection() -> zbus::Result<Connection> {
+    if system_bus() {
+        Connection::system().await
+    } else {
+        Connection::session().await
+    }
+}
+
+/// Object path the daemon serves a service of an account at, e.g.
+/// `/dev/edfloreshz/Accounts/Calendar/<id>` with the dashes of the ID replaced.
+pub fn service_path(service: &Service, id: &Uuid) -> String {
+    let name = match service {
+        Service::Email => "Mail",
+        Service::Calendar => "Calendar",
+        Service::Contacts => "Contacts",
+        Service::Todo => "Todo",
+    };
+    format!(
+        "/dev/edfloreshz/Accounts/{name}/{}",
+        id.to_string().replace('-', "_")
+    )
+}
@@ -0,0 +1,59 @@
+use crate::{
+    clients::service_path,
+    models::{Account, Service, Task, TaskList},
+    proxy::TodoProxy,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use zbus::fdo::Result;
+
+/// The task lists of an account.
+#[derive(Debug, Clone)]
+pub struct TodoClient {
+    proxy: TodoProxy<'static>,
+}
+
+impl TodoClient {
+    pub async fn new(account: &Account) -> Result<Self> {
+        Self::for_account(&account.id).await
+    }
+
+    pub async fn for_account(id: &Uuid) -> Result<Self> {
+        let connection = super::connection().await?;
+        let proxy = TodoProxy::new(&connection, service_path(&Service::Todo, id)).await?;
+        Ok(Self { proxy })
+    }
+
+    pub async fn uri(&self) -> Result<String> {
+        Ok(self.proxy.uri().await?)
+    }
+
+    pub async fn list_task_lists(&self) -> Result<Vec<TaskList>> {
+        self.proxy.list_task_lists().await
+    }
+
+    /// Returns the tasks of the list, completed ones included.
+    pub async fn list_tasks(&self, list_id: &str) -> Result<Vec<Task>> {
+        self.proxy.list_tasks(list_id).await
+    }
+
+    pub async fn complete_task(&self, list_id: &str, task_id: &str) -> Result<()> {
+        self.proxy.complete_task(list_id, task_id).await
+    }
+
+    /// Adds a task to the list and returns it.
+    ///
+    /// `due` is RFC 3339, Google only keeps its date.
+    pub async fn create_task(
+        &self,
+        list_id: &str,
+        title: &str,
+        notes: Option<&str>,
+        due: Option<DateTime<Utc>>,
+    ) -> Result<Task> {
+        let due = due.map(|due| due.to_rfc3339()).unwrap_or_default();
+        self.proxy
+            .create_task(list_id, title, notes.unwrap_or_default(), &due)
+            .await
+    }
+}
@@ -10,58 +10,198 @@ pub const CONFIG_VERSION: u64 = 1;
 #[derive(Clone, Default, Debug, PartialEq, Deserialize, Serialize, CosmicConfigEntry)]
 pub struct AccountsConfig {
     pub accounts: Vec<Account>,
+    /// Groups accounts can be filed under, in the order they are listed.
+    pub groups: Vec<String>,
+    /// Whether every account is paused, tokens are refused and background work is skipped.
+    pub paused: bool,
 }
 
 impl AccountsConfig {
-    pub fn config_handler() -> Option<Config> {
-        Config::new("dev.edfloreshz.AccountsDaemon", CONFIG_VERSION).ok()
+    pub fn get_account(&self, id: &Uuid) -> Option<Account> {
+        self.accounts.iter().find(|a| a.id == *id).cloned()
+    }
+
+    /// Accounts are matched by their remote ID, falling back to the username for
+    /// accounts added before remote IDs were stored.
+    pub fn account_exists(
+        &self,
+        remote_id: Option<&str>,
+        username: &String,
+        provider: &Provider,
+    ) -> bool {
+        self.accounts.iter().any(|a| {
+            a.provider == *provider
+                && match (a.remote_id.as_deref(), remote_id) {
+                    (Some(existing), Some(remote_id)) => existing == remote_id,
+                    _ => a.username == *username,
+                }
+        })
     }
+}
+
+/// The accounts config together with where its changes are saved, cosmic-config or only
+/// memory. Reads go to the [`AccountsConfig`] it dereferences to.
+pub struct AccountsStore {
+    config: AccountsConfig,
+    /// `None` keeps the changes in memory for the lifetime of the store.
+    handler: Option<Config>,
+}
+
+impl std::ops::Deref for AccountsStore {
+    type Target = AccountsConfig;
+
+    fn deref(&self) -> &AccountsConfig {
+        &self.config
+    }
+}
 
-    pub fn config() -> AccountsConfig {
-        match Self::config_handler() {
-            Some(config_handler) => {
-                AccountsConfig::get_entry(&config_handler).unwrap_or_else(|(errs, config)| {
-                    tracing::info!("errors loading config: {:?}", errs);
-                    config
-                })
+impl AccountsStore {
+    /// Loads the accounts from cosmic-config, or starts with none kept in memory when
+    /// `COSMIC_ACCOUNTS_STORAGE=memory` or cosmic-config is unavailable.
+    pub fn load() -> AccountsStore {
+        if std::env::var("COSMIC_ACCOUNTS_STORAGE").is_ok_and(|storage| storage == "memory") {
+            return Self::in_memory();
+        }
+        match Config::new("dev.edfloreshz.AccountsDaemon", CONFIG_VERSION) {
+            Ok(handler) => {
+                let config =
+                    AccountsConfig::get_entry(&handler).unwrap_or_else(|(errs, config)| {
+                        tracing::info!("errors loading config: {:?}", errs);
+                        config
+                    });
+                AccountsStore {
+                    config,
+                    handler: Some(handler),
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "No config handler available, changes are not saved: {}",
+                    err
+                );
+                Self::in_memory()
             }
-            None => AccountsConfig::default(),
+        }
+    }
+
+    /// An empty config whose changes are kept in memory, for tests.
+    pub fn in_memory() -> AccountsStore {
+        AccountsStore {
+            config: AccountsConfig::default(),
+            handler: None,
         }
     }
 
     pub fn save_account(&mut self, account: &Account) -> Result<(), Error> {
-        let mut accounts = self.accounts.clone();
+        let mut accounts = self.config.accounts.clone();
         if let Some(existing) = accounts.iter_mut().find(|a| a.id == account.id) {
             existing.clone_from(&account);
         } else {
-            accounts.push(account.clone());
+            // New accounts are listed last
+            let mut account = account.clone();
+            account.order = accounts
+                .iter()
+                .map(|a| a.order + 1)
+                .max()
+                .unwrap_or_default();
+            accounts.push(account);
         }
-        if let Some(handler) = Self::config_handler() {
-            self.set_accounts(&handler, accounts)?;
-        } else {
-            tracing::warn!("No config handler available, account not saved");
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_accounts(handler, accounts)?;
+            }
+            None => self.config.accounts = accounts,
         }
         Ok(())
     }
 
     pub fn remove_account(&mut self, id: &Uuid) -> Result<(), Error> {
-        let mut accounts = self.accounts.clone();
+        let mut accounts = self.config.accounts.clone();
         accounts.retain(|account| account.id != *id);
-        if let Some(handler) = Self::config_handler() {
-            self.set_accounts(&handler, accounts)?;
-        } else {
-            tracing::warn!("No config handler available, account not removed");
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_accounts(handler, accounts)?;
+            }
+            None => self.config.accounts = accounts,
         }
         Ok(())
     }
 
-    pub fn get_account(&self, id: &Uuid) -> Option<Account> {
-        self.accounts.iter().find(|a| a.id == *id).cloned()
+    /// Orders the accounts as listed, the accounts missing from the list keep their relative
+    /// order after them.
+    pub fn set_order(&mut self, ids: &[Uuid]) -> Result<(), Error> {
+        let mut accounts = self.config.accounts.clone();
+        accounts.sort_by_key(|account| {
+            ids.iter()
+                .position(|id| *id == account.id)
+                .unwrap_or(ids.len())
+        });
+        for (order, account) in accounts.iter_mut().enumerate() {
+            account.order = order as u32;
+        }
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_accounts(handler, accounts)?;
+            }
+            None => self.config.accounts = accounts,
+        }
+        Ok(())
+    }
+
+    /// Applies an edit to every account with a single write, the edit returns whether it
+    /// changed the account. Returns the changed accounts.
+    pub fn edit_accounts(
+        &mut self,
+        mut edit: impl FnMut(&mut Account) -> bool,
+    ) -> Result<Vec<Account>, Error> {
+        let mut accounts = self.config.accounts.clone();
+        let edited: Vec<Account> = accounts
+            .iter_mut()
+            .filter_map(|account| edit(account).then(|| account.clone()))
+            .collect();
+        if edited.is_empty() {
+            return Ok(edited);
+        }
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_accounts(handler, accounts)?;
+            }
+            None => self.config.accounts = accounts,
+        }
+        Ok(edited)
     }
 
-    pub fn account_exists(&self, username: &String, provider: &Provider) -> bool {
-        self.accounts
-            .iter()
-            .any(|a| a.username == *username && a.provider == *provider)
+    /// Applies an edit to an account, the edit returns whether it changed the account.
+    /// Returns the account once saved, `None` when it was left unchanged or doesn't exist.
+    pub fn edit_account(
+        &mut self,
+        id: &Uuid,
+        edit: impl FnOnce(&mut Account) -> bool,
+    ) -> Result<Option<Account>, Error> {
+        let mut edit = Some(edit);
+        let edited = self.edit_accounts(|account| {
+            account.id == *id && edit.take().is_some_and(|edit| edit(account))
+        })?;
+        Ok(edited.into_iter().next())
+    }
+
+    pub fn save_groups(&mut self, groups: Vec<String>) -> Result<(), Error> {
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_groups(handler, groups)?;
+            }
+            None => self.config.groups = groups,
+        }
+        Ok(())
+    }
+
+    pub fn save_paused(&mut self, paused: bool) -> Result<(), Error> {
+        match &self.handler {
+            Some(handler) => {
+                self.config.set_paused(handler, paused)?;
+            }
+            None => self.config.paused = paused,
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,67 @@
+use zbus::DBusError;
+
+/// Errors returned by the accounts daemon over D-Bus.
+///
+/// Each variant maps to an error name under `dev.edfloreshz.Accounts.Error`, so clients
+/// can match on them, e.g. by converting a method call error with `AccountsError::from`.
+/// The name is the machine-readable code of the error, the message only carries the
+/// details it is about, such as an account id or an OAuth error code, and is never meant
+/// to be shown as is.
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "dev.edfloreshz.Accounts.Error")]
+pub enum AccountsError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    AccountNotFound(String),
+    AccountAlreadyExists(String),
+    InvalidArguments(String),
+    InvalidProvider(String),
+    InvalidService(String),
+    AuthenticationFailed(String),
+    ReauthenticationRequired(String),
+    TokenExpired(String),
+    TokenRefreshFailed(String),
+    TokenRevocationFailed(String),
+    /// The provider rejected the request, the message is its OAuth error code, e.g.
+    /// `invalid_grant`.
+    OAuth(String),
+    Network(String),
+    /// The provider failed repeatedly, its requests are held back until the RFC 3339 time
+    /// in the message.
+    Throttled(String),
+    /// Too many requests were sent to the provider, they are held back until the RFC 3339
+    /// time in the message.
+    RateLimited(String),
+    /// The account is locked, it has to be unlocked before being removed or disabled.
+    AccountLocked(String),
+    /// Every account is paused, tokens are refused until they are resumed.
+    Paused(String),
+    /// The user denied the calling application access to the account.
+    AccessDenied(String),
+    /// The user hasn't decided yet whether the calling application may access the account.
+    AuthorizationPending(String),
+    /// PolicyKit refused the caller the action, when the daemon runs on the system bus.
+    NotAuthorized(String),
+    Storage(String),
+    Failed(String),
+}
+
+/// Prefix of the D-Bus error names of [`AccountsError`].
+pub const ERROR_NAME_PREFIX: &str = "dev.edfloreshz.Accounts.Error.";
+
+impl AccountsError {
+    /// Code of the error, its D-Bus error name without [`ERROR_NAME_PREFIX`].
+    pub fn code(&self) -> String {
+        let name = self.name();
+        name.as_str()
+            .strip_prefix(ERROR_NAME_PREFIX)
+            .unwrap_or(name.as_str())
+            .to_string()
+    }
+}
+
+impl From<zbus::fdo::Error> for AccountsError {
+    fn from(error: zbus::fdo::Error) -> Self {
+        AccountsError::ZBus(zbus::Error::FDO(Box::new(error)))
+    }
+}
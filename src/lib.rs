@@ -1,13 +1,18 @@
+pub mod capabilities;
 pub mod clients;
+#[cfg(feature = "config")]
 pub mod config;
+pub mod error;
 pub mod models;
 pub mod proxy;
+pub mod scopes;
 mod service;
 
 pub use clients::AccountsClient;
+pub use error::{AccountsError, ERROR_NAME_PREFIX};
 pub use service::*;
 
 // Re-exports
-pub use chrono::Local;
+pub use chrono::{DateTime, Local, Utc};
 pub use uuid::Uuid;
 pub use zbus;
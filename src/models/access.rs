@@ -0,0 +1,18 @@
+use zbus::zvariant::{DeserializeDict, SerializeDict, Type};
+
+/// A request for the tokens of an account made by a D-Bus peer.
+#[derive(Debug, Clone, PartialEq, DeserializeDict, SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct AccessLogEntry {
+    /// When the token was requested, as RFC 3339.
+    pub accessed_at: String,
+    /// The D-Bus method which was called, e.g. `GetAccessToken`.
+    pub method: String,
+    /// Unique bus name of the caller.
+    pub sender: String,
+    pub pid: Option<u32>,
+    /// Path of the caller's executable, when it could be resolved.
+    pub executable: Option<String>,
+    /// Flatpak application ID of the caller, when it runs sandboxed.
+    pub app_id: Option<String>,
+}
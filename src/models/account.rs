@@ -1,9 +1,9 @@
-use std::{collections::BTreeMap, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use zbus::zvariant::{DeserializeDict, SerializeDict, Type};
+use zbus::zvariant::{DeserializeDict, OwnedValue, SerializeDict, Type, Value};
 
 use crate::models::{Provider, Service};
 
@@ -18,33 +18,144 @@ pub struct Account {
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
     pub services: BTreeMap<Service, bool>,
+    #[serde(default)]
+    pub attention_needed: bool,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Stable identifier of the user at the provider, which survives renames.
+    #[serde(default)]
+    pub remote_id: Option<String>,
+    /// Free-form label chosen by the user, e.g. "Work".
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Color chosen by the user as `#rrggbb`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Settings overriding the defaults of each service, e.g. a custom CalDAV URI.
+    #[serde(default)]
+    pub service_settings: BTreeMap<Service, BTreeMap<String, String>>,
+    /// Whether each application, by Flatpak app ID or executable path, may
+    /// request the account tokens.
+    #[serde(default)]
+    pub app_access: BTreeMap<String, bool>,
+    /// Name of the administrator template the account was provisioned from.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Path of the cached profile picture.
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// Position chosen by the user, accounts are listed in ascending order.
+    #[serde(default)]
+    pub order: u32,
+    /// Group the user filed the account under, e.g. "Work".
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Whether background work such as prefetching runs while the connection is metered.
+    #[serde(default)]
+    pub sync_on_metered: bool,
+    /// Whether the account is protected from being removed or disabled until unlocked.
+    #[serde(default)]
+    pub locked: bool,
 }
 
 impl Account {
     pub fn dbus_id(&self) -> String {
         self.id.to_string().replace("-", "_")
     }
+
+    /// The status to show for the account, a disabled account is reported as such even
+    /// when its credentials are broken.
+    pub fn state(&self) -> AccountState {
+        if !self.enabled {
+            AccountState::Disabled
+        } else if self.attention_needed {
+            AccountState::NeedsReauth
+        } else if let Some(error) = &self.last_error {
+            AccountState::Error(error.clone())
+        } else {
+            AccountState::Active
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountState {
+    Active,
+    /// Disabled by the user.
+    Disabled,
+    /// The provider rejected the credentials, the user has to sign in again.
+    NeedsReauth,
+    /// The last token refresh failed for another reason, e.g. the network.
+    Error(String),
+}
+
+impl Display for AccountState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountState::Active => write!(f, "Active"),
+            AccountState::Disabled => write!(f, "Disabled"),
+            AccountState::NeedsReauth => write!(f, "NeedsReauth"),
+            AccountState::Error(_) => write!(f, "Error"),
+        }
+    }
+}
+
+/// [`AccountState`] without the error, sent over D-Bus as its name, e.g. `"NeedsReauth"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Value, OwnedValue)]
+#[zvariant(signature = "s")]
+pub enum DbusAccountState {
+    Active,
+    Disabled,
+    NeedsReauth,
+    Error,
+}
+
+impl From<&AccountState> for DbusAccountState {
+    fn from(value: &AccountState) -> Self {
+        match value {
+            AccountState::Active => DbusAccountState::Active,
+            AccountState::Disabled => DbusAccountState::Disabled,
+            AccountState::NeedsReauth => DbusAccountState::NeedsReauth,
+            AccountState::Error(_) => DbusAccountState::Error,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, DeserializeDict, SerializeDict, Type)]
 #[zvariant(signature = "dict")]
 pub struct DbusAccount {
     pub id: String,
-    pub provider: String,
+    pub provider: Provider,
     pub display_name: String,
     pub username: String,
     pub email: Option<String>,
     pub enabled: bool,
     pub created_at: String,
     pub last_used: Option<String>,
-    pub services: BTreeMap<String, bool>,
+    pub services: BTreeMap<Service, bool>,
+    pub attention_needed: bool,
+    pub last_error: Option<String>,
+    pub remote_id: Option<String>,
+    pub label: Option<String>,
+    pub color: Option<String>,
+    pub service_settings: BTreeMap<Service, BTreeMap<String, String>>,
+    pub app_access: BTreeMap<String, bool>,
+    pub template: Option<String>,
+    /// [`AccountState`] of the account, the error itself is in `last_error`.
+    pub state: DbusAccountState,
+    pub avatar: Option<String>,
+    pub order: u32,
+    pub group: Option<String>,
+    pub sync_on_metered: bool,
+    pub locked: bool,
 }
 
 impl From<Account> for DbusAccount {
     fn from(value: Account) -> Self {
+        let state = DbusAccountState::from(&value.state());
         Self {
             id: value.id.to_string(),
-            provider: value.provider.to_string(),
+            provider: value.provider,
             display_name: value.display_name,
             username: value.username,
             email: value.email,
@@ -54,11 +165,21 @@ impl From<Account> for DbusAccount {
                 .last_used
                 .clone()
                 .map(|last_used| last_used.to_string()),
-            services: value
-                .services
-                .iter()
-                .map(|(service, enabled)| (service.to_string(), *enabled))
-                .collect(),
+            services: value.services,
+            attention_needed: value.attention_needed,
+            last_error: value.last_error,
+            remote_id: value.remote_id,
+            label: value.label,
+            color: value.color,
+            service_settings: value.service_settings,
+            app_access: value.app_access,
+            template: value.template,
+            state,
+            avatar: value.avatar,
+            order: value.order,
+            group: value.group,
+            sync_on_metered: value.sync_on_metered,
+            locked: value.locked,
         }
     }
 }
@@ -67,7 +188,7 @@ impl From<&Account> for DbusAccount {
     fn from(value: &Account) -> Self {
         Self {
             id: value.id.to_string(),
-            provider: value.provider.to_string(),
+            provider: value.provider,
             display_name: value.display_name.clone(),
             username: value.username.clone(),
             email: value.email.clone(),
@@ -77,34 +198,78 @@ impl From<&Account> for DbusAccount {
                 .last_used
                 .clone()
                 .map(|last_used| last_used.to_string()),
-            services: value
-                .services
-                .iter()
-                .map(|(service, enabled)| (service.to_string(), *enabled))
-                .collect(),
+            services: value.services.clone(),
+            attention_needed: value.attention_needed,
+            last_error: value.last_error.clone(),
+            remote_id: value.remote_id.clone(),
+            label: value.label.clone(),
+            color: value.color.clone(),
+            service_settings: value.service_settings.clone(),
+            app_access: value.app_access.clone(),
+            template: value.template.clone(),
+            state: DbusAccountState::from(&value.state()),
+            avatar: value.avatar.clone(),
+            order: value.order,
+            group: value.group.clone(),
+            sync_on_metered: value.sync_on_metered,
+            locked: value.locked,
         }
     }
 }
 
-impl From<DbusAccount> for Account {
-    fn from(value: DbusAccount) -> Self {
-        Account {
-            id: Uuid::from_str(&value.id).unwrap(),
-            provider: Provider::from_str(&value.provider).unwrap(),
+/// A field of a [`DbusAccount`] that could not be converted back into an [`Account`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidAccount {
+    Id(String),
+    CreatedAt(String),
+    LastUsed(String),
+}
+
+impl Display for InvalidAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidAccount::Id(id) => write!(f, "Invalid account ID: {id}"),
+            InvalidAccount::CreatedAt(date) => write!(f, "Invalid creation date: {date}"),
+            InvalidAccount::LastUsed(date) => write!(f, "Invalid last used date: {date}"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidAccount {}
+
+impl TryFrom<DbusAccount> for Account {
+    type Error = InvalidAccount;
+
+    fn try_from(value: DbusAccount) -> Result<Self, Self::Error> {
+        Ok(Account {
+            id: Uuid::from_str(&value.id).map_err(|_| InvalidAccount::Id(value.id))?,
+            provider: value.provider,
             display_name: value.display_name,
             username: value.username,
             email: value.email,
             enabled: value.enabled,
-            created_at: DateTime::from_str(&value.created_at).unwrap(),
+            created_at: DateTime::from_str(&value.created_at)
+                .map_err(|_| InvalidAccount::CreatedAt(value.created_at))?,
             last_used: value
                 .last_used
-                .map(|lu| DateTime::from_str(&lu).ok())
-                .unwrap(),
-            services: value
-                .services
-                .into_iter()
-                .map(|(service, enabled)| (Service::from_str(service).unwrap(), enabled))
-                .collect(),
-        }
+                .map(|last_used| {
+                    DateTime::from_str(&last_used).map_err(|_| InvalidAccount::LastUsed(last_used))
+                })
+                .transpose()?,
+            services: value.services,
+            attention_needed: value.attention_needed,
+            last_error: value.last_error,
+            remote_id: value.remote_id,
+            label: value.label,
+            color: value.color,
+            service_settings: value.service_settings,
+            app_access: value.app_access,
+            template: value.template,
+            avatar: value.avatar,
+            order: value.order,
+            group: value.group,
+            sync_on_metered: value.sync_on_metered,
+            locked: value.locked,
+        })
     }
 }
@@ -1,10 +1,12 @@
 pub use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::SecretString;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Credential {
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    pub access_token: SecretString,
+    pub refresh_token: Option<SecretString>,
     pub expires_at: Option<DateTime<Utc>>,
     pub scope: Vec<String>,
     pub token_type: String,
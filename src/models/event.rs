@@ -0,0 +1,18 @@
+use uuid::Uuid;
+
+use crate::models::Account;
+
+/// A change to the accounts, as reported by [`AccountsClient::watch`].
+///
+/// [`AccountsClient::watch`]: crate::AccountsClient::watch
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountEvent {
+    Added(Account),
+    Removed(Uuid),
+    Changed(Account),
+    /// The account needs the user to sign in again.
+    AttentionNeeded(Account),
+    /// The daemon started again, changes may have been missed so accounts should be listed
+    /// again.
+    DaemonRestarted,
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Account;
+
+/// Version of the [`AccountManifest`] JSON format, increased on incompatible changes.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The structure of the accounts, as exported by `ExportManifest`.
+///
+/// It never holds credentials, so it can be kept in backups. Restored accounts need the
+/// user to sign in again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountManifest {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl AccountManifest {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
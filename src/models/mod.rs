@@ -1,9 +1,23 @@
+mod access;
 mod account;
 mod credentials;
+mod event;
+mod manifest;
 mod provider;
+mod secret;
 mod service;
+mod state;
+mod task;
+mod usage;
 
-pub use account::{Account, DbusAccount};
+pub use access::AccessLogEntry;
+pub use account::{Account, AccountState, DbusAccount, DbusAccountState, InvalidAccount};
 pub use credentials::Credential;
+pub use event::AccountEvent;
+pub use manifest::{AccountManifest, MANIFEST_VERSION};
 pub use provider::Provider;
+pub use secret::SecretString;
 pub use service::{DbusService, Service};
+pub use state::{DaemonState, PendingAuthorization, STATE_VERSION};
+pub use task::{Task, TaskList};
+pub use usage::UsageStats;
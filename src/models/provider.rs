@@ -1,8 +1,25 @@
 use std::{collections::BTreeMap, fmt::Display};
 
 use serde::{Deserialize, Serialize};
+use zbus::zvariant::{OwnedValue, Type, Value};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Sent over D-Bus as its name, e.g. `"Google"`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Type,
+    Value,
+    OwnedValue,
+)]
+#[zvariant(signature = "s")]
 pub enum Provider {
     Google,
     Microsoft,
@@ -33,10 +50,14 @@ impl Provider {
             Provider::Google => BTreeMap::from([
                 (super::Service::Email, false),
                 (super::Service::Calendar, false),
+                (super::Service::Contacts, false),
+                (super::Service::Todo, false),
             ]),
             Provider::Microsoft => BTreeMap::from([
                 (super::Service::Email, false),
                 (super::Service::Calendar, false),
+                (super::Service::Contacts, false),
+                (super::Service::Todo, false),
             ]),
         }
     }
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A token or client secret, which only shows up as `<redacted>` when formatted so it
+/// can't end up in logs or error messages.
+///
+/// Serialized as the plain string, reading it takes [`SecretString::expose`].
+#[derive(Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// The secret itself, for the request or the client which needs it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// The secret itself, for APIs taking ownership of it.
+    pub fn into_exposed(self) -> String {
+        self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Credential;
+
+    const TOKEN: &str = "ya29.a0-token";
+
+    #[test]
+    fn never_formats_the_secret() {
+        let secret = SecretString::from(TOKEN);
+        assert!(!format!("{secret}").contains(TOKEN));
+        assert!(!format!("{secret:?}").contains(TOKEN));
+        assert_eq!(secret.expose(), TOKEN);
+    }
+
+    #[test]
+    fn keeps_credentials_out_of_debug_output() {
+        let credential = Credential {
+            access_token: TOKEN.into(),
+            refresh_token: Some("1//refresh-token".into()),
+            expires_at: None,
+            scope: vec!["openid".to_string()],
+            token_type: "Bearer".to_string(),
+        };
+        let debug = format!("{credential:?}");
+        assert!(!debug.contains(TOKEN));
+        assert!(!debug.contains("1//refresh-token"));
+        assert!(format!("{credential:#?}").contains("<redacted>"));
+    }
+
+    #[test]
+    fn serializes_the_plain_secret() {
+        let secret = SecretString::from(TOKEN);
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(json, format!("\"{TOKEN}\""));
+        assert_eq!(serde_json::from_str::<SecretString>(&json).unwrap(), secret);
+    }
+}
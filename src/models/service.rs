@@ -1,9 +1,24 @@
 use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
-use zbus::zvariant::Type;
+use zbus::zvariant::{OwnedValue, Type, Value};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialOrd, Ord, PartialEq)]
+/// Sent over D-Bus as its name, e.g. `"Calendar"`.
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    Hash,
+    Eq,
+    PartialOrd,
+    Ord,
+    PartialEq,
+    Type,
+    Value,
+    OwnedValue,
+)]
+#[zvariant(signature = "s")]
 pub enum Service {
     Email,
     Calendar,
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::Account;
+
+/// Version of the [`DaemonState`] JSON format, increased on incompatible changes.
+pub const STATE_VERSION: u32 = 1;
+
+/// A snapshot of the daemon state, as returned as JSON by `DumpState`.
+///
+/// Credentials are never part of it. Fields are only added within a [`STATE_VERSION`], so
+/// scripts can rely on the ones they know.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonState {
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub accounts: Vec<Account>,
+    /// Applications waiting for the user to allow or deny their access to an account.
+    #[serde(default)]
+    pub pending_authorizations: Vec<PendingAuthorization>,
+    /// Whether a sign in is waiting for the provider to redirect back.
+    #[serde(default)]
+    pub authentication_pending: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PendingAuthorization {
+    pub account_id: Uuid,
+    /// Executable path of the application, or its bus name when it could not be resolved.
+    pub app: String,
+}
+
+impl DaemonState {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
@@ -0,0 +1,21 @@
+use zbus::zvariant::{DeserializeDict, SerializeDict, Type};
+
+/// A task list of an account, e.g. Google's "My Tasks".
+#[derive(Debug, Clone, PartialEq, DeserializeDict, SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct TaskList {
+    pub id: String,
+    pub title: String,
+}
+
+/// A task of a [`TaskList`].
+#[derive(Debug, Clone, PartialEq, DeserializeDict, SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct Task {
+    pub id: String,
+    pub title: String,
+    pub notes: Option<String>,
+    /// When the task is due, as RFC 3339. Google only keeps the date.
+    pub due: Option<String>,
+    pub completed: bool,
+}
@@ -0,0 +1,18 @@
+use zbus::zvariant::{DeserializeDict, SerializeDict, Type};
+
+/// The requests the daemon made to the provider for a service of an account, and the
+/// bytes they moved.
+#[derive(Debug, Clone, PartialEq, DeserializeDict, SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct UsageStats {
+    /// The service, e.g. `Calendar`, or `Authentication` for token refreshes and
+    /// `Profile` for profile pictures.
+    pub service: String,
+    pub requests: u64,
+    /// Bytes of the request bodies.
+    pub bytes_sent: u64,
+    /// Bytes of the response bodies.
+    pub bytes_received: u64,
+    /// When the daemon started counting for the account, as RFC 3339.
+    pub since: String,
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+
 use zbus::fdo::Result;
 use zbus::proxy;
+use zbus::zvariant::OwnedValue;
 
-use crate::models::DbusAccount;
+use crate::models::{AccessLogEntry, DbusAccount, Provider, Service, Task, TaskList, UsageStats};
 
 #[proxy(
     default_service = "dev.edfloreshz.Accounts",
@@ -9,20 +12,123 @@ use crate::models::DbusAccount;
     interface = "dev.edfloreshz.Accounts.Account"
 )]
 pub trait Accounts {
+    #[zbus(allow_interactive_auth)]
     async fn list_accounts(&self) -> Result<Vec<DbusAccount>>;
+    async fn list_accounts_paged(
+        &self,
+        offset: u32,
+        limit: u32,
+        fields: &[&str],
+    ) -> Result<Vec<HashMap<String, OwnedValue>>>;
+    async fn list_accounts_by_provider(&self, provider: Provider) -> Result<Vec<DbusAccount>>;
+    async fn list_accounts_by_service(&self, service: Service) -> Result<Vec<DbusAccount>>;
+    async fn find_account(&self, email_or_username: &str) -> Result<DbusAccount>;
+    async fn get_account_by_email(&self, email: &str) -> Result<DbusAccount>;
+    async fn get_accounts_by_domain(&self, domain: &str) -> Result<Vec<DbusAccount>>;
     async fn get_account(&self, id: &str) -> Result<DbusAccount>;
-    async fn start_authentication(&mut self, provider_name: &str) -> Result<String>;
+    async fn validate_providers(&self) -> Result<HashMap<String, Vec<String>>>;
+    async fn start_authentication(&mut self, provider: Provider) -> Result<String>;
+    async fn start_device_authentication(&mut self, provider: Provider)
+    -> Result<(String, String)>;
+    async fn cancel_authentication(&mut self, csrf_token_or_provider: &str) -> Result<()>;
+    async fn reauthenticate_account(&mut self, id: &str) -> Result<String>;
+    async fn request_additional_scopes(&mut self, id: &str, service: Service) -> Result<String>;
     async fn complete_authentication(
         &mut self,
         csrf_token: &str,
         authorization_code: &str,
     ) -> Result<String>;
+    async fn fail_authentication(&mut self, csrf_token: &str, error: &str) -> Result<()>;
+    async fn import_goa_accounts(&mut self) -> Result<Vec<String>>;
+    async fn import_uoa_accounts(&mut self) -> Result<Vec<String>>;
+    #[zbus(allow_interactive_auth)]
+    async fn export_manifest(&self) -> Result<String>;
+    #[zbus(allow_interactive_auth)]
+    async fn import_manifest(&mut self, manifest: &str) -> Result<Vec<String>>;
     async fn remove_account(&mut self, id: &str) -> Result<()>;
+    async fn set_display_name(&mut self, id: &str, name: &str) -> Result<()>;
+    async fn set_account_label(&mut self, id: &str, label: &str) -> Result<()>;
+    async fn set_account_color(&mut self, id: &str, color: &str) -> Result<()>;
+    async fn set_sync_on_metered(&mut self, id: &str, enabled: bool) -> Result<()>;
+    async fn set_account_locked(&mut self, id: &str, locked: bool) -> Result<()>;
     async fn set_account_enabled(&mut self, id: &str, enabled: bool) -> Result<()>;
-    async fn set_service_enabled(&mut self, id: &str, service: &str, enabled: bool) -> Result<()>;
+    async fn set_accounts_enabled(&mut self, ids: &[&str], enabled: bool) -> Result<()>;
+    async fn set_account_order(&mut self, ids: &[&str]) -> Result<()>;
+    async fn create_group(&mut self, name: &str) -> Result<()>;
+    async fn rename_group(&mut self, name: &str, new_name: &str) -> Result<()>;
+    async fn remove_group(&mut self, name: &str) -> Result<()>;
+    async fn set_account_group(&mut self, id: &str, group: &str) -> Result<()>;
+    async fn set_group_enabled(&mut self, name: &str, enabled: bool) -> Result<()>;
+    #[zbus(allow_interactive_auth)]
+    async fn set_all_accounts_paused(&mut self, paused: bool) -> Result<()>;
+    async fn set_service_enabled(
+        &mut self,
+        id: &str,
+        service: Service,
+        enabled: bool,
+    ) -> Result<()>;
+    async fn get_service_setting(&self, id: &str, service: Service, key: &str) -> Result<String>;
+    #[zbus(allow_interactive_auth)]
+    async fn set_service_setting(
+        &mut self,
+        id: &str,
+        service: Service,
+        key: &str,
+        value: &str,
+    ) -> Result<()>;
+    async fn get_service_config(
+        &self,
+        id: &str,
+        service: Service,
+    ) -> Result<HashMap<String, String>>;
+    #[zbus(allow_interactive_auth)]
     async fn get_access_token(&mut self, id: &str) -> Result<String>;
+    #[zbus(allow_interactive_auth)]
+    async fn get_access_tokens(&mut self, ids: &[&str]) -> Result<HashMap<String, String>>;
+    #[zbus(allow_interactive_auth)]
+    async fn get_access_token_for_service(&mut self, id: &str, service: Service) -> Result<String>;
     async fn get_refresh_token(&mut self, id: &str) -> Result<String>;
-    async fn ensure_credentials(&mut self, id: &str) -> Result<()>;
+    async fn get_access_log(&self, id: &str) -> Result<Vec<AccessLogEntry>>;
+    async fn get_usage_stats(&self, id: &str) -> Result<Vec<UsageStats>>;
+    async fn get_token_expiry(&self, id: &str) -> Result<i64>;
+    async fn get_granted_scopes(&self, id: &str) -> Result<Vec<String>>;
+    #[zbus(allow_interactive_auth)]
+    async fn revoke_credentials(&mut self, id: &str) -> Result<()>;
+    async fn replay_authorization_requests(&self) -> Result<()>;
+    #[zbus(allow_interactive_auth)]
+    async fn dump_state(&self) -> Result<String>;
+    async fn set_log_level(&self, level: &str) -> Result<()>;
+    // The daemon asks polkit, which may prompt for the user's password
+    #[zbus(allow_interactive_auth)]
+    async fn set_app_access(&mut self, id: &str, app: &str, allowed: bool) -> Result<()>;
+    #[zbus(allow_interactive_auth)]
+    async fn reset_app_access(&mut self, id: &str, app: &str) -> Result<()>;
+    async fn touch_account(&mut self, id: &str) -> Result<()>;
+    #[zbus(allow_interactive_auth)]
+    async fn ensure_credentials(&mut self, id: &str) -> Result<(bool, i64)>;
+
+    async fn get_capabilities(&self) -> Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn version(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn api_level(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn groups(&self) -> zbus::Result<Vec<String>>;
+    #[zbus(property)]
+    fn all_accounts_paused(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn accounts_needing_attention(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn throttled_until(&self) -> zbus::Result<HashMap<String, String>>;
+
+    #[zbus(property)]
+    fn credential_store(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn credential_store_warning(&self) -> zbus::Result<String>;
 
     async fn emit_account_added(&self, account_id: &str) -> Result<()>;
     async fn emit_account_removed(&self, account_id: &str) -> Result<()>;
@@ -30,23 +136,154 @@ pub trait Accounts {
     async fn emit_account_exists(&self) -> Result<()>;
 
     #[zbus(signal)]
-    fn account_added(account_id: &str) -> Result<()>;
+    fn account_added(account: DbusAccount) -> Result<()>;
 
     #[zbus(signal)]
     fn account_removed(account_id: &str) -> Result<()>;
 
     #[zbus(signal)]
-    fn account_changed(account_id: &str) -> Result<()>;
+    fn account_changed(account: DbusAccount) -> Result<()>;
 
     #[zbus(signal)]
     fn account_exists() -> Result<()>;
+
+    #[zbus(signal)]
+    fn authentication_started(provider: Provider) -> Result<()>;
+
+    #[zbus(signal)]
+    fn authentication_completed(account_id: &str) -> Result<()>;
+
+    #[zbus(signal)]
+    /// `provider` is empty when the flow could not be matched to a pending request, `error`
+    /// is the code of the [`AccountsError`](crate::AccountsError) and `detail` its message.
+    fn authentication_failed(provider: &str, error: &str, detail: &str) -> Result<()>;
+
+    #[zbus(signal)]
+    fn authentication_cancelled(provider: Provider) -> Result<()>;
+
+    #[zbus(signal)]
+    fn authorization_requested(account_id: &str, app: &str) -> Result<()>;
+
+    #[zbus(signal)]
+    fn account_attention_needed(account: DbusAccount) -> Result<()>;
 }
 
+/// The interface sandboxed applications use, the only one they can be allowed to reach.
 #[proxy(
-    interface = "dev.edfloreshz.Accounts",
-    default_service = "dev.edfloreshz.Accounts.Calendar"
+    default_service = "dev.edfloreshz.Accounts.Portal",
+    default_path = "/dev/edfloreshz/Accounts/Portal",
+    interface = "dev.edfloreshz.Accounts.Portal"
+)]
+pub trait Portal {
+    /// Returns the ID of the account and a token limited to the service, once the user
+    /// allowed the application.
+    async fn request_token(&self, provider: Provider, service: Service)
+    -> Result<(String, String)>;
+}
+
+#[proxy(
+    interface = "dev.edfloreshz.Accounts.Calendar",
+    default_service = "dev.edfloreshz.Accounts"
 )]
 pub trait Calendar {
-    async fn uri(&self) -> Result<String>;
-    async fn accept_ssl_errors(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn uri(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn accept_ssl_errors(&self) -> zbus::Result<bool>;
+
+    async fn list_events(&self, start: i64, end: i64) -> Result<Vec<String>>;
+    async fn create_event(&self, ics: &str) -> Result<String>;
+    async fn update_event(&self, uid: &str, ics: &str) -> Result<()>;
+    async fn delete_event(&self, uid: &str) -> Result<()>;
+}
+
+#[proxy(
+    interface = "dev.edfloreshz.Accounts.Mail",
+    default_service = "dev.edfloreshz.Accounts"
+)]
+pub trait Mail {
+    #[zbus(property)]
+    fn email_address(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn imap_host(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn imap_user_name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn imap_supported(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn imap_use_ssl(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn imap_use_tls(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn imap_accept_ssl_errors(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_host(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn smtp_user_name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn smtp_supported(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_use_auth(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_use_ssl(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_use_tls(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_accept_ssl_errors(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_auth_login(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_auth_plain(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn smtp_auth_xoauth2(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "dev.edfloreshz.Accounts.Contacts",
+    default_service = "dev.edfloreshz.Accounts"
+)]
+pub trait Contacts {
+    #[zbus(property)]
+    fn uri(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn accept_ssl_errors(&self) -> zbus::Result<bool>;
+
+    async fn search_contacts(&self, query: &str, limit: u32) -> Result<Vec<String>>;
+    async fn get_contact(&self, uid: &str) -> Result<String>;
+}
+
+#[proxy(
+    interface = "dev.edfloreshz.Accounts.Todo",
+    default_service = "dev.edfloreshz.Accounts"
+)]
+pub trait Todo {
+    #[zbus(property)]
+    fn uri(&self) -> zbus::Result<String>;
+
+    async fn list_task_lists(&self) -> Result<Vec<TaskList>>;
+    async fn list_tasks(&self, list_id: &str) -> Result<Vec<Task>>;
+    async fn complete_task(&self, list_id: &str, task_id: &str) -> Result<()>;
+    async fn create_task(&self, list_id: &str, title: &str, notes: &str, due: &str)
+    -> Result<Task>;
 }
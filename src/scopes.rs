@@ -0,0 +1,66 @@
+//! OAuth scopes of the supported providers, and the ones each service needs.
+
+use crate::models::{Provider, Service};
+
+/// Scopes identifying the user, requested by every sign in.
+pub const OPENID: &str = "openid";
+pub const EMAIL: &str = "email";
+pub const PROFILE: &str = "profile";
+
+pub mod google {
+    pub const MAIL: &str = "https://mail.google.com/";
+    pub const CALENDAR: &str = "https://www.googleapis.com/auth/calendar";
+    pub const CONTACTS: &str = "https://www.googleapis.com/auth/contacts";
+    pub const TASKS: &str = "https://www.googleapis.com/auth/tasks";
+}
+
+pub mod microsoft {
+    /// Needed for Microsoft to issue a refresh token.
+    pub const OFFLINE_ACCESS: &str = "offline_access";
+    pub const USER_READ: &str = "https://graph.microsoft.com/user.read";
+    pub const MAIL_READ: &str = "https://graph.microsoft.com/mail.read";
+    pub const CALENDARS_READ: &str = "https://graph.microsoft.com/calendars.read";
+    pub const CALENDARS_READ_WRITE: &str = "https://graph.microsoft.com/calendars.readwrite";
+    pub const CONTACTS_READ: &str = "https://graph.microsoft.com/contacts.read";
+    pub const TASKS_READ: &str = "https://graph.microsoft.com/tasks.read";
+    pub const TASKS_READ_WRITE: &str = "https://graph.microsoft.com/tasks.readwrite";
+    pub const FILES_READ: &str = "https://graph.microsoft.com/files.read";
+}
+
+/// The scopes a service needs from a provider.
+pub fn required(provider: Provider, service: &Service) -> &'static [&'static str] {
+    match (provider, service) {
+        (Provider::Google, Service::Email) => &[google::MAIL],
+        (Provider::Google, Service::Calendar) => &[google::CALENDAR],
+        (Provider::Google, Service::Contacts) => &[google::CONTACTS],
+        (Provider::Google, Service::Todo) => &[google::TASKS],
+        (Provider::Microsoft, Service::Email) => &[microsoft::OFFLINE_ACCESS, microsoft::MAIL_READ],
+        (Provider::Microsoft, Service::Calendar) => {
+            &[microsoft::OFFLINE_ACCESS, microsoft::CALENDARS_READ_WRITE]
+        }
+        (Provider::Microsoft, Service::Contacts) => {
+            &[microsoft::OFFLINE_ACCESS, microsoft::CONTACTS_READ]
+        }
+        (Provider::Microsoft, Service::Todo) => {
+            &[microsoft::OFFLINE_ACCESS, microsoft::TASKS_READ_WRITE]
+        }
+    }
+}
+
+/// The scopes among `required(provider, service)` missing from `granted`.
+pub fn missing<'a>(
+    provider: Provider,
+    service: &Service,
+    granted: impl IntoIterator<Item = &'a str> + Clone,
+) -> Vec<&'static str> {
+    required(provider, service)
+        .iter()
+        .filter(|scope| {
+            !granted
+                .clone()
+                .into_iter()
+                .any(|granted| granted == **scope)
+        })
+        .copied()
+        .collect()
+}